@@ -1,7 +1,7 @@
 use winit::{
     event::{DeviceEvent, ElementState, Event, KeyboardInput, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
-    window::WindowBuilder,
+    window::{CursorGrabMode, WindowBuilder},
 };
 
 fn main() {
@@ -30,7 +30,21 @@ fn main() {
                     use winit::event::VirtualKeyCode::*;
                     match key {
                         Escape => *control_flow = ControlFlow::Exit,
-                        G => window.set_cursor_grab(!modifiers.shift).unwrap(),
+                        G => window
+                            .set_cursor_grab(match modifiers.shift {
+                                true => CursorGrabMode::None,
+                                false => CursorGrabMode::Confined,
+                            })
+                            .unwrap(),
+                        L => {
+                            let mode = match modifiers.shift {
+                                true => CursorGrabMode::None,
+                                false => CursorGrabMode::Locked,
+                            };
+                            if let Err(err) = window.set_cursor_grab(mode) {
+                                println!("Failed to lock the cursor: {}", err);
+                            }
+                        }
                         H => window.set_cursor_visible(modifiers.shift),
                         _ => (),
                     }
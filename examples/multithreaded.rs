@@ -4,7 +4,7 @@ use std::{collections::HashMap, sync::mpsc, thread, time::Duration};
 use winit::{
     event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
-    window::{CursorIcon, WindowBuilder},
+    window::{CursorGrabMode, CursorIcon, WindowBuilder},
 };
 
 const WINDOW_COUNT: usize = 3;
@@ -48,7 +48,12 @@ fn main() {
                                 true => Some(window.current_monitor()),
                                 false => None,
                             }),
-                            G => window.set_cursor_grab(state).unwrap(),
+                            G => window
+                                .set_cursor_grab(match state {
+                                    true => CursorGrabMode::Confined,
+                                    false => CursorGrabMode::None,
+                                })
+                                .unwrap(),
                             H => window.set_cursor_visible(!state),
                             I => {
                                 println!("Info:");
@@ -24,7 +24,7 @@ fn main() {
             *control_flow = ControlFlow::WaitUntil(Instant::now() + Duration::new(1, 0))
         }
         Event::WindowEvent {
-            event: WindowEvent::RedrawRequested,
+            event: WindowEvent::RedrawRequested(_),
             ..
         } => {
             println!("{:?}", event);
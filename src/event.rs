@@ -4,16 +4,21 @@
 //! processed and used to modify the program state. For more details, see the root-level documentation.
 //!
 //! [event_loop_run]: ../event_loop/struct.EventLoop.html#method.run
-use std::{path::PathBuf, time::Instant};
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
 use crate::{
     dpi::{LogicalPosition, LogicalSize},
+    keyboard::{Key, KeyCode, KeyLocation, KeyboardLayout},
     platform_impl,
     window::WindowId,
 };
 
 /// Describes a generic event.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Event<T> {
     /// Emitted when the OS sends an event to a winit window.
     WindowEvent {
@@ -42,6 +47,50 @@ pub enum Event<T> {
 
     /// Emitted when the application has been resumed.
     Resumed,
+
+    /// Emitted when the contents of the system clipboard have changed.
+    ///
+    /// This may also be emitted when the application itself sets the clipboard, or when the
+    /// contents are offered again without having changed.
+    ///
+    /// ## Platform-specific
+    ///
+    /// Only emitted on X11 and Wayland. On X11, changes made by other applications are only
+    /// reported if the XFixes extension is available.
+    ClipboardChanged,
+
+    /// Emitted when the active layout of the keyboard has changed, usually because the user
+    /// switched to another layout.
+    ///
+    /// This is also emitted when the layouts of the keyboard are replaced by different ones.
+    ///
+    /// ## Platform-specific
+    ///
+    /// Only emitted on X11 and Wayland. On X11, this requires libxkbcommon-x11.
+    KeyboardLayoutChanged(KeyboardLayout),
+
+    /// Emitted when a file descriptor registered with `EventLoopWindowTargetExtUnix::register_fd`
+    /// is ready.
+    ///
+    /// This is emitted again on every iteration of the event loop for as long as the file
+    /// descriptor stays ready, so it should be read from or written to until it would block.
+    ///
+    /// ## Platform-specific
+    ///
+    /// Only emitted on X11 and Wayland.
+    FdReady {
+        token: FdToken,
+        readiness: FdReadiness,
+    },
+
+    /// Emitted when a timer registered with `EventLoopWindowTargetExtUnix::register_timer` fires.
+    ///
+    /// Timers firing at the same time are reported in the order of their deadlines.
+    ///
+    /// ## Platform-specific
+    ///
+    /// Only emitted on X11, Wayland and the headless backend on Unix.
+    TimerFired { token: TimerToken },
 }
 
 impl<T> Event<T> {
@@ -56,25 +105,34 @@ impl<T> Event<T> {
             LoopDestroyed => Ok(LoopDestroyed),
             Suspended => Ok(Suspended),
             Resumed => Ok(Resumed),
+            ClipboardChanged => Ok(ClipboardChanged),
+            KeyboardLayoutChanged(layout) => Ok(KeyboardLayoutChanged(layout)),
+            FdReady { token, readiness } => Ok(FdReady { token, readiness }),
+            TimerFired { token } => Ok(TimerFired { token }),
         }
     }
 }
 
 /// Describes the reason the event loop is resuming.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum StartCause {
     /// Sent if the time specified by `ControlFlow::WaitUntil` has been reached. Contains the
     /// moment the timeout was requested and the requested resume time. The actual resume time is
     /// guaranteed to be equal to or after the requested resume time.
     ResumeTimeReached {
+        #[cfg_attr(feature = "serde", serde(with = "instant_serde"))]
         start: Instant,
+        #[cfg_attr(feature = "serde", serde(with = "instant_serde"))]
         requested_resume: Instant,
     },
 
     /// Sent if the OS has new events to send to the window, after a wait was requested. Contains
     /// the moment the wait was requested and the resume time, if requested.
     WaitCancelled {
+        #[cfg_attr(feature = "serde", serde(with = "instant_serde"))]
         start: Instant,
+        #[cfg_attr(feature = "serde", serde(with = "instant_serde::option"))]
         requested_resume: Option<Instant>,
     },
 
@@ -88,6 +146,7 @@ pub enum StartCause {
 
 /// Describes an event from a `Window`.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum WindowEvent {
     /// The size of the window has changed. Contains the client area's new dimensions.
     Resized(LogicalSize),
@@ -119,9 +178,44 @@ pub enum WindowEvent {
     /// hovered.
     HoveredFileCancelled,
 
+    /// Data is being dragged over the window.
+    ///
+    /// Contains the MIME types the data is offered as. Use `Window::accept_drop` to accept or
+    /// reject the data based on these types. This is emitted for files as well, which are offered
+    /// as `text/uri-list`.
+    HoveredData(Vec<String>),
+
+    /// Data accepted with `Window::accept_drop` has been dropped into the window.
+    DroppedData {
+        /// The MIME type chosen with `Window::accept_drop`.
+        mime_type: String,
+        /// The dropped data, in that MIME type.
+        data: Vec<u8>,
+    },
+
+    /// Data was hovered, but has exited the window or was dropped without being accepted.
+    HoveredDataCancelled,
+
+    /// The action that the drop target under the cursor would perform has changed, during a drag
+    /// started with `Window::start_drag`.
+    ///
+    /// `None` means that the current target, if any, wouldn't accept a drop.
+    DragActionChanged(Option<DragAction>),
+
+    /// A drag started with `Window::start_drag` has ended.
+    ///
+    /// Contains the action performed by the drop target, or `None` if the drag was cancelled or
+    /// the drop was rejected.
+    DragFinished(Option<DragAction>),
+
     /// The window received a unicode character.
+    ///
+    /// Text composed through an input method is delivered as `Ime::Commit` instead.
     ReceivedCharacter(char),
 
+    /// An event from the input method has been received.
+    Ime(Ime),
+
     /// The window gained or lost focus.
     ///
     /// The parameter is true if the window has gained focus, and false if it has lost focus.
@@ -133,6 +227,28 @@ pub enum WindowEvent {
         input: KeyboardInput,
     },
 
+    /// A key has been pressed or released, as seen through the keyboard layout.
+    ///
+    /// This is emitted after the matching `KeyboardInput`.
+    ///
+    /// ## Platform-specific
+    ///
+    /// Only emitted on X11 and Wayland.
+    Key {
+        device_id: DeviceId,
+        event: KeyEvent,
+    },
+
+    /// The keyboard modifiers have changed.
+    ///
+    /// This is sent to the window with keyboard focus, and with all modifiers released when the
+    /// window loses focus.
+    ///
+    /// ## Platform-specific
+    ///
+    /// Only emitted on X11 and Wayland.
+    ModifiersChanged(ModifiersState),
+
     /// The cursor has moved on the window.
     CursorMoved {
         device_id: DeviceId,
@@ -185,7 +301,17 @@ pub enum WindowEvent {
     },
 
     /// The OS or application has requested that the window be redrawn.
-    RedrawRequested,
+    ///
+    /// Contains the parts of the window that need to be redrawn.
+    RedrawRequested(Damage),
+
+    /// The window system is ready for a new frame of the window, as requested with
+    /// `WindowExtUnix::request_frame`.
+    ///
+    /// ## Platform-specific
+    ///
+    /// Only emitted on X11, Wayland and the headless backend on Unix.
+    FrameReady(FrameTiming),
 
     /// Touch event has been received
     Touch(Touch),
@@ -208,6 +334,7 @@ pub enum WindowEvent {
 /// identifies its origin. Note that devices may be virtual (representing an on-screen cursor and keyboard focus) or
 /// physical. Virtual devices typically aggregate inputs from multiple physical devices.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct DeviceId(pub(crate) platform_impl::DeviceId);
 
 impl DeviceId {
@@ -230,6 +357,7 @@ impl DeviceId {
 ///
 /// Note that these events are delivered regardless of input focus.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum DeviceEvent {
     Added,
     Removed,
@@ -291,6 +419,46 @@ pub struct KeyboardInput {
     /// This is tracked internally to avoid tracking errors arising from modifier key state changes when events from
     /// this device are not being delivered to the application, e.g. due to keyboard focus being elsewhere.
     pub modifiers: ModifiersState,
+
+    /// Whether the key press was generated by holding the key down, rather than by pressing it.
+    ///
+    /// This is always `false` for key releases and for `DeviceEvent::Key`.
+    pub is_repeat: bool,
+}
+
+/// Describes a key press or release through the keyboard layout.
+///
+/// See the [`keyboard`](../keyboard/index.html) module for how the key is identified.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct KeyEvent {
+    /// The physical key, which doesn't depend on the keyboard layout.
+    pub physical_key: KeyCode,
+
+    /// The platform-specific code of the physical key, which is the same as the `scancode` of
+    /// `KeyboardInput`.
+    pub scancode: ScanCode,
+
+    /// The meaning of the key under the current keyboard layout and modifiers.
+    ///
+    /// For example, this is `Key::Character("A")` when `Shift` and the `a` key of a US keyboard
+    /// are pressed.
+    pub logical_key: Key,
+
+    /// The text produced by a key press, if any.
+    ///
+    /// This is always `None` for releases and dead keys, and when the key press is part of an
+    /// input method composition, whose text is delivered as `Ime::Commit` instead.
+    pub text: Option<String>,
+
+    /// Which copy of the key was used, for keys appearing several times on the keyboard.
+    pub location: KeyLocation,
+
+    pub state: ElementState,
+
+    /// Whether the key press was generated by holding the key down, which is the same as the
+    /// `is_repeat` of `KeyboardInput`.
+    pub is_repeat: bool,
 }
 
 /// Describes touch-screen input state.
@@ -319,6 +487,7 @@ pub enum TouchPhase {
 ///
 /// Touch may be cancelled if for example window lost focus.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Touch {
     pub device_id: DeviceId,
     pub phase: TouchPhase,
@@ -336,6 +505,75 @@ pub type AxisId = u32;
 /// Identifier for a specific button on some device.
 pub type ButtonId = u32;
 
+/// Identifies a file descriptor registered with `EventLoopWindowTargetExtUnix::register_fd`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FdToken(pub(crate) u64);
+
+/// Identifies a timer registered with `EventLoopWindowTargetExtUnix::register_timer`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TimerToken(pub(crate) u64);
+
+/// The parts of a window that need to be redrawn, given with `WindowEvent::RedrawRequested`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Damage {
+    /// The whole window needs to be redrawn.
+    Full,
+    /// Only the union of these rectangles needs to be redrawn.
+    ///
+    /// ## Platform-specific
+    ///
+    /// Only reported on X11, Wayland and the headless backend on Unix, which redraw the whole
+    /// window otherwise.
+    Region(Vec<DamageRect>),
+}
+
+impl Damage {
+    // Adds a rectangle to the damage, which stays unchanged if it already covers the whole
+    // window.
+    pub(crate) fn add_rect(&mut self, rect: DamageRect) {
+        if let Damage::Region(ref mut rects) = *self {
+            if !rects.contains(&rect) {
+                rects.push(rect);
+            }
+        }
+    }
+}
+
+/// A rectangle of a window, in physical pixels from the top-left corner of its client area.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DamageRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// The timing of the display a window is shown on, given with `WindowEvent::FrameReady` to pace
+/// animations.
+#[derive(Default, Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FrameTiming {
+    /// When the last frame of the window was presented, if the window system reports it.
+    #[cfg_attr(feature = "serde", serde(with = "instant_serde::option"))]
+    pub presentation_time: Option<Instant>,
+    /// The time between two refreshes of the display, if it's known.
+    pub refresh_interval: Option<Duration>,
+}
+
+/// Whether a file descriptor is ready to be read from and written to.
+///
+/// This is also used to choose which of the two to wait for.
+#[derive(Default, Debug, Hash, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FdReadiness {
+    pub readable: bool,
+    pub writable: bool,
+}
+
 /// Describes the input state of a key.
 #[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -354,6 +592,45 @@ pub enum MouseButton {
     Other(u8),
 }
 
+/// Describes what a drop target does with the data of a drag.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DragAction {
+    /// The data is copied.
+    Copy,
+    /// The data is moved, so the source is expected to delete it.
+    Move,
+    /// A link to the data is created.
+    Link,
+}
+
+/// Describes an event from an input method editor (IME).
+///
+/// A composition starts with a non-empty `Preedit`, is updated by further `Preedit` events, and
+/// ends either with an empty `Preedit` (the composition was cancelled) or with an empty `Preedit`
+/// followed by a `Commit`.
+#[derive(Debug, Hash, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Ime {
+    /// The input method was enabled for the window.
+    ///
+    /// From now on, the window may receive `Preedit` and `Commit` events.
+    Enabled,
+    /// The text being composed (the pre-edit string) has changed.
+    ///
+    /// The second field is the byte range of the cursor or selection inside the string, where
+    /// both ends are equal if there is only a cursor. It is `None` if the cursor should be hidden.
+    /// An empty string means that there is no composition in progress anymore.
+    Preedit(String, Option<(usize, usize)>),
+    /// The input method committed a string, which should be inserted into the text.
+    Commit(String),
+    /// The input method was disabled for the window.
+    ///
+    /// Any composition in progress is dropped and no further `Preedit` or `Commit` events are
+    /// sent until the window receives `Enabled` again.
+    Disabled,
+}
+
 /// Describes a difference in the mouse scroll wheel state.
 #[derive(Debug, Clone, Copy, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -589,4 +866,98 @@ pub struct ModifiersState {
     ///
     /// This is the "windows" key on PC and "command" key on Mac.
     pub logo: bool,
+    /// The "AltGr" key, which selects the alternative characters of the keyboard layout
+    pub alt_graph: bool,
+    /// Whether caps lock is on
+    pub caps_lock: bool,
+    /// Whether num lock is on
+    pub num_lock: bool,
+    /// Which of the left and right modifier keys are held down
+    ///
+    /// ## Platform-specific
+    ///
+    /// Only set on Windows, X11 and Wayland.
+    pub sides: ModifierSides,
+}
+
+/// Represents the modifier keys held down on each side of the keyboard
+///
+/// Each field of this struct is `true` if the matching key is held down. A modifier of
+/// `ModifiersState` can be active while none of its keys is held down, for instance when it is
+/// latched.
+#[derive(Default, Debug, Hash, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ModifierSides {
+    pub left_shift: bool,
+    pub right_shift: bool,
+    pub left_ctrl: bool,
+    pub right_ctrl: bool,
+    pub left_alt: bool,
+    pub right_alt: bool,
+    pub left_logo: bool,
+    pub right_logo: bool,
+}
+
+/// Serde support for the `Instant`s of `StartCause`.
+///
+/// An `Instant` only has a meaning inside of the process it was taken in, so it's stored as the
+/// wall-clock time it corresponds to, in nanoseconds since the Unix epoch. Deserializing maps
+/// that time back to an `Instant` of the current process.
+#[cfg(feature = "serde")]
+mod instant_serde {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+    lazy_static! {
+        // An `Instant` and the wall-clock time it was taken at, to convert between the two.
+        static ref ANCHOR: (Instant, SystemTime) = (Instant::now(), SystemTime::now());
+    }
+
+    fn to_nanos(instant: Instant) -> u64 {
+        let (anchor, anchor_time) = *ANCHOR;
+        let time = if instant >= anchor {
+            anchor_time.checked_add(instant - anchor)
+        } else {
+            anchor_time.checked_sub(anchor - instant)
+        };
+        time.and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|since_epoch| since_epoch.as_nanos() as u64)
+            .unwrap_or(0)
+    }
+
+    fn from_nanos(nanos: u64) -> Instant {
+        let (anchor, anchor_time) = *ANCHOR;
+        let instant = match (UNIX_EPOCH + Duration::from_nanos(nanos)).duration_since(anchor_time) {
+            Ok(after) => anchor.checked_add(after),
+            Err(before) => anchor.checked_sub(before.duration()),
+        };
+        instant.unwrap_or(anchor)
+    }
+
+    pub fn serialize<S: Serializer>(instant: &Instant, serializer: S) -> Result<S::Ok, S::Error> {
+        to_nanos(*instant).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Instant, D::Error> {
+        u64::deserialize(deserializer).map(from_nanos)
+    }
+
+    pub mod option {
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+        use std::time::Instant;
+
+        pub fn serialize<S: Serializer>(
+            instant: &Option<Instant>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            instant.map(super::to_nanos).serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<Instant>, D::Error> {
+            Option::<u64>::deserialize(deserializer).map(|nanos| nanos.map(super::from_nanos))
+        }
+    }
 }
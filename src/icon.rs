@@ -1,4 +1,4 @@
-use std::{error::Error, fmt, mem};
+use std::{error::Error, fmt, mem, time::Duration};
 
 #[repr(C)]
 #[derive(Debug)]
@@ -12,7 +12,8 @@ pub(crate) struct Pixel {
 pub(crate) const PIXEL_SIZE: usize = mem::size_of::<Pixel>();
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-/// An error produced when using `Icon::from_rgba` with invalid arguments.
+/// An error produced when using `Icon::from_rgba` or `CustomCursor::from_rgba` with invalid
+/// arguments.
 pub enum BadIcon {
     /// Produced when the length of the `rgba` argument isn't divisible by 4, thus `rgba` can't be
     /// safely interpreted as 32bpp RGBA pixels.
@@ -25,6 +26,18 @@ pub enum BadIcon {
         width_x_height: usize,
         pixel_count: usize,
     },
+    /// Produced when the hotspot of a `CustomCursor` lies outside of its image.
+    HotspotOutOfBounds {
+        width: u32,
+        height: u32,
+        hotspot_x: u32,
+        hotspot_y: u32,
+    },
+    /// Produced when `CustomCursor::from_frames` is given no frames.
+    NoFrames,
+    /// Produced when `CustomCursor::from_frames` is given a frame delay shorter than a
+    /// millisecond.
+    FrameDelayTooShort { delay: Duration },
 }
 
 impl fmt::Display for BadIcon {
@@ -43,6 +56,20 @@ impl fmt::Display for BadIcon {
                 "The specified dimensions ({:?}x{:?}) don't match the number of pixels supplied by the `rgba` argument ({:?}). For those dimensions, the expected pixel count is {:?}.",
                 width, height, pixel_count, width_x_height,
             ),
+            &BadIcon::HotspotOutOfBounds {
+                width,
+                height,
+                hotspot_x,
+                hotspot_y,
+            } => format!(
+                "The hotspot ({:?}, {:?}) lies outside of the {:?}x{:?} cursor image.",
+                hotspot_x, hotspot_y, width, height,
+            ),
+            &BadIcon::NoFrames => "An animated cursor needs at least one frame.".to_owned(),
+            &BadIcon::FrameDelayTooShort { delay } => format!(
+                "The frame delay ({:?}) of an animated cursor is shorter than a millisecond.",
+                delay,
+            ),
         };
         write!(f, "{}", msg)
     }
@@ -66,31 +93,122 @@ pub struct Icon {
     pub(crate) height: u32,
 }
 
+fn check_rgba(rgba: &[u8], width: u32, height: u32) -> Result<(), BadIcon> {
+    if rgba.len() % PIXEL_SIZE != 0 {
+        return Err(BadIcon::ByteCountNotDivisibleBy4 {
+            byte_count: rgba.len(),
+        });
+    }
+    let pixel_count = rgba.len() / PIXEL_SIZE;
+    if pixel_count != (width * height) as usize {
+        Err(BadIcon::DimensionsVsPixelCount {
+            width,
+            height,
+            width_x_height: (width * height) as usize,
+            pixel_count,
+        })
+    } else {
+        Ok(())
+    }
+}
+
 impl Icon {
     /// Creates an `Icon` from 32bpp RGBA data.
     ///
     /// The length of `rgba` must be divisible by 4, and `width * height` must equal
     /// `rgba.len() / 4`. Otherwise, this will return a `BadIcon` error.
     pub fn from_rgba(rgba: Vec<u8>, width: u32, height: u32) -> Result<Self, BadIcon> {
-        if rgba.len() % PIXEL_SIZE != 0 {
-            return Err(BadIcon::ByteCountNotDivisibleBy4 {
-                byte_count: rgba.len(),
-            });
-        }
-        let pixel_count = rgba.len() / PIXEL_SIZE;
-        if pixel_count != (width * height) as usize {
-            Err(BadIcon::DimensionsVsPixelCount {
+        check_rgba(&rgba, width, height)?;
+        Ok(Icon {
+            rgba,
+            width,
+            height,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// An image used as the mouse cursor, see `Window::set_custom_cursor`.
+///
+/// It can be a still image, or an animation made of several frames.
+pub struct CustomCursor {
+    pub(crate) frames: Vec<CursorFrame>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CursorFrame {
+    pub(crate) rgba: Vec<u8>,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) hotspot_x: u32,
+    pub(crate) hotspot_y: u32,
+    // How long the frame is shown before moving on to the next one
+    pub(crate) delay: Duration,
+}
+
+impl CustomCursor {
+    /// Creates a `CustomCursor` from 32bpp RGBA data.
+    ///
+    /// The hotspot is the pixel the cursor points with, counted from the top-left corner of the
+    /// image.
+    ///
+    /// The length of `rgba` must be divisible by 4, `width * height` must equal `rgba.len() / 4`,
+    /// and the hotspot must lie within the image. Otherwise, this will return a `BadIcon` error.
+    pub fn from_rgba(
+        rgba: Vec<u8>,
+        width: u32,
+        height: u32,
+        hotspot_x: u32,
+        hotspot_y: u32,
+    ) -> Result<Self, BadIcon> {
+        check_rgba(&rgba, width, height)?;
+        if hotspot_x >= width || hotspot_y >= height {
+            return Err(BadIcon::HotspotOutOfBounds {
                 width,
                 height,
-                width_x_height: (width * height) as usize,
-                pixel_count,
-            })
-        } else {
-            Ok(Icon {
+                hotspot_x,
+                hotspot_y,
+            });
+        }
+        Ok(CustomCursor {
+            frames: vec![CursorFrame {
                 rgba,
                 width,
                 height,
+                hotspot_x,
+                hotspot_y,
+                delay: Duration::from_millis(0),
+            }],
+        })
+    }
+
+    /// Creates an animated `CustomCursor` from a sequence of frames, each shown for the given
+    /// delay before moving on to the next one. The animation starts over after the last frame.
+    ///
+    /// Frames may have different sizes and hotspots. If one of them is itself animated, all of
+    /// its frames are used, each shown for the given delay.
+    ///
+    /// Returns `BadIcon::NoFrames` if `frames` is empty, and `BadIcon::FrameDelayTooShort` if a
+    /// delay is shorter than a millisecond, which is the precision of the delays on X11.
+    pub fn from_frames(frames: Vec<(CustomCursor, Duration)>) -> Result<Self, BadIcon> {
+        if let Some(&(_, delay)) = frames
+            .iter()
+            .find(|&&(_, delay)| delay < Duration::from_millis(1))
+        {
+            return Err(BadIcon::FrameDelayTooShort { delay });
+        }
+        let frames = frames
+            .into_iter()
+            .flat_map(|(cursor, delay)| {
+                cursor
+                    .frames
+                    .into_iter()
+                    .map(move |frame| CursorFrame { delay, ..frame })
             })
+            .collect::<Vec<_>>();
+        if frames.is_empty() {
+            return Err(BadIcon::NoFrames);
         }
+        Ok(CustomCursor { frames })
     }
 }
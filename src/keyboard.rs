@@ -0,0 +1,567 @@
+//! Types describing keys of the keyboard, independently of and according to the keyboard layout.
+//!
+//! A key press is reported by [`WindowEvent::Key`][key_event] as a [`KeyEvent`][KeyEvent], which
+//! identifies the key in three ways:
+//!
+//! - The [`KeyCode`] is the physical key, which doesn't change with the keyboard layout. Use it
+//!   when the location of the key matters more than its meaning, such as for movement controls
+//!   in a first-person game.
+//! - The [`Key`] is the meaning of the key under the current keyboard layout and modifiers,
+//!   such as `Key::Character("q")` for the physical `KeyCode::KeyA` of an AZERTY keyboard. Use it
+//!   for keyboard shortcuts.
+//! - The text is what the key press would insert into a text field, if anything.
+//!
+//! The active [`KeyboardLayout`] tells which of the layouts of the keyboard gives the keys their
+//! meaning. Users switching between several layouts are reported by
+//! [`Event::KeyboardLayoutChanged`][layout_event].
+//!
+//! [key_event]: ../event/enum.WindowEvent.html#variant.Key
+//! [layout_event]: ../event/enum.Event.html#variant.KeyboardLayoutChanged
+//! [KeyEvent]: ../event/struct.KeyEvent.html
+//! [`KeyCode`]: ./enum.KeyCode.html
+//! [`Key`]: ./enum.Key.html
+//! [`KeyboardLayout`]: ./struct.KeyboardLayout.html
+
+use std::time::Duration;
+
+/// The physical location of a key on the keyboard, regardless of the keyboard layout.
+///
+/// The variants are named after the key producing them on a US keyboard, following the
+/// [`code` values of the UI Events specification][spec].
+///
+/// [spec]: https://www.w3.org/TR/uievents-code/
+#[derive(Debug, Hash, Ord, PartialOrd, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum KeyCode {
+    /// `` ` `` on a US keyboard, and `半角/全角/漢字` on a Japanese keyboard.
+    Backquote,
+    /// `\` on a US keyboard. This is located under `Enter` on ISO keyboards.
+    Backslash,
+    /// `[` on a US keyboard.
+    BracketLeft,
+    /// `]` on a US keyboard.
+    BracketRight,
+    /// `,` on a US keyboard.
+    Comma,
+    /// `0` on a US keyboard.
+    Digit0,
+    /// `1` on a US keyboard.
+    Digit1,
+    /// `2` on a US keyboard.
+    Digit2,
+    /// `3` on a US keyboard.
+    Digit3,
+    /// `4` on a US keyboard.
+    Digit4,
+    /// `5` on a US keyboard.
+    Digit5,
+    /// `6` on a US keyboard.
+    Digit6,
+    /// `7` on a US keyboard.
+    Digit7,
+    /// `8` on a US keyboard.
+    Digit8,
+    /// `9` on a US keyboard.
+    Digit9,
+    /// `=` on a US keyboard.
+    Equal,
+    /// The extra key next to the left `Shift` of ISO keyboards.
+    IntlBackslash,
+    /// `ろ` on a Japanese keyboard.
+    IntlRo,
+    /// `¥` on a Japanese keyboard.
+    IntlYen,
+    /// `a` on a US keyboard, and `q` on an AZERTY keyboard.
+    KeyA,
+    /// `b` on a US keyboard.
+    KeyB,
+    /// `c` on a US keyboard.
+    KeyC,
+    /// `d` on a US keyboard.
+    KeyD,
+    /// `e` on a US keyboard.
+    KeyE,
+    /// `f` on a US keyboard.
+    KeyF,
+    /// `g` on a US keyboard.
+    KeyG,
+    /// `h` on a US keyboard.
+    KeyH,
+    /// `i` on a US keyboard.
+    KeyI,
+    /// `j` on a US keyboard.
+    KeyJ,
+    /// `k` on a US keyboard.
+    KeyK,
+    /// `l` on a US keyboard.
+    KeyL,
+    /// `m` on a US keyboard.
+    KeyM,
+    /// `n` on a US keyboard.
+    KeyN,
+    /// `o` on a US keyboard.
+    KeyO,
+    /// `p` on a US keyboard.
+    KeyP,
+    /// `q` on a US keyboard, and `a` on an AZERTY keyboard.
+    KeyQ,
+    /// `r` on a US keyboard.
+    KeyR,
+    /// `s` on a US keyboard.
+    KeyS,
+    /// `t` on a US keyboard.
+    KeyT,
+    /// `u` on a US keyboard.
+    KeyU,
+    /// `v` on a US keyboard.
+    KeyV,
+    /// `w` on a US keyboard, and `z` on an AZERTY keyboard.
+    KeyW,
+    /// `x` on a US keyboard.
+    KeyX,
+    /// `y` on a US keyboard, and `z` on a QWERTZ keyboard.
+    KeyY,
+    /// `z` on a US keyboard, `w` on an AZERTY keyboard and `y` on a QWERTZ keyboard.
+    KeyZ,
+    /// `-` on a US keyboard.
+    Minus,
+    /// `.` on a US keyboard.
+    Period,
+    /// `'` on a US keyboard.
+    Quote,
+    /// `;` on a US keyboard.
+    Semicolon,
+    /// `/` on a US keyboard.
+    Slash,
+    AltLeft,
+    /// This is labeled `AltGr` on many keyboards.
+    AltRight,
+    Backspace,
+    CapsLock,
+    /// The application menu key.
+    ContextMenu,
+    ControlLeft,
+    ControlRight,
+    Enter,
+    /// The left "logo" key: the "windows" key on PC and "command" key on Mac.
+    SuperLeft,
+    /// The right "logo" key: the "windows" key on PC and "command" key on Mac.
+    SuperRight,
+    ShiftLeft,
+    ShiftRight,
+    Space,
+    Tab,
+    /// `変換` on a Japanese keyboard.
+    Convert,
+    /// `カタカナ/ひらがな/ローマ字` on a Japanese keyboard.
+    KanaMode,
+    /// `한/영` on a Korean keyboard.
+    Lang1,
+    /// `한자` on a Korean keyboard.
+    Lang2,
+    /// `カタカナ` on a Japanese keyboard.
+    Lang3,
+    /// `ひらがな` on a Japanese keyboard.
+    Lang4,
+    /// `無変換` on a Japanese keyboard.
+    NonConvert,
+    Delete,
+    End,
+    Help,
+    Home,
+    Insert,
+    PageDown,
+    PageUp,
+    ArrowDown,
+    ArrowLeft,
+    ArrowRight,
+    ArrowUp,
+    NumLock,
+    Numpad0,
+    Numpad1,
+    Numpad2,
+    Numpad3,
+    Numpad4,
+    Numpad5,
+    Numpad6,
+    Numpad7,
+    Numpad8,
+    Numpad9,
+    NumpadAdd,
+    NumpadComma,
+    NumpadDecimal,
+    NumpadDivide,
+    NumpadEnter,
+    NumpadEqual,
+    NumpadMultiply,
+    NumpadSubtract,
+    Escape,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    F13,
+    F14,
+    F15,
+    F16,
+    F17,
+    F18,
+    F19,
+    F20,
+    F21,
+    F22,
+    F23,
+    F24,
+    PrintScreen,
+    ScrollLock,
+    Pause,
+    BrowserBack,
+    BrowserFavorites,
+    BrowserForward,
+    BrowserHome,
+    BrowserRefresh,
+    BrowserSearch,
+    BrowserStop,
+    Eject,
+    /// This is usually the "Calculator" key.
+    LaunchApp2,
+    LaunchMail,
+    MediaPlayPause,
+    MediaSelect,
+    MediaStop,
+    MediaTrackNext,
+    MediaTrackPrevious,
+    Power,
+    Sleep,
+    AudioVolumeDown,
+    AudioVolumeMute,
+    AudioVolumeUp,
+    WakeUp,
+    Again,
+    Copy,
+    Cut,
+    Find,
+    Open,
+    Paste,
+    Props,
+    Undo,
+    /// The key couldn't be identified. The `scancode` of the event can tell it apart from other
+    /// keys.
+    Unidentified,
+}
+
+/// The meaning of a key under the current keyboard layout and modifiers.
+#[derive(Debug, Hash, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Key {
+    /// A key that has a name rather than producing a character.
+    Named(NamedKey),
+    /// A key producing a character, such as `"a"`, `"A"` or `"é"`.
+    ///
+    /// This is usually a single character, but may hold several of them.
+    Character(String),
+    /// A dead key, which modifies the character produced by the next key press.
+    ///
+    /// This holds the accent it adds, if it is known. The text of the event is empty.
+    Dead(Option<char>),
+    /// The meaning of the key couldn't be identified.
+    Unidentified,
+}
+
+impl Key {
+    /// Returns the character produced by the key, if it is a `Key::Character`.
+    pub fn to_text(&self) -> Option<&str> {
+        match *self {
+            Key::Character(ref text) => Some(text),
+            _ => None,
+        }
+    }
+}
+
+/// The name of a key that doesn't produce a character.
+///
+/// The variants follow the [`key` values of the UI Events specification][spec].
+///
+/// [spec]: https://www.w3.org/TR/uievents-key/
+#[derive(Debug, Hash, Ord, PartialOrd, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum NamedKey {
+    Alt,
+    /// The key selecting the third level of the layout, labeled `AltGr` on many keyboards.
+    AltGraph,
+    CapsLock,
+    Control,
+    NumLock,
+    ScrollLock,
+    Shift,
+    /// The "logo" key: the "windows" key on PC and "command" key on Mac.
+    Super,
+    Enter,
+    Tab,
+    ArrowDown,
+    ArrowLeft,
+    ArrowRight,
+    ArrowUp,
+    End,
+    Home,
+    PageDown,
+    PageUp,
+    Backspace,
+    Clear,
+    Copy,
+    Cut,
+    Delete,
+    Insert,
+    Paste,
+    Redo,
+    Undo,
+    Cancel,
+    /// The application menu key.
+    ContextMenu,
+    Escape,
+    Execute,
+    Find,
+    Help,
+    Pause,
+    Select,
+    PrintScreen,
+    /// The key starting a compose sequence.
+    Compose,
+    Convert,
+    NonConvert,
+    AllCandidates,
+    PreviousCandidate,
+    Eisu,
+    Hankaku,
+    Hiragana,
+    HiraganaKatakana,
+    KanaMode,
+    KanjiMode,
+    Katakana,
+    Romaji,
+    Zenkaku,
+    ZenkakuHankaku,
+    HangulMode,
+    HanjaMode,
+    AudioVolumeDown,
+    AudioVolumeMute,
+    AudioVolumeUp,
+    MediaPause,
+    MediaPlayPause,
+    MediaRecord,
+    MediaStop,
+    MediaTrackNext,
+    MediaTrackPrevious,
+    BrowserBack,
+    BrowserFavorites,
+    BrowserForward,
+    BrowserHome,
+    BrowserRefresh,
+    BrowserSearch,
+    BrowserStop,
+    LaunchApplication1,
+    LaunchApplication2,
+    LaunchMail,
+    Eject,
+    Power,
+    Standby,
+    WakeUp,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    F13,
+    F14,
+    F15,
+    F16,
+    F17,
+    F18,
+    F19,
+    F20,
+    F21,
+    F22,
+    F23,
+    F24,
+}
+
+impl NamedKey {
+    /// Returns the label of the key, in English, for displaying it to the user, e.g. in
+    /// keybinding settings.
+    ///
+    /// The label is the name of the variant with a space before each word or number, such as
+    /// `"Page Up"` for `NamedKey::PageUp` or `"Launch Application 1"` for
+    /// `NamedKey::LaunchApplication1`, except for the following keys, which get the label printed
+    /// on most keyboards:
+    ///
+    /// | Key | Label |
+    /// | --- | ----- |
+    /// | `AltGraph` | `"AltGr"` |
+    /// | `Control` | `"Ctrl"` |
+    /// | `Escape` | `"Esc"` |
+    /// | `ArrowDown` | `"Down"` |
+    /// | `ArrowLeft` | `"Left"` |
+    /// | `ArrowRight` | `"Right"` |
+    /// | `ArrowUp` | `"Up"` |
+    /// | `ContextMenu` | `"Menu"` |
+    /// | `AudioVolumeDown` | `"Volume Down"` |
+    /// | `AudioVolumeMute` | `"Mute"` |
+    /// | `AudioVolumeUp` | `"Volume Up"` |
+    pub fn label(self) -> &'static str {
+        match self {
+            NamedKey::Alt => "Alt",
+            NamedKey::AltGraph => "AltGr",
+            NamedKey::CapsLock => "Caps Lock",
+            NamedKey::Control => "Ctrl",
+            NamedKey::NumLock => "Num Lock",
+            NamedKey::ScrollLock => "Scroll Lock",
+            NamedKey::Shift => "Shift",
+            NamedKey::Super => "Super",
+            NamedKey::Enter => "Enter",
+            NamedKey::Tab => "Tab",
+            NamedKey::ArrowDown => "Down",
+            NamedKey::ArrowLeft => "Left",
+            NamedKey::ArrowRight => "Right",
+            NamedKey::ArrowUp => "Up",
+            NamedKey::End => "End",
+            NamedKey::Home => "Home",
+            NamedKey::PageDown => "Page Down",
+            NamedKey::PageUp => "Page Up",
+            NamedKey::Backspace => "Backspace",
+            NamedKey::Clear => "Clear",
+            NamedKey::Copy => "Copy",
+            NamedKey::Cut => "Cut",
+            NamedKey::Delete => "Delete",
+            NamedKey::Insert => "Insert",
+            NamedKey::Paste => "Paste",
+            NamedKey::Redo => "Redo",
+            NamedKey::Undo => "Undo",
+            NamedKey::Cancel => "Cancel",
+            NamedKey::ContextMenu => "Menu",
+            NamedKey::Escape => "Esc",
+            NamedKey::Execute => "Execute",
+            NamedKey::Find => "Find",
+            NamedKey::Help => "Help",
+            NamedKey::Pause => "Pause",
+            NamedKey::Select => "Select",
+            NamedKey::PrintScreen => "Print Screen",
+            NamedKey::Compose => "Compose",
+            NamedKey::Convert => "Convert",
+            NamedKey::NonConvert => "Non Convert",
+            NamedKey::AllCandidates => "All Candidates",
+            NamedKey::PreviousCandidate => "Previous Candidate",
+            NamedKey::Eisu => "Eisu",
+            NamedKey::Hankaku => "Hankaku",
+            NamedKey::Hiragana => "Hiragana",
+            NamedKey::HiraganaKatakana => "Hiragana Katakana",
+            NamedKey::KanaMode => "Kana Mode",
+            NamedKey::KanjiMode => "Kanji Mode",
+            NamedKey::Katakana => "Katakana",
+            NamedKey::Romaji => "Romaji",
+            NamedKey::Zenkaku => "Zenkaku",
+            NamedKey::ZenkakuHankaku => "Zenkaku Hankaku",
+            NamedKey::HangulMode => "Hangul Mode",
+            NamedKey::HanjaMode => "Hanja Mode",
+            NamedKey::AudioVolumeDown => "Volume Down",
+            NamedKey::AudioVolumeMute => "Mute",
+            NamedKey::AudioVolumeUp => "Volume Up",
+            NamedKey::MediaPause => "Media Pause",
+            NamedKey::MediaPlayPause => "Media Play Pause",
+            NamedKey::MediaRecord => "Media Record",
+            NamedKey::MediaStop => "Media Stop",
+            NamedKey::MediaTrackNext => "Media Track Next",
+            NamedKey::MediaTrackPrevious => "Media Track Previous",
+            NamedKey::BrowserBack => "Browser Back",
+            NamedKey::BrowserFavorites => "Browser Favorites",
+            NamedKey::BrowserForward => "Browser Forward",
+            NamedKey::BrowserHome => "Browser Home",
+            NamedKey::BrowserRefresh => "Browser Refresh",
+            NamedKey::BrowserSearch => "Browser Search",
+            NamedKey::BrowserStop => "Browser Stop",
+            NamedKey::LaunchApplication1 => "Launch Application 1",
+            NamedKey::LaunchApplication2 => "Launch Application 2",
+            NamedKey::LaunchMail => "Launch Mail",
+            NamedKey::Eject => "Eject",
+            NamedKey::Power => "Power",
+            NamedKey::Standby => "Standby",
+            NamedKey::WakeUp => "Wake Up",
+            NamedKey::F1 => "F1",
+            NamedKey::F2 => "F2",
+            NamedKey::F3 => "F3",
+            NamedKey::F4 => "F4",
+            NamedKey::F5 => "F5",
+            NamedKey::F6 => "F6",
+            NamedKey::F7 => "F7",
+            NamedKey::F8 => "F8",
+            NamedKey::F9 => "F9",
+            NamedKey::F10 => "F10",
+            NamedKey::F11 => "F11",
+            NamedKey::F12 => "F12",
+            NamedKey::F13 => "F13",
+            NamedKey::F14 => "F14",
+            NamedKey::F15 => "F15",
+            NamedKey::F16 => "F16",
+            NamedKey::F17 => "F17",
+            NamedKey::F18 => "F18",
+            NamedKey::F19 => "F19",
+            NamedKey::F20 => "F20",
+            NamedKey::F21 => "F21",
+            NamedKey::F22 => "F22",
+            NamedKey::F23 => "F23",
+            NamedKey::F24 => "F24",
+        }
+    }
+}
+
+/// The location of a key on the keyboard, for keys appearing several times.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum KeyLocation {
+    /// The key only appears once, or its location doesn't matter.
+    Standard,
+    /// The left copy of a key, such as the left `Shift`.
+    Left,
+    /// The right copy of a key, such as the right `Shift`.
+    Right,
+    /// The key is on the numeric keypad.
+    Numpad,
+}
+
+/// One of the layouts of the keyboard, such as a French and a Russian layout between which the
+/// user switches.
+#[derive(Debug, Hash, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct KeyboardLayout {
+    /// The index of the layout among the layouts of the keyboard, which is also known as its
+    /// group.
+    pub index: u32,
+    /// The human-readable name of the layout, such as `"English (US)"`.
+    ///
+    /// This is empty if the keyboard doesn't name its layouts.
+    pub name: String,
+}
+
+/// How held keys repeat, as configured by the user.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct KeyRepeatInfo {
+    /// How long a key has to be held down before it starts repeating.
+    pub delay: Duration,
+    /// The number of repeated key presses per second, which is 0 if keys don't repeat.
+    pub rate: u32,
+}
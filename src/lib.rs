@@ -55,7 +55,7 @@
 //!             window.request_redraw();
 //!         },
 //!         Event::WindowEvent {
-//!             event: WindowEvent::RedrawRequested,
+//!             event: WindowEvent::RedrawRequested(_),
 //!             ..
 //!         } => {
 //!             // Redraw the application.
@@ -136,8 +136,10 @@ pub mod error;
 pub mod event;
 pub mod event_loop;
 mod icon;
+pub mod keyboard;
 pub mod monitor;
 mod platform_impl;
+pub mod recording;
 pub mod window;
 
 pub mod platform;
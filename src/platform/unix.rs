@@ -1,19 +1,37 @@
-#![cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd"))]
-
-use std::{os::raw, ptr, sync::Arc};
+#![cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+
+use std::{
+    io,
+    os::{raw, unix::io::RawFd},
+    ptr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use smithay_client_toolkit::window::{ButtonState, Theme};
 
 use crate::{
     dpi::LogicalSize,
-    event_loop::EventLoop,
+    error::ExternalError,
+    event::{Event, FdReadiness, FdToken, TimerToken},
+    event_loop::{ControlFlow, EventLoop, EventLoopWindowTarget},
+    keyboard::{KeyCode, KeyRepeatInfo, KeyboardLayout},
     monitor::MonitorHandle,
+    platform::desktop::EventLoopExtDesktop,
+    recording::EventReplayer,
     window::{Window, WindowBuilder},
 };
 
 use crate::platform_impl::{
     x11::{ffi::XVisualInfo, XConnection},
-    EventLoop as LinuxEventLoop, Window as LinuxWindow,
+    Clipboard as LinuxClipboard, EventLoop as LinuxEventLoop,
+    EventLoopWindowTarget as LinuxEventLoopWindowTarget, Window as LinuxWindow,
 };
 
 // TODO: stupid hack so that glutin can do its work
@@ -22,6 +40,8 @@ pub use crate::platform_impl::x11;
 
 pub use crate::platform_impl::{x11::util::WindowType as XWindowType, XNotSupported};
 
+pub use crate::platform_impl::headless::{HeadlessControl, HeadlessMonitor, HeadlessWindowState};
+
 /// Theme for wayland client side decorations
 ///
 /// Colors must be in ARGB8888 format
@@ -90,6 +110,227 @@ impl Theme for WaylandThemeObject {
     }
 }
 
+/// A handle to the system clipboard, or to the primary selection.
+///
+/// The primary selection holds whatever was last selected, and is usually pasted with a middle
+/// click. It works the same way as the clipboard, but isn't available on every compositor.
+///
+/// Contents are offered under one or more MIME types (or X11 targets), and are served by the
+/// event loop for as long as this application owns the clipboard. They are therefore only
+/// available to other applications while the event loop is running.
+///
+/// Reading blocks until the owner of the clipboard has sent its data, so it shouldn't be done
+/// from inside the event loop callback when the owner could be another window of this same
+/// process. Reading from the clipboard this application set itself is always fine.
+#[derive(Clone)]
+pub struct Clipboard {
+    inner: LinuxClipboard,
+}
+
+impl Clipboard {
+    /// Whether the current backend supports this selection.
+    ///
+    /// This is always true on X11. On Wayland, the clipboard needs `wl_data_device_manager`
+    /// and the primary selection needs `zwp_primary_selection_device_manager_v1`. When it
+    /// returns `false`, every other method returns `ExternalError::NotSupported`.
+    #[inline]
+    pub fn is_supported(&self) -> bool {
+        self.inner.is_supported()
+    }
+
+    /// Replaces the contents of the clipboard with the given text.
+    ///
+    /// The text is offered under the common text MIME types and X11 targets.
+    #[inline]
+    pub fn set_text(&self, text: &str) -> Result<(), ExternalError> {
+        self.inner.set_text(text)
+    }
+
+    /// Replaces the contents of the clipboard, as a list of `(mime_type, data)` pairs.
+    ///
+    /// Pairs earlier in the list are advertised first, so they should be the preferred formats.
+    #[inline]
+    pub fn set_contents(&self, contents: Vec<(String, Vec<u8>)>) -> Result<(), ExternalError> {
+        self.inner.set_contents(contents)
+    }
+
+    /// Empties the clipboard, if this application is currently the one that owns it.
+    #[inline]
+    pub fn clear(&self) -> Result<(), ExternalError> {
+        self.inner.clear()
+    }
+
+    /// Returns the MIME types (or X11 targets) the current contents are available as.
+    #[inline]
+    pub fn mime_types(&self) -> Result<Vec<String>, ExternalError> {
+        self.inner.mime_types()
+    }
+
+    /// Returns the current contents of the clipboard as text.
+    ///
+    /// Returns `Ok(None)` if the clipboard is empty or doesn't contain any text.
+    #[inline]
+    pub fn text(&self) -> Result<Option<String>, ExternalError> {
+        self.inner.text()
+    }
+
+    /// Returns the current contents of the clipboard as the given MIME type.
+    ///
+    /// Returns `Ok(None)` if the clipboard is empty or the contents aren't available as
+    /// `mime_type`.
+    #[inline]
+    pub fn contents(&self, mime_type: &str) -> Result<Option<Vec<u8>>, ExternalError> {
+        self.inner.contents(mime_type)
+    }
+}
+
+/// Additional methods on `EventLoopWindowTarget` that are specific to Unix.
+pub trait EventLoopWindowTargetExtUnix {
+    /// Returns a handle to the system clipboard.
+    fn clipboard(&self) -> Clipboard;
+
+    /// Returns a handle to the primary selection.
+    ///
+    /// Use `Clipboard::is_supported` to check whether the backend supports it.
+    fn primary_selection(&self) -> Clipboard;
+
+    /// Returns the active layout of the keyboard.
+    ///
+    /// Returns `None` if it isn't known yet, such as on Wayland before the compositor sent the
+    /// keymap, or on X11 if libxkbcommon-x11 isn't available.
+    fn keyboard_layout(&self) -> Option<KeyboardLayout>;
+
+    /// Returns the repeat delay and rate of held keys.
+    ///
+    /// Returns `None` if they couldn't be queried from the X server. On Wayland, defaults are
+    /// returned until the compositor sends them.
+    fn key_repeat_info(&self) -> Option<KeyRepeatInfo>;
+
+    /// Returns the label of a physical key under the active keyboard layout, to display it in
+    /// keybinding settings.
+    ///
+    /// This is what the key produces without modifiers: the character in uppercase, such as
+    /// `"Ñ"` for `KeyCode::Semicolon` on a Spanish layout, the accent of a dead key, or the
+    /// `NamedKey::label`, such as `"Enter"` or `"Page Up"`.
+    ///
+    /// Returns `None` if the key isn't part of the layout, or if the keymap isn't known, like for
+    /// `keyboard_layout`.
+    fn key_label(&self, key: KeyCode) -> Option<String>;
+
+    /// Returns the physical key with a label under the active keyboard layout, ignoring case.
+    ///
+    /// This is the reverse of `key_label`.
+    fn key_from_label(&self, label: &str) -> Option<KeyCode>;
+
+    /// Returns a handle to drive the headless backend from a test: injecting events, configuring
+    /// monitors, inspecting windows and advancing the clock.
+    ///
+    /// Returns `None` if the event loop doesn't use the headless backend.
+    fn headless_control(&self) -> Option<HeadlessControl>;
+
+    /// Watches a file descriptor, such as a socket, a pipe or an inotify instance, emitting
+    /// `Event::FdReady` with the returned token whenever it's ready for what `interest` asks for.
+    ///
+    /// This can be called from the event handler, as well as on the `EventLoop` before it runs.
+    /// The file descriptor stays owned by the caller, and must be unregistered with
+    /// `unregister_fd` before it's closed.
+    ///
+    /// Returns an error on the headless backend, which doesn't wait on file descriptors.
+    fn register_fd(&self, fd: RawFd, interest: FdReadiness) -> io::Result<FdToken>;
+
+    /// Stops watching a file descriptor registered with `register_fd`.
+    fn unregister_fd(&self, token: FdToken);
+
+    /// Starts a timer, emitting `Event::TimerFired` with the returned token once `deadline` is
+    /// reached, and then every `interval` if it's a repeating timer.
+    ///
+    /// This can be called from the event handler, as well as on the `EventLoop` before it runs.
+    /// Timers wake the event loop up whatever its `ControlFlow`, and any number of them can be
+    /// running at once. A repeating timer that falls behind fires once for all the intervals it
+    /// missed. On Linux, they're backed by a `timerfd`, so they fire with the precision of the
+    /// kernel's monotonic clock. On the headless backend, the deadlines are compared to the clock
+    /// of `HeadlessControl`, so timers only fire once it's advanced past them.
+    ///
+    /// Returns an error if `interval` is zero.
+    fn register_timer(
+        &self,
+        deadline: Instant,
+        interval: Option<Duration>,
+    ) -> io::Result<TimerToken>;
+
+    /// Stops a timer started with `register_timer`. One-shot timers stop on their own once
+    /// they've fired.
+    fn unregister_timer(&self, token: TimerToken);
+}
+
+impl<T> EventLoopWindowTargetExtUnix for EventLoopWindowTarget<T> {
+    #[inline]
+    fn clipboard(&self) -> Clipboard {
+        Clipboard {
+            inner: self.p.clipboard(),
+        }
+    }
+
+    #[inline]
+    fn primary_selection(&self) -> Clipboard {
+        Clipboard {
+            inner: self.p.primary_selection(),
+        }
+    }
+
+    #[inline]
+    fn keyboard_layout(&self) -> Option<KeyboardLayout> {
+        self.p.keyboard_layout()
+    }
+
+    #[inline]
+    fn key_repeat_info(&self) -> Option<KeyRepeatInfo> {
+        self.p.key_repeat_info()
+    }
+
+    #[inline]
+    fn key_label(&self, key: KeyCode) -> Option<String> {
+        self.p.key_label(key)
+    }
+
+    #[inline]
+    fn key_from_label(&self, label: &str) -> Option<KeyCode> {
+        self.p.key_from_label(label)
+    }
+
+    #[inline]
+    fn headless_control(&self) -> Option<HeadlessControl> {
+        match self.p {
+            LinuxEventLoopWindowTarget::Headless(ref evlp) => Some(evlp.control()),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    fn register_fd(&self, fd: RawFd, interest: FdReadiness) -> io::Result<FdToken> {
+        self.p.register_fd(fd, interest)
+    }
+
+    #[inline]
+    fn unregister_fd(&self, token: FdToken) {
+        self.p.unregister_fd(token)
+    }
+
+    #[inline]
+    fn register_timer(
+        &self,
+        deadline: Instant,
+        interval: Option<Duration>,
+    ) -> io::Result<TimerToken> {
+        self.p.register_timer(deadline, interval)
+    }
+
+    #[inline]
+    fn unregister_timer(&self, token: TimerToken) {
+        self.p.unregister_timer(token)
+    }
+}
+
 /// Additional methods on `EventLoop` that are specific to Unix.
 pub trait EventLoopExtUnix {
     /// Builds a new `EventLoops` that is forced to use X11.
@@ -102,12 +343,25 @@ pub trait EventLoopExtUnix {
     where
         Self: Sized;
 
+    /// Builds a new `EventLoop` that uses the headless backend, which needs no display server.
+    ///
+    /// Windows only exist in memory, and the event loop only dispatches the events injected
+    /// through `EventLoopWindowTargetExtUnix::headless_control`. This is meant for testing
+    /// applications deterministically. Setting `WINIT_UNIX_BACKEND=headless` makes
+    /// `EventLoop::new` build this backend as well.
+    fn new_headless() -> Self
+    where
+        Self: Sized;
+
     /// True if the `EventLoop` uses Wayland.
     fn is_wayland(&self) -> bool;
 
     /// True if the `EventLoop` uses X11.
     fn is_x11(&self) -> bool;
 
+    /// True if the `EventLoop` uses the headless backend.
+    fn is_headless(&self) -> bool;
+
     #[doc(hidden)]
     fn xlib_xconnection(&self) -> Option<Arc<XConnection>>;
 
@@ -139,6 +393,14 @@ impl<T> EventLoopExtUnix for EventLoop<T> {
         }
     }
 
+    #[inline]
+    fn new_headless() -> Self {
+        EventLoop {
+            event_loop: LinuxEventLoop::new_headless(),
+            _marker: ::std::marker::PhantomData,
+        }
+    }
+
     #[inline]
     fn is_wayland(&self) -> bool {
         self.event_loop.is_wayland()
@@ -146,7 +408,12 @@ impl<T> EventLoopExtUnix for EventLoop<T> {
 
     #[inline]
     fn is_x11(&self) -> bool {
-        !self.event_loop.is_wayland()
+        self.event_loop.is_x11()
+    }
+
+    #[inline]
+    fn is_headless(&self) -> bool {
+        self.event_loop.is_headless()
     }
 
     #[inline]
@@ -167,6 +434,98 @@ impl<T> EventLoopExtUnix for EventLoop<T> {
     }
 }
 
+/// Additional methods on `EventReplayer` that are specific to Unix.
+pub trait EventReplayerExtUnix {
+    /// A type provided by the user that can be passed through `Event::UserEvent`.
+    type UserEvent;
+
+    /// Replays the recorded events to an event handler, through an event loop that uses the
+    /// headless backend.
+    ///
+    /// The event loop runs like with `EventLoopExtDesktop::run_return`, so `event_handler` can be
+    /// the one the application gives to `EventLoop::run`. The recorded `WindowEvent`s,
+    /// `DeviceEvent`s and `UserEvent`s are injected once the clock of the event loop reaches
+    /// their time, the clock being advanced to the next of them, or to the `ControlFlow::WaitUntil`
+    /// deadline of the handler if it comes first. The other events, such as `NewEvents` or
+    /// `RedrawRequested`, are skipped, as the event loop produces them from what the handler does.
+    ///
+    /// Returns once all events are replayed, or when the handler sets `ControlFlow::Exit`.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `event_loop` doesn't use the headless backend.
+    fn replay_headless<F>(self, event_loop: &mut EventLoop<Self::UserEvent>, event_handler: F)
+    where
+        F: FnMut(Event<Self::UserEvent>, &EventLoopWindowTarget<Self::UserEvent>, &mut ControlFlow);
+}
+
+impl<T: 'static> EventReplayerExtUnix for EventReplayer<T> {
+    type UserEvent = T;
+
+    fn replay_headless<F>(mut self, event_loop: &mut EventLoop<T>, mut event_handler: F)
+    where
+        F: FnMut(Event<T>, &EventLoopWindowTarget<T>, &mut ControlFlow),
+    {
+        let control = event_loop
+            .headless_control()
+            .expect("Recorded events can only be replayed through the headless backend");
+        let proxy = event_loop.create_proxy();
+        let start = control.now();
+
+        event_loop.run_return(|event, target, control_flow| {
+            match event {
+                Event::EventsCleared => event_handler(event, target, control_flow),
+                _ => return event_handler(event, target, control_flow),
+            }
+            if *control_flow == ControlFlow::Exit {
+                return;
+            }
+
+            // Skips the times at which only events the event loop produces were recorded, as
+            // nothing would wake it up.
+            loop {
+                let next_time = match self.next_time() {
+                    Some(next_time) => next_time,
+                    None => {
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    }
+                };
+                let now = control.now();
+                if let ControlFlow::WaitUntil(deadline) = *control_flow {
+                    if deadline < start + next_time {
+                        if deadline > now {
+                            control.advance_clock(deadline - now);
+                        }
+                        return;
+                    }
+                }
+                if start + next_time > now {
+                    control.advance_clock(start + next_time - now);
+                }
+
+                let mut injected = false;
+                while let Some(event) = self.next_event(next_time) {
+                    match event {
+                        Event::WindowEvent { window_id, event } => {
+                            control.send_window_event(window_id, event)
+                        }
+                        Event::DeviceEvent { event, .. } => control.send_device_event(event),
+                        Event::UserEvent(event) => {
+                            let _ = proxy.send_event(event);
+                        }
+                        _ => continue,
+                    }
+                    injected = true;
+                }
+                if injected {
+                    return;
+                }
+            }
+        });
+    }
+}
+
 /// Additional methods on `Window` that are specific to Unix.
 pub trait WindowExtUnix {
     /// Returns the ID of the `Window` xlib object that is used by this window.
@@ -213,6 +572,30 @@ pub trait WindowExtUnix {
     /// Sets the color theme of the client side window decorations on wayland
     fn set_wayland_theme(&self, theme: WaylandTheme);
 
+    /// Returns a handle to the system clipboard.
+    fn clipboard(&self) -> Clipboard;
+
+    /// Returns a handle to the primary selection.
+    ///
+    /// Use `Clipboard::is_supported` to check whether the backend supports it.
+    fn primary_selection(&self) -> Clipboard;
+
+    /// Requests a `WindowEvent::FrameReady` for when the window system is ready for a new frame,
+    /// which paces redraws on the refresh rate of the display instead of drawing as fast as
+    /// possible.
+    ///
+    /// Several requests before the event is emitted are merged into one.
+    ///
+    /// ## Platform-specific
+    ///
+    /// - **Wayland:** The request applies to the next commit of the window's contents, so it
+    ///   should be made before presenting the current frame. The event is emitted after a frame
+    ///   callback of the compositor, and isn't emitted while the window is hidden.
+    /// - **X11:** The event is emitted on the next vertical blank of the display, through the
+    ///   Present extension, or on a timer ticking at 60Hz if it isn't available.
+    /// - **Headless:** The event is emitted along with the pending `RedrawRequested` events.
+    fn request_frame(&self);
+
     /// Check if the window is ready for drawing
     ///
     /// It is a remnant of a previous implementation detail for the
@@ -296,6 +679,25 @@ impl WindowExtUnix for Window {
         }
     }
 
+    #[inline]
+    fn clipboard(&self) -> Clipboard {
+        Clipboard {
+            inner: self.window.clipboard(),
+        }
+    }
+
+    #[inline]
+    fn primary_selection(&self) -> Clipboard {
+        Clipboard {
+            inner: self.window.primary_selection(),
+        }
+    }
+
+    #[inline]
+    fn request_frame(&self) {
+        self.window.request_frame()
+    }
+
     #[inline]
     fn is_ready(&self) -> bool {
         true
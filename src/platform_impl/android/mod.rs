@@ -15,7 +15,10 @@ use std::{
 use crate::{
     error::{ExternalError, NotSupportedError},
     events::{Touch, TouchPhase},
-    window::MonitorHandle as RootMonitorHandle,
+    window::{
+        CursorGrabMode, CustomCursor, DragData, ImeHints, ImePurpose,
+        MonitorHandle as RootMonitorHandle,
+    },
     CreationError, CursorIcon, Event, LogicalPosition, LogicalSize, PhysicalPosition, PhysicalSize,
     WindowAttributes, WindowEvent, WindowId as RootWindowId,
 };
@@ -162,6 +165,7 @@ impl EventLoopProxy {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct WindowId;
 
 impl WindowId {
@@ -171,6 +175,7 @@ impl WindowId {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct DeviceId;
 
 impl DeviceId {
@@ -344,7 +349,12 @@ impl Window {
     }
 
     #[inline]
-    pub fn set_cursor_grab(&self, _grab: bool) -> Result<(), ExternalError> {
+    pub fn set_custom_cursor(&self, _: &CustomCursor) {
+        // N/A
+    }
+
+    #[inline]
+    pub fn set_cursor_grab(&self, _mode: CursorGrabMode) -> Result<(), ExternalError> {
         Err(ExternalError::NotSupported(NotSupportedError::new()))
     }
 
@@ -353,6 +363,16 @@ impl Window {
         // N/A
     }
 
+    #[inline]
+    pub fn start_drag(&self, _data: DragData) -> Result<(), ExternalError> {
+        Err(ExternalError::NotSupported(NotSupportedError::new()))
+    }
+
+    #[inline]
+    pub fn accept_drop(&self, _mime_type: Option<&str>) {
+        // N/A
+    }
+
     #[inline]
     pub fn set_cursor_position(&self, _position: LogicalPosition) -> Result<(), ExternalError> {
         Err(ExternalError::NotSupported(NotSupportedError::new()))
@@ -393,7 +413,22 @@ impl Window {
     }
 
     #[inline]
-    pub fn set_ime_position(&self, _spot: LogicalPosition) {
+    pub fn set_ime_position(&self, _spot: LogicalPosition, _size: LogicalSize) {
+        // N/A
+    }
+
+    #[inline]
+    pub fn set_ime_allowed(&self, _allowed: bool) {
+        // N/A
+    }
+
+    #[inline]
+    pub fn set_ime_purpose(&self, _purpose: ImePurpose, _hints: ImeHints) {
+        // N/A
+    }
+
+    #[inline]
+    pub fn set_key_repeat(&self, _enabled: bool) {
         // N/A
     }
 
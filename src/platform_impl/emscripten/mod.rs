@@ -17,7 +17,10 @@ use std::{
 use crate::{
     dpi::{LogicalPosition, LogicalSize, PhysicalPosition, PhysicalSize},
     error::{ExternalError, NotSupportedError},
-    window::MonitorHandle as RootMonitorHandle,
+    window::{
+        CursorGrabMode, CustomCursor, DragData, ImeHints, ImePurpose,
+        MonitorHandle as RootMonitorHandle,
+    },
 };
 
 const DOCUMENT_NAME: &'static str = "#document\0";
@@ -35,6 +38,7 @@ unsafe impl Sync for PlatformSpecificWindowBuilderAttributes {}
 pub type OsError = std::io::Error;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct DeviceId;
 
 impl DeviceId {
@@ -177,6 +181,7 @@ impl EventLoop {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct WindowId(usize);
 
 impl WindowId {
@@ -228,6 +233,7 @@ extern "C" fn mouse_callback(
             ctrl: (*event).ctrlKey == ffi::EM_TRUE,
             alt: (*event).altKey == ffi::EM_TRUE,
             logo: (*event).metaKey == ffi::EM_TRUE,
+            ..Default::default()
         };
 
         match event_type {
@@ -294,6 +300,7 @@ extern "C" fn keyboard_callback(
             ctrl: (*event).ctrlKey == ffi::EM_TRUE,
             alt: (*event).altKey == ffi::EM_TRUE,
             logo: (*event).metaKey == ffi::EM_TRUE,
+            ..Default::default()
         };
 
         match event_type {
@@ -307,6 +314,7 @@ extern "C" fn keyboard_callback(
                             state: ::ElementState::Pressed,
                             virtual_keycode: key_translate_virt((*event).key, (*event).location),
                             modifiers,
+                            is_repeat: (*event).repeat == ffi::EM_TRUE,
                         },
                     },
                 });
@@ -321,6 +329,7 @@ extern "C" fn keyboard_callback(
                             state: ::ElementState::Released,
                             virtual_keycode: key_translate_virt((*event).key, (*event).location),
                             modifiers,
+                            is_repeat: false,
                         },
                     },
                 });
@@ -602,7 +611,20 @@ impl Window {
     }
 
     #[inline]
-    pub fn set_cursor_grab(&self, grab: bool) -> Result<(), ExternalError> {
+    pub fn set_custom_cursor(&self, _cursor: &CustomCursor) {
+        // N/A
+    }
+
+    #[inline]
+    pub fn set_cursor_grab(&self, mode: CursorGrabMode) -> Result<(), ExternalError> {
+        // Only pointer lock is available in browsers.
+        let grab = match mode {
+            CursorGrabMode::None => false,
+            CursorGrabMode::Locked => true,
+            CursorGrabMode::Confined => {
+                return Err(ExternalError::NotSupported(NotSupportedError::new()))
+            }
+        };
         let mut grabbed_lock = self.window.cursor_grabbed.lock().unwrap();
         if grab == *grabbed_lock {
             return Ok(());
@@ -647,6 +669,16 @@ impl Window {
         *visible_lock = visible;
     }
 
+    #[inline]
+    pub fn start_drag(&self, _data: DragData) -> Result<(), ExternalError> {
+        Err(ExternalError::NotSupported(NotSupportedError::new()))
+    }
+
+    #[inline]
+    pub fn accept_drop(&self, _mime_type: Option<&str>) {
+        // N/A
+    }
+
     #[inline]
     pub fn hidpi_factor(&self) -> f64 {
         hidpi_factor()
@@ -688,7 +720,22 @@ impl Window {
     }
 
     #[inline]
-    pub fn set_ime_position(&self, _logical_spot: LogicalPosition) {
+    pub fn set_ime_position(&self, _logical_spot: LogicalPosition, _size: LogicalSize) {
+        // N/A
+    }
+
+    #[inline]
+    pub fn set_ime_allowed(&self, _allowed: bool) {
+        // N/A
+    }
+
+    #[inline]
+    pub fn set_ime_purpose(&self, _purpose: ImePurpose, _hints: ImeHints) {
+        // N/A
+    }
+
+    #[inline]
+    pub fn set_key_repeat(&self, _enabled: bool) {
         // N/A
     }
 
@@ -723,7 +770,7 @@ impl Drop for Window {
         unsafe {
             // Return back to normal cursor state
             self.hide_cursor(false);
-            self.set_cursor_grab(false);
+            self.set_cursor_grab(CursorGrabMode::None);
 
             // Exit fullscreen if on
             if self.window.is_fullscreen {
@@ -99,6 +99,23 @@ impl DeviceId {
 unsafe impl Send for DeviceId {}
 unsafe impl Sync for DeviceId {}
 
+// The screen is only ever compared, so its pointer is stored as a plain number.
+#[cfg(feature = "serde")]
+impl serde::Serialize for DeviceId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(&(self.uiscreen as usize), serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for DeviceId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <usize as serde::Deserialize>::deserialize(deserializer).map(|uiscreen| DeviceId {
+            uiscreen: uiscreen as ffi::id,
+        })
+    }
+}
+
 #[derive(Debug)]
 pub enum OsError {}
 
@@ -6,7 +6,7 @@ use objc::{
 };
 
 use crate::{
-    event::{DeviceId as RootDeviceId, Event, Touch, TouchPhase, WindowEvent},
+    event::{Damage, DeviceId as RootDeviceId, Event, Touch, TouchPhase, WindowEvent},
     platform::ios::MonitorHandleExtIOS,
     window::{WindowAttributes, WindowId as RootWindowId},
 };
@@ -43,7 +43,7 @@ unsafe fn get_view_class(root_view_class: &'static Class) -> &'static Class {
                 let window: id = msg_send![object, window];
                 AppState::handle_nonuser_event(Event::WindowEvent {
                     window_id: RootWindowId(window.into()),
-                    event: WindowEvent::RedrawRequested,
+                    event: WindowEvent::RedrawRequested(Damage::Full),
                 });
                 let superclass: &'static Class = msg_send![object, superclass];
                 let () = msg_send![super(object, superclass), drawRect: rect];
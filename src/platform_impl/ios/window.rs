@@ -8,6 +8,7 @@ use objc::runtime::{Class, Object, NO, YES};
 use crate::{
     dpi::{self, LogicalPosition, LogicalSize},
     error::{ExternalError, NotSupportedError, OsError as RootOsError},
+    event::DamageRect,
     icon::Icon,
     monitor::MonitorHandle as RootMonitorHandle,
     platform::ios::{MonitorHandleExtIOS, ValidOrientations},
@@ -17,7 +18,9 @@ use crate::{
         ffi::{id, CGFloat, CGPoint, CGRect, CGSize, UIEdgeInsets, UIInterfaceOrientationMask},
         monitor, view, EventLoopWindowTarget, MonitorHandle,
     },
-    window::{CursorIcon, WindowAttributes},
+    window::{
+        CursorGrabMode, CursorIcon, CustomCursor, DragData, ImeHints, ImePurpose, WindowAttributes,
+    },
 };
 
 pub struct Inner {
@@ -59,6 +62,10 @@ impl Inner {
         }
     }
 
+    pub fn request_redraw_region(&self, _rect: DamageRect) {
+        self.request_redraw();
+    }
+
     pub fn inner_position(&self) -> Result<LogicalPosition, NotSupportedError> {
         unsafe {
             let safe_area = self.safe_area_screen_space();
@@ -141,11 +148,15 @@ impl Inner {
         debug!("`Window::set_cursor_icon` ignored on iOS")
     }
 
+    pub fn set_custom_cursor(&self, _cursor: &CustomCursor) {
+        debug!("`Window::set_custom_cursor` ignored on iOS")
+    }
+
     pub fn set_cursor_position(&self, _position: LogicalPosition) -> Result<(), ExternalError> {
         Err(ExternalError::NotSupported(NotSupportedError::new()))
     }
 
-    pub fn set_cursor_grab(&self, _grab: bool) -> Result<(), ExternalError> {
+    pub fn set_cursor_grab(&self, _mode: CursorGrabMode) -> Result<(), ExternalError> {
         Err(ExternalError::NotSupported(NotSupportedError::new()))
     }
 
@@ -153,6 +164,14 @@ impl Inner {
         debug!("`Window::set_cursor_visible` is ignored on iOS")
     }
 
+    pub fn start_drag(&self, _data: DragData) -> Result<(), ExternalError> {
+        Err(ExternalError::NotSupported(NotSupportedError::new()))
+    }
+
+    pub fn accept_drop(&self, _mime_type: Option<&str>) {
+        debug!("`Window::accept_drop` is ignored on iOS")
+    }
+
     pub fn set_maximized(&self, _maximized: bool) {
         warn!("`Window::set_maximized` is ignored on iOS")
     }
@@ -214,10 +233,22 @@ impl Inner {
         warn!("`Window::set_window_icon` is ignored on iOS")
     }
 
-    pub fn set_ime_position(&self, _position: LogicalPosition) {
+    pub fn set_ime_position(&self, _position: LogicalPosition, _size: LogicalSize) {
         warn!("`Window::set_ime_position` is ignored on iOS")
     }
 
+    pub fn set_ime_allowed(&self, _allowed: bool) {
+        warn!("`Window::set_ime_allowed` is ignored on iOS")
+    }
+
+    pub fn set_ime_purpose(&self, _purpose: ImePurpose, _hints: ImeHints) {
+        warn!("`Window::set_ime_purpose` is ignored on iOS")
+    }
+
+    pub fn set_key_repeat(&self, _enabled: bool) {
+        warn!("`Window::set_key_repeat` is ignored on iOS")
+    }
+
     pub fn current_monitor(&self) -> RootMonitorHandle {
         unsafe {
             let uiscreen: id = msg_send![self.window, screen];
@@ -467,6 +498,23 @@ impl WindowId {
 unsafe impl Send for WindowId {}
 unsafe impl Sync for WindowId {}
 
+// The window is only ever compared, so its pointer is stored as a plain number.
+#[cfg(feature = "serde")]
+impl serde::Serialize for WindowId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(&(self.window as usize), serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for WindowId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <usize as serde::Deserialize>::deserialize(deserializer).map(|window| WindowId {
+            window: window as id,
+        })
+    }
+}
+
 impl From<&Object> for WindowId {
     fn from(window: &Object) -> WindowId {
         WindowId {
@@ -0,0 +1,66 @@
+use std::sync::Arc;
+
+use crate::{error::ExternalError, event::Event};
+
+use super::event_loop::Shared;
+
+/// A selection held in memory, which only the application itself can read and write.
+#[derive(Clone)]
+pub struct Clipboard {
+    shared: Arc<Shared>,
+    // Whether this is the primary selection rather than the clipboard
+    primary: bool,
+}
+
+impl Clipboard {
+    pub(super) fn new(shared: Arc<Shared>, primary: bool) -> Clipboard {
+        Clipboard { shared, primary }
+    }
+
+    pub fn is_supported(&self) -> bool {
+        true
+    }
+
+    pub fn set_contents(&self, contents: Vec<(String, Vec<u8>)>) -> Result<(), ExternalError> {
+        let primary = self.primary;
+        self.shared.update(move |state| {
+            if primary {
+                state.primary_selection = contents;
+            } else {
+                state.clipboard = contents;
+                state.pending_events.push_back(Event::ClipboardChanged);
+            }
+        });
+        Ok(())
+    }
+
+    pub fn clear(&self) -> Result<(), ExternalError> {
+        self.set_contents(Vec::new())
+    }
+
+    pub fn mime_types(&self) -> Result<Vec<String>, ExternalError> {
+        let state = self.shared.state.lock().unwrap();
+        let contents = if self.primary {
+            &state.primary_selection
+        } else {
+            &state.clipboard
+        };
+        Ok(contents
+            .iter()
+            .map(|(mime_type, _)| mime_type.clone())
+            .collect())
+    }
+
+    pub fn contents(&self, mime_type: &str) -> Result<Option<Vec<u8>>, ExternalError> {
+        let state = self.shared.state.lock().unwrap();
+        let contents = if self.primary {
+            &state.primary_selection
+        } else {
+            &state.clipboard
+        };
+        Ok(contents
+            .iter()
+            .find(|&(offered, _)| offered == mime_type)
+            .map(|(_, data)| data.clone()))
+    }
+}
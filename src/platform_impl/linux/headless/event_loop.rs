@@ -0,0 +1,457 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    io, mem,
+    sync::{Arc, Condvar, Mutex, Weak},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    event::{Damage, DeviceEvent, Event, FrameTiming, StartCause, TimerToken, WindowEvent},
+    event_loop::{ControlFlow, EventLoopClosed, EventLoopWindowTarget as RootELW},
+    platform_impl::platform::{sticky_exit_callback, timer::TimerSet},
+};
+
+use super::{
+    make_wid, Clipboard, DeviceId, HeadlessMonitor, HeadlessWindowState, MonitorHandle, WindowId,
+};
+
+// What the event loop shares with its windows, proxies and controls.
+pub(super) struct Shared {
+    pub state: Mutex<State>,
+    // Notified whenever the state changes, to wake the event loop up.
+    condvar: Condvar,
+}
+
+impl Shared {
+    // Changes the state, and wakes the event loop up so that it sees the change.
+    pub fn update<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut State) -> R,
+    {
+        let result = f(&mut self.state.lock().unwrap());
+        self.condvar.notify_all();
+        result
+    }
+}
+
+pub(super) struct State {
+    // Never empty, the first one being the primary monitor
+    pub monitors: Vec<HeadlessMonitor>,
+    pub windows: BTreeMap<WindowId, HeadlessWindowState>,
+    pub next_window_id: u64,
+    pub pending_events: VecDeque<Event<()>>,
+    // Ordered so that redraws are dispatched in the same order on every run
+    pub pending_redraws: BTreeMap<WindowId, Damage>,
+    pub pending_frames: BTreeSet<WindowId>,
+    // Set when a proxy sends a user event
+    pub user_event_sent: bool,
+    pub clipboard: Vec<(String, Vec<u8>)>,
+    pub primary_selection: Vec<(String, Vec<u8>)>,
+    // Compared to the virtual clock
+    timers: TimerSet,
+    // The virtual clock starts when the event loop is created, and only moves when advanced.
+    start: Instant,
+    elapsed: Duration,
+}
+
+impl State {
+    pub fn now(&self) -> Instant {
+        self.start + self.elapsed
+    }
+
+    pub fn primary_monitor(&self) -> MonitorHandle {
+        MonitorHandle {
+            id: 0,
+            monitor: self.monitors[0].clone(),
+        }
+    }
+
+    pub fn available_monitors(&self) -> VecDeque<MonitorHandle> {
+        self.monitors
+            .iter()
+            .enumerate()
+            .map(|(id, monitor)| MonitorHandle {
+                id: id as u32,
+                monitor: monitor.clone(),
+            })
+            .collect()
+    }
+
+    fn has_events(&self) -> bool {
+        let timer_expired = match self.timers.next_deadline() {
+            Some(deadline) => deadline <= self.now(),
+            None => false,
+        };
+        timer_expired
+            || self.user_event_sent
+            || !self.pending_events.is_empty()
+            || !self.pending_redraws.is_empty()
+            || !self.pending_frames.is_empty()
+    }
+
+    // Queues the events of the timers whose deadline the clock reached.
+    fn expire_timers(&mut self) {
+        let now = self.now();
+        for token in self.timers.expire(now) {
+            self.pending_events.push_back(Event::TimerFired { token });
+        }
+    }
+}
+
+/// Drives the headless backend from a test.
+///
+/// It configures the monitors, injects events into the event loop, reads back the state of the
+/// windows, and controls the clock `ControlFlow::WaitUntil` deadlines are compared to. It can be
+/// sent to another thread to inject events while the event loop waits for them.
+#[derive(Clone)]
+pub struct HeadlessControl {
+    shared: Arc<Shared>,
+}
+
+impl HeadlessControl {
+    /// Replaces the monitors, the first of which becomes the primary monitor.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `monitors` is empty.
+    pub fn set_monitors(&self, monitors: Vec<HeadlessMonitor>) {
+        assert!(!monitors.is_empty(), "The headless backend needs a monitor");
+        self.shared.update(|state| state.monitors = monitors);
+    }
+
+    /// Returns the device injected events come from.
+    pub fn device_id(&self) -> crate::event::DeviceId {
+        crate::event::DeviceId(crate::platform_impl::DeviceId::Headless(DeviceId))
+    }
+
+    /// Queues an event for a window.
+    ///
+    /// `Resized`, `Moved`, `HiDpiFactorChanged` and `Focused` also update the state of the window,
+    /// as the window system would.
+    pub fn send_window_event(&self, window_id: crate::window::WindowId, event: WindowEvent) {
+        let wid = match window_id.0 {
+            crate::platform_impl::WindowId::Headless(wid) => wid,
+            _ => return,
+        };
+        self.shared.update(|state| {
+            if let Some(window) = state.windows.get_mut(&wid) {
+                match event {
+                    WindowEvent::Resized(size) => window.inner_size = size,
+                    WindowEvent::Moved(position) => window.outer_position = position,
+                    WindowEvent::HiDpiFactorChanged(factor) => window.hidpi_factor = factor,
+                    WindowEvent::Focused(focused) => window.focused = focused,
+                    _ => (),
+                }
+            }
+            state
+                .pending_events
+                .push_back(Event::WindowEvent { window_id, event });
+        });
+    }
+
+    /// Queues an event from the device of `device_id`.
+    pub fn send_device_event(&self, event: DeviceEvent) {
+        let device_id = self.device_id();
+        self.shared.update(|state| {
+            state
+                .pending_events
+                .push_back(Event::DeviceEvent { device_id, event })
+        });
+    }
+
+    /// Returns the state of a window, or `None` if it was dropped.
+    pub fn window_state(&self, window_id: crate::window::WindowId) -> Option<HeadlessWindowState> {
+        match window_id.0 {
+            crate::platform_impl::WindowId::Headless(wid) => {
+                self.shared.state.lock().unwrap().windows.get(&wid).cloned()
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the current time of the clock.
+    ///
+    /// The clock starts when the event loop is created, and only moves through `advance_clock`.
+    /// The times of `StartCause` are read from it.
+    pub fn now(&self) -> Instant {
+        self.shared.state.lock().unwrap().now()
+    }
+
+    /// Moves the clock forward, waking the event loop up if it reaches the deadline of
+    /// `ControlFlow::WaitUntil` or of a timer started with
+    /// `EventLoopWindowTargetExtUnix::register_timer`.
+    pub fn advance_clock(&self, duration: Duration) {
+        self.shared.update(|state| state.elapsed += duration);
+    }
+}
+
+pub struct EventLoopWindowTarget<T> {
+    pub(super) shared: Arc<Shared>,
+    _marker: ::std::marker::PhantomData<T>,
+}
+
+impl<T> EventLoopWindowTarget<T> {
+    pub fn clipboard(&self) -> Clipboard {
+        Clipboard::new(self.shared.clone(), false)
+    }
+
+    pub fn primary_selection(&self) -> Clipboard {
+        Clipboard::new(self.shared.clone(), true)
+    }
+
+    pub fn control(&self) -> HeadlessControl {
+        HeadlessControl {
+            shared: self.shared.clone(),
+        }
+    }
+
+    pub fn register_timer(
+        &self,
+        deadline: Instant,
+        interval: Option<Duration>,
+    ) -> io::Result<TimerToken> {
+        self.shared
+            .update(|state| state.timers.insert(deadline, interval))
+    }
+
+    pub fn unregister_timer(&self, token: TimerToken) {
+        self.shared.update(|state| state.timers.remove(token));
+    }
+}
+
+pub struct EventLoop<T: 'static> {
+    // Shared with the proxies, which can't send events anymore once it is dropped
+    user_events: Arc<Mutex<VecDeque<T>>>,
+    window_target: RootELW<T>,
+}
+
+pub struct EventLoopProxy<T: 'static> {
+    user_events: Weak<Mutex<VecDeque<T>>>,
+    shared: Arc<Shared>,
+}
+
+impl<T: 'static> Clone for EventLoopProxy<T> {
+    fn clone(&self) -> Self {
+        EventLoopProxy {
+            user_events: self.user_events.clone(),
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T: 'static> EventLoopProxy<T> {
+    pub fn send_event(&self, event: T) -> Result<(), EventLoopClosed> {
+        let user_events = self.user_events.upgrade().ok_or(EventLoopClosed)?;
+        user_events.lock().unwrap().push_back(event);
+        self.shared.update(|state| state.user_event_sent = true);
+        Ok(())
+    }
+}
+
+impl<T: 'static> EventLoop<T> {
+    pub fn new() -> EventLoop<T> {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                monitors: vec![HeadlessMonitor::default()],
+                windows: BTreeMap::new(),
+                // 0 is the dummy window ID
+                next_window_id: 1,
+                pending_events: VecDeque::new(),
+                pending_redraws: BTreeMap::new(),
+                pending_frames: BTreeSet::new(),
+                user_event_sent: false,
+                clipboard: Vec::new(),
+                primary_selection: Vec::new(),
+                timers: TimerSet::default(),
+                start: Instant::now(),
+                elapsed: Duration::from_secs(0),
+            }),
+            condvar: Condvar::new(),
+        });
+        EventLoop {
+            user_events: Arc::new(Mutex::new(VecDeque::new())),
+            window_target: RootELW {
+                p: crate::platform_impl::EventLoopWindowTarget::Headless(EventLoopWindowTarget {
+                    shared,
+                    _marker: ::std::marker::PhantomData,
+                }),
+                _marker: ::std::marker::PhantomData,
+            },
+        }
+    }
+
+    pub fn create_proxy(&self) -> EventLoopProxy<T> {
+        EventLoopProxy {
+            user_events: Arc::downgrade(&self.user_events),
+            shared: self.shared().clone(),
+        }
+    }
+
+    pub fn run<F>(mut self, callback: F) -> !
+    where
+        F: 'static + FnMut(Event<T>, &RootELW<T>, &mut ControlFlow),
+    {
+        self.run_return(callback);
+        ::std::process::exit(0);
+    }
+
+    pub fn run_return<F>(&mut self, mut callback: F)
+    where
+        F: FnMut(Event<T>, &RootELW<T>, &mut ControlFlow),
+    {
+        let mut control_flow = ControlFlow::default();
+
+        callback(
+            Event::NewEvents(StartCause::Init),
+            &self.window_target,
+            &mut control_flow,
+        );
+
+        loop {
+            self.shared().update(State::expire_timers);
+
+            // The events queued by the callback are dispatched in the same iteration.
+            while let Some(event) = self.next_event() {
+                let event = event.map_nonuser_event().unwrap_or_else(|_| unreachable!());
+                sticky_exit_callback(event, &self.window_target, &mut control_flow, &mut callback);
+            }
+
+            self.shared().update(|state| state.user_event_sent = false);
+            while let Some(event) = self.next_user_event() {
+                sticky_exit_callback(
+                    Event::UserEvent(event),
+                    &self.window_target,
+                    &mut control_flow,
+                    &mut callback,
+                );
+            }
+
+            let redraws = self
+                .shared()
+                .update(|state| mem::take(&mut state.pending_redraws));
+            for (wid, damage) in redraws {
+                sticky_exit_callback(
+                    Event::WindowEvent {
+                        window_id: make_wid(wid),
+                        event: WindowEvent::RedrawRequested(damage),
+                    },
+                    &self.window_target,
+                    &mut control_flow,
+                    &mut callback,
+                );
+            }
+
+            // There is no display to pace the frames on.
+            let frames = self
+                .shared()
+                .update(|state| mem::take(&mut state.pending_frames));
+            for wid in frames {
+                sticky_exit_callback(
+                    Event::WindowEvent {
+                        window_id: make_wid(wid),
+                        event: WindowEvent::FrameReady(FrameTiming::default()),
+                    },
+                    &self.window_target,
+                    &mut control_flow,
+                    &mut callback,
+                );
+            }
+
+            sticky_exit_callback(
+                Event::EventsCleared,
+                &self.window_target,
+                &mut control_flow,
+                &mut callback,
+            );
+
+            let start = self.shared().state.lock().unwrap().now();
+            let cause = match control_flow {
+                ControlFlow::Exit => break,
+                ControlFlow::Poll => StartCause::Poll,
+                ControlFlow::Wait => {
+                    self.wait(None);
+                    StartCause::WaitCancelled {
+                        start,
+                        requested_resume: None,
+                    }
+                }
+                ControlFlow::WaitUntil(deadline) => {
+                    if self.wait(Some(deadline)) {
+                        StartCause::ResumeTimeReached {
+                            start,
+                            requested_resume: deadline,
+                        }
+                    } else {
+                        StartCause::WaitCancelled {
+                            start,
+                            requested_resume: Some(deadline),
+                        }
+                    }
+                }
+            };
+            callback(
+                Event::NewEvents(cause),
+                &self.window_target,
+                &mut control_flow,
+            );
+        }
+
+        callback(Event::LoopDestroyed, &self.window_target, &mut control_flow);
+    }
+
+    pub fn primary_monitor(&self) -> MonitorHandle {
+        self.shared().state.lock().unwrap().primary_monitor()
+    }
+
+    pub fn available_monitors(&self) -> VecDeque<MonitorHandle> {
+        self.shared().state.lock().unwrap().available_monitors()
+    }
+
+    pub fn window_target(&self) -> &RootELW<T> {
+        &self.window_target
+    }
+}
+
+/*
+ * Private EventLoop Internals
+ */
+
+impl<T> EventLoop<T> {
+    fn shared(&self) -> &Arc<Shared> {
+        match self.window_target.p {
+            crate::platform_impl::EventLoopWindowTarget::Headless(ref wt) => &wt.shared,
+            _ => unreachable!(),
+        }
+    }
+
+    // The lock is released before the event is dispatched, as the callback may queue events.
+    fn next_event(&self) -> Option<Event<()>> {
+        self.shared()
+            .state
+            .lock()
+            .unwrap()
+            .pending_events
+            .pop_front()
+    }
+
+    fn next_user_event(&self) -> Option<T> {
+        self.user_events.lock().unwrap().pop_front()
+    }
+
+    // Blocks until there are events to dispatch, or until the clock reaches the deadline, and
+    // returns whether the deadline was reached.
+    fn wait(&self, deadline: Option<Instant>) -> bool {
+        let shared = self.shared();
+        let mut state = shared.state.lock().unwrap();
+        loop {
+            let deadline_reached = match deadline {
+                Some(deadline) => state.now() >= deadline,
+                None => false,
+            };
+            if deadline_reached || state.has_events() {
+                return deadline_reached;
+            }
+            state = shared.condvar.wait(state).unwrap();
+        }
+    }
+}
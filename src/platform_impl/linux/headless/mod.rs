@@ -0,0 +1,38 @@
+#![cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+
+//! A backend without display server, for testing applications.
+//!
+//! Windows only exist in memory, and nothing happens unless a `HeadlessControl` injects it:
+//! input events, monitor changes and the passing of time are all up to the test driving the
+//! event loop.
+
+pub use self::{
+    clipboard::Clipboard,
+    event_loop::{EventLoop, EventLoopProxy, EventLoopWindowTarget, HeadlessControl},
+    monitor::{HeadlessMonitor, MonitorHandle},
+    window::{HeadlessWindowState, Window},
+};
+
+mod clipboard;
+mod event_loop;
+mod monitor;
+mod window;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DeviceId;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct WindowId(u64);
+
+#[inline]
+fn make_wid(wid: WindowId) -> crate::window::WindowId {
+    crate::window::WindowId(crate::platform_impl::WindowId::Headless(wid))
+}
@@ -0,0 +1,67 @@
+use crate::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    monitor::VideoMode,
+};
+
+/// A monitor of the headless backend, as configured by `HeadlessControl::set_monitors`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeadlessMonitor {
+    pub name: String,
+    pub size: PhysicalSize,
+    pub position: PhysicalPosition,
+    pub hidpi_factor: f64,
+}
+
+impl Default for HeadlessMonitor {
+    fn default() -> Self {
+        HeadlessMonitor {
+            name: "headless".to_owned(),
+            size: PhysicalSize::new(1920.0, 1080.0),
+            position: PhysicalPosition::new(0.0, 0.0),
+            hidpi_factor: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MonitorHandle {
+    // The index of the monitor in the configured list
+    pub(super) id: u32,
+    pub(super) monitor: HeadlessMonitor,
+}
+
+impl MonitorHandle {
+    #[inline]
+    pub fn name(&self) -> Option<String> {
+        Some(self.monitor.name.clone())
+    }
+
+    #[inline]
+    pub fn native_identifier(&self) -> u32 {
+        self.id
+    }
+
+    #[inline]
+    pub fn size(&self) -> PhysicalSize {
+        self.monitor.size
+    }
+
+    #[inline]
+    pub fn position(&self) -> PhysicalPosition {
+        self.monitor.position
+    }
+
+    #[inline]
+    pub fn hidpi_factor(&self) -> f64 {
+        self.monitor.hidpi_factor
+    }
+
+    #[inline]
+    pub fn video_modes(&self) -> impl Iterator<Item = VideoMode> {
+        std::iter::once(VideoMode {
+            size: self.monitor.size.into(),
+            bit_depth: 32,
+            refresh_rate: 60,
+        })
+    }
+}
@@ -0,0 +1,362 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use crate::{
+    dpi::{LogicalPosition, LogicalSize},
+    error::{ExternalError, NotSupportedError, OsError as RootOsError},
+    event::{Damage, DamageRect, Event, WindowEvent},
+    icon::Icon,
+    monitor::MonitorHandle as RootMonitorHandle,
+    platform_impl::{
+        MonitorHandle as PlatformMonitorHandle, PlatformSpecificWindowBuilderAttributes,
+    },
+    window::{CursorGrabMode, CursorIcon, CustomCursor, ImeHints, ImePurpose, WindowAttributes},
+};
+
+use super::{
+    event_loop::Shared, make_wid, Clipboard, EventLoopWindowTarget, MonitorHandle, WindowId,
+};
+
+/// The state of a window of the headless backend, as last set by the application or by an
+/// injected event.
+#[derive(Debug, Clone)]
+pub struct HeadlessWindowState {
+    pub title: String,
+    pub visible: bool,
+    pub inner_size: LogicalSize,
+    pub min_inner_size: Option<LogicalSize>,
+    pub max_inner_size: Option<LogicalSize>,
+    pub outer_position: LogicalPosition,
+    pub resizable: bool,
+    pub maximized: bool,
+    pub fullscreen: Option<RootMonitorHandle>,
+    pub decorations: bool,
+    pub always_on_top: bool,
+    pub hidpi_factor: f64,
+    /// Whether the window has the keyboard focus, as told by an injected `WindowEvent::Focused`.
+    pub focused: bool,
+    pub cursor_icon: CursorIcon,
+    /// Whether a custom cursor was set after `cursor_icon`.
+    pub custom_cursor: bool,
+    pub cursor_visible: bool,
+    pub cursor_grab: CursorGrabMode,
+    /// Where the cursor was last moved by `Window::set_cursor_position`.
+    pub cursor_position: Option<LogicalPosition>,
+    pub ime_allowed: bool,
+    pub key_repeat: bool,
+}
+
+pub struct Window {
+    id: WindowId,
+    shared: Arc<Shared>,
+}
+
+impl Window {
+    pub fn new<T>(
+        evlp: &EventLoopWindowTarget<T>,
+        attributes: WindowAttributes,
+        _pl_attribs: PlatformSpecificWindowBuilderAttributes,
+    ) -> Result<Window, RootOsError> {
+        let shared = evlp.shared.clone();
+        let id = shared.update(|state| {
+            let id = WindowId(state.next_window_id);
+            state.next_window_id += 1;
+            let window = HeadlessWindowState {
+                title: attributes.title,
+                visible: attributes.visible,
+                inner_size: attributes
+                    .inner_size
+                    .unwrap_or_else(|| LogicalSize::new(800.0, 600.0)),
+                min_inner_size: attributes.min_inner_size,
+                max_inner_size: attributes.max_inner_size,
+                outer_position: LogicalPosition::new(0.0, 0.0),
+                resizable: attributes.resizable,
+                maximized: attributes.maximized,
+                fullscreen: attributes.fullscreen,
+                decorations: attributes.decorations,
+                always_on_top: attributes.always_on_top,
+                hidpi_factor: state.monitors[0].hidpi_factor,
+                focused: false,
+                cursor_icon: CursorIcon::Default,
+                custom_cursor: false,
+                cursor_visible: true,
+                cursor_grab: CursorGrabMode::None,
+                cursor_position: None,
+                ime_allowed: true,
+                key_repeat: true,
+            };
+            state.windows.insert(id, window);
+            id
+        });
+        Ok(Window { id, shared })
+    }
+
+    // Reads the state of the window.
+    fn state<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&HeadlessWindowState) -> R,
+    {
+        f(&self.shared.state.lock().unwrap().windows[&self.id])
+    }
+
+    // Changes the state of the window, and queues the event the change produces, if any.
+    fn update<F>(&self, f: F)
+    where
+        F: FnOnce(&mut HeadlessWindowState) -> Option<WindowEvent>,
+    {
+        let id = self.id;
+        self.shared.update(|state| {
+            if let Some(event) = state.windows.get_mut(&id).and_then(f) {
+                state.pending_events.push_back(Event::WindowEvent {
+                    window_id: make_wid(id),
+                    event,
+                });
+            }
+        });
+    }
+
+    #[inline]
+    pub fn id(&self) -> WindowId {
+        self.id
+    }
+
+    pub fn set_title(&self, title: &str) {
+        self.update(|window| {
+            window.title = title.to_owned();
+            None
+        });
+    }
+
+    pub fn set_visible(&self, visible: bool) {
+        self.update(|window| {
+            window.visible = visible;
+            None
+        });
+    }
+
+    pub fn outer_position(&self) -> Result<LogicalPosition, NotSupportedError> {
+        Ok(self.state(|window| window.outer_position))
+    }
+
+    // There are no decorations to set the content apart from the window.
+    pub fn inner_position(&self) -> Result<LogicalPosition, NotSupportedError> {
+        self.outer_position()
+    }
+
+    pub fn set_outer_position(&self, position: LogicalPosition) {
+        self.update(|window| {
+            if window.outer_position == position {
+                return None;
+            }
+            window.outer_position = position;
+            Some(WindowEvent::Moved(position))
+        });
+    }
+
+    pub fn inner_size(&self) -> LogicalSize {
+        self.state(|window| window.inner_size)
+    }
+
+    pub fn outer_size(&self) -> LogicalSize {
+        self.inner_size()
+    }
+
+    pub fn set_inner_size(&self, size: LogicalSize) {
+        self.update(|window| {
+            if window.inner_size == size {
+                return None;
+            }
+            window.inner_size = size;
+            Some(WindowEvent::Resized(size))
+        });
+    }
+
+    pub fn set_min_inner_size(&self, dimensions: Option<LogicalSize>) {
+        self.update(|window| {
+            window.min_inner_size = dimensions;
+            None
+        });
+    }
+
+    pub fn set_max_inner_size(&self, dimensions: Option<LogicalSize>) {
+        self.update(|window| {
+            window.max_inner_size = dimensions;
+            None
+        });
+    }
+
+    pub fn set_resizable(&self, resizable: bool) {
+        self.update(|window| {
+            window.resizable = resizable;
+            None
+        });
+    }
+
+    pub fn set_cursor_icon(&self, cursor: CursorIcon) {
+        self.update(|window| {
+            window.cursor_icon = cursor;
+            window.custom_cursor = false;
+            None
+        });
+    }
+
+    pub fn set_custom_cursor(&self, _cursor: &CustomCursor) {
+        self.update(|window| {
+            window.custom_cursor = true;
+            None
+        });
+    }
+
+    pub fn set_cursor_grab(&self, mode: CursorGrabMode) -> Result<(), ExternalError> {
+        self.update(|window| {
+            window.cursor_grab = mode;
+            None
+        });
+        Ok(())
+    }
+
+    pub fn set_cursor_visible(&self, visible: bool) {
+        self.update(|window| {
+            window.cursor_visible = visible;
+            None
+        });
+    }
+
+    pub fn hidpi_factor(&self) -> f64 {
+        self.state(|window| window.hidpi_factor)
+    }
+
+    pub fn set_cursor_position(&self, position: LogicalPosition) -> Result<(), ExternalError> {
+        self.update(|window| {
+            window.cursor_position = Some(position);
+            None
+        });
+        Ok(())
+    }
+
+    pub fn set_maximized(&self, maximized: bool) {
+        self.update(|window| {
+            window.maximized = maximized;
+            None
+        });
+    }
+
+    pub fn fullscreen(&self) -> Option<RootMonitorHandle> {
+        self.state(|window| window.fullscreen.clone())
+    }
+
+    pub fn set_fullscreen(&self, monitor: Option<RootMonitorHandle>) {
+        self.update(|window| {
+            window.fullscreen = monitor;
+            None
+        });
+    }
+
+    pub fn set_decorations(&self, decorations: bool) {
+        self.update(|window| {
+            window.decorations = decorations;
+            None
+        });
+    }
+
+    pub fn set_always_on_top(&self, always_on_top: bool) {
+        self.update(|window| {
+            window.always_on_top = always_on_top;
+            None
+        });
+    }
+
+    #[inline]
+    pub fn set_window_icon(&self, _window_icon: Option<Icon>) {}
+
+    #[inline]
+    pub fn set_ime_position(&self, _position: LogicalPosition, _size: LogicalSize) {}
+
+    pub fn set_ime_allowed(&self, allowed: bool) {
+        self.update(|window| {
+            window.ime_allowed = allowed;
+            None
+        });
+    }
+
+    #[inline]
+    pub fn set_ime_purpose(&self, _purpose: ImePurpose, _hints: ImeHints) {}
+
+    pub fn set_key_repeat(&self, enabled: bool) {
+        self.update(|window| {
+            window.key_repeat = enabled;
+            None
+        });
+    }
+
+    pub fn request_redraw(&self) {
+        let id = self.id;
+        self.shared
+            .update(|state| state.pending_redraws.insert(id, Damage::Full));
+    }
+
+    pub fn request_redraw_region(&self, rect: DamageRect) {
+        let id = self.id;
+        self.shared.update(|state| {
+            state
+                .pending_redraws
+                .entry(id)
+                .or_insert_with(|| Damage::Region(Vec::new()))
+                .add_rect(rect)
+        });
+    }
+
+    pub fn request_frame(&self) {
+        let id = self.id;
+        self.shared.update(|state| state.pending_frames.insert(id));
+    }
+
+    // The monitor the window is fullscreen on, or the primary monitor.
+    pub fn current_monitor(&self) -> MonitorHandle {
+        let state = self.shared.state.lock().unwrap();
+        match state.windows[&self.id].fullscreen {
+            Some(RootMonitorHandle {
+                inner: PlatformMonitorHandle::Headless(ref monitor),
+            }) => monitor.clone(),
+            _ => state.primary_monitor(),
+        }
+    }
+
+    pub fn available_monitors(&self) -> VecDeque<MonitorHandle> {
+        self.shared.state.lock().unwrap().available_monitors()
+    }
+
+    pub fn primary_monitor(&self) -> MonitorHandle {
+        self.shared.state.lock().unwrap().primary_monitor()
+    }
+
+    pub fn clipboard(&self) -> Clipboard {
+        Clipboard::new(self.shared.clone(), false)
+    }
+
+    pub fn primary_selection(&self) -> Clipboard {
+        Clipboard::new(self.shared.clone(), true)
+    }
+
+    // There is no other application to drag data to.
+    pub fn start_drag(&self, _contents: Vec<(String, Vec<u8>)>) -> Result<(), ExternalError> {
+        Err(ExternalError::NotSupported(NotSupportedError::new()))
+    }
+
+    #[inline]
+    pub fn accept_drop(&self, _mime_type: Option<&str>) {}
+}
+
+impl Drop for Window {
+    fn drop(&mut self) {
+        let id = self.id;
+        self.shared.update(|state| {
+            state.windows.remove(&id);
+            state.pending_redraws.remove(&id);
+            state.pending_frames.remove(&id);
+            state.pending_events.push_back(Event::WindowEvent {
+                window_id: make_wid(id),
+                event: WindowEvent::Destroyed,
+            });
+        });
+    }
+}
@@ -1,8 +1,22 @@
 #![cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd"))]
 
-use std::{collections::VecDeque, env, ffi::CStr, fmt, mem::MaybeUninit, os::raw::*, sync::Arc};
+use std::{
+    collections::{HashMap, VecDeque},
+    env,
+    ffi::CStr,
+    fmt, fs, io,
+    mem::MaybeUninit,
+    os::{
+        raw::*,
+        unix::{ffi::OsStrExt, io::RawFd},
+    },
+    rc::Rc,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use parking_lot::Mutex;
+use percent_encoding::{percent_encode, AsciiSet, CONTROLS};
 use smithay_client_toolkit::reexports::client::ConnectError;
 
 pub use self::x11::XNotSupported;
@@ -10,20 +24,26 @@ use self::x11::{ffi::XVisualInfo, XConnection, XError};
 use crate::{
     dpi::{LogicalPosition, LogicalSize, PhysicalPosition, PhysicalSize},
     error::{ExternalError, NotSupportedError, OsError as RootOsError},
-    event::Event,
+    event::{DamageRect, Event, FdReadiness, FdToken, TimerToken},
     event_loop::{ControlFlow, EventLoopClosed, EventLoopWindowTarget as RootELW},
     icon::Icon,
+    keyboard::{KeyCode, KeyRepeatInfo, KeyboardLayout},
     monitor::{MonitorHandle as RootMonitorHandle, VideoMode},
-    window::{CursorIcon, WindowAttributes},
+    window::{
+        CursorGrabMode, CursorIcon, CustomCursor, DragData, ImeHints, ImePurpose, WindowAttributes,
+    },
 };
 
 mod dlopen;
+pub mod headless;
+mod timer;
 pub mod wayland;
 pub mod x11;
+mod xkb;
 
 /// Environment variable specifying which backend should be used on unix platform.
 ///
-/// Legal values are x11 and wayland. If this variable is set only the named backend
+/// Legal values are x11, wayland and headless. If this variable is set only the named backend
 /// will be tried by winit. If it is not set, winit will try to connect to a wayland connection,
 /// and if it fails will fallback on x11.
 ///
@@ -52,6 +72,8 @@ lazy_static! {
 pub enum OsError {
     XError(XError),
     XMisc(&'static str),
+    WaylandMisc(&'static str),
+    Misc(&'static str),
 }
 
 impl fmt::Display for OsError {
@@ -59,6 +81,8 @@ impl fmt::Display for OsError {
         match self {
             OsError::XError(e) => f.pad(&e.description),
             OsError::XMisc(e) => f.pad(e),
+            OsError::WaylandMisc(e) => f.pad(e),
+            OsError::Misc(e) => f.pad(e),
         }
     }
 }
@@ -66,12 +90,15 @@ impl fmt::Display for OsError {
 pub enum Window {
     X(x11::Window),
     Wayland(wayland::Window),
+    Headless(headless::Window),
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum WindowId {
     X(x11::WindowId),
     Wayland(wayland::WindowId),
+    Headless(headless::WindowId),
 }
 
 impl WindowId {
@@ -81,9 +108,11 @@ impl WindowId {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum DeviceId {
     X(x11::DeviceId),
     Wayland(wayland::DeviceId),
+    Headless(headless::DeviceId),
 }
 
 impl DeviceId {
@@ -96,6 +125,7 @@ impl DeviceId {
 pub enum MonitorHandle {
     X(x11::MonitorHandle),
     Wayland(wayland::MonitorHandle),
+    Headless(headless::MonitorHandle),
 }
 
 impl MonitorHandle {
@@ -104,6 +134,7 @@ impl MonitorHandle {
         match self {
             &MonitorHandle::X(ref m) => m.name(),
             &MonitorHandle::Wayland(ref m) => m.name(),
+            &MonitorHandle::Headless(ref m) => m.name(),
         }
     }
 
@@ -112,6 +143,7 @@ impl MonitorHandle {
         match self {
             &MonitorHandle::X(ref m) => m.native_identifier(),
             &MonitorHandle::Wayland(ref m) => m.native_identifier(),
+            &MonitorHandle::Headless(ref m) => m.native_identifier(),
         }
     }
 
@@ -120,6 +152,7 @@ impl MonitorHandle {
         match self {
             &MonitorHandle::X(ref m) => m.size(),
             &MonitorHandle::Wayland(ref m) => m.size(),
+            &MonitorHandle::Headless(ref m) => m.size(),
         }
     }
 
@@ -128,6 +161,7 @@ impl MonitorHandle {
         match self {
             &MonitorHandle::X(ref m) => m.position(),
             &MonitorHandle::Wayland(ref m) => m.position(),
+            &MonitorHandle::Headless(ref m) => m.position(),
         }
     }
 
@@ -136,6 +170,7 @@ impl MonitorHandle {
         match self {
             &MonitorHandle::X(ref m) => m.hidpi_factor(),
             &MonitorHandle::Wayland(ref m) => m.hidpi_factor() as f64,
+            &MonitorHandle::Headless(ref m) => m.hidpi_factor(),
         }
     }
 
@@ -144,6 +179,7 @@ impl MonitorHandle {
         match self {
             MonitorHandle::X(m) => Box::new(m.video_modes()),
             MonitorHandle::Wayland(m) => Box::new(m.video_modes()),
+            MonitorHandle::Headless(m) => Box::new(m.video_modes()),
         }
     }
 }
@@ -162,6 +198,9 @@ impl Window {
             EventLoopWindowTarget::X(ref window_target) => {
                 x11::Window::new(window_target, attribs, pl_attribs).map(Window::X)
             }
+            EventLoopWindowTarget::Headless(ref window_target) => {
+                headless::Window::new(window_target, attribs, pl_attribs).map(Window::Headless)
+            }
         }
     }
 
@@ -170,6 +209,7 @@ impl Window {
         match self {
             &Window::X(ref w) => WindowId::X(w.id()),
             &Window::Wayland(ref w) => WindowId::Wayland(w.id()),
+            &Window::Headless(ref w) => WindowId::Headless(w.id()),
         }
     }
 
@@ -178,6 +218,7 @@ impl Window {
         match self {
             &Window::X(ref w) => w.set_title(title),
             &Window::Wayland(ref w) => w.set_title(title),
+            &Window::Headless(ref w) => w.set_title(title),
         }
     }
 
@@ -186,6 +227,7 @@ impl Window {
         match self {
             &Window::X(ref w) => w.set_visible(visible),
             &Window::Wayland(ref w) => w.set_visible(visible),
+            &Window::Headless(ref w) => w.set_visible(visible),
         }
     }
 
@@ -194,6 +236,7 @@ impl Window {
         match self {
             &Window::X(ref w) => w.outer_position(),
             &Window::Wayland(ref w) => w.outer_position(),
+            &Window::Headless(ref w) => w.outer_position(),
         }
     }
 
@@ -202,6 +245,7 @@ impl Window {
         match self {
             &Window::X(ref m) => m.inner_position(),
             &Window::Wayland(ref m) => m.inner_position(),
+            &Window::Headless(ref m) => m.inner_position(),
         }
     }
 
@@ -210,6 +254,7 @@ impl Window {
         match self {
             &Window::X(ref w) => w.set_outer_position(position),
             &Window::Wayland(ref w) => w.set_outer_position(position),
+            &Window::Headless(ref w) => w.set_outer_position(position),
         }
     }
 
@@ -218,6 +263,7 @@ impl Window {
         match self {
             &Window::X(ref w) => w.inner_size(),
             &Window::Wayland(ref w) => w.inner_size(),
+            &Window::Headless(ref w) => w.inner_size(),
         }
     }
 
@@ -226,6 +272,7 @@ impl Window {
         match self {
             &Window::X(ref w) => w.outer_size(),
             &Window::Wayland(ref w) => w.outer_size(),
+            &Window::Headless(ref w) => w.outer_size(),
         }
     }
 
@@ -234,6 +281,7 @@ impl Window {
         match self {
             &Window::X(ref w) => w.set_inner_size(size),
             &Window::Wayland(ref w) => w.set_inner_size(size),
+            &Window::Headless(ref w) => w.set_inner_size(size),
         }
     }
 
@@ -242,6 +290,7 @@ impl Window {
         match self {
             &Window::X(ref w) => w.set_min_inner_size(dimensions),
             &Window::Wayland(ref w) => w.set_min_inner_size(dimensions),
+            &Window::Headless(ref w) => w.set_min_inner_size(dimensions),
         }
     }
 
@@ -250,6 +299,7 @@ impl Window {
         match self {
             &Window::X(ref w) => w.set_max_inner_size(dimensions),
             &Window::Wayland(ref w) => w.set_max_inner_size(dimensions),
+            &Window::Headless(ref w) => w.set_max_inner_size(dimensions),
         }
     }
 
@@ -258,6 +308,7 @@ impl Window {
         match self {
             &Window::X(ref w) => w.set_resizable(resizable),
             &Window::Wayland(ref w) => w.set_resizable(resizable),
+            &Window::Headless(ref w) => w.set_resizable(resizable),
         }
     }
 
@@ -266,14 +317,25 @@ impl Window {
         match self {
             &Window::X(ref w) => w.set_cursor_icon(cursor),
             &Window::Wayland(ref w) => w.set_cursor_icon(cursor),
+            &Window::Headless(ref w) => w.set_cursor_icon(cursor),
+        }
+    }
+
+    #[inline]
+    pub fn set_custom_cursor(&self, cursor: &CustomCursor) {
+        match self {
+            &Window::X(ref w) => w.set_custom_cursor(cursor),
+            &Window::Wayland(ref w) => w.set_custom_cursor(cursor),
+            &Window::Headless(ref w) => w.set_custom_cursor(cursor),
         }
     }
 
     #[inline]
-    pub fn set_cursor_grab(&self, grab: bool) -> Result<(), ExternalError> {
+    pub fn set_cursor_grab(&self, mode: CursorGrabMode) -> Result<(), ExternalError> {
         match self {
-            &Window::X(ref window) => window.set_cursor_grab(grab),
-            &Window::Wayland(ref window) => window.set_cursor_grab(grab),
+            &Window::X(ref window) => window.set_cursor_grab(mode),
+            &Window::Wayland(ref window) => window.set_cursor_grab(mode),
+            &Window::Headless(ref window) => window.set_cursor_grab(mode),
         }
     }
 
@@ -282,6 +344,7 @@ impl Window {
         match self {
             &Window::X(ref window) => window.set_cursor_visible(visible),
             &Window::Wayland(ref window) => window.set_cursor_visible(visible),
+            &Window::Headless(ref window) => window.set_cursor_visible(visible),
         }
     }
 
@@ -290,6 +353,7 @@ impl Window {
         match self {
             &Window::X(ref w) => w.hidpi_factor(),
             &Window::Wayland(ref w) => w.hidpi_factor() as f64,
+            &Window::Headless(ref w) => w.hidpi_factor(),
         }
     }
 
@@ -298,6 +362,7 @@ impl Window {
         match self {
             &Window::X(ref w) => w.set_cursor_position(position),
             &Window::Wayland(ref w) => w.set_cursor_position(position),
+            &Window::Headless(ref w) => w.set_cursor_position(position),
         }
     }
 
@@ -306,6 +371,7 @@ impl Window {
         match self {
             &Window::X(ref w) => w.set_maximized(maximized),
             &Window::Wayland(ref w) => w.set_maximized(maximized),
+            &Window::Headless(ref w) => w.set_maximized(maximized),
         }
     }
 
@@ -316,6 +382,7 @@ impl Window {
             &Window::Wayland(ref w) => w.fullscreen().map(|monitor_id| RootMonitorHandle {
                 inner: MonitorHandle::Wayland(monitor_id),
             }),
+            &Window::Headless(ref w) => w.fullscreen(),
         }
     }
 
@@ -324,6 +391,7 @@ impl Window {
         match self {
             &Window::X(ref w) => w.set_fullscreen(monitor),
             &Window::Wayland(ref w) => w.set_fullscreen(monitor),
+            &Window::Headless(ref w) => w.set_fullscreen(monitor),
         }
     }
 
@@ -332,6 +400,7 @@ impl Window {
         match self {
             &Window::X(ref w) => w.set_decorations(decorations),
             &Window::Wayland(ref w) => w.set_decorations(decorations),
+            &Window::Headless(ref w) => w.set_decorations(decorations),
         }
     }
 
@@ -340,6 +409,7 @@ impl Window {
         match self {
             &Window::X(ref w) => w.set_always_on_top(always_on_top),
             &Window::Wayland(_) => (),
+            &Window::Headless(ref w) => w.set_always_on_top(always_on_top),
         }
     }
 
@@ -348,14 +418,43 @@ impl Window {
         match self {
             &Window::X(ref w) => w.set_window_icon(window_icon),
             &Window::Wayland(_) => (),
+            &Window::Headless(ref w) => w.set_window_icon(window_icon),
         }
     }
 
     #[inline]
-    pub fn set_ime_position(&self, position: LogicalPosition) {
+    pub fn set_ime_position(&self, position: LogicalPosition, size: LogicalSize) {
         match self {
-            &Window::X(ref w) => w.set_ime_position(position),
-            &Window::Wayland(_) => (),
+            &Window::X(ref w) => w.set_ime_position(position, size),
+            &Window::Wayland(ref w) => w.set_ime_position(position, size),
+            &Window::Headless(ref w) => w.set_ime_position(position, size),
+        }
+    }
+
+    #[inline]
+    pub fn set_ime_allowed(&self, allowed: bool) {
+        match self {
+            &Window::X(ref w) => w.set_ime_allowed(allowed),
+            &Window::Wayland(ref w) => w.set_ime_allowed(allowed),
+            &Window::Headless(ref w) => w.set_ime_allowed(allowed),
+        }
+    }
+
+    #[inline]
+    pub fn set_ime_purpose(&self, purpose: ImePurpose, hints: ImeHints) {
+        match self {
+            &Window::X(ref w) => w.set_ime_purpose(purpose, hints),
+            &Window::Wayland(ref w) => w.set_ime_purpose(purpose, hints),
+            &Window::Headless(ref w) => w.set_ime_purpose(purpose, hints),
+        }
+    }
+
+    #[inline]
+    pub fn set_key_repeat(&self, enabled: bool) {
+        match self {
+            &Window::X(ref w) => w.set_key_repeat(enabled),
+            &Window::Wayland(ref w) => w.set_key_repeat(enabled),
+            &Window::Headless(ref w) => w.set_key_repeat(enabled),
         }
     }
 
@@ -364,6 +463,24 @@ impl Window {
         match self {
             &Window::X(ref w) => w.request_redraw(),
             &Window::Wayland(ref w) => w.request_redraw(),
+            &Window::Headless(ref w) => w.request_redraw(),
+        }
+    }
+
+    #[inline]
+    pub fn request_redraw_region(&self, rect: DamageRect) {
+        match self {
+            &Window::X(ref w) => w.request_redraw_region(rect),
+            &Window::Wayland(ref w) => w.request_redraw_region(rect),
+            &Window::Headless(ref w) => w.request_redraw_region(rect),
+        }
+    }
+
+    pub fn request_frame(&self) {
+        match self {
+            &Window::X(ref w) => w.request_frame(),
+            &Window::Wayland(ref w) => w.request_frame(),
+            &Window::Headless(ref w) => w.request_frame(),
         }
     }
 
@@ -376,6 +493,9 @@ impl Window {
             &Window::Wayland(ref window) => RootMonitorHandle {
                 inner: MonitorHandle::Wayland(window.current_monitor()),
             },
+            &Window::Headless(ref window) => RootMonitorHandle {
+                inner: MonitorHandle::Headless(window.current_monitor()),
+            },
         }
     }
 
@@ -392,6 +512,11 @@ impl Window {
                 .into_iter()
                 .map(MonitorHandle::Wayland)
                 .collect(),
+            &Window::Headless(ref window) => window
+                .available_monitors()
+                .into_iter()
+                .map(MonitorHandle::Headless)
+                .collect(),
         }
     }
 
@@ -400,8 +525,265 @@ impl Window {
         match self {
             &Window::X(ref window) => MonitorHandle::X(window.primary_monitor()),
             &Window::Wayland(ref window) => MonitorHandle::Wayland(window.primary_monitor()),
+            &Window::Headless(ref window) => MonitorHandle::Headless(window.primary_monitor()),
+        }
+    }
+
+    #[inline]
+    pub fn clipboard(&self) -> Clipboard {
+        match self {
+            &Window::X(ref w) => Clipboard::X(w.clipboard()),
+            &Window::Wayland(ref w) => Clipboard::Wayland(w.clipboard()),
+            &Window::Headless(ref w) => Clipboard::Headless(w.clipboard()),
+        }
+    }
+
+    #[inline]
+    pub fn primary_selection(&self) -> Clipboard {
+        match self {
+            &Window::X(ref w) => Clipboard::X(w.primary_selection()),
+            &Window::Wayland(ref w) => Clipboard::Wayland(w.primary_selection()),
+            &Window::Headless(ref w) => Clipboard::Headless(w.primary_selection()),
+        }
+    }
+
+    #[inline]
+    pub fn start_drag(&self, data: DragData) -> Result<(), ExternalError> {
+        let contents = drag_contents(data)?;
+        match self {
+            &Window::X(ref w) => w.start_drag(contents),
+            &Window::Wayland(ref w) => w.start_drag(contents),
+            &Window::Headless(ref w) => w.start_drag(contents),
+        }
+    }
+
+    #[inline]
+    pub fn accept_drop(&self, mime_type: Option<&str>) {
+        match self {
+            &Window::X(ref w) => w.accept_drop(mime_type),
+            &Window::Wayland(ref w) => w.accept_drop(mime_type),
+            &Window::Headless(ref w) => w.accept_drop(mime_type),
+        }
+    }
+}
+
+/// MIME types and X11 targets that text is offered as, in order of preference.
+const TEXT_MIME_TYPES: &[&str] = &[
+    "text/plain;charset=utf-8",
+    "UTF8_STRING",
+    "text/plain",
+    "STRING",
+    "TEXT",
+];
+
+fn text_contents(text: &str) -> Vec<(String, Vec<u8>)> {
+    TEXT_MIME_TYPES
+        .iter()
+        .filter_map(|&mime_type| {
+            let data = if mime_type == "STRING" {
+                // `STRING` is Latin-1, so it's only offered when the text can be encoded as such.
+                text.chars()
+                    .map(|c| {
+                        if (c as u32) < 256 {
+                            Some(c as u8)
+                        } else {
+                            None
+                        }
+                    })
+                    .collect::<Option<Vec<u8>>>()?
+            } else {
+                text.as_bytes().to_owned()
+            };
+            Some((mime_type.to_owned(), data))
+        })
+        .collect()
+}
+
+/// Characters that have to be escaped in the path of a `file://` URI.
+const URI_PATH: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+fn drag_contents(data: DragData) -> Result<Vec<(String, Vec<u8>)>, ExternalError> {
+    Ok(match data {
+        DragData::Files(paths) => {
+            let mut uri_list = String::new();
+            for path in paths {
+                // A relative path would be resolved by the drop target, from its own working
+                // directory.
+                let path = fs::canonicalize(&path).map_err(|_| {
+                    ExternalError::Os(os_error!(OsError::Misc(
+                        "Failed to canonicalize the path of a dragged file"
+                    )))
+                })?;
+                uri_list.push_str("file://");
+                uri_list.extend(percent_encode(path.as_os_str().as_bytes(), URI_PATH));
+                uri_list.push_str("\r\n");
+            }
+            vec![("text/uri-list".to_owned(), uri_list.into_bytes())]
+        }
+        DragData::Text(text) => text_contents(&text),
+        DragData::Custom(contents) => contents,
+    })
+}
+
+/// Converts RGBA pixels to premultiplied ARGB, which is what both X11 and Wayland expect cursor
+/// images in.
+fn cursor_pixels(rgba: &[u8]) -> Vec<u32> {
+    rgba.chunks_exact(4)
+        .map(|pixel| {
+            let alpha = pixel[3] as u32;
+            let premultiply = |channel: u8| (channel as u32 * alpha + 127) / 255;
+            alpha << 24
+                | premultiply(pixel[0]) << 16
+                | premultiply(pixel[1]) << 8
+                | premultiply(pixel[2])
+        })
+        .collect()
+}
+
+/// The names a cursor icon goes by in cursor themes, the better looking (or more suiting) ones
+/// first, since the names differ between desktop environments and themes.
+fn cursor_names(cursor: CursorIcon) -> &'static [&'static str] {
+    match cursor {
+        CursorIcon::Alias => &["link"],
+        CursorIcon::Arrow => &["arrow"],
+        CursorIcon::Cell => &["plus"],
+        CursorIcon::Copy => &["copy"],
+        CursorIcon::Crosshair => &["crosshair"],
+        CursorIcon::Default => &["left_ptr"],
+        CursorIcon::Hand => &["hand2", "hand1"],
+        CursorIcon::Help => &["question_arrow"],
+        CursorIcon::Move => &["move"],
+        CursorIcon::Grab => &["openhand", "grab"],
+        CursorIcon::Grabbing => &["closedhand", "grabbing"],
+        CursorIcon::Progress => &["left_ptr_watch"],
+        CursorIcon::AllScroll => &["all-scroll"],
+        CursorIcon::ContextMenu => &["context-menu"],
+
+        CursorIcon::NoDrop => &["no-drop", "circle"],
+        CursorIcon::NotAllowed => &["crossed_circle"],
+
+        // Resize cursors
+        CursorIcon::EResize => &["right_side"],
+        CursorIcon::NResize => &["top_side"],
+        CursorIcon::NeResize => &["top_right_corner"],
+        CursorIcon::NwResize => &["top_left_corner"],
+        CursorIcon::SResize => &["bottom_side"],
+        CursorIcon::SeResize => &["bottom_right_corner"],
+        CursorIcon::SwResize => &["bottom_left_corner"],
+        CursorIcon::WResize => &["left_side"],
+        CursorIcon::EwResize => &["h_double_arrow"],
+        CursorIcon::NsResize => &["v_double_arrow"],
+        CursorIcon::NwseResize => &["bd_double_arrow", "size_bdiag"],
+        CursorIcon::NeswResize => &["fd_double_arrow", "size_fdiag"],
+        CursorIcon::ColResize => &["split_h", "h_double_arrow"],
+        CursorIcon::RowResize => &["split_v", "v_double_arrow"],
+
+        CursorIcon::Text => &["text", "xterm"],
+        CursorIcon::VerticalText => &["vertical-text"],
+
+        CursorIcon::Wait => &["watch"],
+
+        CursorIcon::ZoomIn => &["zoom-in"],
+        CursorIcon::ZoomOut => &["zoom-out"],
+    }
+}
+
+/// What the application chose to do with data dragged over one of its windows, through
+/// `Window::accept_drop`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DropChoice {
+    /// `Window::accept_drop` wasn't called, so only files are accepted.
+    Default,
+    Accept(String),
+    Reject,
+}
+
+#[derive(Clone)]
+pub enum Clipboard {
+    X(x11::Clipboard),
+    Wayland(wayland::Clipboard),
+    Headless(headless::Clipboard),
+}
+
+impl Clipboard {
+    #[inline]
+    pub fn is_supported(&self) -> bool {
+        match *self {
+            Clipboard::X(ref c) => c.is_supported(),
+            Clipboard::Wayland(ref c) => c.is_supported(),
+            Clipboard::Headless(ref c) => c.is_supported(),
+        }
+    }
+
+    #[inline]
+    pub fn set_contents(&self, contents: Vec<(String, Vec<u8>)>) -> Result<(), ExternalError> {
+        match *self {
+            Clipboard::X(ref c) => c.set_contents(contents),
+            Clipboard::Wayland(ref c) => c.set_contents(contents),
+            Clipboard::Headless(ref c) => c.set_contents(contents),
+        }
+    }
+
+    #[inline]
+    pub fn clear(&self) -> Result<(), ExternalError> {
+        match *self {
+            Clipboard::X(ref c) => c.clear(),
+            Clipboard::Wayland(ref c) => c.clear(),
+            Clipboard::Headless(ref c) => c.clear(),
+        }
+    }
+
+    #[inline]
+    pub fn mime_types(&self) -> Result<Vec<String>, ExternalError> {
+        match *self {
+            Clipboard::X(ref c) => c.mime_types(),
+            Clipboard::Wayland(ref c) => c.mime_types(),
+            Clipboard::Headless(ref c) => c.mime_types(),
+        }
+    }
+
+    #[inline]
+    pub fn contents(&self, mime_type: &str) -> Result<Option<Vec<u8>>, ExternalError> {
+        match *self {
+            Clipboard::X(ref c) => c.contents(mime_type),
+            Clipboard::Wayland(ref c) => c.contents(mime_type),
+            Clipboard::Headless(ref c) => c.contents(mime_type),
         }
     }
+
+    pub fn set_text(&self, text: &str) -> Result<(), ExternalError> {
+        self.set_contents(text_contents(text))
+    }
+
+    pub fn text(&self) -> Result<Option<String>, ExternalError> {
+        let available = self.mime_types()?;
+        let mime_type = TEXT_MIME_TYPES.iter().find_map(|&mime_type| {
+            available
+                .iter()
+                .find(|available| available.eq_ignore_ascii_case(mime_type))
+        });
+        let mime_type = match mime_type {
+            Some(mime_type) => mime_type,
+            None => return Ok(None),
+        };
+        Ok(self.contents(mime_type)?.map(|data| {
+            if mime_type == "STRING" {
+                data.iter().map(|&byte| byte as char).collect()
+            } else {
+                String::from_utf8_lossy(&data).into_owned()
+            }
+        }))
+    }
 }
 
 unsafe extern "C" fn x_error_callback(
@@ -439,12 +821,14 @@ unsafe extern "C" fn x_error_callback(
 pub enum EventLoop<T: 'static> {
     Wayland(wayland::EventLoop<T>),
     X(x11::EventLoop<T>),
+    Headless(headless::EventLoop<T>),
 }
 
 #[derive(Clone)]
 pub enum EventLoopProxy<T: 'static> {
     X(x11::EventLoopProxy<T>),
     Wayland(wayland::EventLoopProxy<T>),
+    Headless(headless::EventLoopProxy<T>),
 }
 
 impl<T: 'static> EventLoop<T> {
@@ -458,8 +842,9 @@ impl<T: 'static> EventLoop<T> {
                 "wayland" => {
                     return EventLoop::new_wayland().expect("Failed to initialize Wayland backend");
                 }
+                "headless" => return EventLoop::new_headless(),
                 _ => panic!(
-                    "Unknown environment variable value for {}, try one of `x11`,`wayland`",
+                    "Unknown environment variable value for {}, try one of `x11`,`wayland`,`headless`",
                     BACKEND_PREFERENCE_ENV_VAR,
                 ),
             }
@@ -496,6 +881,10 @@ impl<T: 'static> EventLoop<T> {
             .map_err(|err| err.clone())
     }
 
+    pub fn new_headless() -> EventLoop<T> {
+        EventLoop::Headless(headless::EventLoop::new())
+    }
+
     #[inline]
     pub fn available_monitors(&self) -> VecDeque<MonitorHandle> {
         match *self {
@@ -510,6 +899,11 @@ impl<T: 'static> EventLoop<T> {
                 .into_iter()
                 .map(MonitorHandle::X)
                 .collect(),
+            EventLoop::Headless(ref evlp) => evlp
+                .available_monitors()
+                .into_iter()
+                .map(MonitorHandle::Headless)
+                .collect(),
         }
     }
 
@@ -518,6 +912,7 @@ impl<T: 'static> EventLoop<T> {
         match *self {
             EventLoop::Wayland(ref evlp) => MonitorHandle::Wayland(evlp.primary_monitor()),
             EventLoop::X(ref evlp) => MonitorHandle::X(evlp.x_connection().primary_monitor()),
+            EventLoop::Headless(ref evlp) => MonitorHandle::Headless(evlp.primary_monitor()),
         }
     }
 
@@ -525,6 +920,7 @@ impl<T: 'static> EventLoop<T> {
         match *self {
             EventLoop::Wayland(ref evlp) => EventLoopProxy::Wayland(evlp.create_proxy()),
             EventLoop::X(ref evlp) => EventLoopProxy::X(evlp.create_proxy()),
+            EventLoop::Headless(ref evlp) => EventLoopProxy::Headless(evlp.create_proxy()),
         }
     }
 
@@ -535,6 +931,7 @@ impl<T: 'static> EventLoop<T> {
         match *self {
             EventLoop::Wayland(ref mut evlp) => evlp.run_return(callback),
             EventLoop::X(ref mut evlp) => evlp.run_return(callback),
+            EventLoop::Headless(ref mut evlp) => evlp.run_return(callback),
         }
     }
 
@@ -545,6 +942,7 @@ impl<T: 'static> EventLoop<T> {
         match self {
             EventLoop::Wayland(evlp) => evlp.run(callback),
             EventLoop::X(evlp) => evlp.run(callback),
+            EventLoop::Headless(evlp) => evlp.run(callback),
         }
     }
 
@@ -552,7 +950,23 @@ impl<T: 'static> EventLoop<T> {
     pub fn is_wayland(&self) -> bool {
         match *self {
             EventLoop::Wayland(_) => true,
-            EventLoop::X(_) => false,
+            EventLoop::X(_) | EventLoop::Headless(_) => false,
+        }
+    }
+
+    #[inline]
+    pub fn is_x11(&self) -> bool {
+        match *self {
+            EventLoop::X(_) => true,
+            EventLoop::Wayland(_) | EventLoop::Headless(_) => false,
+        }
+    }
+
+    #[inline]
+    pub fn is_headless(&self) -> bool {
+        match *self {
+            EventLoop::Headless(_) => true,
+            EventLoop::Wayland(_) | EventLoop::X(_) => false,
         }
     }
 
@@ -560,6 +974,7 @@ impl<T: 'static> EventLoop<T> {
         match *self {
             EventLoop::Wayland(ref evl) => evl.window_target(),
             EventLoop::X(ref evl) => evl.window_target(),
+            EventLoop::Headless(ref evl) => evl.window_target(),
         }
     }
 }
@@ -569,6 +984,7 @@ impl<T: 'static> EventLoopProxy<T> {
         match *self {
             EventLoopProxy::Wayland(ref proxy) => proxy.send_event(event),
             EventLoopProxy::X(ref proxy) => proxy.send_event(event),
+            EventLoopProxy::Headless(ref proxy) => proxy.send_event(event),
         }
     }
 }
@@ -576,6 +992,195 @@ impl<T: 'static> EventLoopProxy<T> {
 pub enum EventLoopWindowTarget<T> {
     Wayland(wayland::EventLoopWindowTarget<T>),
     X(x11::EventLoopWindowTarget<T>),
+    Headless(headless::EventLoopWindowTarget<T>),
+}
+
+impl<T> EventLoopWindowTarget<T> {
+    #[inline]
+    pub fn clipboard(&self) -> Clipboard {
+        match *self {
+            EventLoopWindowTarget::Wayland(ref evlp) => Clipboard::Wayland(evlp.clipboard.clone()),
+            EventLoopWindowTarget::X(ref evlp) => Clipboard::X(evlp.clipboard()),
+            EventLoopWindowTarget::Headless(ref evlp) => Clipboard::Headless(evlp.clipboard()),
+        }
+    }
+
+    #[inline]
+    pub fn primary_selection(&self) -> Clipboard {
+        match *self {
+            EventLoopWindowTarget::Wayland(ref evlp) => {
+                Clipboard::Wayland(evlp.primary_selection.clone())
+            }
+            EventLoopWindowTarget::X(ref evlp) => Clipboard::X(evlp.primary_selection()),
+            EventLoopWindowTarget::Headless(ref evlp) => {
+                Clipboard::Headless(evlp.primary_selection())
+            }
+        }
+    }
+
+    #[inline]
+    pub fn keyboard_layout(&self) -> Option<KeyboardLayout> {
+        match *self {
+            EventLoopWindowTarget::Wayland(ref evlp) => {
+                evlp.keyboard_info.lock().unwrap().layout.clone()
+            }
+            EventLoopWindowTarget::X(ref evlp) => evlp.keyboard_layout(),
+            // There is no keyboard, only the events a test injects.
+            EventLoopWindowTarget::Headless(_) => None,
+        }
+    }
+
+    #[inline]
+    pub fn key_repeat_info(&self) -> Option<KeyRepeatInfo> {
+        match *self {
+            EventLoopWindowTarget::Wayland(ref evlp) => {
+                Some(evlp.keyboard_info.lock().unwrap().repeat_info)
+            }
+            EventLoopWindowTarget::X(ref evlp) => evlp.key_repeat_info(),
+            EventLoopWindowTarget::Headless(_) => None,
+        }
+    }
+
+    #[inline]
+    pub fn key_label(&self, key: KeyCode) -> Option<String> {
+        match *self {
+            EventLoopWindowTarget::Wayland(ref evlp) => {
+                evlp.keyboard_info.lock().unwrap().key_label(key)
+            }
+            EventLoopWindowTarget::X(ref evlp) => evlp.key_label(key),
+            EventLoopWindowTarget::Headless(_) => None,
+        }
+    }
+
+    #[inline]
+    pub fn key_from_label(&self, label: &str) -> Option<KeyCode> {
+        match *self {
+            EventLoopWindowTarget::Wayland(ref evlp) => {
+                evlp.keyboard_info.lock().unwrap().key_from_label(label)
+            }
+            EventLoopWindowTarget::X(ref evlp) => evlp.key_from_label(label),
+            EventLoopWindowTarget::Headless(_) => None,
+        }
+    }
+
+    pub fn register_fd(&self, fd: RawFd, interest: FdReadiness) -> io::Result<FdToken> {
+        match *self {
+            EventLoopWindowTarget::Wayland(ref evlp) => {
+                evlp.fd_sources.borrow_mut().register(fd, interest)
+            }
+            EventLoopWindowTarget::X(ref evlp) => evlp.register_fd(fd, interest),
+            EventLoopWindowTarget::Headless(_) => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "the headless backend can't wait on file descriptors",
+            )),
+        }
+    }
+
+    pub fn unregister_fd(&self, token: FdToken) {
+        match *self {
+            EventLoopWindowTarget::Wayland(ref evlp) => {
+                evlp.fd_sources.borrow_mut().unregister(token)
+            }
+            EventLoopWindowTarget::X(ref evlp) => evlp.unregister_fd(token),
+            EventLoopWindowTarget::Headless(_) => (),
+        }
+    }
+
+    pub fn register_timer(
+        &self,
+        deadline: Instant,
+        interval: Option<Duration>,
+    ) -> io::Result<TimerToken> {
+        match *self {
+            EventLoopWindowTarget::Wayland(ref evlp) => evlp.timers.register(deadline, interval),
+            EventLoopWindowTarget::X(ref evlp) => evlp.register_timer(deadline, interval),
+            EventLoopWindowTarget::Headless(ref evlp) => evlp.register_timer(deadline, interval),
+        }
+    }
+
+    pub fn unregister_timer(&self, token: TimerToken) {
+        match *self {
+            EventLoopWindowTarget::Wayland(ref evlp) => evlp.timers.unregister(token),
+            EventLoopWindowTarget::X(ref evlp) => evlp.unregister_timer(token),
+            EventLoopWindowTarget::Headless(ref evlp) => evlp.unregister_timer(token),
+        }
+    }
+}
+
+/// The file descriptors registered with `EventLoopWindowTargetExtUnix::register_fd`, for the
+/// backends driven by calloop.
+pub struct FdSources {
+    handle: calloop::LoopHandle<()>,
+    // Queues the `Event::FdReady` of a file descriptor
+    on_ready: Rc<dyn Fn(FdToken, FdReadiness)>,
+    next_token: u64,
+    sources: HashMap<
+        FdToken,
+        calloop::Source<calloop::generic::Generic<calloop::generic::EventedRawFd>>,
+    >,
+}
+
+impl FdSources {
+    fn new<F>(handle: calloop::LoopHandle<()>, on_ready: F) -> FdSources
+    where
+        F: Fn(FdToken, FdReadiness) + 'static,
+    {
+        FdSources {
+            handle,
+            on_ready: Rc::new(on_ready),
+            next_token: 0,
+            sources: HashMap::new(),
+        }
+    }
+
+    fn register(&mut self, fd: RawFd, interest: FdReadiness) -> io::Result<FdToken> {
+        let token = FdToken(self.next_token);
+        let mut ready = calloop::mio::Ready::empty();
+        if interest.readable {
+            ready |= calloop::mio::Ready::readable();
+        }
+        if interest.writable {
+            ready |= calloop::mio::Ready::writable();
+        }
+        let mut events = calloop::generic::Generic::from_raw_fd(fd);
+        events.set_interest(ready);
+        let on_ready = self.on_ready.clone();
+        let source = self
+            .handle
+            .insert_source(events, move |evt, &mut ()| {
+                let readiness = FdReadiness {
+                    readable: evt.readiness.is_readable(),
+                    writable: evt.readiness.is_writable(),
+                };
+                on_ready(token, readiness);
+            })
+            .map_err(io::Error::from)?;
+        self.next_token += 1;
+        self.sources.insert(token, source);
+        Ok(token)
+    }
+
+    fn unregister(&mut self, token: FdToken) {
+        if let Some(source) = self.sources.remove(&token) {
+            source.remove();
+        }
+    }
+}
+
+// Converts a time of `CLOCK_MONOTONIC`, which `Instant` is based on, to an `Instant`.
+fn instant_from_monotonic(time: Duration) -> Instant {
+    let mut now = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) };
+    let instant = Instant::now();
+    let now = Duration::new(now.tv_sec as u64, now.tv_nsec as u32);
+    if time <= now {
+        instant - (now - time)
+    } else {
+        instant + (time - now)
+    }
 }
 
 fn sticky_exit_callback<T, F>(
@@ -0,0 +1,330 @@
+#![cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+
+use std::{
+    cell::{RefCell, RefMut},
+    collections::HashMap,
+    convert::TryFrom,
+    io,
+    os::unix::io::RawFd,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use calloop::{
+    generic::{EventedRawFd, Generic},
+    LoopHandle, Source,
+};
+
+use crate::event::TimerToken;
+
+/// The timers registered with `EventLoopWindowTargetExtUnix::register_timer`, for the backends
+/// driven by calloop.
+///
+/// All timers share a timerfd, which is armed for the earliest deadline so that the event loop
+/// wakes up precisely then, whatever its control flow.
+pub struct Timers {
+    // `None` if the timerfd couldn't be created
+    inner: Option<TimersInner>,
+}
+
+struct TimersInner {
+    queue: Rc<RefCell<TimerQueue>>,
+    _source: Source<Generic<EventedRawFd>>,
+}
+
+impl Timers {
+    /// Registers the timerfd with the event loop, `callback` being called with the token of each
+    /// timer that fires.
+    pub fn new<F>(handle: &LoopHandle<()>, callback: F) -> Timers
+    where
+        F: FnMut(TimerToken) + 'static,
+    {
+        match Timers::insert_source(handle, callback) {
+            Ok(inner) => Timers { inner: Some(inner) },
+            Err(err) => {
+                warn!("Timers are unavailable: {}", err);
+                Timers { inner: None }
+            }
+        }
+    }
+
+    fn insert_source<F>(handle: &LoopHandle<()>, mut callback: F) -> io::Result<TimersInner>
+    where
+        F: FnMut(TimerToken) + 'static,
+    {
+        let queue = Rc::new(RefCell::new(TimerQueue {
+            timerfd: timerfd::create()?,
+            timers: TimerSet::default(),
+        }));
+        let mut events = Generic::from_raw_fd(queue.borrow().timerfd);
+        events.set_interest(calloop::mio::Ready::readable());
+        let expired_queue = queue.clone();
+        let source = handle
+            .insert_source(events, move |_, &mut ()| {
+                let expired = expired_queue.borrow_mut().expire();
+                for token in expired {
+                    callback(token);
+                }
+            })
+            .map_err(io::Error::from)?;
+        Ok(TimersInner {
+            queue,
+            _source: source,
+        })
+    }
+
+    fn queue(&self) -> io::Result<RefMut<'_, TimerQueue>> {
+        match self.inner {
+            Some(ref inner) => Ok(inner.queue.borrow_mut()),
+            None => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "timers are unavailable",
+            )),
+        }
+    }
+
+    pub fn register(
+        &self,
+        deadline: Instant,
+        interval: Option<Duration>,
+    ) -> io::Result<TimerToken> {
+        let mut queue = self.queue()?;
+        let token = queue.timers.insert(deadline, interval)?;
+        queue.arm()?;
+        Ok(token)
+    }
+
+    pub fn unregister(&self, token: TimerToken) {
+        if let Ok(mut queue) = self.queue() {
+            if queue.timers.remove(token) {
+                let _ = queue.arm();
+            }
+        }
+    }
+}
+
+/// Timers waiting for their deadline, whatever clock it's compared to.
+///
+/// The headless backend uses it with its own clock.
+#[derive(Default)]
+pub struct TimerSet {
+    next_token: u64,
+    timers: HashMap<TimerToken, Timer>,
+}
+
+struct Timer {
+    deadline: Instant,
+    // The time between two firings of a repeating timer
+    interval: Option<Duration>,
+}
+
+impl TimerSet {
+    pub fn insert(
+        &mut self,
+        deadline: Instant,
+        interval: Option<Duration>,
+    ) -> io::Result<TimerToken> {
+        if interval == Some(Duration::from_secs(0)) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the interval of a repeating timer can't be zero",
+            ));
+        }
+        let token = TimerToken(self.next_token);
+        self.next_token += 1;
+        self.timers.insert(token, Timer { deadline, interval });
+        Ok(token)
+    }
+
+    /// Returns whether the timer was running.
+    pub fn remove(&mut self, token: TimerToken) -> bool {
+        self.timers.remove(&token).is_some()
+    }
+
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.timers.values().map(|timer| timer.deadline).min()
+    }
+
+    /// Returns the timers whose deadline has passed at `now`, in the order of their deadlines.
+    /// Repeating timers are scheduled again after `now`, firing only once for the intervals that
+    /// were missed, and one-shot timers are dropped.
+    pub fn expire(&mut self, now: Instant) -> Vec<TimerToken> {
+        let mut expired: Vec<_> = self
+            .timers
+            .iter()
+            .filter(|&(_, timer)| timer.deadline <= now)
+            .map(|(&token, timer)| (timer.deadline, token))
+            .collect();
+        expired.sort();
+
+        for &(_, token) in &expired {
+            let timer = self.timers.get_mut(&token).unwrap();
+            let next_deadline = timer
+                .interval
+                .and_then(|interval| next_deadline(timer.deadline, interval, now));
+            match next_deadline {
+                Some(deadline) => timer.deadline = deadline,
+                // A repeating timer whose next deadline can't be represented never fires again
+                None => {
+                    self.timers.remove(&token);
+                }
+            }
+        }
+        expired.into_iter().map(|(_, token)| token).collect()
+    }
+}
+
+// The first deadline after `now` that's a whole number of intervals after `deadline`.
+fn next_deadline(deadline: Instant, interval: Duration, now: Instant) -> Option<Instant> {
+    let interval = interval.as_nanos();
+    let delay = ((now - deadline).as_nanos() / interval + 1).checked_mul(interval)?;
+    let secs = u64::try_from(delay / NANOS_PER_SEC).ok()?;
+    deadline.checked_add(Duration::new(secs, (delay % NANOS_PER_SEC) as u32))
+}
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+struct TimerQueue {
+    timerfd: RawFd,
+    timers: TimerSet,
+}
+
+impl TimerQueue {
+    // Arms the timerfd for the earliest deadline, or disarms it if there are no timers left.
+    fn arm(&self) -> io::Result<()> {
+        let delay = self.timers.next_deadline().map(|deadline| {
+            // A zero delay would disarm the timerfd instead of firing it right away.
+            let now = Instant::now();
+            if deadline > now {
+                deadline - now
+            } else {
+                Duration::from_nanos(1)
+            }
+        });
+        timerfd::set(self.timerfd, delay)
+    }
+
+    fn expire(&mut self) -> Vec<TimerToken> {
+        timerfd::clear(self.timerfd);
+        let expired = self.timers.expire(Instant::now());
+        if let Err(err) = self.arm() {
+            warn!("Failed to arm the timerfd: {}", err);
+        }
+        expired
+    }
+}
+
+impl Drop for TimerQueue {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.timerfd);
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "netbsd"))]
+mod timerfd {
+    use std::{io, os::unix::io::RawFd, ptr, time::Duration};
+
+    pub fn create() -> io::Result<RawFd> {
+        let fd = unsafe {
+            libc::timerfd_create(
+                libc::CLOCK_MONOTONIC,
+                libc::TFD_NONBLOCK | libc::TFD_CLOEXEC,
+            )
+        };
+        if fd < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(fd)
+        }
+    }
+
+    // Arms the timer to fire once after `delay`, or disarms it.
+    pub fn set(fd: RawFd, delay: Option<Duration>) -> io::Result<()> {
+        let delay = delay.unwrap_or_default();
+        let spec = libc::itimerspec {
+            it_interval: libc::timespec {
+                tv_sec: 0,
+                tv_nsec: 0,
+            },
+            it_value: libc::timespec {
+                tv_sec: delay.as_secs() as libc::time_t,
+                tv_nsec: delay.subsec_nanos() as _,
+            },
+        };
+        if unsafe { libc::timerfd_settime(fd, 0, &spec, ptr::null_mut()) } < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    // Reads the expiration count, so that the timerfd stops being readable.
+    pub fn clear(fd: RawFd) {
+        let mut expirations = 0u64;
+        unsafe {
+            libc::read(fd, &mut expirations as *mut u64 as *mut _, 8);
+        }
+    }
+}
+
+// Without timerfd, timers are unavailable.
+#[cfg(not(any(target_os = "linux", target_os = "freebsd", target_os = "netbsd")))]
+mod timerfd {
+    use std::{io, os::unix::io::RawFd, time::Duration};
+
+    pub fn create() -> io::Result<RawFd> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "timerfd isn't available on this platform",
+        ))
+    }
+
+    pub fn set(_fd: RawFd, _delay: Option<Duration>) -> io::Result<()> {
+        unreachable!()
+    }
+
+    pub fn clear(_fd: RawFd) {
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::TimerSet;
+
+    #[test]
+    fn repeating_timer_misses_many_intervals() {
+        let start = Instant::now();
+        let interval = Duration::from_millis(1);
+        let mut timers = TimerSet::default();
+        let token = timers.insert(start + interval, Some(interval)).unwrap();
+
+        // Far more missed intervals than fit in a `u32`
+        let now = start + Duration::from_secs(100 * 24 * 60 * 60);
+        assert_eq!(timers.expire(now), vec![token]);
+        assert_eq!(timers.next_deadline(), Some(now + interval));
+        assert_eq!(timers.expire(now), vec![]);
+    }
+
+    #[test]
+    fn one_shot_timer_fires_once() {
+        let start = Instant::now();
+        let mut timers = TimerSet::default();
+        let token = timers.insert(start + Duration::from_secs(1), None).unwrap();
+
+        assert_eq!(timers.expire(start), vec![]);
+        assert_eq!(timers.expire(start + Duration::from_secs(2)), vec![token]);
+        assert_eq!(timers.next_deadline(), None);
+        assert!(!timers.remove(token));
+    }
+}
@@ -0,0 +1,1014 @@
+use std::{
+    ffi::OsStr,
+    fs::File,
+    io::{self, Read, Write},
+    os::{
+        raw::c_int,
+        unix::{
+            ffi::OsStrExt,
+            io::{AsRawFd, FromRawFd},
+        },
+    },
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use percent_encoding::percent_decode;
+use smithay_client_toolkit::reexports::{
+    client::{
+        protocol::{
+            wl_data_device, wl_data_device_manager, wl_data_offer, wl_data_source, wl_seat,
+            wl_surface,
+        },
+        Display, NewProxy,
+    },
+    protocols::unstable::primary_selection::v1::client::{
+        zwp_primary_selection_device_manager_v1::ZwpPrimarySelectionDeviceManagerV1,
+        zwp_primary_selection_device_v1, zwp_primary_selection_offer_v1,
+        zwp_primary_selection_source_v1,
+    },
+};
+
+use crate::{
+    error::{ExternalError, NotSupportedError},
+    event::{DragAction, Event, WindowEvent},
+    platform_impl::{DropChoice, OsError},
+};
+
+use super::{event_loop::WindowEventsSink, window::WindowStore, WindowId};
+
+// How long we wait on the selection owner before giving up on a transfer. This is reset every
+// time some data arrives.
+const TRANSFER_TIMEOUT: Duration = Duration::from_secs(1);
+
+// The MIME type files are dragged as.
+const URI_LIST: &str = "text/uri-list";
+
+// The selection can either be the clipboard, which goes through `wl_data_device`, or the primary
+// selection, which goes through `zwp_primary_selection_device_v1`. Both protocols work the same,
+// so the types below only paper over the differences in naming.
+
+/// The global used to create the data devices and sources of a selection.
+#[derive(Clone)]
+pub enum DeviceManager {
+    Data(wl_data_device_manager::WlDataDeviceManager),
+    Primary(ZwpPrimarySelectionDeviceManagerV1),
+}
+
+#[derive(Clone)]
+enum DataOffer {
+    Data(wl_data_offer::WlDataOffer),
+    Primary(zwp_primary_selection_offer_v1::ZwpPrimarySelectionOfferV1),
+}
+
+impl DataOffer {
+    fn equals(&self, other: &DataOffer) -> bool {
+        match (self, other) {
+            (DataOffer::Data(a), DataOffer::Data(b)) => a.as_ref().equals(b.as_ref()),
+            (DataOffer::Primary(a), DataOffer::Primary(b)) => a.as_ref().equals(b.as_ref()),
+            _ => false,
+        }
+    }
+
+    fn receive(&self, mime_type: String, fd: c_int) {
+        match self {
+            DataOffer::Data(offer) => offer.receive(mime_type, fd),
+            DataOffer::Primary(offer) => offer.receive(mime_type, fd),
+        }
+    }
+
+    fn destroy(&self) {
+        match self {
+            DataOffer::Data(offer) => offer.destroy(),
+            DataOffer::Primary(offer) => offer.destroy(),
+        }
+    }
+}
+
+#[derive(Clone)]
+enum DataSource {
+    Data(wl_data_source::WlDataSource),
+    Primary(zwp_primary_selection_source_v1::ZwpPrimarySelectionSourceV1),
+}
+
+impl DataSource {
+    fn equals(&self, other: &DataSource) -> bool {
+        match (self, other) {
+            (DataSource::Data(a), DataSource::Data(b)) => a.as_ref().equals(b.as_ref()),
+            (DataSource::Primary(a), DataSource::Primary(b)) => a.as_ref().equals(b.as_ref()),
+            _ => false,
+        }
+    }
+
+    fn offer(&self, mime_type: String) {
+        match self {
+            DataSource::Data(source) => source.offer(mime_type),
+            DataSource::Primary(source) => source.offer(mime_type),
+        }
+    }
+
+    fn destroy(&self) {
+        match self {
+            DataSource::Data(source) => source.destroy(),
+            DataSource::Primary(source) => source.destroy(),
+        }
+    }
+}
+
+enum DataDevice {
+    Data(wl_data_device::WlDataDevice),
+    Primary(zwp_primary_selection_device_v1::ZwpPrimarySelectionDeviceV1),
+}
+
+impl DataDevice {
+    fn set_selection(&self, source: Option<&DataSource>, serial: u32) {
+        match (self, source) {
+            (DataDevice::Data(device), Some(DataSource::Data(source))) => {
+                device.set_selection(Some(source), serial)
+            }
+            (DataDevice::Data(device), None) => device.set_selection(None, serial),
+            (DataDevice::Primary(device), Some(DataSource::Primary(source))) => {
+                device.set_selection(Some(source), serial)
+            }
+            (DataDevice::Primary(device), None) => device.set_selection(None, serial),
+            _ => (),
+        }
+    }
+
+    fn release(&self) {
+        match self {
+            DataDevice::Data(device) => {
+                if device.as_ref().version() >= 2 {
+                    device.release();
+                }
+            }
+            DataDevice::Primary(device) => device.destroy(),
+        }
+    }
+}
+
+enum DeviceEvent {
+    DataOffer(Offer),
+    Selection(Option<DataOffer>),
+    Enter {
+        serial: u32,
+        surface: wl_surface::WlSurface,
+        id: Option<DataOffer>,
+    },
+    Leave,
+    Drop,
+}
+
+enum SourceEvent {
+    Send { mime_type: String, fd: c_int },
+    Cancelled,
+}
+
+// An offer from another client, along with the MIME types it was advertised with. Offers are
+// implemented as threadsafe, so that a dropped offer can be finished by the thread reading it.
+struct Offer {
+    offer: DataOffer,
+    mime_types: Arc<Mutex<Vec<String>>>,
+}
+
+impl Offer {
+    fn new_data(offer: NewProxy<wl_data_offer::WlDataOffer>) -> Offer {
+        let mime_types = Arc::new(Mutex::new(Vec::new()));
+        let offer = {
+            let mime_types = mime_types.clone();
+            offer.implement_closure_threadsafe(
+                move |evt, _| {
+                    if let wl_data_offer::Event::Offer { mime_type } = evt {
+                        mime_types.lock().unwrap().push(mime_type);
+                    }
+                },
+                (),
+            )
+        };
+        Offer {
+            offer: DataOffer::Data(offer),
+            mime_types,
+        }
+    }
+
+    fn new_primary(
+        offer: NewProxy<zwp_primary_selection_offer_v1::ZwpPrimarySelectionOfferV1>,
+    ) -> Offer {
+        let mime_types = Arc::new(Mutex::new(Vec::new()));
+        let offer = {
+            let mime_types = mime_types.clone();
+            offer.implement_closure_threadsafe(
+                move |evt, _| {
+                    if let zwp_primary_selection_offer_v1::Event::Offer { mime_type } = evt {
+                        mime_types.lock().unwrap().push(mime_type);
+                    }
+                },
+                (),
+            )
+        };
+        Offer {
+            offer: DataOffer::Primary(offer),
+            mime_types,
+        }
+    }
+}
+
+impl Drop for Offer {
+    fn drop(&mut self) {
+        self.offer.destroy();
+    }
+}
+
+// Data being dragged over one of our windows.
+struct DropTarget {
+    offer: Offer,
+    window_id: WindowId,
+    // The serial of the `enter` event, needed to accept the offer.
+    serial: u32,
+    choice: DropChoice,
+    // Cleared once the data leaves the window or is dropped. `HoveredFile` is sent from another
+    // thread, and this keeps it from coming after `HoveredFileCancelled`.
+    hovering: Arc<Mutex<bool>>,
+}
+
+impl DropTarget {
+    // The MIME type we want the data as, if we accept it at all.
+    fn mime_type(&self) -> Option<String> {
+        let offered = |mime_type: &str| {
+            self.offer
+                .mime_types
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|mime| mime == mime_type)
+        };
+        match self.choice {
+            DropChoice::Default if offered(URI_LIST) => Some(URI_LIST.to_owned()),
+            DropChoice::Accept(ref mime_type) if offered(mime_type) => Some(mime_type.clone()),
+            _ => None,
+        }
+    }
+
+    // Tells the source whether we accept the data.
+    fn accept(&self) {
+        if let DataOffer::Data(ref offer) = self.offer.offer {
+            let mime_type = self.mime_type();
+            if offer.as_ref().version() >= 3 {
+                // The data is only ever read, so copying it is the only action that makes sense.
+                let actions = if mime_type.is_some() {
+                    wl_data_device_manager::DndAction::Copy
+                } else {
+                    wl_data_device_manager::DndAction::empty()
+                };
+                offer.set_actions(actions.to_raw(), actions.to_raw());
+            }
+            offer.accept(self.serial, mime_type);
+        }
+    }
+}
+
+struct SeatDevice {
+    id: u32,
+    seat: wl_seat::WlSeat,
+    // The serial of the latest input event on this seat, needed to set the selection.
+    serial: Arc<Mutex<u32>>,
+    device: Option<DataDevice>,
+    // Offers that have been introduced, but not yet used for anything.
+    pending_offers: Vec<Offer>,
+    selection: Option<Offer>,
+    drop_target: Option<DropTarget>,
+}
+
+impl SeatDevice {
+    fn take_offer(&mut self, offer: &DataOffer) -> Option<Offer> {
+        let idx = self
+            .pending_offers
+            .iter()
+            .position(|pending| pending.offer.equals(offer))?;
+        Some(self.pending_offers.swap_remove(idx))
+    }
+}
+
+struct Source {
+    source: DataSource,
+    contents: Arc<Vec<(String, Vec<u8>)>>,
+}
+
+#[derive(Default)]
+struct ClipboardInner {
+    manager: Option<DeviceManager>,
+    seats: Vec<SeatDevice>,
+    // Set while we own the selection.
+    source: Option<Source>,
+}
+
+impl ClipboardInner {
+    fn selection(&self) -> Option<&Offer> {
+        self.seats
+            .iter()
+            .filter_map(|seat| seat.selection.as_ref())
+            .next()
+    }
+}
+
+/// A selection, as seen through the data devices of all seats.
+#[derive(Clone)]
+pub struct Clipboard {
+    inner: Arc<Mutex<ClipboardInner>>,
+    display: Arc<Display>,
+    // Drag sources live on their own, so their events go through a channel.
+    window_events: ::calloop::channel::Sender<(WindowEvent, WindowId)>,
+}
+
+impl Clipboard {
+    pub fn new(
+        display: Arc<Display>,
+        window_events: ::calloop::channel::Sender<(WindowEvent, WindowId)>,
+    ) -> Clipboard {
+        Clipboard {
+            inner: Default::default(),
+            display,
+            window_events,
+        }
+    }
+
+    pub(super) fn set_manager<T: 'static>(
+        &self,
+        manager: DeviceManager,
+        sink: &Arc<Mutex<WindowEventsSink<T>>>,
+        store: &Arc<Mutex<WindowStore>>,
+    ) {
+        let mut inner = self.inner.lock().unwrap();
+        for seat in &mut inner.seats {
+            seat.device = Some(implement_data_device(
+                &manager,
+                &seat.seat,
+                seat.id,
+                self.inner.clone(),
+                sink.clone(),
+                store.clone(),
+                self.window_events.clone(),
+            ));
+        }
+        inner.manager = Some(manager);
+    }
+
+    pub(super) fn add_seat<T: 'static>(
+        &self,
+        id: u32,
+        seat: &wl_seat::WlSeat,
+        serial: Arc<Mutex<u32>>,
+        sink: &Arc<Mutex<WindowEventsSink<T>>>,
+        store: &Arc<Mutex<WindowStore>>,
+    ) {
+        let mut inner = self.inner.lock().unwrap();
+        let device = inner.manager.as_ref().map(|manager| {
+            implement_data_device(
+                manager,
+                seat,
+                id,
+                self.inner.clone(),
+                sink.clone(),
+                store.clone(),
+                self.window_events.clone(),
+            )
+        });
+        inner.seats.push(SeatDevice {
+            id,
+            seat: seat.clone(),
+            serial,
+            device,
+            pending_offers: Vec::new(),
+            selection: None,
+            drop_target: None,
+        });
+    }
+
+    pub(super) fn remove_seat(&self, id: u32) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(idx) = inner.seats.iter().position(|seat| seat.id == id) {
+            let seat = inner.seats.swap_remove(idx);
+            if let Some(device) = seat.device {
+                device.release();
+            }
+        }
+    }
+
+    /// Whether the compositor supports this selection.
+    pub fn is_supported(&self) -> bool {
+        self.inner.lock().unwrap().manager.is_some()
+    }
+
+    pub fn set_contents(&self, contents: Vec<(String, Vec<u8>)>) -> Result<(), ExternalError> {
+        let mut inner = self.inner.lock().unwrap();
+        let manager = match inner.manager {
+            Some(ref manager) => manager.clone(),
+            None => return Err(ExternalError::NotSupported(NotSupportedError::new())),
+        };
+
+        let contents = Arc::new(contents);
+        let source =
+            create_source(&manager, contents.clone(), self.inner.clone()).map_err(|_| {
+                ExternalError::Os(os_error!(OsError::WaylandMisc(
+                    "Failed to create a data source"
+                )))
+            })?;
+        for (mime_type, _) in contents.iter() {
+            source.offer(mime_type.clone());
+        }
+
+        for seat in &inner.seats {
+            if let Some(ref device) = seat.device {
+                device.set_selection(Some(&source), *seat.serial.lock().unwrap());
+            }
+        }
+        if let Some(previous) = inner.source.take() {
+            previous.source.destroy();
+        }
+        inner.source = Some(Source { source, contents });
+        drop(inner);
+
+        self.flush()
+    }
+
+    pub fn clear(&self) -> Result<(), ExternalError> {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(previous) = inner.source.take() {
+            for seat in &inner.seats {
+                if let Some(ref device) = seat.device {
+                    device.set_selection(None, *seat.serial.lock().unwrap());
+                }
+            }
+            previous.source.destroy();
+        }
+        drop(inner);
+
+        self.flush()
+    }
+
+    pub fn mime_types(&self) -> Result<Vec<String>, ExternalError> {
+        let inner = self.inner.lock().unwrap();
+        if let Some(ref source) = inner.source {
+            return Ok(source
+                .contents
+                .iter()
+                .map(|(mime_type, _)| mime_type.clone())
+                .collect());
+        }
+        Ok(inner
+            .selection()
+            .map(|offer| offer.mime_types.lock().unwrap().clone())
+            .unwrap_or_default())
+    }
+
+    pub fn contents(&self, mime_type: &str) -> Result<Option<Vec<u8>>, ExternalError> {
+        let file = {
+            let inner = self.inner.lock().unwrap();
+            // Reading our own offer would deadlock if we're on the event loop's thread.
+            if let Some(ref source) = inner.source {
+                return Ok(source
+                    .contents
+                    .iter()
+                    .find(|(mime, _)| mime == mime_type)
+                    .map(|(_, data)| data.clone()));
+            }
+            let offer = match inner.selection() {
+                Some(offer) => offer,
+                None => return Ok(None),
+            };
+            if !offer
+                .mime_types
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|mime| mime == mime_type)
+            {
+                return Ok(None);
+            }
+            receive(&offer.offer, mime_type)?
+        };
+        self.flush()?;
+
+        read_to_end(file).map(Some)
+    }
+
+    pub fn start_drag(
+        &self,
+        contents: Vec<(String, Vec<u8>)>,
+        surface: &wl_surface::WlSurface,
+        window_id: WindowId,
+    ) -> Result<(), ExternalError> {
+        let inner = self.inner.lock().unwrap();
+        let manager = match inner.manager {
+            Some(DeviceManager::Data(ref manager)) => manager.clone(),
+            _ => return Err(ExternalError::NotSupported(NotSupportedError::new())),
+        };
+        let (device, serial) = match inner.seats.iter().find_map(|seat| match seat.device {
+            Some(DataDevice::Data(ref device)) => Some((device, *seat.serial.lock().unwrap())),
+            _ => None,
+        }) {
+            Some(seat) => seat,
+            None => {
+                return Err(ExternalError::Os(os_error!(OsError::WaylandMisc(
+                    "No seat to drag with"
+                ))))
+            }
+        };
+
+        let contents = Arc::new(contents);
+        let source = create_drag_source(
+            &manager,
+            contents.clone(),
+            window_id,
+            self.window_events.clone(),
+        )
+        .map_err(|_| {
+            ExternalError::Os(os_error!(OsError::WaylandMisc(
+                "Failed to create a data source"
+            )))
+        })?;
+        for (mime_type, _) in contents.iter() {
+            source.offer(mime_type.clone());
+        }
+        if source.as_ref().version() >= 3 {
+            source.set_actions(
+                (wl_data_device_manager::DndAction::Copy | wl_data_device_manager::DndAction::Move)
+                    .to_raw(),
+            );
+        }
+        device.start_drag(Some(&source), surface, None, serial);
+        drop(inner);
+
+        self.flush()
+    }
+
+    pub fn accept_drop(&self, window_id: WindowId, mime_type: Option<&str>) {
+        let mut inner = self.inner.lock().unwrap();
+        for seat in &mut inner.seats {
+            if let Some(ref mut target) = seat.drop_target {
+                if target.window_id == window_id {
+                    target.choice = match mime_type {
+                        Some(mime_type) => DropChoice::Accept(mime_type.to_owned()),
+                        None => DropChoice::Reject,
+                    };
+                    target.accept();
+                }
+            }
+        }
+        drop(inner);
+
+        let _ = self.flush();
+    }
+
+    fn flush(&self) -> Result<(), ExternalError> {
+        self.display.flush().map_err(|_| {
+            ExternalError::Os(os_error!(OsError::WaylandMisc(
+                "Failed to flush the Wayland connection"
+            )))
+        })
+    }
+}
+
+// Asks the owner of `offer` to write its data as `mime_type`, returning the end of the pipe the
+// data can be read from.
+fn receive(offer: &DataOffer, mime_type: &str) -> Result<File, ExternalError> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(ExternalError::Os(os_error!(OsError::WaylandMisc(
+            "Failed to create a pipe"
+        ))));
+    }
+    // The write end is duplicated when the request is sent, so we can close ours.
+    let (read, write) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+    offer.receive(mime_type.to_owned(), fds[1]);
+    drop(write);
+    Ok(read)
+}
+
+fn read_to_end(mut file: File) -> Result<Vec<u8>, ExternalError> {
+    let mut data = Vec::new();
+    let mut buf = [0; 4096];
+    let mut deadline = Instant::now() + TRANSFER_TIMEOUT;
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(ExternalError::Os(os_error!(OsError::WaylandMisc(
+                "Timed out waiting for the selection owner"
+            ))));
+        }
+        let mut fd = libc::pollfd {
+            fd: file.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        if unsafe { libc::poll(&mut fd, 1, (deadline - now).as_millis() as c_int) } <= 0 {
+            continue;
+        }
+        match file.read(&mut buf) {
+            Ok(0) => return Ok(data),
+            Ok(count) => {
+                data.extend_from_slice(&buf[..count]);
+                deadline = Instant::now() + TRANSFER_TIMEOUT;
+            }
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => (),
+            Err(_) => {
+                return Err(ExternalError::Os(os_error!(OsError::WaylandMisc(
+                    "Failed to read from the selection owner"
+                ))))
+            }
+        }
+    }
+}
+
+fn handle_source_event(
+    event: SourceEvent,
+    source: DataSource,
+    contents: &Arc<Vec<(String, Vec<u8>)>>,
+    clipboard: &Mutex<ClipboardInner>,
+) {
+    match event {
+        SourceEvent::Send { mime_type, fd } => {
+            let mut file = unsafe { File::from_raw_fd(fd) };
+            let contents = contents.clone();
+            // The reader may take its time, so don't block the event loop on it.
+            thread::spawn(move || {
+                if let Some((_, data)) = contents.iter().find(|(mime, _)| *mime == mime_type) {
+                    let _ = file.write_all(data);
+                }
+            });
+        }
+        SourceEvent::Cancelled => {
+            let mut clipboard = clipboard.lock().unwrap();
+            let is_current = clipboard
+                .source
+                .as_ref()
+                .map(|current| current.source.equals(&source))
+                .unwrap_or(false);
+            if is_current {
+                clipboard.source = None;
+            }
+            source.destroy();
+        }
+    }
+}
+
+fn create_source(
+    manager: &DeviceManager,
+    contents: Arc<Vec<(String, Vec<u8>)>>,
+    clipboard: Arc<Mutex<ClipboardInner>>,
+) -> Result<DataSource, ()> {
+    match manager {
+        DeviceManager::Data(manager) => manager
+            .create_data_source(|source| {
+                source.implement_closure_threadsafe(
+                    move |evt, source| {
+                        let event = match evt {
+                            wl_data_source::Event::Send { mime_type, fd } => {
+                                SourceEvent::Send { mime_type, fd }
+                            }
+                            wl_data_source::Event::Cancelled => SourceEvent::Cancelled,
+                            _ => return,
+                        };
+                        handle_source_event(event, DataSource::Data(source), &contents, &clipboard)
+                    },
+                    (),
+                )
+            })
+            .map(DataSource::Data),
+        DeviceManager::Primary(manager) => manager
+            .create_source(|source| {
+                source.implement_closure_threadsafe(
+                    move |evt, source| {
+                        let event = match evt {
+                            zwp_primary_selection_source_v1::Event::Send { mime_type, fd } => {
+                                SourceEvent::Send { mime_type, fd }
+                            }
+                            zwp_primary_selection_source_v1::Event::Cancelled => {
+                                SourceEvent::Cancelled
+                            }
+                            _ => return,
+                        };
+                        handle_source_event(
+                            event,
+                            DataSource::Primary(source),
+                            &contents,
+                            &clipboard,
+                        )
+                    },
+                    (),
+                )
+            })
+            .map(DataSource::Primary),
+    }
+}
+
+// The progress of an outgoing drag, as reported by the compositor.
+struct DragState {
+    // Whether the target under the cursor accepts one of our MIME types
+    accepted: bool,
+    // The action the compositor negotiated with the target
+    action: Option<DragAction>,
+    // The action we last reported
+    reported: Option<DragAction>,
+}
+
+fn create_drag_source(
+    manager: &wl_data_device_manager::WlDataDeviceManager,
+    contents: Arc<Vec<(String, Vec<u8>)>>,
+    window_id: WindowId,
+    window_events: ::calloop::channel::Sender<(WindowEvent, WindowId)>,
+) -> Result<wl_data_source::WlDataSource, ()> {
+    // Before version 3, there are no actions, so anything that's accepted is copied.
+    let mut state = DragState {
+        accepted: false,
+        action: if manager.as_ref().version() >= 3 {
+            None
+        } else {
+            Some(DragAction::Copy)
+        },
+        reported: None,
+    };
+    manager.create_data_source(|source| {
+        source.implement_closure_threadsafe(
+            move |evt, source| {
+                let finished = match evt {
+                    wl_data_source::Event::Send { mime_type, fd } => {
+                        let mut file = unsafe { File::from_raw_fd(fd) };
+                        let contents = contents.clone();
+                        thread::spawn(move || {
+                            if let Some((_, data)) =
+                                contents.iter().find(|(mime, _)| *mime == mime_type)
+                            {
+                                let _ = file.write_all(data);
+                            }
+                        });
+                        return;
+                    }
+                    wl_data_source::Event::Target { mime_type } => {
+                        state.accepted = mime_type.is_some();
+                        None
+                    }
+                    wl_data_source::Event::Action { dnd_action } => {
+                        let action =
+                            wl_data_device_manager::DndAction::from_bits_truncate(dnd_action);
+                        state.action = if action.contains(wl_data_device_manager::DndAction::Move) {
+                            Some(DragAction::Move)
+                        } else if action.is_empty() {
+                            None
+                        } else {
+                            Some(DragAction::Copy)
+                        };
+                        None
+                    }
+                    wl_data_source::Event::DndFinished => Some(state.action),
+                    wl_data_source::Event::Cancelled => Some(None),
+                    _ => return,
+                };
+
+                if let Some(action) = finished {
+                    source.destroy();
+                    let _ = window_events.send((WindowEvent::DragFinished(action), window_id));
+                    return;
+                }
+                let current = if state.accepted { state.action } else { None };
+                if current != state.reported {
+                    state.reported = current;
+                    let _ =
+                        window_events.send((WindowEvent::DragActionChanged(current), window_id));
+                }
+            },
+            (),
+        )
+    })
+}
+
+// Turns a `text/uri-list` into the paths of the local files it lists.
+fn parse_uri_list(data: &[u8]) -> Vec<PathBuf> {
+    String::from_utf8_lossy(data)
+        .lines()
+        .map(str::trim)
+        .filter(|uri| uri.starts_with("file://"))
+        .filter_map(|uri| {
+            // Local files have either no host or `localhost`.
+            let mut path = &uri["file://".len()..];
+            if path.starts_with("localhost/") {
+                path = &path["localhost".len()..];
+            }
+            if !path.starts_with('/') {
+                return None;
+            }
+            let path = percent_decode(path.as_bytes()).collect::<Vec<u8>>();
+            Path::new(OsStr::from_bytes(&path)).canonicalize().ok()
+        })
+        .collect()
+}
+
+fn handle_device_event<T: 'static>(
+    event: DeviceEvent,
+    seat_id: u32,
+    clipboard: &Mutex<ClipboardInner>,
+    sink: &Mutex<WindowEventsSink<T>>,
+    store: &Mutex<WindowStore>,
+    window_events: &::calloop::channel::Sender<(WindowEvent, WindowId)>,
+) {
+    let mut clipboard = clipboard.lock().unwrap();
+    let seat = match clipboard.seats.iter_mut().find(|seat| seat.id == seat_id) {
+        Some(seat) => seat,
+        None => return,
+    };
+    match event {
+        DeviceEvent::DataOffer(offer) => seat.pending_offers.push(offer),
+        DeviceEvent::Selection(id) => {
+            seat.selection = id.and_then(|id| seat.take_offer(&id));
+            // Changes are only reported for the clipboard.
+            if let Some(DataDevice::Data(_)) = seat.device {
+                sink.lock().unwrap().send_event(Event::ClipboardChanged);
+            }
+        }
+        DeviceEvent::Enter {
+            serial,
+            surface,
+            id,
+        } => {
+            let offer = id.and_then(|id| seat.take_offer(&id));
+            seat.drop_target = None;
+            let window_id = store.lock().unwrap().find_wid(&surface);
+            // Drags that stay within another client come without an offer, and there's nothing
+            // to do with drags over surfaces that aren't our windows.
+            let (offer, window_id) = match (offer, window_id) {
+                (Some(offer), Some(window_id)) => (offer, window_id),
+                _ => return,
+            };
+
+            let mime_types = offer.mime_types.lock().unwrap().clone();
+            let _ = window_events.send((WindowEvent::HoveredData(mime_types), window_id));
+            let target = DropTarget {
+                offer,
+                window_id,
+                serial,
+                choice: DropChoice::Default,
+                hovering: Arc::new(Mutex::new(true)),
+            };
+            target.accept();
+
+            // Until the application says otherwise, files are accepted, and reported right away.
+            if target.mime_type().is_some() {
+                if let Ok(file) = receive(&target.offer.offer, URI_LIST) {
+                    let hovering = target.hovering.clone();
+                    let window_events = window_events.clone();
+                    thread::spawn(move || {
+                        let paths = read_to_end(file)
+                            .map(|data| parse_uri_list(&data))
+                            .unwrap_or_default();
+                        let hovering = hovering.lock().unwrap();
+                        if *hovering {
+                            for path in paths {
+                                let _ =
+                                    window_events.send((WindowEvent::HoveredFile(path), window_id));
+                            }
+                        }
+                    });
+                }
+            }
+            seat.drop_target = Some(target);
+        }
+        DeviceEvent::Leave => {
+            if let Some(target) = seat.drop_target.take() {
+                let mut hovering = target.hovering.lock().unwrap();
+                *hovering = false;
+                let _ = window_events.send((WindowEvent::HoveredFileCancelled, target.window_id));
+                let _ = window_events.send((WindowEvent::HoveredDataCancelled, target.window_id));
+            }
+        }
+        DeviceEvent::Drop => {
+            let target = match seat.drop_target.take() {
+                Some(target) => target,
+                None => return,
+            };
+            *target.hovering.lock().unwrap() = false;
+            let window_id = target.window_id;
+            let transfer = target.mime_type().and_then(|mime_type| {
+                receive(&target.offer.offer, &mime_type)
+                    .ok()
+                    .map(|file| (mime_type, file))
+            });
+            let (mime_type, file) = match transfer {
+                Some(transfer) => transfer,
+                None => {
+                    let _ = window_events.send((WindowEvent::HoveredDataCancelled, window_id));
+                    return;
+                }
+            };
+
+            // The source may take its time, or even be one of our own windows, so the data is
+            // read on another thread.
+            let window_events = window_events.clone();
+            thread::spawn(move || {
+                let data = read_to_end(file).ok();
+                let events = match (&target.choice, data) {
+                    (DropChoice::Accept(_), Some(data)) => {
+                        vec![WindowEvent::DroppedData { mime_type, data }]
+                    }
+                    (DropChoice::Accept(_), None) => vec![WindowEvent::HoveredDataCancelled],
+                    (_, data) => parse_uri_list(&data.unwrap_or_default())
+                        .into_iter()
+                        .map(WindowEvent::DroppedFile)
+                        .collect(),
+                };
+                if let DataOffer::Data(ref offer) = target.offer.offer {
+                    if offer.as_ref().version() >= 3 {
+                        offer.finish();
+                    }
+                }
+                // This destroys the offer.
+                drop(target);
+                for event in events {
+                    let _ = window_events.send((event, window_id));
+                }
+            });
+        }
+    }
+}
+
+fn implement_data_device<T: 'static>(
+    manager: &DeviceManager,
+    seat: &wl_seat::WlSeat,
+    seat_id: u32,
+    clipboard: Arc<Mutex<ClipboardInner>>,
+    sink: Arc<Mutex<WindowEventsSink<T>>>,
+    store: Arc<Mutex<WindowStore>>,
+    window_events: ::calloop::channel::Sender<(WindowEvent, WindowId)>,
+) -> DataDevice {
+    match manager {
+        DeviceManager::Data(manager) => DataDevice::Data(
+            manager
+                .get_data_device(seat, |device| {
+                    device.implement_closure(
+                        move |evt, _| {
+                            let event = match evt {
+                                wl_data_device::Event::DataOffer { id } => {
+                                    DeviceEvent::DataOffer(Offer::new_data(id))
+                                }
+                                wl_data_device::Event::Selection { id } => {
+                                    DeviceEvent::Selection(id.map(DataOffer::Data))
+                                }
+                                wl_data_device::Event::Enter {
+                                    serial,
+                                    surface,
+                                    id,
+                                    ..
+                                } => DeviceEvent::Enter {
+                                    serial,
+                                    surface,
+                                    id: id.map(DataOffer::Data),
+                                },
+                                wl_data_device::Event::Leave => DeviceEvent::Leave,
+                                wl_data_device::Event::Drop => DeviceEvent::Drop,
+                                _ => return,
+                            };
+                            handle_device_event(
+                                event,
+                                seat_id,
+                                &clipboard,
+                                &sink,
+                                &store,
+                                &window_events,
+                            )
+                        },
+                        (),
+                    )
+                })
+                .unwrap(),
+        ),
+        DeviceManager::Primary(manager) => DataDevice::Primary(
+            manager
+                .get_device(seat, |device| {
+                    device.implement_closure(
+                        move |evt, _| {
+                            let event = match evt {
+                                zwp_primary_selection_device_v1::Event::DataOffer { offer } => {
+                                    DeviceEvent::DataOffer(Offer::new_primary(offer))
+                                }
+                                zwp_primary_selection_device_v1::Event::Selection { id } => {
+                                    DeviceEvent::Selection(id.map(DataOffer::Primary))
+                                }
+                                _ => return,
+                            };
+                            handle_device_event(
+                                event,
+                                seat_id,
+                                &clipboard,
+                                &sink,
+                                &store,
+                                &window_events,
+                            )
+                        },
+                        (),
+                    )
+                })
+                .unwrap(),
+        ),
+    }
+}
@@ -0,0 +1,590 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    fs::File,
+    io::{self, Write},
+    os::unix::io::{AsRawFd, FromRawFd},
+    time::Duration,
+};
+
+use calloop::timer::{Timeout, TimerHandle};
+
+use smithay_client_toolkit::reexports::{
+    client::{
+        cursor::{self as wl_cursor, CursorTheme},
+        protocol::{wl_buffer, wl_compositor, wl_pointer, wl_shm, wl_surface},
+        NewProxy,
+    },
+    protocols::unstable::pointer_constraints::v1::client::{
+        zwp_confined_pointer_v1::ZwpConfinedPointerV1,
+        zwp_locked_pointer_v1::ZwpLockedPointerV1,
+        zwp_pointer_constraints_v1::{Lifetime, ZwpPointerConstraintsV1},
+    },
+};
+
+use crate::error::{ExternalError, NotSupportedError};
+
+use crate::{
+    icon::CursorFrame,
+    platform_impl::platform::{cursor_names, cursor_pixels},
+    window::{CursorGrabMode, CursorIcon, CustomCursor},
+};
+
+use super::WindowId;
+
+// A frame of a cursor, ready to be attached to the cursor surface of a pointer.
+#[derive(Clone)]
+struct Frame {
+    buffer: wl_buffer::WlBuffer,
+    // The size of the buffer, in pixels
+    width: i32,
+    height: i32,
+    // The hotspot, in surface coordinates
+    hotspot_x: i32,
+    hotspot_y: i32,
+    scale: i32,
+    delay: Duration,
+}
+
+// A frame of a custom cursor, copied into memory shared with the compositor.
+struct CursorImage {
+    frame: Frame,
+}
+
+impl CursorImage {
+    fn new(shm: &wl_shm::WlShm, frame: &CursorFrame) -> Result<CursorImage, &'static str> {
+        let (width, height) = (frame.width as i32, frame.height as i32);
+        // `Argb8888` is little-endian, whatever the endianness of the machine.
+        let data = cursor_pixels(&frame.rgba)
+            .iter()
+            .flat_map(|pixel| pixel.to_le_bytes().to_vec())
+            .collect::<Vec<u8>>();
+        let file = create_shm_file(&data).map_err(|_| "failed to create a shared memory file")?;
+
+        let failed = |_| "failed to create a shared memory buffer";
+        let pool = shm
+            .create_pool(
+                file.as_raw_fd(),
+                data.len() as i32,
+                NewProxy::implement_dummy,
+            )
+            .map_err(failed)?;
+        let buffer = pool.create_buffer(
+            0,
+            width,
+            height,
+            width * 4,
+            wl_shm::Format::Argb8888,
+            NewProxy::implement_dummy,
+        );
+        // The buffer keeps the memory of the pool alive on its own.
+        pool.destroy();
+
+        Ok(CursorImage {
+            frame: Frame {
+                buffer: buffer.map_err(failed)?,
+                width,
+                height,
+                hotspot_x: frame.hotspot_x as i32,
+                hotspot_y: frame.hotspot_y as i32,
+                scale: 1,
+                delay: frame.delay,
+            },
+        })
+    }
+}
+
+impl Drop for CursorImage {
+    fn drop(&mut self) {
+        self.frame.buffer.destroy();
+    }
+}
+
+// The cursor theme of the user, loaded once for every scale factor it's needed at.
+struct Themes {
+    name: Option<String>,
+    size: u32,
+    themes: Vec<(u32, CursorTheme)>,
+}
+
+impl Themes {
+    fn new() -> Themes {
+        let name = env::var("XCURSOR_THEME")
+            .ok()
+            .filter(|name| !name.is_empty());
+        let size = env::var("XCURSOR_SIZE")
+            .ok()
+            .and_then(|size| size.parse().ok())
+            .unwrap_or(24);
+        Themes {
+            name,
+            size,
+            themes: Vec::new(),
+        }
+    }
+
+    fn frames(&mut self, shm: &wl_shm::WlShm, icon: CursorIcon, scale: u32) -> Vec<Frame> {
+        if !wl_cursor::is_available() {
+            return Vec::new();
+        }
+        let idx = match self.themes.iter().position(|&(s, _)| s == scale) {
+            Some(idx) => idx,
+            None => {
+                let theme = wl_cursor::load_theme(
+                    self.name.as_ref().map(|name| &name[..]),
+                    self.size * scale,
+                    shm,
+                );
+                self.themes.push((scale, theme));
+                self.themes.len() - 1
+            }
+        };
+        let theme = &self.themes[idx].1;
+
+        // Fall back to the default cursor when the theme has none of the names of the icon.
+        let cursor = match cursor_names(icon)
+            .iter()
+            .chain(cursor_names(CursorIcon::Default))
+            .filter_map(|name| theme.get_cursor(name))
+            .next()
+        {
+            Some(cursor) => cursor,
+            None => return Vec::new(),
+        };
+        let scale = scale as i32;
+        (0..cursor.image_count())
+            .filter_map(|i| {
+                let buffer = cursor.frame_buffer(i)?;
+                let (width, height, hotspot_x, hotspot_y, delay) = cursor.frame_info(i)?;
+                Some(Frame {
+                    buffer: (*buffer).clone(),
+                    width: width as i32,
+                    height: height as i32,
+                    hotspot_x: hotspot_x as i32 / scale,
+                    hotspot_y: hotspot_y as i32 / scale,
+                    scale,
+                    delay: Duration::from_millis(delay as u64),
+                })
+            })
+            .collect()
+    }
+}
+
+// Creates an anonymous file holding `data`, that can be shared with the compositor.
+fn create_shm_file(data: &[u8]) -> io::Result<File> {
+    let mut file = create_anonymous_file()?;
+    file.write_all(data)?;
+    Ok(file)
+}
+
+#[cfg(target_os = "linux")]
+fn create_anonymous_file() -> io::Result<File> {
+    let fd = unsafe { libc::memfd_create(b"winit-cursor\0".as_ptr() as _, libc::MFD_CLOEXEC) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn create_anonymous_file() -> io::Result<File> {
+    use std::{
+        ffi::CString,
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    loop {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.subsec_nanos())
+            .unwrap_or(0);
+        let name = CString::new(format!("/winit-cursor-{}-{}", std::process::id(), nanos)).unwrap();
+        let fd = unsafe {
+            libc::shm_open(
+                name.as_ptr(),
+                libc::O_CREAT | libc::O_EXCL | libc::O_RDWR | libc::O_CLOEXEC,
+                0o600,
+            )
+        };
+        if fd >= 0 {
+            unsafe { libc::shm_unlink(name.as_ptr()) };
+            return Ok(unsafe { File::from_raw_fd(fd) });
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::AlreadyExists {
+            return Err(err);
+        }
+    }
+}
+
+// The cursor chosen for a window.
+enum WindowCursor {
+    Icon(CursorIcon),
+    Custom(Vec<CursorImage>),
+}
+
+// A constraint on the motion of a pointer, keeping it inside of a window.
+enum Constraint {
+    // The pointer can't move at all, only relative motion is reported
+    Locked(ZwpLockedPointerV1),
+    // The pointer can't leave the window
+    Confined(ZwpConfinedPointerV1),
+}
+
+impl Constraint {
+    fn is_locked(&self) -> bool {
+        match *self {
+            Constraint::Locked(_) => true,
+            Constraint::Confined(_) => false,
+        }
+    }
+
+    fn destroy(&self) {
+        match *self {
+            Constraint::Locked(ref locked) => locked.destroy(),
+            Constraint::Confined(ref confined) => confined.destroy(),
+        }
+    }
+}
+
+struct Pointer {
+    pointer: wl_pointer::WlPointer,
+    // The surface cursors are shown with, created the first time it's needed
+    surface: Option<wl_surface::WlSurface>,
+    // The window under the pointer, along with the serial of the `enter` event
+    focus: Option<(WindowId, u32)>,
+    // The frame of the cursor currently shown, and the timeout moving on to the next one
+    frame: usize,
+    timeout: Option<Timeout>,
+    // The constraint on the pointer, while it's over a window that grabbed the cursor
+    constraint: Option<Constraint>,
+}
+
+impl Pointer {
+    fn show(
+        &mut self,
+        frames: &[Frame],
+        compositor: &wl_compositor::WlCompositor,
+        timer: &TimerHandle<wl_pointer::WlPointer>,
+    ) {
+        self.stop_animation(timer);
+        let serial = match self.focus {
+            Some((_, serial)) => serial,
+            None => return,
+        };
+        if frames.is_empty() {
+            return;
+        }
+        let frame = &frames[self.frame % frames.len()];
+        let surface = self.surface.get_or_insert_with(|| {
+            compositor
+                .create_surface(NewProxy::implement_dummy)
+                .unwrap()
+        });
+        surface.set_buffer_scale(frame.scale);
+        surface.attach(Some(&frame.buffer), 0, 0);
+        if surface.as_ref().version() >= 4 {
+            surface.damage_buffer(0, 0, frame.width, frame.height);
+        } else {
+            surface.damage(0, 0, frame.width / frame.scale, frame.height / frame.scale);
+        }
+        surface.commit();
+        self.pointer
+            .set_cursor(serial, Some(surface), frame.hotspot_x, frame.hotspot_y);
+        if frames.len() > 1 {
+            self.timeout = Some(timer.add_timeout(frame.delay, self.pointer.clone()));
+        }
+    }
+
+    fn hide(&mut self, timer: &TimerHandle<wl_pointer::WlPointer>) {
+        self.stop_animation(timer);
+        if let Some((_, serial)) = self.focus {
+            self.pointer.set_cursor(serial, None, 0, 0);
+        }
+    }
+
+    fn unconstrain(&mut self) {
+        if let Some(constraint) = self.constraint.take() {
+            constraint.destroy();
+        }
+    }
+
+    fn stop_animation(&mut self, timer: &TimerHandle<wl_pointer::WlPointer>) {
+        if let Some(timeout) = self.timeout.take() {
+            timer.cancel_timeout(&timeout);
+        }
+    }
+}
+
+/// Keeps track of the cursor of every window, and shows it on the pointers that are over it.
+///
+/// Named cursors are loaded from the cursor theme of the user, at the scale factor of the window
+/// they're shown on. Animated cursors are played by moving on to the next frame whenever a
+/// timeout of `timer` expires, see `next_frame`.
+///
+/// It also hides the cursor of windows, and locks or confines the pointers over the windows that
+/// grabbed it, through `zwp_pointer_constraints_v1`.
+pub struct CursorManager {
+    globals: Option<(wl_compositor::WlCompositor, wl_shm::WlShm)>,
+    pointer_constraints: Option<ZwpPointerConstraintsV1>,
+    pointers: Vec<Pointer>,
+    cursors: HashMap<WindowId, WindowCursor>,
+    scales: HashMap<WindowId, u32>,
+    hidden: HashSet<WindowId>,
+    // The windows that grabbed the cursor, along with their surface and whether the cursor is
+    // locked rather than confined
+    grabs: HashMap<WindowId, (wl_surface::WlSurface, bool)>,
+    themes: Themes,
+    timer: TimerHandle<wl_pointer::WlPointer>,
+}
+
+impl CursorManager {
+    pub fn new(timer: TimerHandle<wl_pointer::WlPointer>) -> CursorManager {
+        CursorManager {
+            globals: None,
+            pointer_constraints: None,
+            pointers: Vec::new(),
+            cursors: HashMap::new(),
+            scales: HashMap::new(),
+            hidden: HashSet::new(),
+            grabs: HashMap::new(),
+            themes: Themes::new(),
+            timer,
+        }
+    }
+
+    pub fn set_globals(&mut self, compositor: wl_compositor::WlCompositor, shm: wl_shm::WlShm) {
+        self.globals = Some((compositor, shm));
+    }
+
+    pub fn set_pointer_constraints(&mut self, pointer_constraints: ZwpPointerConstraintsV1) {
+        self.pointer_constraints = Some(pointer_constraints);
+    }
+
+    pub fn enter(&mut self, pointer: &wl_pointer::WlPointer, window_id: WindowId, serial: u32) {
+        let idx = match self.find_pointer(pointer) {
+            Some(idx) => idx,
+            None => {
+                self.pointers.push(Pointer {
+                    pointer: pointer.clone(),
+                    surface: None,
+                    focus: None,
+                    frame: 0,
+                    timeout: None,
+                    constraint: None,
+                });
+                self.pointers.len() - 1
+            }
+        };
+        self.pointers[idx].focus = Some((window_id, serial));
+        self.pointers[idx].frame = 0;
+        self.show(idx);
+        self.constrain(idx);
+    }
+
+    pub fn leave(&mut self, pointer: &wl_pointer::WlPointer) {
+        if let Some(idx) = self.find_pointer(pointer) {
+            let pointer = &mut self.pointers[idx];
+            pointer.focus = None;
+            pointer.stop_animation(&self.timer);
+            pointer.unconstrain();
+        }
+    }
+
+    // Shows the next frame of the animated cursor under `pointer`.
+    pub fn next_frame(&mut self, pointer: &wl_pointer::WlPointer) {
+        if let Some(idx) = self.find_pointer(pointer) {
+            // The timeout has just expired, there's nothing to cancel anymore.
+            self.pointers[idx].timeout = None;
+            self.pointers[idx].frame += 1;
+            self.show(idx);
+        }
+    }
+
+    pub fn remove_pointer(&mut self, pointer: &wl_pointer::WlPointer) {
+        if let Some(idx) = self.find_pointer(pointer) {
+            let mut pointer = self.pointers.remove(idx);
+            pointer.stop_animation(&self.timer);
+            pointer.unconstrain();
+            if let Some(ref surface) = pointer.surface {
+                surface.destroy();
+            }
+        }
+    }
+
+    pub fn remove_window(&mut self, window_id: WindowId) {
+        self.cursors.remove(&window_id);
+        self.scales.remove(&window_id);
+        self.hidden.remove(&window_id);
+        if self.grabs.remove(&window_id).is_some() {
+            self.update_constraints(window_id);
+        }
+    }
+
+    // Named cursors are shown at the scale factor of the window, so that they're sharp on HiDPI
+    // outputs.
+    pub fn set_scale(&mut self, window_id: WindowId, scale: u32) {
+        self.scales.insert(window_id, scale);
+        self.refresh(window_id);
+    }
+
+    pub fn set_cursor_visible(&mut self, window_id: WindowId, visible: bool) {
+        let changed = if visible {
+            self.hidden.remove(&window_id)
+        } else {
+            self.hidden.insert(window_id)
+        };
+        if changed {
+            self.refresh(window_id);
+        }
+    }
+
+    pub fn set_cursor_grab(
+        &mut self,
+        surface: &wl_surface::WlSurface,
+        window_id: WindowId,
+        mode: CursorGrabMode,
+    ) -> Result<(), ExternalError> {
+        if self.pointer_constraints.is_none() {
+            return Err(ExternalError::NotSupported(NotSupportedError::new()));
+        }
+        match mode {
+            CursorGrabMode::None => self.grabs.remove(&window_id),
+            CursorGrabMode::Confined => self.grabs.insert(window_id, (surface.clone(), false)),
+            CursorGrabMode::Locked => self.grabs.insert(window_id, (surface.clone(), true)),
+        };
+        self.update_constraints(window_id);
+        Ok(())
+    }
+
+    pub fn set_cursor_icon(&mut self, window_id: WindowId, cursor: CursorIcon) {
+        self.set_cursor(window_id, WindowCursor::Icon(cursor));
+    }
+
+    pub fn set_custom_cursor(&mut self, window_id: WindowId, cursor: &CustomCursor) {
+        let images = match self.globals {
+            Some((_, ref shm)) => cursor
+                .frames
+                .iter()
+                .map(|frame| CursorImage::new(shm, frame))
+                .collect::<Result<Vec<_>, _>>(),
+            None => return,
+        };
+        match images {
+            Ok(images) => self.set_cursor(window_id, WindowCursor::Custom(images)),
+            Err(err) => warn!("Failed to create a custom cursor: {}", err),
+        }
+    }
+
+    fn find_pointer(&self, pointer: &wl_pointer::WlPointer) -> Option<usize> {
+        self.pointers
+            .iter()
+            .position(|known| known.pointer.as_ref().equals(pointer.as_ref()))
+    }
+
+    fn set_cursor(&mut self, window_id: WindowId, cursor: WindowCursor) {
+        // The previous cursor is only dropped once the new one is shown, so that its buffers
+        // aren't destroyed while one of them is still attached.
+        let _previous = self.cursors.insert(window_id, cursor);
+        self.refresh(window_id);
+    }
+
+    // Shows the cursor of a window again, from its first frame, on every pointer over it.
+    fn refresh(&mut self, window_id: WindowId) {
+        for idx in 0..self.pointers.len() {
+            if let Some((focus, _)) = self.pointers[idx].focus {
+                if focus == window_id {
+                    self.pointers[idx].frame = 0;
+                    self.show(idx);
+                }
+            }
+        }
+    }
+
+    fn show(&mut self, idx: usize) {
+        let window_id = match self.pointers[idx].focus {
+            Some((window_id, _)) => window_id,
+            None => return,
+        };
+        let (compositor, shm) = match self.globals {
+            Some((ref compositor, ref shm)) => (compositor, shm),
+            None => return,
+        };
+        if self.hidden.contains(&window_id) {
+            self.pointers[idx].hide(&self.timer);
+            return;
+        }
+        let frames = match self.cursors.get(&window_id) {
+            Some(WindowCursor::Custom(images)) => {
+                images.iter().map(|image| image.frame.clone()).collect()
+            }
+            cursor => {
+                let icon = match cursor {
+                    Some(&WindowCursor::Icon(icon)) => icon,
+                    _ => CursorIcon::Default,
+                };
+                let scale = self.scales.get(&window_id).cloned().unwrap_or(1);
+                self.themes.frames(shm, icon, scale)
+            }
+        };
+        self.pointers[idx].show(&frames, compositor, &self.timer);
+    }
+
+    // Updates the constraints of the pointers over a window, after it changed.
+    fn update_constraints(&mut self, window_id: WindowId) {
+        for idx in 0..self.pointers.len() {
+            if let Some((focus, _)) = self.pointers[idx].focus {
+                if focus == window_id {
+                    self.constrain(idx);
+                }
+            }
+        }
+    }
+
+    // Constrains a pointer according to the window under it.
+    fn constrain(&mut self, idx: usize) {
+        let pointer = &mut self.pointers[idx];
+        let window_id = match pointer.focus {
+            Some((window_id, _)) => window_id,
+            None => return pointer.unconstrain(),
+        };
+        let (surface, lock, pointer_constraints) = match (
+            self.grabs.get(&window_id),
+            self.pointer_constraints.as_ref(),
+        ) {
+            (Some(&(ref surface, lock)), Some(pointer_constraints)) => {
+                (surface, lock, pointer_constraints)
+            }
+            _ => return pointer.unconstrain(),
+        };
+        match pointer.constraint {
+            Some(ref constraint) if constraint.is_locked() == lock => return,
+            // Only one constraint can exist at a time for a pointer.
+            _ => pointer.unconstrain(),
+        }
+        let lifetime = Lifetime::Persistent as u32;
+        pointer.constraint = if lock {
+            pointer_constraints
+                .lock_pointer(
+                    surface,
+                    &pointer.pointer,
+                    None,
+                    lifetime,
+                    NewProxy::implement_dummy,
+                )
+                .ok()
+                .map(Constraint::Locked)
+        } else {
+            pointer_constraints
+                .confine_pointer(
+                    surface,
+                    &pointer.pointer,
+                    None,
+                    lifetime,
+                    NewProxy::implement_dummy,
+                )
+                .ok()
+                .map(Constraint::Confined)
+        };
+    }
+}
@@ -1,31 +1,48 @@
 use std::{
     cell::RefCell,
+    cmp::min,
     collections::VecDeque,
     fmt,
     rc::Rc,
     sync::{Arc, Mutex},
-    time::Instant,
+    time::{Duration, Instant},
 };
 
-use smithay_client_toolkit::reexports::protocols::unstable::relative_pointer::v1::client::{
-    zwp_relative_pointer_manager_v1::ZwpRelativePointerManagerV1,
-    zwp_relative_pointer_v1::ZwpRelativePointerV1,
+use smithay_client_toolkit::reexports::protocols::unstable::{
+    pointer_constraints::v1::client::zwp_pointer_constraints_v1::ZwpPointerConstraintsV1,
+    primary_selection::v1::client::zwp_primary_selection_device_manager_v1::ZwpPrimarySelectionDeviceManagerV1,
+    relative_pointer::v1::client::{
+        zwp_relative_pointer_manager_v1::ZwpRelativePointerManagerV1,
+        zwp_relative_pointer_v1::ZwpRelativePointerV1,
+    },
+    text_input::v3::client::zwp_text_input_manager_v3::ZwpTextInputManagerV3,
 };
 
 use crate::{
     dpi::{PhysicalPosition, PhysicalSize},
-    event::ModifiersState,
+    event::{Event, ModifiersState},
     event_loop::{ControlFlow, EventLoopClosed, EventLoopWindowTarget as RootELW},
     monitor::VideoMode,
-    platform_impl::platform::sticky_exit_callback,
+    platform_impl::platform::{sticky_exit_callback, timer::Timers, FdSources},
 };
 
-use super::{window::WindowStore, DeviceId, WindowId};
+use super::{
+    clipboard::{Clipboard, DeviceManager},
+    cursor::CursorManager,
+    frame::FrameClock,
+    keyboard::KeyboardInfo,
+    text_input::TextInput,
+    window::WindowStore,
+    DeviceId, WindowId,
+};
 
 use smithay_client_toolkit::{
     output::OutputMgr,
     reexports::client::{
-        protocol::{wl_keyboard, wl_output, wl_pointer, wl_registry, wl_seat, wl_touch},
+        protocol::{
+            wl_data_device_manager::WlDataDeviceManager, wl_keyboard, wl_output, wl_pointer,
+            wl_registry, wl_seat, wl_touch,
+        },
         ConnectError, Display, EventQueue, GlobalEvent,
     },
     Environment,
@@ -56,6 +73,10 @@ impl<T> WindowEventsSink<T> {
         });
     }
 
+    pub fn send_event(&mut self, evt: crate::event::Event<T>) {
+        self.buffer.push_back(evt);
+    }
+
     fn empty_with<F>(&mut self, mut callback: F)
     where
         F: FnMut(crate::event::Event<T>),
@@ -81,6 +102,7 @@ pub struct EventLoop<T: 'static> {
     _kbd_source: ::calloop::Source<
         ::calloop::channel::Channel<(crate::event::WindowEvent, super::WindowId)>,
     >,
+    _cursor_timer_source: ::calloop::Source<::calloop::timer::Timer<wl_pointer::WlPointer>>,
     window_target: RootELW<T>,
 }
 
@@ -105,6 +127,22 @@ pub struct EventLoopWindowTarget<T> {
     pub display: Arc<Display>,
     // The list of seats
     pub seats: Arc<Mutex<Vec<(u32, wl_seat::WlSeat)>>>,
+    // The clipboard, shared by all seats
+    pub clipboard: Clipboard,
+    // The primary selection, shared by all seats
+    pub primary_selection: Clipboard,
+    // The cursors of all windows
+    pub cursors: Arc<Mutex<CursorManager>>,
+    // The input methods of all seats
+    pub text_input: TextInput,
+    // Paces the frames of all windows
+    pub frame_clock: FrameClock,
+    // The layout and repeat settings of the keyboards of all seats
+    pub keyboard_info: Arc<Mutex<KeyboardInfo>>,
+    // The file descriptors registered by the application
+    pub fd_sources: Rc<RefCell<FdSources>>,
+    // The timers started by the application
+    pub timers: Timers,
     _marker: ::std::marker::PhantomData<T>,
 }
 
@@ -122,7 +160,6 @@ impl<T: 'static> EventLoop<T> {
         let sink = Arc::new(Mutex::new(WindowEventsSink::new()));
         let store = Arc::new(Mutex::new(WindowStore::new()));
         let seats = Arc::new(Mutex::new(Vec::new()));
-
         let inner_loop = ::calloop::EventLoop::new().unwrap();
 
         let (kbd_sender, kbd_channel) = ::calloop::channel::channel();
@@ -136,12 +173,33 @@ impl<T: 'static> EventLoop<T> {
             })
             .unwrap();
 
+        let clipboard = Clipboard::new(display.clone(), kbd_sender.clone());
+        let primary_selection = Clipboard::new(display.clone(), kbd_sender.clone());
+        let text_input = TextInput::new(kbd_sender.clone());
+        let frame_clock = FrameClock::new(kbd_sender.clone());
+        let keyboard_info = Arc::new(Mutex::new(KeyboardInfo::new()));
+        let cursor_timer = ::calloop::timer::Timer::with_resolution(Duration::from_millis(1));
+        let cursors = Arc::new(Mutex::new(CursorManager::new(cursor_timer.handle())));
+        let timer_cursors = cursors.clone();
+        let cursor_timer_source = inner_loop
+            .handle()
+            .insert_source(cursor_timer, move |(pointer, _), &mut ()| {
+                timer_cursors.lock().unwrap().next_frame(&pointer);
+            })
+            .unwrap();
+
         let mut seat_manager = SeatManager {
             sink: sink.clone(),
             relative_pointer_manager_proxy: None,
             store: store.clone(),
             seats: seats.clone(),
             kbd_sender,
+            clipboard: clipboard.clone(),
+            primary_selection: primary_selection.clone(),
+            cursors: cursors.clone(),
+            text_input: text_input.clone(),
+            frame_clock: frame_clock.clone(),
+            keyboard_info: keyboard_info.clone(),
         };
 
         let env = Environment::from_display_with_cb(
@@ -162,6 +220,49 @@ impl<T: 'static> EventLoop<T> {
                                 .unwrap(),
                         )
                     }
+                    if interface == "wl_data_device_manager" {
+                        seat_manager.set_data_device_manager(
+                            registry
+                                .bind(min(version, 3), id, move |manager| {
+                                    manager.implement_closure(|_, _| (), ())
+                                })
+                                .unwrap(),
+                        )
+                    }
+                    if interface == "zwp_primary_selection_device_manager_v1" {
+                        seat_manager.set_primary_selection_manager(
+                            registry
+                                .bind(1, id, move |manager| {
+                                    manager.implement_closure(|_, _| (), ())
+                                })
+                                .unwrap(),
+                        )
+                    }
+                    if interface == "zwp_pointer_constraints_v1" {
+                        seat_manager.set_pointer_constraints(
+                            registry
+                                .bind(1, id, move |pointer_constraints| {
+                                    pointer_constraints.implement_closure(|_, _| (), ())
+                                })
+                                .unwrap(),
+                        )
+                    }
+                    if interface == "zwp_text_input_manager_v3" {
+                        seat_manager.set_text_input_manager(
+                            registry
+                                .bind(1, id, move |manager| {
+                                    manager.implement_closure(|_, _| (), ())
+                                })
+                                .unwrap(),
+                        )
+                    }
+                    if interface == "wp_presentation" {
+                        registry
+                            .bind(1, id, |presentation| {
+                                seat_manager.frame_clock.set_presentation(presentation)
+                            })
+                            .unwrap();
+                    }
                     if interface == "wl_seat" {
                         seat_manager.add_seat(id, version, registry)
                     }
@@ -175,6 +276,11 @@ impl<T: 'static> EventLoop<T> {
         )
         .unwrap();
 
+        cursors
+            .lock()
+            .unwrap()
+            .set_globals(env.compositor.clone(), env.shm.clone());
+
         let source = inner_loop
             .handle()
             .insert_source(event_queue, |(), &mut ()| {})
@@ -194,6 +300,20 @@ impl<T: 'static> EventLoop<T> {
             })
             .unwrap();
 
+        let timers = Timers::new(&inner_loop.handle(), {
+            let sink = sink.clone();
+            move |token| sink.lock().unwrap().send_event(Event::TimerFired { token })
+        });
+
+        let fd_sources = FdSources::new(inner_loop.handle(), {
+            let sink = sink.clone();
+            move |token, readiness| {
+                sink.lock()
+                    .unwrap()
+                    .send_event(Event::FdReady { token, readiness })
+            }
+        });
+
         Ok(EventLoop {
             inner_loop,
             sink,
@@ -203,6 +323,7 @@ impl<T: 'static> EventLoop<T> {
             _user_source: user_source,
             user_sender,
             _kbd_source: kbd_source,
+            _cursor_timer_source: cursor_timer_source,
             window_target: RootELW {
                 p: crate::platform_impl::EventLoopWindowTarget::Wayland(EventLoopWindowTarget {
                     evq: RefCell::new(source),
@@ -210,7 +331,15 @@ impl<T: 'static> EventLoop<T> {
                     env,
                     cleanup_needed: Arc::new(Mutex::new(false)),
                     seats,
+                    clipboard,
+                    primary_selection,
+                    cursors,
+                    text_input,
+                    frame_clock,
+                    keyboard_info,
                     display,
+                    fd_sources: Rc::new(RefCell::new(fd_sources)),
+                    timers,
                     _marker: ::std::marker::PhantomData,
                 }),
                 _marker: ::std::marker::PhantomData,
@@ -426,7 +555,7 @@ impl<T> EventLoop<T> {
                         *size = (w, h);
                     } else if frame_refresh {
                         frame.refresh();
-                        if !refresh {
+                        if refresh.is_none() {
                             frame.surface().commit()
                         }
                     }
@@ -437,8 +566,8 @@ impl<T> EventLoop<T> {
                         wid,
                     );
                 }
-                if refresh {
-                    sink.send_window_event(crate::event::WindowEvent::RedrawRequested, wid);
+                if let Some(damage) = refresh {
+                    sink.send_window_event(crate::event::WindowEvent::RedrawRequested(damage), wid);
                 }
                 if closed {
                     sink.send_window_event(crate::event::WindowEvent::CloseRequested, wid);
@@ -458,12 +587,41 @@ struct SeatManager<T: 'static> {
     seats: Arc<Mutex<Vec<(u32, wl_seat::WlSeat)>>>,
     kbd_sender: ::calloop::channel::Sender<(crate::event::WindowEvent, super::WindowId)>,
     relative_pointer_manager_proxy: Option<ZwpRelativePointerManagerV1>,
+    clipboard: Clipboard,
+    primary_selection: Clipboard,
+    cursors: Arc<Mutex<CursorManager>>,
+    text_input: TextInput,
+    frame_clock: FrameClock,
+    keyboard_info: Arc<Mutex<KeyboardInfo>>,
 }
 
 impl<T: 'static> SeatManager<T> {
-    fn add_seat(&mut self, id: u32, version: u32, registry: wl_registry::WlRegistry) {
-        use std::cmp::min;
+    fn set_data_device_manager(&mut self, manager: WlDataDeviceManager) {
+        self.clipboard
+            .set_manager(DeviceManager::Data(manager), &self.sink, &self.store);
+    }
+
+    fn set_primary_selection_manager(&mut self, manager: ZwpPrimarySelectionDeviceManagerV1) {
+        self.primary_selection.set_manager(
+            DeviceManager::Primary(manager),
+            &self.sink,
+            &self.store,
+        );
+    }
+
+    fn set_pointer_constraints(&mut self, pointer_constraints: ZwpPointerConstraintsV1) {
+        self.cursors
+            .lock()
+            .unwrap()
+            .set_pointer_constraints(pointer_constraints);
+    }
+
+    fn set_text_input_manager(&mut self, manager: ZwpTextInputManagerV3) {
+        self.text_input.set_manager(manager);
+    }
 
+    fn add_seat(&mut self, id: u32, version: u32, registry: wl_registry::WlRegistry) {
+        let serial = Arc::new(Mutex::new(0));
         let mut seat_data = SeatData {
             sink: self.sink.clone(),
             store: self.store.clone(),
@@ -474,17 +632,28 @@ impl<T: 'static> SeatManager<T> {
             touch: None,
             kbd_sender: self.kbd_sender.clone(),
             modifiers_tracker: Arc::new(Mutex::new(ModifiersState::default())),
+            serial: serial.clone(),
+            cursors: self.cursors.clone(),
+            keyboard_info: self.keyboard_info.clone(),
         };
         let seat = registry
             .bind(min(version, 5), id, move |seat| {
                 seat.implement_closure(move |event, seat| seat_data.receive(event, seat), ())
             })
             .unwrap();
+        self.clipboard
+            .add_seat(id, &seat, serial.clone(), &self.sink, &self.store);
+        self.primary_selection
+            .add_seat(id, &seat, serial, &self.sink, &self.store);
+        self.text_input.add_seat(id, &seat);
         self.store.lock().unwrap().new_seat(&seat);
         self.seats.lock().unwrap().push((id, seat));
     }
 
     fn remove_seat(&mut self, id: u32) {
+        self.clipboard.remove_seat(id);
+        self.primary_selection.remove_seat(id);
+        self.text_input.remove_seat(id);
         let mut seats = self.seats.lock().unwrap();
         if let Some(idx) = seats.iter().position(|&(i, _)| i == id) {
             let (_, seat) = seats.swap_remove(idx);
@@ -505,6 +674,9 @@ struct SeatData<T> {
     keyboard: Option<wl_keyboard::WlKeyboard>,
     touch: Option<wl_touch::WlTouch>,
     modifiers_tracker: Arc<Mutex<ModifiersState>>,
+    serial: Arc<Mutex<u32>>,
+    cursors: Arc<Mutex<CursorManager>>,
+    keyboard_info: Arc<Mutex<KeyboardInfo>>,
 }
 
 impl<T: 'static> SeatData<T> {
@@ -519,6 +691,8 @@ impl<T: 'static> SeatData<T> {
                         self.sink.clone(),
                         self.store.clone(),
                         self.modifiers_tracker.clone(),
+                        self.serial.clone(),
+                        self.cursors.clone(),
                     ));
 
                     self.relative_pointer =
@@ -536,6 +710,7 @@ impl<T: 'static> SeatData<T> {
                 // destroy pointer if applicable
                 if !capabilities.contains(wl_seat::Capability::Pointer) {
                     if let Some(pointer) = self.pointer.take() {
+                        self.cursors.lock().unwrap().remove_pointer(&pointer);
                         if pointer.as_ref().version() >= 3 {
                             pointer.release();
                         }
@@ -545,8 +720,12 @@ impl<T: 'static> SeatData<T> {
                 if capabilities.contains(wl_seat::Capability::Keyboard) && self.keyboard.is_none() {
                     self.keyboard = Some(super::keyboard::init_keyboard(
                         &seat,
+                        self.sink.clone(),
+                        self.store.clone(),
                         self.kbd_sender.clone(),
                         self.modifiers_tracker.clone(),
+                        self.serial.clone(),
+                        self.keyboard_info.clone(),
                     ))
                 }
                 // destroy keyboard if applicable
@@ -582,6 +761,7 @@ impl<T: 'static> SeatData<T> {
 impl<T> Drop for SeatData<T> {
     fn drop(&mut self) {
         if let Some(pointer) = self.pointer.take() {
+            self.cursors.lock().unwrap().remove_pointer(&pointer);
             if pointer.as_ref().version() >= 3 {
                 pointer.release();
             }
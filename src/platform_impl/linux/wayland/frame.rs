@@ -0,0 +1,155 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use smithay_client_toolkit::reexports::{
+    client::{
+        protocol::{wl_callback, wl_surface},
+        NewProxy,
+    },
+    protocols::presentation_time::client::{
+        wp_presentation::{self, WpPresentation},
+        wp_presentation_feedback,
+    },
+};
+
+use crate::{
+    event::{FrameTiming, WindowEvent},
+    platform_impl::platform::instant_from_monotonic,
+};
+
+use super::{make_wid, WindowId};
+
+// The frame callbacks of a window.
+#[derive(Default)]
+struct WindowFrames {
+    // Whether a frame callback is pending
+    requested: bool,
+    // The timing of the last presented frame
+    timing: FrameTiming,
+}
+
+#[derive(Default)]
+struct FrameClockInner {
+    presentation: Option<WpPresentation>,
+    // Whether the presentation clock is `CLOCK_MONOTONIC`
+    monotonic_clock: bool,
+    windows: HashMap<WindowId, WindowFrames>,
+}
+
+/// Paces the frames of the windows on the frame callbacks of the compositor, with the timing
+/// reported through the presentation-time protocol if the compositor supports it.
+#[derive(Clone)]
+pub struct FrameClock {
+    inner: Arc<Mutex<FrameClockInner>>,
+    window_events: ::calloop::channel::Sender<(WindowEvent, WindowId)>,
+}
+
+impl FrameClock {
+    pub fn new(window_events: ::calloop::channel::Sender<(WindowEvent, WindowId)>) -> FrameClock {
+        FrameClock {
+            inner: Default::default(),
+            window_events,
+        }
+    }
+
+    pub(super) fn set_presentation(
+        &self,
+        presentation: NewProxy<WpPresentation>,
+    ) -> WpPresentation {
+        let inner = self.inner.clone();
+        let presentation = presentation.implement_closure(
+            move |evt, _| {
+                if let wp_presentation::Event::ClockId { clk_id } = evt {
+                    inner.lock().unwrap().monotonic_clock = clk_id == libc::CLOCK_MONOTONIC as u32;
+                }
+            },
+            (),
+        );
+        self.inner.lock().unwrap().presentation = Some(presentation.clone());
+        presentation
+    }
+
+    pub fn request_frame(&self, surface: &wl_surface::WlSurface) {
+        let wid = make_wid(surface);
+        let mut inner = self.inner.lock().unwrap();
+        let frames = inner.windows.entry(wid).or_default();
+        if frames.requested {
+            return;
+        }
+        frames.requested = true;
+
+        let clock = self.clone();
+        let _ = surface.frame(move |callback| {
+            callback.implement_closure_threadsafe(
+                move |evt, _| {
+                    if let wl_callback::Event::Done { .. } = evt {
+                        clock.frame_done(wid);
+                    }
+                },
+                (),
+            )
+        });
+
+        if let Some(ref presentation) = inner.presentation {
+            let clock = self.clone();
+            let _ = presentation.feedback(surface, move |feedback| {
+                feedback.implement_closure_threadsafe(
+                    move |evt, _| {
+                        if let wp_presentation_feedback::Event::Presented {
+                            tv_sec_hi,
+                            tv_sec_lo,
+                            tv_nsec,
+                            refresh,
+                            ..
+                        } = evt
+                        {
+                            let time = Duration::new(
+                                (u64::from(tv_sec_hi) << 32) | u64::from(tv_sec_lo),
+                                tv_nsec,
+                            );
+                            clock.presented(wid, time, refresh);
+                        }
+                    },
+                    (),
+                )
+            });
+        }
+    }
+
+    pub fn remove_window(&self, wid: WindowId) {
+        self.inner.lock().unwrap().windows.remove(&wid);
+    }
+
+    fn frame_done(&self, wid: WindowId) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(frames) = inner.windows.get_mut(&wid) {
+            frames.requested = false;
+            let _ = self
+                .window_events
+                .send((WindowEvent::FrameReady(frames.timing), wid));
+        }
+    }
+
+    // `refresh` is the refresh interval of the output in nanoseconds, or 0 if it's unknown.
+    fn presented(&self, wid: WindowId, time: Duration, refresh: u32) {
+        let mut inner = self.inner.lock().unwrap();
+        let monotonic_clock = inner.monotonic_clock;
+        if let Some(frames) = inner.windows.get_mut(&wid) {
+            frames.timing = FrameTiming {
+                presentation_time: if monotonic_clock {
+                    Some(instant_from_monotonic(time))
+                } else {
+                    None
+                },
+                refresh_interval: if refresh > 0 {
+                    Some(Duration::from_nanos(u64::from(refresh)))
+                } else {
+                    None
+                },
+            };
+        }
+    }
+}
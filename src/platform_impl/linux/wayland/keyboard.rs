@@ -1,378 +1,374 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    mem,
+    os::unix::io::RawFd,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use super::{event_loop::WindowEventsSink, make_wid, window::WindowStore, DeviceId, WindowId};
+use smithay_client_toolkit::reexports::client::protocol::{wl_keyboard, wl_seat};
 
-use super::{make_wid, DeviceId};
-use smithay_client_toolkit::{
-    keyboard::{
-        self, map_keyboard_auto_with_repeat, Event as KbEvent, KeyRepeatEvent, KeyRepeatKind,
-    },
-    reexports::client::protocol::{wl_keyboard, wl_seat},
+use crate::{
+    event::{ElementState, Event, KeyEvent, KeyboardInput, ModifiersState, WindowEvent},
+    keyboard::{Key, KeyCode, KeyLocation, KeyRepeatInfo, KeyboardLayout},
+    platform_impl::platform::xkb,
 };
 
-use crate::event::{ElementState, KeyboardInput, ModifiersState, VirtualKeyCode, WindowEvent};
+// The repeat delay and rate used until the compositor sends its own, in milliseconds and keys
+// per second.
+const DEFAULT_REPEAT_DELAY: u64 = 600;
+const DEFAULT_REPEAT_RATE: u32 = 25;
+
+// Wayland keycodes are evdev keycodes, which are offset by 8 in xkb keymaps.
+const XKB_KEYCODE_OFFSET: u32 = 8;
+
+// What is known about the keyboards of all seats.
+pub struct KeyboardInfo {
+    // The active layout of the last keyboard whose layout changed
+    pub layout: Option<KeyboardLayout>,
+    // The repeat delay and rate last sent by the compositor
+    pub repeat_info: KeyRepeatInfo,
+    // The keymap of the last keyboard to send one
+    kb_state: Option<Arc<Mutex<Option<xkb::KbState>>>>,
+}
+
+impl KeyboardInfo {
+    pub fn new() -> KeyboardInfo {
+        KeyboardInfo {
+            layout: None,
+            repeat_info: KeyRepeatInfo {
+                delay: Duration::from_millis(DEFAULT_REPEAT_DELAY),
+                rate: DEFAULT_REPEAT_RATE,
+            },
+            kb_state: None,
+        }
+    }
 
-pub fn init_keyboard(
+    pub fn key_label(&self, key: KeyCode) -> Option<String> {
+        let kb_state = self.kb_state.as_ref()?.lock().unwrap();
+        kb_state.as_ref()?.key_label(key)
+    }
+
+    pub fn key_from_label(&self, label: &str) -> Option<KeyCode> {
+        let kb_state = self.kb_state.as_ref()?.lock().unwrap();
+        kb_state.as_ref()?.key_from_label(label)
+    }
+}
+
+pub fn init_keyboard<T: 'static>(
     seat: &wl_seat::WlSeat,
-    sink: ::calloop::channel::Sender<(crate::event::WindowEvent, super::WindowId)>,
+    sink: Arc<Mutex<WindowEventsSink<T>>>,
+    store: Arc<Mutex<WindowStore>>,
+    kbd_sender: ::calloop::channel::Sender<(WindowEvent, WindowId)>,
     modifiers_tracker: Arc<Mutex<ModifiersState>>,
+    last_serial: Arc<Mutex<u32>>,
+    keyboard_info: Arc<Mutex<KeyboardInfo>>,
 ) -> wl_keyboard::WlKeyboard {
-    // { variables to be captured by the closures
-    let target = Arc::new(Mutex::new(None));
-    let my_sink = sink.clone();
-    let repeat_sink = sink.clone();
-    let repeat_target = target.clone();
-    let my_modifiers = modifiers_tracker.clone();
-    // }
-    let ret = map_keyboard_auto_with_repeat(
-        seat,
-        KeyRepeatKind::System,
-        move |evt: KbEvent<'_>, _| {
-            match evt {
-                KbEvent::Enter { surface, .. } => {
-                    let wid = make_wid(&surface);
-                    my_sink.send((WindowEvent::Focused(true), wid)).unwrap();
-                    *target.lock().unwrap() = Some(wid);
-                }
-                KbEvent::Leave { surface, .. } => {
-                    let wid = make_wid(&surface);
-                    my_sink.send((WindowEvent::Focused(false), wid)).unwrap();
-                    *target.lock().unwrap() = None;
+    let mut handler = KbdHandler {
+        sink,
+        store,
+        kbd_sender,
+        modifiers_tracker,
+        last_serial,
+        keyboard_info,
+        kb_state: Arc::new(Mutex::new(None)),
+        target: None,
+        repeat: None,
+    };
+    seat.get_keyboard(|keyboard| keyboard.implement_closure(move |evt, _| handler.event(evt), ()))
+        .unwrap()
+}
+
+struct KbdHandler<T: 'static> {
+    sink: Arc<Mutex<WindowEventsSink<T>>>,
+    store: Arc<Mutex<WindowStore>>,
+    kbd_sender: ::calloop::channel::Sender<(WindowEvent, WindowId)>,
+    modifiers_tracker: Arc<Mutex<ModifiersState>>,
+    last_serial: Arc<Mutex<u32>>,
+    keyboard_info: Arc<Mutex<KeyboardInfo>>,
+    // `None` until the compositor sends the keymap, or if libxkbcommon is not available
+    kb_state: Arc<Mutex<Option<xkb::KbState>>>,
+    target: Option<WindowId>,
+    // The key being repeated, whose repeat stops when the sender is dropped
+    repeat: Option<(u32, mpsc::Sender<()>)>,
+}
+
+impl<T: 'static> KbdHandler<T> {
+    fn event(&mut self, evt: wl_keyboard::Event) {
+        match evt {
+            wl_keyboard::Event::Keymap { format, fd, size } => {
+                self.repeat = None;
+                *self.kb_state.lock().unwrap() = match format {
+                    wl_keyboard::KeymapFormat::XkbV1 => unsafe {
+                        xkb::KbState::from_wayland_keymap(fd, size as usize)
+                    },
+                    _ => {
+                        close(fd);
+                        None
+                    }
+                };
+                self.keyboard_info.lock().unwrap().kb_state = Some(self.kb_state.clone());
+                self.update_layout();
+            }
+            wl_keyboard::Event::Enter {
+                surface,
+                serial,
+                keys,
+            } => {
+                *self.last_serial.lock().unwrap() = serial;
+                let wid = make_wid(&surface);
+                self.kbd_sender
+                    .send((WindowEvent::Focused(true), wid))
+                    .unwrap();
+                self.target = Some(wid);
+
+                // The keys held down when the window gains focus come as an array of keycodes.
+                let mut modifiers = *self.modifiers_tracker.lock().unwrap();
+                if let Some(ref kb_state) = *self.kb_state.lock().unwrap() {
+                    for key in keys.chunks_exact(4) {
+                        let key = u32::from_ne_bytes([key[0], key[1], key[2], key[3]]);
+                        let keysym = kb_state.key_get_one_sym(key + XKB_KEYCODE_OFFSET);
+                        xkb::update_modifier_sides(&mut modifiers.sides, keysym, true);
+                    }
                 }
-                KbEvent::Key {
-                    rawkey,
-                    keysym,
-                    state,
-                    utf8,
-                    ..
-                } => {
-                    if let Some(wid) = *target.lock().unwrap() {
-                        let state = match state {
-                            wl_keyboard::KeyState::Pressed => ElementState::Pressed,
-                            wl_keyboard::KeyState::Released => ElementState::Released,
-                            _ => unreachable!(),
-                        };
-                        let vkcode = key_to_vkey(rawkey, keysym);
-                        my_sink
-                            .send((
-                                WindowEvent::KeyboardInput {
-                                    device_id: crate::event::DeviceId(
-                                        crate::platform_impl::DeviceId::Wayland(DeviceId),
-                                    ),
-                                    input: KeyboardInput {
-                                        state,
-                                        scancode: rawkey,
-                                        virtual_keycode: vkcode,
-                                        modifiers: modifiers_tracker.lock().unwrap().clone(),
-                                    },
+                self.update_modifiers(modifiers);
+            }
+            wl_keyboard::Event::Leave { surface, .. } => {
+                self.repeat = None;
+                let wid = make_wid(&surface);
+                // The window doesn't see the modifiers released while it is unfocused.
+                self.update_modifiers(ModifiersState::default());
+                self.kbd_sender
+                    .send((WindowEvent::Focused(false), wid))
+                    .unwrap();
+                self.target = None;
+            }
+            wl_keyboard::Event::Key {
+                key, state, serial, ..
+            } => {
+                *self.last_serial.lock().unwrap() = serial;
+                let wid = match self.target {
+                    Some(wid) => wid,
+                    None => return,
+                };
+                let state = match state {
+                    wl_keyboard::KeyState::Pressed => ElementState::Pressed,
+                    wl_keyboard::KeyState::Released => ElementState::Released,
+                    _ => unreachable!(),
+                };
+                let (keysym, text, repeats) = {
+                    let mut kb_state = self.kb_state.lock().unwrap();
+                    match *kb_state {
+                        Some(ref mut kb_state) => {
+                            let keycode = key + XKB_KEYCODE_OFFSET;
+                            let keysym = kb_state.key_get_one_sym(keycode);
+                            let text = match state {
+                                ElementState::Pressed => match kb_state.compose(keysym) {
+                                    xkb::ComposeStatus::Ignored => kb_state.key_get_utf8(keycode),
+                                    xkb::ComposeStatus::Composed(text) => text,
+                                    xkb::ComposeStatus::Composing
+                                    | xkb::ComposeStatus::Cancelled => None,
                                 },
-                                wid,
-                            ))
-                            .unwrap();
-                        // send char event only on key press, not release
-                        if let ElementState::Released = state {
-                            return;
-                        }
-                        if let Some(txt) = utf8 {
-                            for chr in txt.chars() {
-                                my_sink
-                                    .send((WindowEvent::ReceivedCharacter(chr), wid))
-                                    .unwrap();
-                            }
+                                ElementState::Released => None,
+                            };
+                            (Some(keysym), text, kb_state.key_repeats(keycode))
                         }
+                        None => (None, None, false),
+                    }
+                };
+                let mut modifiers = *self.modifiers_tracker.lock().unwrap();
+                let event = key_event(key, keysym, state, text, false);
+                send_key_events(&self.kbd_sender, wid, event, modifiers);
+
+                if let Some(keysym) = keysym {
+                    let pressed = state == ElementState::Pressed;
+                    if xkb::update_modifier_sides(&mut modifiers.sides, keysym, pressed) {
+                        self.update_modifiers(modifiers);
                     }
                 }
-                KbEvent::RepeatInfo { .. } => { /* Handled by smithay client toolkit */ }
-                KbEvent::Modifiers {
-                    modifiers: event_modifiers,
-                } => *modifiers_tracker.lock().unwrap() = event_modifiers.into(),
-            }
-        },
-        move |repeat_event: KeyRepeatEvent, _| {
-            if let Some(wid) = *repeat_target.lock().unwrap() {
-                let state = ElementState::Pressed;
-                let vkcode = key_to_vkey(repeat_event.rawkey, repeat_event.keysym);
-                repeat_sink
-                    .send((
-                        WindowEvent::KeyboardInput {
-                            device_id: crate::event::DeviceId(
-                                crate::platform_impl::DeviceId::Wayland(DeviceId),
-                            ),
-                            input: KeyboardInput {
-                                state,
-                                scancode: repeat_event.rawkey,
-                                virtual_keycode: vkcode,
-                                modifiers: my_modifiers.lock().unwrap().clone(),
-                            },
-                        },
-                        wid,
-                    ))
-                    .unwrap();
-                if let Some(txt) = repeat_event.utf8 {
-                    for chr in txt.chars() {
-                        repeat_sink
-                            .send((WindowEvent::ReceivedCharacter(chr), wid))
-                            .unwrap();
+
+                match state {
+                    ElementState::Pressed
+                        if repeats && self.store.lock().unwrap().key_repeat(wid) =>
+                    {
+                        self.start_repeat(wid, key)
+                    }
+                    ElementState::Released
+                        if self.repeat.as_ref().map(|&(rawkey, _)| rawkey) == Some(key) =>
+                    {
+                        self.repeat = None
                     }
+                    _ => (),
                 }
             }
-        },
-    );
-
-    match ret {
-        Ok(keyboard) => keyboard,
-        Err(_) => {
-            // This is a fallback impl if libxkbcommon was not available
-            // This case should probably never happen, as most wayland
-            // compositors _need_ libxkbcommon anyway...
-            //
-            // In this case, we don't have the keymap information (it is
-            // supposed to be serialized by the compositor using libxkbcommon)
+            wl_keyboard::Event::Modifiers {
+                mods_depressed,
+                mods_latched,
+                mods_locked,
+                group,
+                ..
+            } => {
+                let modifiers = self.kb_state.lock().unwrap().as_mut().map(|kb_state| {
+                    kb_state.update_mask(mods_depressed, mods_latched, mods_locked, 0, 0, group);
+                    kb_state.modifiers()
+                });
+                if let Some(modifiers) = modifiers {
+                    let sides = self.modifiers_tracker.lock().unwrap().sides;
+                    self.update_modifiers(ModifiersState { sides, ..modifiers });
+                }
+                self.update_layout();
+            }
+            wl_keyboard::Event::RepeatInfo { rate, delay } => {
+                self.keyboard_info.lock().unwrap().repeat_info = KeyRepeatInfo {
+                    delay: Duration::from_millis(delay.max(0) as u64),
+                    rate: rate.max(0) as u32,
+                };
+            }
+            _ => (),
+        }
+    }
 
-            seat.get_keyboard(|keyboard| {
-                // { variables to be captured by the closure
-                let mut target = None;
-                let my_sink = sink;
-                // }
+    // Records the new state of the modifiers, and reports it to the focused window if it changed.
+    fn update_modifiers(&self, modifiers: ModifiersState) {
+        let mut modifiers_tracker = self.modifiers_tracker.lock().unwrap();
+        if *modifiers_tracker == modifiers {
+            return;
+        }
+        *modifiers_tracker = modifiers;
+        if let Some(wid) = self.target {
+            self.kbd_sender
+                .send((WindowEvent::ModifiersChanged(modifiers), wid))
+                .unwrap();
+        }
+    }
 
-                keyboard.implement_closure(
-                    move |evt, _| match evt {
-                        wl_keyboard::Event::Enter { surface, .. } => {
-                            let wid = make_wid(&surface);
-                            my_sink.send((WindowEvent::Focused(true), wid)).unwrap();
-                            target = Some(wid);
-                        }
-                        wl_keyboard::Event::Leave { surface, .. } => {
-                            let wid = make_wid(&surface);
-                            my_sink.send((WindowEvent::Focused(false), wid)).unwrap();
-                            target = None;
-                        }
-                        wl_keyboard::Event::Key { key, state, .. } => {
-                            if let Some(wid) = target {
-                                let state = match state {
-                                    wl_keyboard::KeyState::Pressed => ElementState::Pressed,
-                                    wl_keyboard::KeyState::Released => ElementState::Released,
-                                    _ => unreachable!(),
-                                };
-                                my_sink
-                                    .send((
-                                        WindowEvent::KeyboardInput {
-                                            device_id: crate::event::DeviceId(
-                                                crate::platform_impl::DeviceId::Wayland(DeviceId),
-                                            ),
-                                            input: KeyboardInput {
-                                                state,
-                                                scancode: key,
-                                                virtual_keycode: None,
-                                                modifiers: ModifiersState::default(),
-                                            },
-                                        },
-                                        wid,
-                                    ))
-                                    .unwrap();
-                            }
-                        }
-                        _ => (),
-                    },
-                    (),
-                )
-            })
+    // Publishes the active layout, and reports it if it changed from a previously known one.
+    fn update_layout(&self) {
+        let layout = self
+            .kb_state
+            .lock()
             .unwrap()
+            .as_ref()
+            .map(|kb_state| kb_state.layout());
+        let mut keyboard_info = self.keyboard_info.lock().unwrap();
+        if layout.is_none() || layout == keyboard_info.layout {
+            return;
+        }
+        let previous_layout = mem::replace(&mut keyboard_info.layout, layout.clone());
+        if let (Some(_), Some(layout)) = (previous_layout, layout) {
+            self.sink
+                .lock()
+                .unwrap()
+                .send_event(Event::KeyboardLayoutChanged(layout));
         }
     }
+
+    // Repeats a key from another thread until it is released, another key is pressed or the
+    // window loses focus.
+    fn start_repeat(&mut self, wid: WindowId, rawkey: u32) {
+        let repeat_info = self.keyboard_info.lock().unwrap().repeat_info;
+        if repeat_info.rate == 0 {
+            return;
+        }
+        let (stop_sender, stop_receiver) = mpsc::channel::<()>();
+        self.repeat = Some((rawkey, stop_sender));
+
+        let kbd_sender = self.kbd_sender.clone();
+        let kb_state = self.kb_state.clone();
+        let modifiers_tracker = self.modifiers_tracker.clone();
+        let interval = Duration::from_micros(1_000_000 / u64::from(repeat_info.rate));
+        thread::spawn(move || {
+            let mut timeout = repeat_info.delay;
+            while let Err(mpsc::RecvTimeoutError::Timeout) = stop_receiver.recv_timeout(timeout) {
+                // The modifiers may have changed since the key was pressed
+                let (keysym, text) = match *kb_state.lock().unwrap() {
+                    Some(ref kb_state) => {
+                        let keycode = rawkey + XKB_KEYCODE_OFFSET;
+                        (
+                            Some(kb_state.key_get_one_sym(keycode)),
+                            kb_state.key_get_utf8(keycode),
+                        )
+                    }
+                    None => return,
+                };
+                let modifiers = *modifiers_tracker.lock().unwrap();
+                let event = key_event(rawkey, keysym, ElementState::Pressed, text, true);
+                send_key_events(&kbd_sender, wid, event, modifiers);
+                timeout = interval;
+            }
+        });
+    }
 }
 
-fn key_to_vkey(rawkey: u32, keysym: u32) -> Option<VirtualKeyCode> {
-    match rawkey {
-        1 => Some(VirtualKeyCode::Escape),
-        2 => Some(VirtualKeyCode::Key1),
-        3 => Some(VirtualKeyCode::Key2),
-        4 => Some(VirtualKeyCode::Key3),
-        5 => Some(VirtualKeyCode::Key4),
-        6 => Some(VirtualKeyCode::Key5),
-        7 => Some(VirtualKeyCode::Key6),
-        8 => Some(VirtualKeyCode::Key7),
-        9 => Some(VirtualKeyCode::Key8),
-        10 => Some(VirtualKeyCode::Key9),
-        11 => Some(VirtualKeyCode::Key0),
-        _ => keysym_to_vkey(keysym),
+fn close(fd: RawFd) {
+    unsafe {
+        libc::close(fd);
     }
 }
 
-fn keysym_to_vkey(keysym: u32) -> Option<VirtualKeyCode> {
-    use smithay_client_toolkit::keyboard::keysyms;
-    match keysym {
-        // letters
-        keysyms::XKB_KEY_A | keysyms::XKB_KEY_a => Some(VirtualKeyCode::A),
-        keysyms::XKB_KEY_B | keysyms::XKB_KEY_b => Some(VirtualKeyCode::B),
-        keysyms::XKB_KEY_C | keysyms::XKB_KEY_c => Some(VirtualKeyCode::C),
-        keysyms::XKB_KEY_D | keysyms::XKB_KEY_d => Some(VirtualKeyCode::D),
-        keysyms::XKB_KEY_E | keysyms::XKB_KEY_e => Some(VirtualKeyCode::E),
-        keysyms::XKB_KEY_F | keysyms::XKB_KEY_f => Some(VirtualKeyCode::F),
-        keysyms::XKB_KEY_G | keysyms::XKB_KEY_g => Some(VirtualKeyCode::G),
-        keysyms::XKB_KEY_H | keysyms::XKB_KEY_h => Some(VirtualKeyCode::H),
-        keysyms::XKB_KEY_I | keysyms::XKB_KEY_i => Some(VirtualKeyCode::I),
-        keysyms::XKB_KEY_J | keysyms::XKB_KEY_j => Some(VirtualKeyCode::J),
-        keysyms::XKB_KEY_K | keysyms::XKB_KEY_k => Some(VirtualKeyCode::K),
-        keysyms::XKB_KEY_L | keysyms::XKB_KEY_l => Some(VirtualKeyCode::L),
-        keysyms::XKB_KEY_M | keysyms::XKB_KEY_m => Some(VirtualKeyCode::M),
-        keysyms::XKB_KEY_N | keysyms::XKB_KEY_n => Some(VirtualKeyCode::N),
-        keysyms::XKB_KEY_O | keysyms::XKB_KEY_o => Some(VirtualKeyCode::O),
-        keysyms::XKB_KEY_P | keysyms::XKB_KEY_p => Some(VirtualKeyCode::P),
-        keysyms::XKB_KEY_Q | keysyms::XKB_KEY_q => Some(VirtualKeyCode::Q),
-        keysyms::XKB_KEY_R | keysyms::XKB_KEY_r => Some(VirtualKeyCode::R),
-        keysyms::XKB_KEY_S | keysyms::XKB_KEY_s => Some(VirtualKeyCode::S),
-        keysyms::XKB_KEY_T | keysyms::XKB_KEY_t => Some(VirtualKeyCode::T),
-        keysyms::XKB_KEY_U | keysyms::XKB_KEY_u => Some(VirtualKeyCode::U),
-        keysyms::XKB_KEY_V | keysyms::XKB_KEY_v => Some(VirtualKeyCode::V),
-        keysyms::XKB_KEY_W | keysyms::XKB_KEY_w => Some(VirtualKeyCode::W),
-        keysyms::XKB_KEY_X | keysyms::XKB_KEY_x => Some(VirtualKeyCode::X),
-        keysyms::XKB_KEY_Y | keysyms::XKB_KEY_y => Some(VirtualKeyCode::Y),
-        keysyms::XKB_KEY_Z | keysyms::XKB_KEY_z => Some(VirtualKeyCode::Z),
-        // F--
-        keysyms::XKB_KEY_F1 => Some(VirtualKeyCode::F1),
-        keysyms::XKB_KEY_F2 => Some(VirtualKeyCode::F2),
-        keysyms::XKB_KEY_F3 => Some(VirtualKeyCode::F3),
-        keysyms::XKB_KEY_F4 => Some(VirtualKeyCode::F4),
-        keysyms::XKB_KEY_F5 => Some(VirtualKeyCode::F5),
-        keysyms::XKB_KEY_F6 => Some(VirtualKeyCode::F6),
-        keysyms::XKB_KEY_F7 => Some(VirtualKeyCode::F7),
-        keysyms::XKB_KEY_F8 => Some(VirtualKeyCode::F8),
-        keysyms::XKB_KEY_F9 => Some(VirtualKeyCode::F9),
-        keysyms::XKB_KEY_F10 => Some(VirtualKeyCode::F10),
-        keysyms::XKB_KEY_F11 => Some(VirtualKeyCode::F11),
-        keysyms::XKB_KEY_F12 => Some(VirtualKeyCode::F12),
-        keysyms::XKB_KEY_F13 => Some(VirtualKeyCode::F13),
-        keysyms::XKB_KEY_F14 => Some(VirtualKeyCode::F14),
-        keysyms::XKB_KEY_F15 => Some(VirtualKeyCode::F15),
-        keysyms::XKB_KEY_F16 => Some(VirtualKeyCode::F16),
-        keysyms::XKB_KEY_F17 => Some(VirtualKeyCode::F17),
-        keysyms::XKB_KEY_F18 => Some(VirtualKeyCode::F18),
-        keysyms::XKB_KEY_F19 => Some(VirtualKeyCode::F19),
-        keysyms::XKB_KEY_F20 => Some(VirtualKeyCode::F20),
-        keysyms::XKB_KEY_F21 => Some(VirtualKeyCode::F21),
-        keysyms::XKB_KEY_F22 => Some(VirtualKeyCode::F22),
-        keysyms::XKB_KEY_F23 => Some(VirtualKeyCode::F23),
-        keysyms::XKB_KEY_F24 => Some(VirtualKeyCode::F24),
-        // flow control
-        keysyms::XKB_KEY_Print => Some(VirtualKeyCode::Snapshot),
-        keysyms::XKB_KEY_Scroll_Lock => Some(VirtualKeyCode::Scroll),
-        keysyms::XKB_KEY_Pause => Some(VirtualKeyCode::Pause),
-        keysyms::XKB_KEY_Insert => Some(VirtualKeyCode::Insert),
-        keysyms::XKB_KEY_Home => Some(VirtualKeyCode::Home),
-        keysyms::XKB_KEY_Delete => Some(VirtualKeyCode::Delete),
-        keysyms::XKB_KEY_End => Some(VirtualKeyCode::End),
-        keysyms::XKB_KEY_Page_Down => Some(VirtualKeyCode::PageDown),
-        keysyms::XKB_KEY_Page_Up => Some(VirtualKeyCode::PageUp),
-        // arrows
-        keysyms::XKB_KEY_Left => Some(VirtualKeyCode::Left),
-        keysyms::XKB_KEY_Up => Some(VirtualKeyCode::Up),
-        keysyms::XKB_KEY_Right => Some(VirtualKeyCode::Right),
-        keysyms::XKB_KEY_Down => Some(VirtualKeyCode::Down),
-        //
-        keysyms::XKB_KEY_BackSpace => Some(VirtualKeyCode::Back),
-        keysyms::XKB_KEY_Return => Some(VirtualKeyCode::Return),
-        keysyms::XKB_KEY_space => Some(VirtualKeyCode::Space),
-        // keypad
-        keysyms::XKB_KEY_Num_Lock => Some(VirtualKeyCode::Numlock),
-        keysyms::XKB_KEY_KP_0 => Some(VirtualKeyCode::Numpad0),
-        keysyms::XKB_KEY_KP_1 => Some(VirtualKeyCode::Numpad1),
-        keysyms::XKB_KEY_KP_2 => Some(VirtualKeyCode::Numpad2),
-        keysyms::XKB_KEY_KP_3 => Some(VirtualKeyCode::Numpad3),
-        keysyms::XKB_KEY_KP_4 => Some(VirtualKeyCode::Numpad4),
-        keysyms::XKB_KEY_KP_5 => Some(VirtualKeyCode::Numpad5),
-        keysyms::XKB_KEY_KP_6 => Some(VirtualKeyCode::Numpad6),
-        keysyms::XKB_KEY_KP_7 => Some(VirtualKeyCode::Numpad7),
-        keysyms::XKB_KEY_KP_8 => Some(VirtualKeyCode::Numpad8),
-        keysyms::XKB_KEY_KP_9 => Some(VirtualKeyCode::Numpad9),
-        // misc
-        // => Some(VirtualKeyCode::AbntC1),
-        // => Some(VirtualKeyCode::AbntC2),
-        keysyms::XKB_KEY_plus => Some(VirtualKeyCode::Add),
-        keysyms::XKB_KEY_apostrophe => Some(VirtualKeyCode::Apostrophe),
-        // => Some(VirtualKeyCode::Apps),
-        // => Some(VirtualKeyCode::At),
-        // => Some(VirtualKeyCode::Ax),
-        keysyms::XKB_KEY_backslash => Some(VirtualKeyCode::Backslash),
-        // => Some(VirtualKeyCode::Calculator),
-        // => Some(VirtualKeyCode::Capital),
-        keysyms::XKB_KEY_colon => Some(VirtualKeyCode::Colon),
-        keysyms::XKB_KEY_comma => Some(VirtualKeyCode::Comma),
-        // => Some(VirtualKeyCode::Convert),
-        // => Some(VirtualKeyCode::Decimal),
-        // => Some(VirtualKeyCode::Divide),
-        keysyms::XKB_KEY_equal => Some(VirtualKeyCode::Equals),
-        // => Some(VirtualKeyCode::Grave),
-        // => Some(VirtualKeyCode::Kana),
-        // => Some(VirtualKeyCode::Kanji),
-        keysyms::XKB_KEY_Alt_L => Some(VirtualKeyCode::LAlt),
-        // => Some(VirtualKeyCode::LBracket),
-        keysyms::XKB_KEY_Control_L => Some(VirtualKeyCode::LControl),
-        keysyms::XKB_KEY_Shift_L => Some(VirtualKeyCode::LShift),
-        // => Some(VirtualKeyCode::LWin),
-        // => Some(VirtualKeyCode::Mail),
-        // => Some(VirtualKeyCode::MediaSelect),
-        // => Some(VirtualKeyCode::MediaStop),
-        keysyms::XKB_KEY_minus => Some(VirtualKeyCode::Minus),
-        keysyms::XKB_KEY_asterisk => Some(VirtualKeyCode::Multiply),
-        // => Some(VirtualKeyCode::Mute),
-        // => Some(VirtualKeyCode::MyComputer),
-        // => Some(VirtualKeyCode::NextTrack),
-        // => Some(VirtualKeyCode::NoConvert),
-        keysyms::XKB_KEY_KP_Separator => Some(VirtualKeyCode::NumpadComma),
-        keysyms::XKB_KEY_KP_Enter => Some(VirtualKeyCode::NumpadEnter),
-        keysyms::XKB_KEY_KP_Equal => Some(VirtualKeyCode::NumpadEquals),
-        keysyms::XKB_KEY_KP_Add => Some(VirtualKeyCode::Add),
-        keysyms::XKB_KEY_KP_Subtract => Some(VirtualKeyCode::Subtract),
-        keysyms::XKB_KEY_KP_Divide => Some(VirtualKeyCode::Divide),
-        keysyms::XKB_KEY_KP_Page_Up => Some(VirtualKeyCode::PageUp),
-        keysyms::XKB_KEY_KP_Page_Down => Some(VirtualKeyCode::PageDown),
-        keysyms::XKB_KEY_KP_Home => Some(VirtualKeyCode::Home),
-        keysyms::XKB_KEY_KP_End => Some(VirtualKeyCode::End),
-        // => Some(VirtualKeyCode::OEM102),
-        // => Some(VirtualKeyCode::Period),
-        // => Some(VirtualKeyCode::Playpause),
-        // => Some(VirtualKeyCode::Power),
-        // => Some(VirtualKeyCode::Prevtrack),
-        keysyms::XKB_KEY_Alt_R => Some(VirtualKeyCode::RAlt),
-        // => Some(VirtualKeyCode::RBracket),
-        keysyms::XKB_KEY_Control_R => Some(VirtualKeyCode::RControl),
-        keysyms::XKB_KEY_Shift_R => Some(VirtualKeyCode::RShift),
-        // => Some(VirtualKeyCode::RWin),
-        keysyms::XKB_KEY_semicolon => Some(VirtualKeyCode::Semicolon),
-        keysyms::XKB_KEY_slash => Some(VirtualKeyCode::Slash),
-        // => Some(VirtualKeyCode::Sleep),
-        // => Some(VirtualKeyCode::Stop),
-        // => Some(VirtualKeyCode::Subtract),
-        // => Some(VirtualKeyCode::Sysrq),
-        keysyms::XKB_KEY_Tab => Some(VirtualKeyCode::Tab),
-        keysyms::XKB_KEY_ISO_Left_Tab => Some(VirtualKeyCode::Tab),
-        // => Some(VirtualKeyCode::Underline),
-        // => Some(VirtualKeyCode::Unlabeled),
-        keysyms::XKB_KEY_XF86AudioLowerVolume => Some(VirtualKeyCode::VolumeDown),
-        keysyms::XKB_KEY_XF86AudioRaiseVolume => Some(VirtualKeyCode::VolumeUp),
-        // => Some(VirtualKeyCode::Wake),
-        // => Some(VirtualKeyCode::Webback),
-        // => Some(VirtualKeyCode::WebFavorites),
-        // => Some(VirtualKeyCode::WebForward),
-        // => Some(VirtualKeyCode::WebHome),
-        // => Some(VirtualKeyCode::WebRefresh),
-        // => Some(VirtualKeyCode::WebSearch),
-        // => Some(VirtualKeyCode::WebStop),
-        // => Some(VirtualKeyCode::Yen),
-        keysyms::XKB_KEY_XF86Copy => Some(VirtualKeyCode::Copy),
-        keysyms::XKB_KEY_XF86Paste => Some(VirtualKeyCode::Paste),
-        keysyms::XKB_KEY_XF86Cut => Some(VirtualKeyCode::Cut),
-        // fallback
-        _ => None,
+// Describes a key press or release. Without keymap, there is no keysym to tell the meaning of
+// the key.
+fn key_event(
+    rawkey: u32,
+    keysym: Option<u32>,
+    state: ElementState,
+    utf8: Option<String>,
+    is_repeat: bool,
+) -> KeyEvent {
+    let (logical_key, location) = match keysym {
+        Some(keysym) => (xkb::keysym_to_key(keysym), xkb::keysym_location(keysym)),
+        None => (Key::Unidentified, KeyLocation::Standard),
+    };
+    // text is only produced on key press, not release
+    let text = match state {
+        ElementState::Pressed => utf8.filter(|text| !text.is_empty()),
+        ElementState::Released => None,
+    };
+    KeyEvent {
+        physical_key: xkb::raw_keycode_to_keycode(rawkey),
+        scancode: rawkey,
+        logical_key,
+        text,
+        location,
+        state,
+        is_repeat,
     }
 }
 
-impl From<keyboard::ModifiersState> for ModifiersState {
-    fn from(mods: keyboard::ModifiersState) -> ModifiersState {
-        ModifiersState {
-            shift: mods.shift,
-            ctrl: mods.ctrl,
-            alt: mods.alt,
-            logo: mods.logo,
+// Sends the `KeyboardInput`, `Key` and `ReceivedCharacter` events of a key press or release.
+fn send_key_events(
+    sink: &::calloop::channel::Sender<(WindowEvent, WindowId)>,
+    wid: WindowId,
+    event: KeyEvent,
+    modifiers: ModifiersState,
+) {
+    let device_id = crate::event::DeviceId(crate::platform_impl::DeviceId::Wayland(DeviceId));
+    let virtual_keycode =
+        xkb::virtual_keycode(event.physical_key, &event.logical_key, event.location);
+    sink.send((
+        WindowEvent::KeyboardInput {
+            device_id,
+            input: KeyboardInput {
+                state: event.state,
+                scancode: event.scancode,
+                virtual_keycode,
+                modifiers,
+                is_repeat: event.is_repeat,
+            },
+        },
+        wid,
+    ))
+    .unwrap();
+
+    let text = event.text.clone();
+    sink.send((WindowEvent::Key { device_id, event }, wid))
+        .unwrap();
+
+    if let Some(text) = text {
+        for chr in text.chars() {
+            sink.send((WindowEvent::ReceivedCharacter(chr), wid))
+                .unwrap();
         }
     }
 }
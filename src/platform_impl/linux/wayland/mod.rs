@@ -2,6 +2,7 @@
            target_os = "netbsd", target_os = "openbsd"))]
 
 pub use self::{
+    clipboard::Clipboard,
     event_loop::{
         EventLoop, EventLoopProxy, EventLoopWindowTarget, MonitorHandle, WindowEventsSink,
     },
@@ -10,13 +11,18 @@ pub use self::{
 
 use smithay_client_toolkit::reexports::client::protocol::wl_surface;
 
+mod clipboard;
+mod cursor;
 mod event_loop;
+mod frame;
 mod keyboard;
 mod pointer;
+mod text_input;
 mod touch;
 mod window;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct DeviceId;
 
 impl DeviceId {
@@ -26,6 +32,7 @@ impl DeviceId {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct WindowId(usize);
 
 impl WindowId {
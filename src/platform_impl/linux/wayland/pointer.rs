@@ -5,7 +5,7 @@ use crate::event::{
     WindowEvent,
 };
 
-use super::{event_loop::WindowEventsSink, window::WindowStore, DeviceId};
+use super::{cursor::CursorManager, event_loop::WindowEventsSink, window::WindowStore, DeviceId};
 
 use smithay_client_toolkit::reexports::client::protocol::{
     wl_pointer::{self, Event as PtrEvent, WlPointer},
@@ -22,6 +22,8 @@ pub fn implement_pointer<T: 'static>(
     sink: Arc<Mutex<WindowEventsSink<T>>>,
     store: Arc<Mutex<WindowStore>>,
     modifiers_tracker: Arc<Mutex<ModifiersState>>,
+    last_serial: Arc<Mutex<u32>>,
+    cursors: Arc<Mutex<CursorManager>>,
 ) -> WlPointer {
     seat.get_pointer(|pointer| {
         let mut mouse_focus = None;
@@ -38,11 +40,13 @@ pub fn implement_pointer<T: 'static>(
                         surface,
                         surface_x,
                         surface_y,
-                        ..
+                        serial,
                     } => {
+                        *last_serial.lock().unwrap() = serial;
                         let wid = store.find_wid(&surface);
                         if let Some(wid) = wid {
                             mouse_focus = Some(wid);
+                            cursors.lock().unwrap().enter(&pointer, wid, serial);
                             sink.send_window_event(
                                 WindowEvent::CursorEntered {
                                     device_id: crate::event::DeviceId(
@@ -65,6 +69,7 @@ pub fn implement_pointer<T: 'static>(
                     }
                     PtrEvent::Leave { surface, .. } => {
                         mouse_focus = None;
+                        cursors.lock().unwrap().leave(&pointer);
                         let wid = store.find_wid(&surface);
                         if let Some(wid) = wid {
                             sink.send_window_event(
@@ -95,7 +100,13 @@ pub fn implement_pointer<T: 'static>(
                             );
                         }
                     }
-                    PtrEvent::Button { button, state, .. } => {
+                    PtrEvent::Button {
+                        button,
+                        state,
+                        serial,
+                        ..
+                    } => {
+                        *last_serial.lock().unwrap() = serial;
                         if let Some(wid) = mouse_focus {
                             let state = match state {
                                 wl_pointer::ButtonState::Pressed => ElementState::Pressed,
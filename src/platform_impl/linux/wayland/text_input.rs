@@ -0,0 +1,347 @@
+use std::{
+    collections::HashMap,
+    mem,
+    sync::{Arc, Mutex},
+};
+
+use smithay_client_toolkit::reexports::{
+    client::protocol::wl_seat,
+    protocols::unstable::text_input::v3::client::{
+        zwp_text_input_manager_v3::ZwpTextInputManagerV3,
+        zwp_text_input_v3::{self, ContentHint, ContentPurpose, ZwpTextInputV3},
+    },
+};
+
+use crate::{
+    event::{Ime, WindowEvent},
+    window::{ImeHints, ImePurpose},
+};
+
+use super::{make_wid, WindowId};
+
+fn content_purpose(purpose: ImePurpose) -> ContentPurpose {
+    match purpose {
+        ImePurpose::Normal => ContentPurpose::Normal,
+        ImePurpose::Alpha => ContentPurpose::Alpha,
+        ImePurpose::Digits => ContentPurpose::Digits,
+        ImePurpose::Number => ContentPurpose::Number,
+        ImePurpose::Phone => ContentPurpose::Phone,
+        ImePurpose::Url => ContentPurpose::Url,
+        ImePurpose::Email => ContentPurpose::Email,
+        ImePurpose::Name => ContentPurpose::Name,
+        ImePurpose::Password => ContentPurpose::Password,
+        ImePurpose::Pin => ContentPurpose::Pin,
+        ImePurpose::Date => ContentPurpose::Date,
+        ImePurpose::Time => ContentPurpose::Time,
+        ImePurpose::DateTime => ContentPurpose::Datetime,
+        ImePurpose::Terminal => ContentPurpose::Terminal,
+    }
+}
+
+fn content_hint(hints: ImeHints) -> ContentHint {
+    let mut hint = ContentHint::None;
+    hint.set(ContentHint::Completion, hints.completion);
+    hint.set(ContentHint::Spellcheck, hints.spellcheck);
+    hint.set(ContentHint::AutoCapitalization, hints.auto_capitalization);
+    hint.set(ContentHint::Lowercase, hints.lowercase);
+    hint.set(ContentHint::Uppercase, hints.uppercase);
+    hint.set(ContentHint::Titlecase, hints.titlecase);
+    hint.set(ContentHint::HiddenText, hints.hidden_text);
+    hint.set(ContentHint::SensitiveData, hints.sensitive_data);
+    hint.set(ContentHint::Latin, hints.latin);
+    hint.set(ContentHint::Multiline, hints.multiline);
+    hint
+}
+
+// The text input settings of a window.
+#[derive(Clone, Copy)]
+struct WindowState {
+    allowed: bool,
+    // The area of the text cursor, in surface coordinates.
+    cursor_rectangle: (i32, i32, i32, i32),
+    purpose: ContentPurpose,
+    hint: ContentHint,
+}
+
+impl Default for WindowState {
+    fn default() -> WindowState {
+        WindowState {
+            allowed: true,
+            cursor_rectangle: (0, 0, 0, 0),
+            purpose: ContentPurpose::Normal,
+            hint: ContentHint::None,
+        }
+    }
+}
+
+// The state of a `done` event, built from the events preceding it.
+#[derive(Default)]
+struct PendingState {
+    preedit: Option<(String, Option<(usize, usize)>)>,
+    commit: Option<String>,
+}
+
+struct SeatTextInput {
+    id: u32,
+    seat: wl_seat::WlSeat,
+    text_input: Option<ZwpTextInputV3>,
+    // The text input focus follows the keyboard focus.
+    focus: Option<WindowId>,
+    // Whether text input is enabled for the focused window.
+    enabled: bool,
+    // Whether the pre-edit text shown in the focused window is not empty.
+    has_preedit: bool,
+    pending: PendingState,
+}
+
+impl SeatTextInput {
+    // Sends the settings of the focused window, which requires text input to be enabled.
+    fn update(&self, state: &WindowState) {
+        if let Some(ref text_input) = self.text_input {
+            let (x, y, width, height) = state.cursor_rectangle;
+            text_input.set_content_type(state.hint, state.purpose);
+            text_input.set_cursor_rectangle(x, y, width, height);
+            text_input.commit();
+        }
+    }
+}
+
+#[derive(Default)]
+struct TextInputInner {
+    manager: Option<ZwpTextInputManagerV3>,
+    seats: Vec<SeatTextInput>,
+    windows: HashMap<WindowId, WindowState>,
+}
+
+/// The input methods of all seats, through `zwp_text_input_v3`.
+#[derive(Clone)]
+pub struct TextInput {
+    inner: Arc<Mutex<TextInputInner>>,
+    window_events: ::calloop::channel::Sender<(WindowEvent, WindowId)>,
+}
+
+impl TextInput {
+    pub fn new(window_events: ::calloop::channel::Sender<(WindowEvent, WindowId)>) -> TextInput {
+        TextInput {
+            inner: Default::default(),
+            window_events,
+        }
+    }
+
+    pub(super) fn set_manager(&self, manager: ZwpTextInputManagerV3) {
+        let mut inner = self.inner.lock().unwrap();
+        for seat in &mut inner.seats {
+            seat.text_input = Some(self.implement_text_input(&manager, &seat.seat, seat.id));
+        }
+        inner.manager = Some(manager);
+    }
+
+    pub(super) fn add_seat(&self, id: u32, seat: &wl_seat::WlSeat) {
+        let mut inner = self.inner.lock().unwrap();
+        let text_input = inner
+            .manager
+            .as_ref()
+            .map(|manager| self.implement_text_input(manager, seat, id));
+        inner.seats.push(SeatTextInput {
+            id,
+            seat: seat.clone(),
+            text_input,
+            focus: None,
+            enabled: false,
+            has_preedit: false,
+            pending: Default::default(),
+        });
+    }
+
+    pub(super) fn remove_seat(&self, id: u32) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(idx) = inner.seats.iter().position(|seat| seat.id == id) {
+            let mut seat = inner.seats.swap_remove(idx);
+            self.disable(&mut seat);
+            if let Some(text_input) = seat.text_input {
+                text_input.destroy();
+            }
+        }
+    }
+
+    pub(super) fn remove_window(&self, window_id: WindowId) {
+        self.inner.lock().unwrap().windows.remove(&window_id);
+    }
+
+    /// Moves the candidate window of the input method next to the given area.
+    pub(super) fn set_ime_position(&self, window_id: WindowId, rectangle: (i32, i32, i32, i32)) {
+        self.update_window(window_id, |state| state.cursor_rectangle = rectangle);
+    }
+
+    pub(super) fn set_ime_allowed(&self, window_id: WindowId, allowed: bool) {
+        self.update_window(window_id, |state| state.allowed = allowed);
+    }
+
+    pub(super) fn set_ime_purpose(
+        &self,
+        window_id: WindowId,
+        purpose: ImePurpose,
+        hints: ImeHints,
+    ) {
+        self.update_window(window_id, |state| {
+            state.purpose = content_purpose(purpose);
+            state.hint = content_hint(hints);
+        });
+    }
+
+    // Changes the settings of a window, and applies them to the seats it has the focus of.
+    fn update_window<F>(&self, window_id: WindowId, f: F)
+    where
+        F: FnOnce(&mut WindowState),
+    {
+        let mut inner = self.inner.lock().unwrap();
+        let TextInputInner {
+            ref mut seats,
+            ref mut windows,
+            ..
+        } = *inner;
+        let state = windows.entry(window_id).or_insert_with(Default::default);
+        f(state);
+        for seat in seats.iter_mut() {
+            if seat.focus == Some(window_id) {
+                self.apply(seat, state);
+            }
+        }
+    }
+
+    // Enables, updates or disables text input for the focused window of a seat.
+    fn apply(&self, seat: &mut SeatTextInput, state: &WindowState) {
+        if !state.allowed {
+            self.disable(seat);
+            return;
+        }
+        if !seat.enabled {
+            let window_id = match seat.focus {
+                Some(window_id) => window_id,
+                None => return,
+            };
+            if let Some(ref text_input) = seat.text_input {
+                text_input.enable();
+                seat.enabled = true;
+                self.send(window_id, Ime::Enabled);
+            }
+        }
+        // This also commits the enable request.
+        seat.update(state);
+    }
+
+    // Disables text input, dropping any composition in progress.
+    fn disable(&self, seat: &mut SeatTextInput) {
+        if !seat.enabled {
+            return;
+        }
+        seat.enabled = false;
+        seat.pending = Default::default();
+        if let Some(ref text_input) = seat.text_input {
+            text_input.disable();
+            text_input.commit();
+        }
+        if let Some(window_id) = seat.focus {
+            if seat.has_preedit {
+                self.send(window_id, Ime::Preedit(String::new(), None));
+            }
+            self.send(window_id, Ime::Disabled);
+        }
+        seat.has_preedit = false;
+    }
+
+    fn implement_text_input(
+        &self,
+        manager: &ZwpTextInputManagerV3,
+        seat: &wl_seat::WlSeat,
+        id: u32,
+    ) -> ZwpTextInputV3 {
+        let text_input = self.clone();
+        manager
+            .get_text_input(seat, move |text_input_proxy| {
+                text_input_proxy
+                    .implement_closure(move |event, _| text_input.receive(id, event), ())
+            })
+            .unwrap()
+    }
+
+    fn send(&self, window_id: WindowId, event: Ime) {
+        let _ = self
+            .window_events
+            .send((WindowEvent::Ime(event), window_id));
+    }
+
+    fn receive(&self, id: u32, event: zwp_text_input_v3::Event) {
+        let mut inner = self.inner.lock().unwrap();
+        let TextInputInner {
+            ref mut seats,
+            ref windows,
+            ..
+        } = *inner;
+        let seat = match seats.iter_mut().find(|seat| seat.id == id) {
+            Some(seat) => seat,
+            None => return,
+        };
+        match event {
+            zwp_text_input_v3::Event::Enter { surface } => {
+                let window_id = make_wid(&surface);
+                seat.focus = Some(window_id);
+                let state = windows.get(&window_id).cloned().unwrap_or_default();
+                self.apply(seat, &state);
+            }
+            zwp_text_input_v3::Event::Leave { .. } => {
+                self.disable(seat);
+                seat.focus = None;
+            }
+            zwp_text_input_v3::Event::PreeditString {
+                text,
+                cursor_begin,
+                cursor_end,
+            } => {
+                let text = text.unwrap_or_default();
+                // The cursor is hidden when both ends are -1.
+                let cursor = if cursor_begin < 0 || cursor_end < 0 {
+                    None
+                } else {
+                    Some((cursor_begin as usize, cursor_end as usize))
+                };
+                seat.pending.preedit = Some((text, cursor));
+            }
+            zwp_text_input_v3::Event::CommitString { text } => {
+                seat.pending.commit = text;
+            }
+            zwp_text_input_v3::Event::DeleteSurroundingText { .. } => {
+                // We never send the surrounding text, so there is nothing to delete.
+            }
+            zwp_text_input_v3::Event::Done { .. } => {
+                let window_id = match seat.focus {
+                    Some(window_id) if seat.enabled => window_id,
+                    _ => return,
+                };
+                let pending = mem::take(&mut seat.pending);
+                // The previous pre-edit text is replaced in any case, and the committed text is
+                // inserted before the new one.
+                if let Some(commit) = pending.commit {
+                    if seat.has_preedit {
+                        self.send(window_id, Ime::Preedit(String::new(), None));
+                        seat.has_preedit = false;
+                    }
+                    self.send(window_id, Ime::Commit(commit));
+                }
+                match pending.preedit {
+                    Some((text, cursor)) if !text.is_empty() => {
+                        seat.has_preedit = true;
+                        self.send(window_id, Ime::Preedit(text, cursor));
+                    }
+                    _ => {
+                        if seat.has_preedit {
+                            seat.has_preedit = false;
+                            self.send(window_id, Ime::Preedit(String::new(), None));
+                        }
+                    }
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+}
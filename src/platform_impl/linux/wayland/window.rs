@@ -6,12 +6,13 @@ use std::{
 use crate::{
     dpi::{LogicalPosition, LogicalSize},
     error::{ExternalError, NotSupportedError, OsError as RootOsError},
+    event::{Damage, DamageRect},
     monitor::MonitorHandle as RootMonitorHandle,
     platform_impl::{
         MonitorHandle as PlatformMonitorHandle,
         PlatformSpecificWindowBuilderAttributes as PlAttributes,
     },
-    window::{CursorIcon, WindowAttributes},
+    window::{CursorGrabMode, CursorIcon, CustomCursor, ImeHints, ImePurpose, WindowAttributes},
 };
 
 use smithay_client_toolkit::{
@@ -24,7 +25,10 @@ use smithay_client_toolkit::{
     window::{ConceptFrame, Event as WEvent, State as WState, Theme, Window as SWindow},
 };
 
-use super::{make_wid, EventLoopWindowTarget, MonitorHandle, WindowId};
+use super::{
+    cursor::CursorManager, frame::FrameClock, make_wid, text_input::TextInput, Clipboard,
+    EventLoopWindowTarget, MonitorHandle, WindowId,
+};
 use crate::platform_impl::platform::wayland::event_loop::{available_monitors, primary_monitor};
 
 pub struct Window {
@@ -35,8 +39,15 @@ pub struct Window {
     kill_switch: (Arc<Mutex<bool>>, Arc<Mutex<bool>>),
     display: Arc<Display>,
     need_frame_refresh: Arc<Mutex<bool>>,
-    need_refresh: Arc<Mutex<bool>>,
+    need_refresh: Arc<Mutex<Option<Damage>>>,
     fullscreen: Arc<Mutex<bool>>,
+    visible: Arc<Mutex<bool>>,
+    key_repeat: Arc<Mutex<bool>>,
+    clipboard: Clipboard,
+    primary_selection: Clipboard,
+    cursors: Arc<Mutex<CursorManager>>,
+    text_input: TextInput,
+    frame_clock: FrameClock,
 }
 
 impl Window {
@@ -51,9 +62,14 @@ impl Window {
         let fullscreen = Arc::new(Mutex::new(false));
 
         let window_store = evlp.store.clone();
+        let cursors = evlp.cursors.clone();
         let surface = evlp.env.create_surface(move |dpi, surface| {
             window_store.lock().unwrap().dpi_change(&surface, dpi);
             surface.set_buffer_scale(dpi);
+            cursors
+                .lock()
+                .unwrap()
+                .set_scale(make_wid(&surface), dpi as u32);
         });
 
         let window_store = evlp.store.clone();
@@ -70,7 +86,7 @@ impl Window {
                     for window in &mut store.windows {
                         if window.surface.as_ref().equals(&my_surface.as_ref()) {
                             window.newsize = new_size;
-                            *(window.need_refresh.lock().unwrap()) = true;
+                            *(window.need_refresh.lock().unwrap()) = Some(Damage::Full);
                             *(window.fullscreen.lock().unwrap()) = is_fullscreen;
                             *(window.need_frame_refresh.lock().unwrap()) = true;
                             return;
@@ -132,7 +148,10 @@ impl Window {
         let kill_switch = Arc::new(Mutex::new(false));
         let need_frame_refresh = Arc::new(Mutex::new(true));
         let frame = Arc::new(Mutex::new(frame));
-        let need_refresh = Arc::new(Mutex::new(true));
+        let need_refresh = Arc::new(Mutex::new(Some(Damage::Full)));
+        // An invisible window is simply never committed, so that it doesn't get mapped.
+        let visible = Arc::new(Mutex::new(attributes.visible));
+        let key_repeat = Arc::new(Mutex::new(true));
 
         evlp.store.lock().unwrap().windows.push(InternalWindow {
             closed: false,
@@ -140,6 +159,8 @@ impl Window {
             size: size.clone(),
             need_refresh: need_refresh.clone(),
             fullscreen: fullscreen.clone(),
+            visible: visible.clone(),
+            key_repeat: key_repeat.clone(),
             need_frame_refresh: need_frame_refresh.clone(),
             surface: surface.clone(),
             kill_switch: kill_switch.clone(),
@@ -159,6 +180,13 @@ impl Window {
             need_frame_refresh,
             need_refresh,
             fullscreen,
+            visible,
+            key_repeat,
+            clipboard: evlp.clipboard.clone(),
+            primary_selection: evlp.primary_selection.clone(),
+            cursors: evlp.cursors.clone(),
+            text_input: evlp.text_input.clone(),
+            frame_clock: evlp.frame_clock.clone(),
         })
     }
 
@@ -171,8 +199,28 @@ impl Window {
         self.frame.lock().unwrap().set_title(title.into());
     }
 
-    pub fn set_visible(&self, _visible: bool) {
-        // TODO
+    pub fn set_visible(&self, visible: bool) {
+        let mut visible_lock = self.visible.lock().unwrap();
+        if visible == *visible_lock {
+            return;
+        }
+        *visible_lock = visible;
+        if visible {
+            // Committing without a buffer maps the surface again, once the compositor configured
+            // it: the window is then redrawn, and the application attaches its content.
+            *self.need_refresh.lock().unwrap() = Some(Damage::Full);
+            *self.need_frame_refresh.lock().unwrap() = true;
+            self.surface.commit();
+        } else {
+            // Committing a null buffer unmaps the surface, along with its decorations.
+            self.surface.attach(None, 0, 0);
+            self.surface.commit();
+        }
+    }
+
+    #[inline]
+    pub fn set_key_repeat(&self, enabled: bool) {
+        *self.key_repeat.lock().unwrap() = enabled;
     }
 
     #[inline]
@@ -195,7 +243,19 @@ impl Window {
     }
 
     pub fn request_redraw(&self) {
-        *self.need_refresh.lock().unwrap() = true;
+        *self.need_refresh.lock().unwrap() = Some(Damage::Full);
+    }
+
+    pub fn request_redraw_region(&self, rect: DamageRect) {
+        self.need_refresh
+            .lock()
+            .unwrap()
+            .get_or_insert_with(|| Damage::Region(Vec::new()))
+            .add_rect(rect);
+    }
+
+    pub fn request_frame(&self) {
+        self.frame_clock.request_frame(&self.surface);
     }
 
     #[inline]
@@ -279,18 +339,35 @@ impl Window {
     }
 
     #[inline]
-    pub fn set_cursor_icon(&self, _cursor: CursorIcon) {
-        // TODO
+    pub fn set_cursor_icon(&self, cursor: CursorIcon) {
+        self.cursors
+            .lock()
+            .unwrap()
+            .set_cursor_icon(self.id(), cursor);
     }
 
     #[inline]
-    pub fn set_cursor_visible(&self, _visible: bool) {
-        // TODO: This isn't possible on Wayland yet
+    pub fn set_custom_cursor(&self, cursor: &CustomCursor) {
+        self.cursors
+            .lock()
+            .unwrap()
+            .set_custom_cursor(self.id(), cursor);
     }
 
     #[inline]
-    pub fn set_cursor_grab(&self, _grab: bool) -> Result<(), ExternalError> {
-        Err(ExternalError::NotSupported(NotSupportedError::new()))
+    pub fn set_cursor_visible(&self, visible: bool) {
+        self.cursors
+            .lock()
+            .unwrap()
+            .set_cursor_visible(self.id(), visible);
+    }
+
+    #[inline]
+    pub fn set_cursor_grab(&self, mode: CursorGrabMode) -> Result<(), ExternalError> {
+        self.cursors
+            .lock()
+            .unwrap()
+            .set_cursor_grab(&self.surface, self.id(), mode)
     }
 
     #[inline]
@@ -298,6 +375,24 @@ impl Window {
         Err(ExternalError::NotSupported(NotSupportedError::new()))
     }
 
+    #[inline]
+    pub fn set_ime_position(&self, position: LogicalPosition, size: LogicalSize) {
+        let (x, y): (f64, f64) = position.into();
+        let (width, height): (f64, f64) = size.into();
+        self.text_input
+            .set_ime_position(self.id(), (x as i32, y as i32, width as i32, height as i32));
+    }
+
+    #[inline]
+    pub fn set_ime_allowed(&self, allowed: bool) {
+        self.text_input.set_ime_allowed(self.id(), allowed);
+    }
+
+    #[inline]
+    pub fn set_ime_purpose(&self, purpose: ImePurpose, hints: ImeHints) {
+        self.text_input.set_ime_purpose(self.id(), purpose, hints);
+    }
+
     pub fn display(&self) -> &Display {
         &*self.display
     }
@@ -306,6 +401,23 @@ impl Window {
         &self.surface
     }
 
+    pub fn clipboard(&self) -> Clipboard {
+        self.clipboard.clone()
+    }
+
+    pub fn primary_selection(&self) -> Clipboard {
+        self.primary_selection.clone()
+    }
+
+    pub fn start_drag(&self, contents: Vec<(String, Vec<u8>)>) -> Result<(), ExternalError> {
+        self.clipboard
+            .start_drag(contents, &self.surface, self.id())
+    }
+
+    pub fn accept_drop(&self, mime_type: Option<&str>) {
+        self.clipboard.accept_drop(self.id(), mime_type)
+    }
+
     pub fn current_monitor(&self) -> MonitorHandle {
         let output = get_outputs(&self.surface).last().unwrap().clone();
         MonitorHandle {
@@ -327,6 +439,9 @@ impl Drop for Window {
     fn drop(&mut self) {
         *(self.kill_switch.0.lock().unwrap()) = true;
         *(self.kill_switch.1.lock().unwrap()) = true;
+        self.cursors.lock().unwrap().remove_window(self.id());
+        self.text_input.remove_window(self.id());
+        self.frame_clock.remove_window(self.id());
     }
 }
 
@@ -338,9 +453,11 @@ struct InternalWindow {
     surface: wl_surface::WlSurface,
     newsize: Option<(u32, u32)>,
     size: Arc<Mutex<(u32, u32)>>,
-    need_refresh: Arc<Mutex<bool>>,
+    need_refresh: Arc<Mutex<Option<Damage>>>,
     fullscreen: Arc<Mutex<bool>>,
     need_frame_refresh: Arc<Mutex<bool>>,
+    visible: Arc<Mutex<bool>>,
+    key_repeat: Arc<Mutex<bool>>,
     closed: bool,
     kill_switch: Arc<Mutex<bool>>,
     frame: Weak<Mutex<SWindow<ConceptFrame>>>,
@@ -368,6 +485,15 @@ impl WindowStore {
         None
     }
 
+    // Whether held keys repeat in a window, which is the case unless the application disabled it.
+    pub fn key_repeat(&self, wid: WindowId) -> bool {
+        self.windows
+            .iter()
+            .find(|window| make_wid(&window.surface) == wid)
+            .map(|window| *window.key_repeat.lock().unwrap())
+            .unwrap_or(true)
+    }
+
     pub fn cleanup(&mut self) -> Vec<WindowId> {
         let mut pruned = Vec::new();
         self.windows.retain(|w| {
@@ -405,7 +531,7 @@ impl WindowStore {
             Option<(u32, u32)>,
             &mut (u32, u32),
             Option<i32>,
-            bool,
+            Option<Damage>,
             bool,
             bool,
             WindowId,
@@ -415,12 +541,20 @@ impl WindowStore {
         for window in &mut self.windows {
             let opt_arc = window.frame.upgrade();
             let mut opt_mutex_lock = opt_arc.as_ref().map(|m| m.lock().unwrap());
+            // Hidden windows keep their pending refreshes until they're shown again, since
+            // drawing them would map them.
+            let visible = *window.visible.lock().unwrap();
             f(
                 window.newsize.take(),
                 &mut *(window.size.lock().unwrap()),
                 window.new_dpi,
-                ::std::mem::replace(&mut *window.need_refresh.lock().unwrap(), false),
-                ::std::mem::replace(&mut *window.need_frame_refresh.lock().unwrap(), false),
+                if visible {
+                    window.need_refresh.lock().unwrap().take()
+                } else {
+                    None
+                },
+                visible
+                    && ::std::mem::replace(&mut *window.need_frame_refresh.lock().unwrap(), false),
                 window.closed,
                 make_wid(&window.surface),
                 opt_mutex_lock.as_mut().map(|m| &mut **m),
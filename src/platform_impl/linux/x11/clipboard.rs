@@ -0,0 +1,728 @@
+use std::{
+    cmp,
+    collections::HashMap,
+    ffi::CString,
+    mem::{self, MaybeUninit},
+    os::raw::*,
+    ptr, slice,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use libc;
+use parking_lot::Mutex;
+
+use crate::{error::ExternalError, platform_impl::OsError};
+
+use super::{ffi, util, XConnection, XError};
+
+// How long we wait on the selection owner before giving up on a transfer. This is reset every
+// time a chunk of an incremental transfer arrives.
+const TRANSFER_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+pub struct SelectionAtoms {
+    pub clipboard: ffi::Atom,
+    pub primary: ffi::Atom,
+    pub targets: ffi::Atom,
+    pub multiple: ffi::Atom,
+    pub timestamp: ffi::Atom,
+    pub incr: ffi::Atom,
+    // The property on our own window that selection owners write into.
+    pub property: ffi::Atom,
+}
+
+impl SelectionAtoms {
+    fn new(xconn: &XConnection) -> Self {
+        unsafe {
+            SelectionAtoms {
+                clipboard: xconn.get_atom_unchecked(b"CLIPBOARD\0"),
+                primary: ffi::XA_PRIMARY,
+                targets: xconn.get_atom_unchecked(b"TARGETS\0"),
+                multiple: xconn.get_atom_unchecked(b"MULTIPLE\0"),
+                timestamp: xconn.get_atom_unchecked(b"TIMESTAMP\0"),
+                incr: xconn.get_atom_unchecked(b"INCR\0"),
+                property: xconn.get_atom_unchecked(b"_WINIT_SELECTION\0"),
+            }
+        }
+    }
+}
+
+// An outgoing transfer that's too large to be sent in one go.
+struct IncrTransfer {
+    requestor: ffi::Window,
+    property: ffi::Atom,
+    target: ffi::Atom,
+    data: Arc<Vec<u8>>,
+    offset: usize,
+    // The events we had selected on the requestor before the transfer, restored once it ends.
+    requestor_mask: c_long,
+}
+
+// The contents of a selection we own, as `(target, data)` pairs.
+type OwnedContents = Vec<(ffi::Atom, Arc<Vec<u8>>)>;
+
+#[derive(Default)]
+struct SelectionState {
+    owned: HashMap<ffi::Atom, OwnedContents>,
+    incr_transfers: Vec<IncrTransfer>,
+}
+
+/// Owns and reads X selections on behalf of an event loop.
+///
+/// All transfers go through a hidden window. Requests from other clients are answered by the event
+/// processor, while reads block the calling thread until the owner has responded.
+pub struct Selections {
+    xconn: Arc<XConnection>,
+    pub window: ffi::Window,
+    pub atoms: SelectionAtoms,
+    xfixes_event_base: Option<c_int>,
+    // Payloads larger than this many bytes are sent using INCR.
+    incr_threshold: usize,
+    state: Mutex<SelectionState>,
+    // Transfers into `window` share a property, so only one can be in flight at a time.
+    transfer_lock: Mutex<()>,
+}
+
+// The event `Selections::wait_for_event` is waiting for.
+#[derive(Clone, Copy)]
+enum Awaited {
+    // Anything left behind by an earlier transfer.
+    Stale,
+    SelectionNotify(ffi::Atom),
+    NewValue(ffi::Atom),
+}
+
+struct EventFilter {
+    window: ffi::Window,
+    awaited: Awaited,
+}
+
+unsafe extern "C" fn transfer_predicate(
+    _display: *mut ffi::Display,
+    event: *mut ffi::XEvent,
+    arg: ffi::XPointer, // We populate this with a pointer to an `EventFilter`
+) -> ffi::Bool {
+    let filter = &*(arg as *const EventFilter);
+    let event = &*event;
+    if !is_transfer_event(event, filter.window) {
+        return ffi::False;
+    }
+    let matches = match filter.awaited {
+        Awaited::Stale => true,
+        Awaited::SelectionNotify(selection) => {
+            let xsel: &ffi::XSelectionEvent = event.as_ref();
+            xsel.type_ == ffi::SelectionNotify && xsel.selection == selection
+        }
+        Awaited::NewValue(property) => {
+            let xprop: &ffi::XPropertyEvent = event.as_ref();
+            xprop.type_ == ffi::PropertyNotify
+                && xprop.atom == property
+                && xprop.state == ffi::PropertyNewValue
+        }
+    };
+    matches as ffi::Bool
+}
+
+/// Whether `event` is part of a transfer into `window`, the window owning our selections.
+///
+/// The event loop leaves these on the queue, since they're consumed by whichever thread is reading
+/// the selection.
+pub fn is_transfer_event(event: &ffi::XEvent, window: ffi::Window) -> bool {
+    let xany: &ffi::XAnyEvent = event.as_ref();
+    // For `SelectionNotify`, the `window` field overlaps with `requestor`.
+    xany.window == window
+        && (xany.type_ == ffi::SelectionNotify || xany.type_ == ffi::PropertyNotify)
+}
+
+fn transfer_timed_out() -> ExternalError {
+    ExternalError::Os(os_error!(OsError::XMisc(
+        "Timed out waiting for the selection owner"
+    )))
+}
+
+impl Selections {
+    pub fn new(xconn: Arc<XConnection>, root: ffi::Window) -> Result<Self, XError> {
+        let window = unsafe {
+            let mut set_win_attr = {
+                let mut swa: ffi::XSetWindowAttributes = mem::zeroed();
+                swa.event_mask = ffi::PropertyChangeMask;
+                swa
+            };
+            (xconn.xlib.XCreateWindow)(
+                xconn.display,
+                root,
+                0,
+                0,
+                1,
+                1,
+                0,
+                0,
+                ffi::InputOnly as c_uint,
+                ptr::null_mut(),
+                ffi::CWEventMask,
+                &mut set_win_attr,
+            )
+        };
+        xconn.check_errors()?;
+
+        let atoms = SelectionAtoms::new(&xconn);
+
+        let xfixes_event_base = xconn.xfixes.as_ref().and_then(|xfixes| unsafe {
+            let (mut event_base, mut error_base) = (0, 0);
+            if (xfixes.XFixesQueryExtension)(xconn.display, &mut event_base, &mut error_base)
+                == ffi::False
+            {
+                return None;
+            }
+            // The server won't send selection events until we've told it which version we speak.
+            let (mut major, minor) = (5, 0);
+            (xfixes.XFixesQueryVersion)(xconn.display, &mut major, &minor);
+            (xfixes.XFixesSelectSelectionInput)(
+                xconn.display,
+                window,
+                atoms.clipboard,
+                ffi::XFixesSetSelectionOwnerNotifyMask
+                    | ffi::XFixesSelectionWindowDestroyNotifyMask
+                    | ffi::XFixesSelectionClientCloseNotifyMask,
+            );
+            Some(event_base)
+        });
+
+        // The maximum request size is given in 4-byte units, and is only extended if the server
+        // supports BIG-REQUESTS. The margin leaves room for the rest of the request.
+        let max_request_size = unsafe {
+            match (xconn.xlib.XExtendedMaxRequestSize)(xconn.display) {
+                0 => (xconn.xlib.XMaxRequestSize)(xconn.display),
+                size => size,
+            }
+        } as usize;
+        let incr_threshold = max_request_size * 4 - 100;
+
+        Ok(Selections {
+            xconn,
+            window,
+            atoms,
+            xfixes_event_base,
+            incr_threshold,
+            state: Default::default(),
+            transfer_lock: Mutex::new(()),
+        })
+    }
+
+    fn mime_type_atom(&self, mime_type: &str) -> Option<ffi::Atom> {
+        CString::new(mime_type)
+            .ok()
+            .map(|name| self.xconn.get_atom(&name))
+    }
+
+    // Returns our own contents if we're still the owner of `selection`.
+    fn owned_contents(&self, selection: ffi::Atom) -> Option<OwnedContents> {
+        let state = self.state.lock();
+        let contents = state.owned.get(&selection)?;
+        let owner = unsafe { (self.xconn.xlib.XGetSelectionOwner)(self.xconn.display, selection) };
+        if owner == self.window {
+            Some(contents.clone())
+        } else {
+            None
+        }
+    }
+
+    pub fn set_contents(
+        &self,
+        selection: ffi::Atom,
+        contents: Vec<(String, Vec<u8>)>,
+    ) -> Result<(), ExternalError> {
+        let contents = contents
+            .into_iter()
+            .filter_map(|(mime_type, data)| {
+                self.mime_type_atom(&mime_type)
+                    .map(|target| (target, Arc::new(data)))
+            })
+            .collect();
+        self.state.lock().owned.insert(selection, contents);
+
+        let owner = unsafe {
+            (self.xconn.xlib.XSetSelectionOwner)(
+                self.xconn.display,
+                selection,
+                self.window,
+                ffi::CurrentTime,
+            );
+            (self.xconn.xlib.XGetSelectionOwner)(self.xconn.display, selection)
+        };
+        if owner != self.window {
+            self.state.lock().owned.remove(&selection);
+            return Err(ExternalError::Os(os_error!(OsError::XMisc(
+                "Failed to take ownership of the selection"
+            ))));
+        }
+        self.xconn
+            .flush_requests()
+            .map_err(|err| ExternalError::Os(os_error!(OsError::XError(err))))
+    }
+
+    /// Returns the targets we offer for `selection`, in order of preference.
+    pub fn owned_targets(&self, selection: ffi::Atom) -> Vec<ffi::Atom> {
+        self.state
+            .lock()
+            .owned
+            .get(&selection)
+            .map(|contents| contents.iter().map(|&(target, _)| target).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn clear(&self, selection: ffi::Atom) -> Result<(), ExternalError> {
+        if self.owned_contents(selection).is_some() {
+            unsafe {
+                (self.xconn.xlib.XSetSelectionOwner)(
+                    self.xconn.display,
+                    selection,
+                    0,
+                    ffi::CurrentTime,
+                );
+            }
+        }
+        self.state.lock().owned.remove(&selection);
+        self.xconn
+            .flush_requests()
+            .map_err(|err| ExternalError::Os(os_error!(OsError::XError(err))))
+    }
+
+    pub fn mime_types(&self, selection: ffi::Atom) -> Result<Vec<String>, ExternalError> {
+        let targets = if let Some(contents) = self.owned_contents(selection) {
+            contents.iter().map(|&(target, _)| target).collect()
+        } else {
+            match unsafe { self.convert(selection, self.atoms.targets) }? {
+                Some((ffi::XA_ATOM, data)) => data
+                    .chunks(4)
+                    .filter(|chunk| chunk.len() == 4)
+                    .map(|chunk| {
+                        u32::from_ne_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) as ffi::Atom
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            }
+        };
+        Ok(targets
+            .into_iter()
+            .filter(|&target| {
+                target != self.atoms.targets
+                    && target != self.atoms.multiple
+                    && target != self.atoms.timestamp
+            })
+            .filter_map(|target| self.xconn.get_atom_name(target))
+            .collect())
+    }
+
+    pub fn contents(
+        &self,
+        selection: ffi::Atom,
+        mime_type: &str,
+    ) -> Result<Option<Vec<u8>>, ExternalError> {
+        if let Some(data) = self.owned_data(selection, mime_type) {
+            return Ok(data);
+        }
+        let target = match self.mime_type_atom(mime_type) {
+            Some(target) => target,
+            None => return Ok(None),
+        };
+        Ok(unsafe { self.convert(selection, target) }?.map(|(_, data)| data))
+    }
+
+    /// Returns our own contents of `selection` in `mime_type`, or `None` if we don't own it.
+    ///
+    /// Unlike `contents`, this never waits for another client.
+    pub fn owned_data(&self, selection: ffi::Atom, mime_type: &str) -> Option<Option<Vec<u8>>> {
+        let contents = self.owned_contents(selection)?;
+        let data = self.mime_type_atom(mime_type).and_then(|target| {
+            contents
+                .iter()
+                .find(|(owned_target, _)| *owned_target == target)
+                .map(|(_, data)| (**data).clone())
+        });
+        Some(data)
+    }
+
+    // Asks the owner of `selection` to convert it to `target`, and waits for the result. Returns
+    // the type of the data along with the data itself, or `None` if the owner refused.
+    unsafe fn convert(
+        &self,
+        selection: ffi::Atom,
+        target: ffi::Atom,
+    ) -> Result<Option<(ffi::Atom, Vec<u8>)>, ExternalError> {
+        let _transfer = self.transfer_lock.lock();
+
+        // Drop whatever an earlier transfer that timed out might have left behind.
+        while self.poll_for_event(Awaited::Stale).is_some() {}
+
+        (self.xconn.xlib.XConvertSelection)(
+            self.xconn.display,
+            selection,
+            target,
+            self.atoms.property,
+            self.window,
+            ffi::CurrentTime,
+        );
+        self.xconn
+            .flush_requests()
+            .map_err(|err| ExternalError::Os(os_error!(OsError::XError(err))))?;
+
+        let event = self.wait_for_event(Awaited::SelectionNotify(selection))?;
+        let xsel: &ffi::XSelectionEvent = event.as_ref();
+        if xsel.property == 0 {
+            return Ok(None);
+        }
+
+        let (data_type, data) = self.read_property(self.window, xsel.property)?;
+        if data_type != self.atoms.incr {
+            return Ok(Some((data_type, data)));
+        }
+
+        // Deleting the property (which `read_property` does) tells the owner to start sending
+        // chunks. An empty chunk marks the end of the transfer.
+        let mut incr_type = 0;
+        let mut incr_data = Vec::new();
+        loop {
+            self.wait_for_event(Awaited::NewValue(xsel.property))?;
+            let (chunk_type, chunk) = self.read_property(self.window, xsel.property)?;
+            if chunk.is_empty() {
+                break;
+            }
+            incr_type = chunk_type;
+            incr_data.extend_from_slice(&chunk);
+        }
+        Ok(Some((incr_type, incr_data)))
+    }
+
+    unsafe fn poll_for_event(&self, awaited: Awaited) -> Option<ffi::XEvent> {
+        let filter = EventFilter {
+            window: self.window,
+            awaited,
+        };
+        let mut event = MaybeUninit::uninit();
+        let found = (self.xconn.xlib.XCheckIfEvent)(
+            self.xconn.display,
+            event.as_mut_ptr(),
+            Some(transfer_predicate),
+            &filter as *const _ as ffi::XPointer,
+        );
+        if found != 0 {
+            Some(event.assume_init())
+        } else {
+            None
+        }
+    }
+
+    unsafe fn wait_for_event(&self, awaited: Awaited) -> Result<ffi::XEvent, ExternalError> {
+        let deadline = Instant::now() + TRANSFER_TIMEOUT;
+        loop {
+            if let Some(event) = self.poll_for_event(awaited) {
+                return Ok(event);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(transfer_timed_out());
+            }
+            // The event loop may read our event off the connection while we're sleeping, in which
+            // case the descriptor never becomes readable. Wake up regularly to check the queue.
+            let timeout = cmp::min(deadline - now, Duration::from_millis(10));
+            let mut fd = libc::pollfd {
+                fd: self.xconn.x11_fd,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            libc::poll(&mut fd, 1, timeout.as_millis() as c_int);
+        }
+    }
+
+    // Reads and deletes a property of one of our windows, regardless of its type. Items of 16 and
+    // 32 bit formats are packed to their actual size.
+    pub unsafe fn read_property(
+        &self,
+        window: ffi::Window,
+        property: ffi::Atom,
+    ) -> Result<(ffi::Atom, Vec<u8>), ExternalError> {
+        let mut actual_type = 0;
+        let mut actual_format = 0;
+        let mut quantity_returned = 0;
+        let mut bytes_after = 0;
+        let mut buf: *mut c_uchar = ptr::null_mut();
+        (self.xconn.xlib.XGetWindowProperty)(
+            self.xconn.display,
+            window,
+            property,
+            0,
+            c_long::MAX / 4,
+            ffi::True,
+            ffi::AnyPropertyType as ffi::Atom,
+            &mut actual_type,
+            &mut actual_format,
+            &mut quantity_returned,
+            &mut bytes_after,
+            &mut buf,
+        );
+        self.xconn
+            .check_errors()
+            .map_err(|err| ExternalError::Os(os_error!(OsError::XError(err))))?;
+
+        let mut data = Vec::new();
+        if !buf.is_null() {
+            let quantity = quantity_returned as usize;
+            match actual_format {
+                16 => {
+                    for &item in slice::from_raw_parts(buf as *const c_short, quantity) {
+                        data.extend_from_slice(&(item as u16).to_ne_bytes());
+                    }
+                }
+                32 => {
+                    for &item in slice::from_raw_parts(buf as *const c_long, quantity) {
+                        data.extend_from_slice(&(item as u32).to_ne_bytes());
+                    }
+                }
+                _ => data.extend_from_slice(slice::from_raw_parts(buf, quantity)),
+            }
+            (self.xconn.xlib.XFree)(buf as _);
+        }
+        Ok((actual_type, data))
+    }
+
+    // Returns the events we select on `window`, or none if it's already gone.
+    fn event_mask(&self, window: ffi::Window) -> c_long {
+        unsafe {
+            let mut attributes: ffi::XWindowAttributes = mem::zeroed();
+            if (self.xconn.xlib.XGetWindowAttributes)(self.xconn.display, window, &mut attributes)
+                == 0
+            {
+                // Catch the `BadWindow` error, so that it isn't reported later.
+                let _ = self.xconn.check_errors();
+                return ffi::NoEventMask;
+            }
+            attributes.your_event_mask
+        }
+    }
+
+    /// Answers a `SelectionRequest` for one of the selections we own.
+    pub fn handle_request(&self, request: &ffi::XSelectionRequestEvent) {
+        // Obsolete clients don't specify a property, and expect the target to be used instead.
+        let property = if request.property == 0 {
+            request.target
+        } else {
+            request.property
+        };
+
+        let mut state = self.state.lock();
+        let mut incr_transfer = None;
+        let accepted = match state.owned.get(&request.selection) {
+            Some(contents) if request.target == self.atoms.targets => {
+                let mut targets = vec![self.atoms.targets];
+                targets.extend(contents.iter().map(|&(target, _)| target));
+                self.xconn
+                    .change_property(
+                        request.requestor,
+                        property,
+                        ffi::XA_ATOM,
+                        util::PropMode::Replace,
+                        &targets,
+                    )
+                    .queue();
+                true
+            }
+            Some(contents) => {
+                if let Some(&(target, ref data)) = contents
+                    .iter()
+                    .find(|&&(target, _)| target == request.target)
+                {
+                    if data.len() > self.incr_threshold {
+                        // We'll be notified whenever the requestor deletes the property, which is
+                        // our cue to send the next chunk. Event masks are per client, so this
+                        // adds to the events we already select on the requestor, which is one of
+                        // our own windows when data is dropped between windows of this process.
+                        let requestor_mask = state
+                            .incr_transfers
+                            .iter()
+                            .find(|transfer| transfer.requestor == request.requestor)
+                            .map(|transfer| transfer.requestor_mask)
+                            .unwrap_or_else(|| self.event_mask(request.requestor));
+                        unsafe {
+                            (self.xconn.xlib.XSelectInput)(
+                                self.xconn.display,
+                                request.requestor,
+                                requestor_mask | ffi::PropertyChangeMask,
+                            );
+                        }
+                        self.xconn
+                            .change_property(
+                                request.requestor,
+                                property,
+                                self.atoms.incr,
+                                util::PropMode::Replace,
+                                &[data.len() as c_long],
+                            )
+                            .queue();
+                        incr_transfer = Some(IncrTransfer {
+                            requestor: request.requestor,
+                            property,
+                            target,
+                            data: Arc::clone(data),
+                            offset: 0,
+                            requestor_mask,
+                        });
+                    } else {
+                        self.xconn
+                            .change_property(
+                                request.requestor,
+                                property,
+                                target,
+                                util::PropMode::Replace,
+                                &data[..],
+                            )
+                            .queue();
+                    }
+                    true
+                } else {
+                    false
+                }
+            }
+            None => false,
+        };
+        state.incr_transfers.extend(incr_transfer);
+
+        let response = ffi::XSelectionEvent {
+            type_: ffi::SelectionNotify,
+            serial: 0,
+            send_event: ffi::True,
+            display: self.xconn.display,
+            requestor: request.requestor,
+            selection: request.selection,
+            target: request.target,
+            property: if accepted { property } else { 0 },
+            time: request.time,
+        };
+        // The requestor may well have been destroyed in the meantime, which we don't care about.
+        let _ = self
+            .xconn
+            .send_event(request.requestor, None, response)
+            .sync();
+    }
+
+    /// Sends the next chunk of an incremental transfer, if `xev` asks for one.
+    pub fn handle_property_notify(&self, xev: &ffi::XPropertyEvent) {
+        if xev.state != ffi::PropertyDelete {
+            return;
+        }
+
+        let mut state = self.state.lock();
+        let index =
+            match state.incr_transfers.iter().position(|transfer| {
+                transfer.requestor == xev.window && transfer.property == xev.atom
+            }) {
+                Some(index) => index,
+                None => return,
+            };
+
+        let finished = {
+            let transfer = &mut state.incr_transfers[index];
+            let end = cmp::min(transfer.offset + self.incr_threshold, transfer.data.len());
+            // Once everything has been sent, this writes the empty chunk that ends the transfer.
+            self.xconn
+                .change_property(
+                    transfer.requestor,
+                    transfer.property,
+                    transfer.target,
+                    util::PropMode::Replace,
+                    &transfer.data[transfer.offset..end],
+                )
+                .queue();
+            let finished = transfer.offset == end;
+            transfer.offset = end;
+            finished
+        };
+
+        if finished {
+            let transfer = state.incr_transfers.swap_remove(index);
+            if !state
+                .incr_transfers
+                .iter()
+                .any(|other| other.requestor == transfer.requestor)
+            {
+                unsafe {
+                    (self.xconn.xlib.XSelectInput)(
+                        self.xconn.display,
+                        transfer.requestor,
+                        transfer.requestor_mask,
+                    );
+                }
+            }
+        }
+        let _ = self.xconn.sync_with_server();
+    }
+
+    /// Forgets the contents of a selection another client took from us. Returns the selection.
+    pub fn handle_clear(&self, xev: &ffi::XSelectionClearEvent) -> Option<ffi::Atom> {
+        if xev.window != self.window {
+            return None;
+        }
+        self.state.lock().owned.remove(&xev.selection);
+        Some(xev.selection)
+    }
+
+    /// Whether we're told about selection changes through XFixes.
+    pub fn has_xfixes(&self) -> bool {
+        self.xfixes_event_base.is_some()
+    }
+
+    /// Returns the selection that changed owner, if `xev` is an XFixes selection event.
+    pub fn changed_selection(&self, xev: &ffi::XEvent) -> Option<ffi::Atom> {
+        let event_base = self.xfixes_event_base?;
+        if xev.get_type() != event_base + ffi::XFixesSelectionNotify {
+            return None;
+        }
+        let xev: &ffi::XFixesSelectionNotifyEvent = util::reinterpret(xev);
+        Some(xev.selection)
+    }
+}
+
+impl Drop for Selections {
+    fn drop(&mut self) {
+        unsafe {
+            (self.xconn.xlib.XDestroyWindow)(self.xconn.display, self.window);
+            let _ = self.xconn.check_errors();
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Clipboard {
+    selections: Arc<Selections>,
+    selection: ffi::Atom,
+}
+
+impl Clipboard {
+    pub fn new(selections: Arc<Selections>, selection: ffi::Atom) -> Self {
+        Clipboard {
+            selections,
+            selection,
+        }
+    }
+
+    pub fn is_supported(&self) -> bool {
+        true
+    }
+
+    pub fn set_contents(&self, contents: Vec<(String, Vec<u8>)>) -> Result<(), ExternalError> {
+        self.selections.set_contents(self.selection, contents)
+    }
+
+    pub fn clear(&self) -> Result<(), ExternalError> {
+        self.selections.clear(self.selection)
+    }
+
+    pub fn mime_types(&self) -> Result<Vec<String>, ExternalError> {
+        self.selections.mime_types(self.selection)
+    }
+
+    pub fn contents(&self, mime_type: &str) -> Result<Option<Vec<u8>>, ExternalError> {
+        self.selections.contents(self.selection, mime_type)
+    }
+}
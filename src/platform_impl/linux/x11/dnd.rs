@@ -1,14 +1,26 @@
 use std::{
+    ffi::CString,
     io,
     os::raw::*,
     path::{Path, PathBuf},
     str::Utf8Error,
     sync::Arc,
+    time::Duration,
 };
 
 use percent_encoding::percent_decode;
 
 use super::{ffi, util, XConnection, XError};
+use crate::event::DragAction;
+
+/// The version of the XDND protocol we speak as a drag source.
+const XDND_VERSION: c_long = 5;
+
+/// The pointer events we grab for the duration of an outgoing drag.
+pub const DRAG_EVENT_MASK: c_uint = (ffi::ButtonReleaseMask | ffi::PointerMotionMask) as c_uint;
+
+/// How long we wait for the target of a drop to send `XdndFinished`, before giving up on it.
+pub const DROP_FINISH_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[derive(Debug)]
 pub struct DndAtoms {
@@ -19,6 +31,9 @@ pub struct DndAtoms {
     pub position: ffi::Atom,
     pub status: ffi::Atom,
     pub action_private: ffi::Atom,
+    pub action_copy: ffi::Atom,
+    pub action_move: ffi::Atom,
+    pub action_link: ffi::Atom,
     pub selection: ffi::Atom,
     pub finished: ffi::Atom,
     pub type_list: ffi::Atom,
@@ -41,6 +56,9 @@ impl DndAtoms {
             b"XdndTypeList\0".as_ptr() as *mut c_char,
             b"text/uri-list\0".as_ptr() as *mut c_char,
             b"None\0".as_ptr() as *mut c_char,
+            b"XdndActionCopy\0".as_ptr() as *mut c_char,
+            b"XdndActionMove\0".as_ptr() as *mut c_char,
+            b"XdndActionLink\0".as_ptr() as *mut c_char,
         ];
         let atoms = unsafe { xconn.get_atoms(&names) }?;
         Ok(DndAtoms {
@@ -56,6 +74,9 @@ impl DndAtoms {
             type_list: atoms[9],
             uri_list: atoms[10],
             none: atoms[11],
+            action_copy: atoms[12],
+            action_move: atoms[13],
+            action_link: atoms[14],
         })
     }
 }
@@ -87,16 +108,78 @@ impl From<io::Error> for DndDataParseError {
     }
 }
 
+// An outgoing drag, started by one of our windows.
+#[derive(Debug)]
+pub struct Drag {
+    pub source_window: ffi::Window,
+    pub types: Vec<ffi::Atom>,
+    // The XDND-aware window under the cursor, along with the protocol version we use with it
+    pub target: Option<(ffi::Window, c_long)>,
+    // Set while the target hasn't answered our last `XdndPosition` yet
+    pub awaiting_status: bool,
+    // The latest cursor position and time, held back while `awaiting_status` is set
+    pub pending_position: Option<(c_int, c_int, ffi::Time)>,
+    // What the target said it would do, if it accepts the drop
+    pub action: Option<DragAction>,
+    // The time `XdndDrop` was sent at, while we wait for `XdndFinished`
+    pub dropped: Option<ffi::Time>,
+}
+
+impl Drag {
+    pub fn new(source_window: ffi::Window, types: Vec<ffi::Atom>) -> Self {
+        Drag {
+            source_window,
+            types,
+            target: None,
+            awaiting_status: false,
+            pending_position: None,
+            action: None,
+            dropped: None,
+        }
+    }
+}
+
+/// Loads the cursor shown during a drag, according to what the target would do with it.
+pub unsafe fn load_drag_cursor(xconn: &XConnection, action: Option<DragAction>) -> ffi::Cursor {
+    let names: &[&[u8]] = match action {
+        Some(DragAction::Copy) => &[b"dnd-copy\0", b"copy\0"],
+        Some(DragAction::Move) => &[b"dnd-move\0", b"grabbing\0"],
+        Some(DragAction::Link) => &[b"dnd-link\0", b"link\0"],
+        None => &[b"dnd-no-drop\0", b"no-drop\0", b"circle\0"],
+    };
+    for name in names {
+        let cursor =
+            (xconn.xcursor.XcursorLibraryLoadCursor)(xconn.display, name.as_ptr() as *const c_char);
+        if cursor != 0 {
+            return cursor;
+        }
+    }
+    0
+}
+
+// The data of a drop we accepted, while the source transfers it.
+pub struct DropTransfer {
+    pub window: ffi::Window,
+    pub source_window: ffi::Window,
+    pub mime_type: String,
+    pub target: ffi::Atom,
+    // The data received so far, once the source started an incremental transfer
+    pub incr_data: Option<Vec<u8>>,
+}
+
 pub struct Dnd {
     xconn: Arc<XConnection>,
     pub atoms: DndAtoms,
     // Populated by XdndEnter event handler
     pub version: Option<c_long>,
     pub type_list: Option<Vec<c_ulong>>,
+    pub type_names: Vec<String>,
     // Populated by XdndPosition event handler
     pub source_window: Option<c_ulong>,
     // Populated by SelectionNotify event handler (triggered by XdndPosition event handler)
     pub result: Option<Result<Vec<PathBuf>, DndDataParseError>>,
+    // Populated by XdndDrop event handler, until the data arrived (this outlives `reset`)
+    pub transfer: Option<DropTransfer>,
 }
 
 impl Dnd {
@@ -107,14 +190,17 @@ impl Dnd {
             atoms,
             version: None,
             type_list: None,
+            type_names: Vec::new(),
             source_window: None,
             result: None,
+            transfer: None,
         })
     }
 
     pub fn reset(&mut self) {
         self.version = None;
         self.type_list = None;
+        self.type_names.clear();
         self.source_window = None;
         self.result = None;
     }
@@ -169,6 +255,19 @@ impl Dnd {
             .get_property(source_window, self.atoms.type_list, ffi::XA_ATOM)
     }
 
+    // Looks up the names of the types in `type_list`, which are MIME types for well-behaved
+    // sources.
+    pub fn load_type_names(&mut self) {
+        let xconn = &self.xconn;
+        self.type_names = self
+            .type_list
+            .iter()
+            .flatten()
+            .filter(|&&atom| atom != 0)
+            .filter_map(|&atom| xconn.get_atom_name(atom))
+            .collect();
+    }
+
     pub unsafe fn convert_selection(&self, window: c_ulong, time: c_ulong) {
         (self.xconn.xlib.XConvertSelection)(
             self.xconn.display,
@@ -180,6 +279,26 @@ impl Dnd {
         );
     }
 
+    // Asks the source of a drop for its data as `mime_type`, which results in a `SelectionNotify`
+    // event. Returns the target the data was asked for, or `None` if `mime_type` can't be one.
+    pub unsafe fn convert_drop(
+        &self,
+        window: c_ulong,
+        mime_type: &str,
+        time: c_ulong,
+    ) -> Option<ffi::Atom> {
+        let target = self.xconn.get_atom(&CString::new(mime_type).ok()?);
+        (self.xconn.xlib.XConvertSelection)(
+            self.xconn.display,
+            self.atoms.selection,
+            target,
+            self.atoms.selection,
+            window,
+            time,
+        );
+        Some(target)
+    }
+
     pub unsafe fn read_data(
         &self,
         window: c_ulong,
@@ -188,6 +307,158 @@ impl Dnd {
             .get_property(window, self.atoms.selection, self.atoms.uri_list)
     }
 
+    pub fn action_from_atom(&self, atom: ffi::Atom) -> DragAction {
+        if atom == self.atoms.action_move {
+            DragAction::Move
+        } else if atom == self.atoms.action_link {
+            DragAction::Link
+        } else {
+            // Copying is what we ask for, so it's also what we assume for private actions.
+            DragAction::Copy
+        }
+    }
+
+    // Returns the XDND version supported by `window`, if it's a drop target.
+    unsafe fn aware_version(&self, window: ffi::Window) -> Option<c_long> {
+        self.xconn
+            .get_property::<c_ulong>(window, self.atoms.aware, ffi::XA_ATOM)
+            .ok()
+            .and_then(|versions| versions.first().cloned())
+            .map(|version| version as c_long)
+    }
+
+    /// Finds the drop target under the given position, in root window coordinates.
+    ///
+    /// Window managers reparent top-level windows, so we have to search through the window
+    /// hierarchy until we find one that has `XdndAware` set.
+    pub unsafe fn find_target(
+        &self,
+        root: ffi::Window,
+        x: c_int,
+        y: c_int,
+    ) -> Option<(ffi::Window, c_long)> {
+        let mut parent = root;
+        loop {
+            let (mut child_x, mut child_y, mut child) = (0, 0, 0);
+            (self.xconn.xlib.XTranslateCoordinates)(
+                self.xconn.display,
+                root,
+                parent,
+                x,
+                y,
+                &mut child_x,
+                &mut child_y,
+                &mut child,
+            );
+            if child == 0 {
+                return None;
+            }
+            if let Some(version) = self.aware_version(child) {
+                return Some((child, version.min(XDND_VERSION)));
+            }
+            parent = child;
+        }
+    }
+
+    pub unsafe fn send_enter(&self, drag: &Drag) -> Result<(), XError> {
+        let (target_window, version) = match drag.target {
+            Some(target) => target,
+            None => return Ok(()),
+        };
+        // The first three types go in the message, the rest is read from `XdndTypeList`.
+        let more_types = (drag.types.len() > 3) as c_long;
+        let type_at = |index: usize| drag.types.get(index).cloned().unwrap_or(0) as c_long;
+        self.xconn
+            .send_client_msg(
+                target_window,
+                target_window,
+                self.atoms.enter,
+                None,
+                [
+                    drag.source_window as c_long,
+                    (version << 24) | more_types,
+                    type_at(0),
+                    type_at(1),
+                    type_at(2),
+                ],
+            )
+            .flush()
+    }
+
+    pub unsafe fn send_position(
+        &self,
+        drag: &Drag,
+        x: c_int,
+        y: c_int,
+        time: ffi::Time,
+    ) -> Result<(), XError> {
+        let target_window = match drag.target {
+            Some((target_window, _)) => target_window,
+            None => return Ok(()),
+        };
+        self.xconn
+            .send_client_msg(
+                target_window,
+                target_window,
+                self.atoms.position,
+                None,
+                [
+                    drag.source_window as c_long,
+                    0,
+                    ((x as c_long) << 16) | (y as c_long & 0xffff),
+                    time as c_long,
+                    self.atoms.action_copy as c_long,
+                ],
+            )
+            .flush()
+    }
+
+    pub unsafe fn send_leave(&self, drag: &Drag) -> Result<(), XError> {
+        let target_window = match drag.target {
+            Some((target_window, _)) => target_window,
+            None => return Ok(()),
+        };
+        self.xconn
+            .send_client_msg(
+                target_window,
+                target_window,
+                self.atoms.leave,
+                None,
+                [drag.source_window as c_long, 0, 0, 0, 0],
+            )
+            .flush()
+    }
+
+    pub unsafe fn send_drop(&self, drag: &Drag, time: ffi::Time) -> Result<(), XError> {
+        let target_window = match drag.target {
+            Some((target_window, _)) => target_window,
+            None => return Ok(()),
+        };
+        self.xconn
+            .send_client_msg(
+                target_window,
+                target_window,
+                self.atoms.drop,
+                None,
+                [drag.source_window as c_long, 0, time as c_long, 0, 0],
+            )
+            .flush()
+    }
+
+    pub unsafe fn set_drag_cursor(&self, action: Option<DragAction>) {
+        let cursor = load_drag_cursor(&self.xconn, action);
+        (self.xconn.xlib.XChangeActivePointerGrab)(
+            self.xconn.display,
+            DRAG_EVENT_MASK,
+            cursor,
+            ffi::CurrentTime,
+        );
+        if cursor != 0 {
+            (self.xconn.xlib.XFreeCursor)(self.xconn.display, cursor);
+        }
+        let _ = self.xconn.flush_requests();
+    }
+
     pub fn parse_data(&self, data: &mut Vec<c_uchar>) -> Result<Vec<PathBuf>, DndDataParseError> {
         if !data.is_empty() {
             let mut path_list = Vec::new();
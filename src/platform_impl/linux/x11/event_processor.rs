@@ -1,25 +1,55 @@
-use std::{cell::RefCell, collections::HashMap, ptr, rc::Rc, slice};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    ptr,
+    rc::Rc,
+    slice,
+};
 
+use calloop::timer::TimerHandle;
 use libc::{c_char, c_int, c_long, c_uint, c_ulong};
 
 use super::{
-    events, ffi, get_xtarget, mkdid, mkwid, monitor, util, Device, DeviceId, DeviceInfo, Dnd,
-    DndState, GenericEventCookie, ImeReceiver, ScrollOrientation, UnownedWindow, WindowId,
-    XExtension,
+    clipboard::{self, Selections},
+    ffi, get_xtarget, mkdid, mkwid, monitor, util, xcb_connection, Device, DeviceId, DeviceInfo,
+    Dnd, DndState, DropChoice, DropTransfer, GenericEventCookie, ImeEvent, ImeEventReceiver,
+    ImeReceiver, ImeRequest, ScrollOrientation, UnownedWindow, WindowId, XExtension,
+    DROP_FINISH_TIMEOUT,
 };
 
 use crate::{
     dpi::{LogicalPosition, LogicalSize},
-    event::{DeviceEvent, Event, KeyboardInput, ModifiersState, WindowEvent},
+    event::{
+        Damage, DamageRect, DeviceEvent, Event, Ime, KeyEvent, KeyboardInput, ModifiersState,
+        WindowEvent,
+    },
     event_loop::EventLoopWindowTarget as RootELW,
+    platform_impl::platform::xkb,
 };
 
 pub(super) struct EventProcessor<T: 'static> {
     pub(super) dnd: Dnd,
     pub(super) ime_receiver: ImeReceiver,
+    pub(super) ime_event_receiver: ImeEventReceiver,
+    // The windows in which a composition is in progress, in which case text coming from key
+    // presses is the result of the composition.
+    pub(super) composing: HashSet<ffi::Window>,
+    // The keycodes of the keys held down, to tell repeated key presses apart.
+    pub(super) held_keys: HashSet<c_uint>,
+    // The modifiers last reported, along with the sides of the modifier keys held down.
+    pub(super) modifiers: ModifiersState,
+    pub(super) focused_window: Option<ffi::Window>,
+    // The rectangles of the exposures whose series of `Expose` events isn't over yet
+    pub(super) exposed: HashMap<ffi::Window, Vec<DamageRect>>,
     pub(super) randr_event_offset: c_int,
     pub(super) devices: RefCell<HashMap<DeviceId, Device>>,
     pub(super) xi2ext: XExtension,
+    // The XKB extension, if it's available
+    pub(super) xkbext: Option<XExtension>,
+    // The Present extension, if it's available
+    pub(super) presentext: Option<XExtension>,
+    // Times out the drops the targets don't finish, identified by source window and drop time
+    pub(super) drag_timer: TimerHandle<(ffi::Window, ffi::Time)>,
     pub(super) target: Rc<RootELW<T>>,
 }
 
@@ -62,6 +92,14 @@ impl<T: 'static> EventProcessor<T> {
         self.with_window(window_id, |_| ()).is_some()
     }
 
+    // Adds the sides of the modifier keys held down to the modifiers of an event.
+    fn event_modifiers(&self, modifiers: ModifiersState) -> ModifiersState {
+        ModifiersState {
+            sides: self.modifiers.sides,
+            ..modifiers
+        }
+    }
+
     pub(super) unsafe fn poll_one_event(&mut self, event_ptr: *mut ffi::XEvent) -> bool {
         let wt = get_xtarget(&self.target);
         // This function is used to poll and remove a single event
@@ -72,18 +110,19 @@ impl<T: 'static> EventProcessor<T> {
         // XPeekEvent does not remove events from the queue.
         unsafe extern "C" fn predicate(
             _display: *mut ffi::Display,
-            _event: *mut ffi::XEvent,
-            _arg: *mut c_char,
+            event: *mut ffi::XEvent,
+            arg: *mut c_char, // We populate this with the selection window ID (by value)
         ) -> c_int {
-            // This predicate always returns "true" (1) to accept all events
-            1
+            // This predicate accepts all events, except for selection transfers, which are
+            // waited on by whoever requested them.
+            !clipboard::is_transfer_event(&*event, arg as ffi::Window) as c_int
         }
 
         let result = (wt.xconn.xlib.XCheckIfEvent)(
             wt.xconn.display,
             event_ptr,
             Some(predicate),
-            std::ptr::null_mut(),
+            wt.selections.window as *mut c_char,
         );
 
         result != 0
@@ -94,18 +133,30 @@ impl<T: 'static> EventProcessor<T> {
         F: FnMut(Event<T>),
     {
         let wt = get_xtarget(&self.target);
+        // The key events of windows that don't allow input methods are kept from the input method,
+        // which would compose with them otherwise.
+        let is_ime_event = match xev.get_type() {
+            ffi::KeyPress | ffi::KeyRelease => {
+                let xkev: &ffi::XKeyEvent = xev.as_ref();
+                wt.ime.borrow().is_ime_allowed(xkev.window)
+            }
+            _ => true,
+        };
         // XFilterEvent tells us when an event has been discarded by the input method.
         // Specifically, this involves all of the KeyPress events in compose/pre-edit sequences,
         // along with an extra copy of the KeyRelease events. This also prevents backspace and
         // arrow keys from being detected twice.
-        if ffi::True
-            == unsafe {
-                (wt.xconn.xlib.XFilterEvent)(xev, {
-                    let xev: &ffi::XAnyEvent = xev.as_ref();
-                    xev.window
-                })
-            }
+        // The pre-edit callbacks of the input method are also called from here.
+        if is_ime_event
+            && ffi::True
+                == unsafe {
+                    (wt.xconn.xlib.XFilterEvent)(xev, {
+                        let xev: &ffi::XAnyEvent = xev.as_ref();
+                        xev.window
+                    })
+                }
         {
+            self.process_ime_events(&mut callback);
             return;
         }
 
@@ -158,6 +209,12 @@ impl<T: 'static> EventProcessor<T> {
                     {
                         self.dnd.type_list = Some(more_types);
                     }
+                    self.dnd.load_type_names();
+                    self.with_window(window, |window| window.set_drop_choice(DropChoice::Default));
+                    callback(Event::WindowEvent {
+                        window_id,
+                        event: WindowEvent::HoveredData(self.dnd.type_names.clone()),
+                    });
                 } else if client_msg.message_type == self.dnd.atoms.position {
                     // This event occurs every time the mouse moves while a file's being dragged
                     // over our window. We emit HoveredFile in response; while the macOS backend
@@ -184,59 +241,146 @@ impl<T: 'static> EventProcessor<T> {
                     // Action is specified in versions 2 and up, though we don't need it anyway.
                     //let action = client_msg.data.get_long(4);
 
-                    let accepted = if let Some(ref type_list) = self.dnd.type_list {
-                        type_list.contains(&self.dnd.atoms.uri_list)
-                    } else {
-                        false
-                    };
+                    let choice = self
+                        .with_window(window, |window| window.drop_choice())
+                        .unwrap_or(DropChoice::Default);
 
-                    if accepted {
-                        self.dnd.source_window = Some(source_window);
-                        unsafe {
-                            if self.dnd.result.is_none() {
-                                let time = if version >= 1 {
-                                    client_msg.data.get_long(3) as c_ulong
-                                } else {
-                                    // In version 0, time isn't specified
-                                    ffi::CurrentTime
-                                };
-                                // This results in the `SelectionNotify` event below
-                                self.dnd.convert_selection(window, time);
+                    if let DropChoice::Default = choice {
+                        let accepted = if let Some(ref type_list) = self.dnd.type_list {
+                            type_list.contains(&self.dnd.atoms.uri_list)
+                        } else {
+                            false
+                        };
+
+                        if accepted {
+                            self.dnd.source_window = Some(source_window);
+                            unsafe {
+                                if self.dnd.result.is_none() {
+                                    let time = if version >= 1 {
+                                        client_msg.data.get_long(3) as c_ulong
+                                    } else {
+                                        // In version 0, time isn't specified
+                                        ffi::CurrentTime
+                                    };
+                                    // This results in the `SelectionNotify` event below
+                                    self.dnd.convert_selection(window, time);
+                                }
+                                self.dnd
+                                    .send_status(window, source_window, DndState::Accepted)
+                                    .expect("Failed to send `XdndStatus` message.");
                             }
-                            self.dnd
-                                .send_status(window, source_window, DndState::Accepted)
-                                .expect("Failed to send `XdndStatus` message.");
+                        } else {
+                            unsafe {
+                                self.dnd
+                                    .send_status(window, source_window, DndState::Rejected)
+                                    .expect("Failed to send `XdndStatus` message.");
+                            }
+                            self.dnd.reset();
                         }
                     } else {
+                        // The application made its choice through `Window::accept_drop`. The data
+                        // itself is only converted once it's dropped.
+                        let accepted = match choice {
+                            DropChoice::Accept(ref mime_type) => {
+                                self.dnd.type_names.contains(mime_type)
+                            }
+                            _ => false,
+                        };
+                        let state = if accepted {
+                            self.dnd.source_window = Some(source_window);
+                            DndState::Accepted
+                        } else {
+                            self.dnd.source_window = None;
+                            DndState::Rejected
+                        };
                         unsafe {
                             self.dnd
-                                .send_status(window, source_window, DndState::Rejected)
+                                .send_status(window, source_window, state)
                                 .expect("Failed to send `XdndStatus` message.");
                         }
-                        self.dnd.reset();
                     }
                 } else if client_msg.message_type == self.dnd.atoms.drop {
-                    let (source_window, state) = if let Some(source_window) = self.dnd.source_window
-                    {
-                        if let Some(Ok(ref path_list)) = self.dnd.result {
-                            for path in path_list {
-                                callback(Event::WindowEvent {
-                                    window_id,
-                                    event: WindowEvent::DroppedFile(path.clone()),
-                                });
+                    let choice = self
+                        .with_window(window, |window| window.drop_choice())
+                        .unwrap_or(DropChoice::Default);
+                    let finished = if let Some(source_window) = self.dnd.source_window {
+                        if let DropChoice::Accept(mime_type) = choice {
+                            cancel_drop_transfer(&mut self.dnd, &mut callback);
+                            // Time is specified in versions 1 and up.
+                            let time = if self.dnd.version.unwrap_or(5) >= 1 {
+                                client_msg.data.get_long(2) as c_ulong
+                            } else {
+                                ffi::CurrentTime
+                            };
+                            // A drag from one of our own windows is served from the contents we
+                            // own, instead of converting the selection to ourselves.
+                            let wt = get_xtarget(&self.target);
+                            let owned_data = if wt.drag.lock().unwrap().is_some() {
+                                wt.selections
+                                    .owned_data(self.dnd.atoms.selection, &mime_type)
+                            } else {
+                                None
+                            };
+                            if let Some(data) = owned_data {
+                                let (event, state) = match data {
+                                    Some(data) => (
+                                        WindowEvent::DroppedData { mime_type, data },
+                                        DndState::Accepted,
+                                    ),
+                                    None => (WindowEvent::HoveredDataCancelled, DndState::Rejected),
+                                };
+                                callback(Event::WindowEvent { window_id, event });
+                                Some((source_window, state))
+                            } else {
+                                // The drop is finished once the data arrived, which results in
+                                // the `SelectionNotify` event below.
+                                match unsafe { self.dnd.convert_drop(window, &mime_type, time) } {
+                                    Some(target) => {
+                                        self.dnd.transfer = Some(DropTransfer {
+                                            window,
+                                            source_window,
+                                            mime_type,
+                                            target,
+                                            incr_data: None,
+                                        });
+                                        None
+                                    }
+                                    None => {
+                                        callback(Event::WindowEvent {
+                                            window_id,
+                                            event: WindowEvent::HoveredDataCancelled,
+                                        });
+                                        Some((source_window, DndState::Rejected))
+                                    }
+                                }
+                            }
+                        } else {
+                            if let Some(Ok(ref path_list)) = self.dnd.result {
+                                for path in path_list {
+                                    callback(Event::WindowEvent {
+                                        window_id,
+                                        event: WindowEvent::DroppedFile(path.clone()),
+                                    });
+                                }
                             }
+                            Some((source_window, DndState::Accepted))
                         }
-                        (source_window, DndState::Accepted)
                     } else {
                         // `source_window` won't be part of our DND state if we already rejected the drop in our
                         // `XdndPosition` handler.
                         let source_window = client_msg.data.get_long(0) as c_ulong;
-                        (source_window, DndState::Rejected)
+                        callback(Event::WindowEvent {
+                            window_id,
+                            event: WindowEvent::HoveredDataCancelled,
+                        });
+                        Some((source_window, DndState::Rejected))
                     };
-                    unsafe {
-                        self.dnd
-                            .send_finished(window, source_window, state)
-                            .expect("Failed to send `XdndFinished` message.");
+                    if let Some((source_window, state)) = finished {
+                        unsafe {
+                            self.dnd
+                                .send_finished(window, source_window, state)
+                                .expect("Failed to send `XdndFinished` message.");
+                        }
                     }
                     self.dnd.reset();
                 } else if client_msg.message_type == self.dnd.atoms.leave {
@@ -245,6 +389,14 @@ impl<T: 'static> EventProcessor<T> {
                         window_id,
                         event: WindowEvent::HoveredFileCancelled,
                     });
+                    callback(Event::WindowEvent {
+                        window_id,
+                        event: WindowEvent::HoveredDataCancelled,
+                    });
+                } else if client_msg.message_type == self.dnd.atoms.status {
+                    self.drag_status(client_msg, &mut callback);
+                } else if client_msg.message_type == self.dnd.atoms.finished {
+                    self.drag_finished(client_msg, &mut callback);
                 }
             }
 
@@ -254,7 +406,18 @@ impl<T: 'static> EventProcessor<T> {
                 let window = xsel.requestor;
                 let window_id = mkwid(window);
 
-                if xsel.property == self.dnd.atoms.selection {
+                let is_drop_data = match self.dnd.transfer {
+                    Some(ref transfer) => {
+                        transfer.window == window
+                            && transfer.target == xsel.target
+                            && xsel.selection == self.dnd.atoms.selection
+                    }
+                    None => false,
+                };
+
+                if is_drop_data {
+                    drop_data_notify(&mut self.dnd, &wt.selections, xsel.property, &mut callback);
+                } else if xsel.property == self.dnd.atoms.selection {
                     let mut result = None;
 
                     // This is where we receive data from drag and drop
@@ -275,6 +438,52 @@ impl<T: 'static> EventProcessor<T> {
                 }
             }
 
+            // Core pointer events are only delivered to us while a drag holds the pointer grab.
+            ffi::MotionNotify => {
+                let xev: &ffi::XMotionEvent = xev.as_ref();
+                self.drag_motion(xev.x_root, xev.y_root, xev.time, &mut callback);
+            }
+
+            ffi::ButtonRelease => {
+                let xev: &ffi::XButtonEvent = xev.as_ref();
+                // Releasing a scroll "button" doesn't end the drag.
+                if xev.button <= ffi::Button3 {
+                    self.drag_release(xev.time, &mut callback);
+                }
+            }
+
+            ffi::SelectionRequest => {
+                let xev: &ffi::XSelectionRequestEvent = xev.as_ref();
+                wt.selections.handle_request(xev);
+            }
+
+            ffi::SelectionClear => {
+                let xev: &ffi::XSelectionClearEvent = xev.as_ref();
+                let selection = wt.selections.handle_clear(xev);
+                // Without XFixes, losing ownership is the only change we get to hear about.
+                if selection == Some(wt.selections.atoms.clipboard) && !wt.selections.has_xfixes() {
+                    callback(Event::ClipboardChanged);
+                }
+            }
+
+            ffi::PropertyNotify => {
+                let xev: &ffi::XPropertyEvent = xev.as_ref();
+                let is_drop_chunk = match self.dnd.transfer {
+                    Some(ref transfer) => {
+                        transfer.incr_data.is_some()
+                            && transfer.window == xev.window
+                            && xev.atom == self.dnd.atoms.selection
+                            && xev.state == ffi::PropertyNewValue
+                    }
+                    None => false,
+                };
+                if is_drop_chunk {
+                    drop_data_chunk(&mut self.dnd, &wt.selections, &mut callback);
+                } else {
+                    wt.selections.handle_property_notify(xev);
+                }
+            }
+
             ffi::ConfigureNotify => {
                 #[derive(Debug, Default)]
                 struct Events {
@@ -465,6 +674,14 @@ impl<T: 'static> EventProcessor<T> {
                 // In the event that the window's been destroyed without being dropped first, we
                 // cleanup again here.
                 wt.windows.borrow_mut().remove(&WindowId(window));
+                wt.frame_clock.remove_window(window);
+                self.exposed.remove(&window);
+                self.composing.remove(&window);
+                if let Some(ref transfer) = self.dnd.transfer {
+                    if transfer.window == window {
+                        self.dnd.transfer = None;
+                    }
+                }
 
                 // Since all XIM stuff needs to happen from the same thread, we destroy the input
                 // context here instead of when dropping the window.
@@ -483,12 +700,28 @@ impl<T: 'static> EventProcessor<T> {
                 let xev: &ffi::XExposeEvent = xev.as_ref();
 
                 let window = xev.window;
-                let window_id = mkwid(window);
+                let rect = DamageRect {
+                    x: xev.x,
+                    y: xev.y,
+                    width: xev.width as u32,
+                    height: xev.height as u32,
+                };
 
-                callback(Event::WindowEvent {
-                    window_id,
-                    event: WindowEvent::RedrawRequested,
-                });
+                // An exposure is reported as a series of events, `count` being the number of
+                // events left, so the redraw waits for the last one.
+                let mut rects = self.exposed.remove(&window).unwrap_or_default();
+                rects.push(rect);
+                if xev.count > 0 {
+                    self.exposed.insert(window, rects);
+                    return;
+                }
+                let mut pending_redraws = wt.pending_redraws.lock().unwrap();
+                let damage = pending_redraws
+                    .entry(WindowId(window))
+                    .or_insert_with(|| Damage::Region(Vec::new()));
+                for rect in rects {
+                    damage.add_rect(rect);
+                }
             }
 
             ffi::KeyPress | ffi::KeyRelease => {
@@ -506,34 +739,75 @@ impl<T: 'static> EventProcessor<T> {
                 let window = xkev.window;
                 let window_id = mkwid(window);
 
+                // With detectable auto-repeat, a held key sends key presses without releases in
+                // between.
+                let is_repeat = match state {
+                    Pressed => xkev.keycode != 0 && !self.held_keys.insert(xkev.keycode),
+                    Released => {
+                        self.held_keys.remove(&xkev.keycode);
+                        false
+                    }
+                };
+                if is_repeat
+                    && !self
+                        .with_window(window, |window| window.key_repeat())
+                        .unwrap_or(true)
+                {
+                    return;
+                }
+
                 // Standard virtual core keyboard ID. XInput2 needs to be used to get a reliable
                 // value, though this should only be an issue under multiseat configurations.
                 let device = util::VIRTUAL_CORE_KEYBOARD;
                 let device_id = mkdid(device);
 
+                let written = if state == Pressed {
+                    wt.ime
+                        .borrow()
+                        .get_context(window)
+                        .map(|ic| wt.xconn.lookup_utf8(ic, xkev))
+                } else {
+                    None
+                };
+                // Input methods deliver committed text through key presses without a keycode,
+                // which may arrive before or after the end of the composition.
+                let is_commit = match written {
+                    Some(ref written) => {
+                        (self.composing.contains(&window) || xkev.keycode == 0)
+                            && !written.is_empty()
+                    }
+                    None => false,
+                };
+
                 // When a compose sequence or IME pre-edit is finished, it ends in a KeyPress with
                 // a keycode of 0.
                 if xkev.keycode != 0 {
-                    let modifiers = ModifiersState {
-                        alt: xkev.state & ffi::Mod1Mask != 0,
-                        shift: xkev.state & ffi::ShiftMask != 0,
-                        ctrl: xkev.state & ffi::ControlMask != 0,
-                        logo: xkev.state & ffi::Mod4Mask != 0,
-                    };
+                    let modifiers = self.event_modifiers(util::modifiers_from_state(xkev.state));
 
-                    let keysym = unsafe {
-                        let mut keysym = 0;
-                        (wt.xconn.xlib.XLookupString)(
-                            xkev,
-                            ptr::null_mut(),
-                            0,
-                            &mut keysym,
-                            ptr::null_mut(),
-                        );
-                        wt.xconn.check_errors().expect("Failed to lookup keysym");
-                        keysym
+                    let keysym = match *wt.kb_state.borrow_mut() {
+                        Some(ref mut kb_state) => {
+                            kb_state.update_from_x11_state(xkev.state);
+                            kb_state.key_get_one_sym(xkev.keycode)
+                        }
+                        None => unsafe {
+                            let mut keysym = 0;
+                            (wt.xconn.xlib.XLookupString)(
+                                xkev,
+                                ptr::null_mut(),
+                                0,
+                                &mut keysym,
+                                ptr::null_mut(),
+                            );
+                            wt.xconn.check_errors().expect("Failed to lookup keysym");
+                            keysym as u32
+                        },
                     };
-                    let virtual_keycode = events::keysym_to_element(keysym as c_uint);
+                    let scancode = xkev.keycode - 8;
+                    let physical_key = xkb::raw_keycode_to_keycode(scancode);
+                    let logical_key = xkb::keysym_to_key(keysym);
+                    let location = xkb::keysym_location(keysym);
+                    let virtual_keycode =
+                        xkb::virtual_keycode(physical_key, &logical_key, location);
 
                     callback(Event::WindowEvent {
                         window_id,
@@ -541,27 +815,58 @@ impl<T: 'static> EventProcessor<T> {
                             device_id,
                             input: KeyboardInput {
                                 state,
-                                scancode: xkev.keycode - 8,
+                                scancode,
                                 virtual_keycode,
                                 modifiers,
+                                is_repeat,
                             },
                         },
                     });
-                }
 
-                if state == Pressed {
-                    let written = if let Some(ic) = wt.ime.borrow().get_context(window) {
-                        wt.xconn.lookup_utf8(ic, xkev)
-                    } else {
-                        return;
+                    let text = match written {
+                        Some(ref written) if !is_commit && !written.is_empty() => {
+                            Some(written.clone())
+                        }
+                        _ => None,
                     };
+                    callback(Event::WindowEvent {
+                        window_id,
+                        event: WindowEvent::Key {
+                            device_id,
+                            event: KeyEvent {
+                                physical_key,
+                                scancode,
+                                logical_key,
+                                text,
+                                location,
+                                state,
+                                is_repeat,
+                            },
+                        },
+                    });
+                }
 
-                    for chr in written.chars() {
-                        let event = Event::WindowEvent {
+                if let Some(written) = written {
+                    if is_commit {
+                        if self.composing.remove(&window) {
+                            // The pre-edit text is cleared before the text is inserted.
+                            callback(Event::WindowEvent {
+                                window_id,
+                                event: WindowEvent::Ime(Ime::Preedit(String::new(), None)),
+                            });
+                        }
+                        callback(Event::WindowEvent {
                             window_id,
-                            event: WindowEvent::ReceivedCharacter(chr),
-                        };
-                        callback(event);
+                            event: WindowEvent::Ime(Ime::Commit(written)),
+                        });
+                    } else {
+                        for chr in written.chars() {
+                            let event = Event::WindowEvent {
+                                window_id,
+                                event: WindowEvent::ReceivedCharacter(chr),
+                            };
+                            callback(event);
+                        }
                     }
                 }
             }
@@ -573,6 +878,23 @@ impl<T: 'static> EventProcessor<T> {
                     return;
                 };
                 let xev = &guard.cookie;
+                if let Some(ref presentext) = self.presentext {
+                    if presentext.opcode == xev.extension {
+                        if xev.evtype == ffi::PresentCompleteNotify {
+                            let xev: &ffi::XPresentCompleteNotifyEvent =
+                                unsafe { &*(xev.data as *const _) };
+                            if let Some(timing) =
+                                wt.frame_clock.complete(xev.window, xev.ust, xev.msc)
+                            {
+                                callback(Event::WindowEvent {
+                                    window_id: mkwid(xev.window),
+                                    event: WindowEvent::FrameReady(timing),
+                                });
+                            }
+                        }
+                        return;
+                    }
+                }
                 if self.xi2ext.opcode != xev.extension {
                     return;
                 }
@@ -598,7 +920,7 @@ impl<T: 'static> EventProcessor<T> {
                             return;
                         }
 
-                        let modifiers = ModifiersState::from(xev.mods);
+                        let modifiers = self.event_modifiers(ModifiersState::from(xev.mods));
 
                         let state = if xev.evtype == ffi::XI_ButtonPress {
                             Pressed
@@ -674,9 +996,13 @@ impl<T: 'static> EventProcessor<T> {
                         let window_id = mkwid(xev.event);
                         let new_cursor_pos = (xev.event_x, xev.event_y);
 
-                        let modifiers = ModifiersState::from(xev.mods);
+                        let modifiers = self.event_modifiers(ModifiersState::from(xev.mods));
 
                         let cursor_moved = self.with_window(xev.event, |window| {
+                            // A locked cursor doesn't move, it's only warped back in place.
+                            if window.restore_locked_cursor(new_cursor_pos) {
+                                return false;
+                            }
                             let mut shared_state_lock = window.shared_state.lock();
                             util::maybe_change(&mut shared_state_lock.cursor_pos, new_cursor_pos)
                         });
@@ -808,11 +1134,12 @@ impl<T: 'static> EventProcessor<T> {
                             // This needs to only be done after confirming the window still exists,
                             // since otherwise we risk getting a `BadWindow` error if the window was
                             // dropped with queued events.
-                            let modifiers = wt
-                                .xconn
-                                .query_pointer(xev.event, xev.deviceid)
-                                .expect("Failed to query pointer device")
-                                .get_modifier_state();
+                            let modifiers = self.event_modifiers(
+                                wt.xconn
+                                    .query_pointer(xev.event, xev.deviceid)
+                                    .expect("Failed to query pointer device")
+                                    .get_modifier_state(),
+                            );
 
                             callback(Event::WindowEvent {
                                 window_id,
@@ -872,14 +1199,25 @@ impl<T: 'static> EventProcessor<T> {
                             (xev.event_x as f64, xev.event_y as f64),
                             dpi_factor,
                         );
+                        let modifiers = self.event_modifiers(ModifiersState::from(xev.mods));
                         callback(Event::WindowEvent {
                             window_id,
                             event: CursorMoved {
                                 device_id: mkdid(pointer_id),
                                 position,
-                                modifiers: ModifiersState::from(xev.mods),
+                                modifiers,
                             },
                         });
+
+                        // The window is told of the modifiers held down when it gains focus.
+                        self.focused_window = Some(xev.event);
+                        if modifiers != ModifiersState::default() {
+                            callback(Event::WindowEvent {
+                                window_id,
+                                event: WindowEvent::ModifiersChanged(modifiers),
+                            });
+                        }
+                        self.modifiers = modifiers;
                     }
                     ffi::XI_FocusOut => {
                         let xev: &ffi::XIFocusOutEvent = unsafe { &*(xev.data as *const _) };
@@ -890,6 +1228,17 @@ impl<T: 'static> EventProcessor<T> {
                             .borrow_mut()
                             .unfocus(xev.event)
                             .expect("Failed to unfocus input context");
+                        // Keys released while the window is unfocused aren't reported.
+                        self.held_keys.clear();
+                        if self.focused_window == Some(xev.event) {
+                            self.focused_window = None;
+                            if self.modifiers != ModifiersState::default() {
+                                callback(Event::WindowEvent {
+                                    window_id: mkwid(xev.event),
+                                    event: WindowEvent::ModifiersChanged(ModifiersState::default()),
+                                });
+                            }
+                        }
                         callback(Event::WindowEvent {
                             window_id: mkwid(xev.event),
                             event: Focused(false),
@@ -1019,7 +1368,11 @@ impl<T: 'static> EventProcessor<T> {
                             .check_errors()
                             .expect("Failed to lookup raw keysym");
 
-                        let virtual_keycode = events::keysym_to_element(keysym as c_uint);
+                        let physical_key = xkb::raw_keycode_to_keycode(scancode);
+                        let logical_key = xkb::keysym_to_key(keysym as u32);
+                        let location = xkb::keysym_location(keysym as u32);
+                        let virtual_keycode =
+                            xkb::virtual_keycode(physical_key, &logical_key, location);
 
                         callback(Event::DeviceEvent {
                             device_id: mkdid(device_id),
@@ -1033,8 +1386,25 @@ impl<T: 'static> EventProcessor<T> {
                                 // comprehensive keyboard state updates, but interpreting that
                                 // info manually is going to be involved.
                                 modifiers: ModifiersState::default(),
+                                is_repeat: false,
                             }),
                         });
+
+                        // Raw events are received whichever window is focused, which keeps the
+                        // sides of the modifier keys right across focus changes.
+                        let mut modifiers = self.modifiers;
+                        if xkb::update_modifier_sides(
+                            &mut modifiers.sides,
+                            keysym as u32,
+                            state == Pressed,
+                        ) {
+                            update_modifiers(
+                                &mut self.modifiers,
+                                self.focused_window,
+                                modifiers,
+                                &mut callback,
+                            );
+                        }
                     }
 
                     ffi::XI_HierarchyChanged => {
@@ -1063,6 +1433,54 @@ impl<T: 'static> EventProcessor<T> {
                     _ => {}
                 }
             }
+            _ if self.xkbext.map(|ext| ext.first_event_id) == Some(event_type) => {
+                let xev: &ffi::XkbAnyEvent = util::reinterpret(xev);
+                match xev.xkb_type {
+                    ffi::XkbNewKeyboardNotify | ffi::XkbMapNotify => {
+                        // The keymap changed, and has to be loaded again.
+                        let previous_layout = wt.keyboard_layout();
+                        *wt.kb_state.borrow_mut() =
+                            unsafe { xkb::KbState::from_x11_connection(xcb_connection(&wt.xconn)) };
+                        let layout = wt.keyboard_layout();
+                        if let Some(layout) =
+                            layout.filter(|layout| Some(layout) != previous_layout.as_ref())
+                        {
+                            callback(Event::KeyboardLayoutChanged(layout));
+                        }
+                    }
+                    ffi::XkbStateNotify => {
+                        let xev: &ffi::XkbStateNotifyEvent = util::reinterpret(xev);
+                        // The layout is only known with libxkbcommon-x11, unlike the modifiers.
+                        let changed_layout = match *wt.kb_state.borrow_mut() {
+                            Some(ref mut kb_state) => {
+                                let previous_layout = kb_state.layout();
+                                kb_state.update_mask(
+                                    xev.base_mods,
+                                    xev.latched_mods,
+                                    xev.locked_mods,
+                                    xev.base_group as u32,
+                                    xev.latched_group as u32,
+                                    xev.locked_group as u32,
+                                );
+                                Some(kb_state.layout()).filter(|layout| *layout != previous_layout)
+                            }
+                            None => None,
+                        };
+                        if let Some(layout) = changed_layout {
+                            callback(Event::KeyboardLayoutChanged(layout));
+                        }
+                        let modifiers =
+                            self.event_modifiers(util::modifiers_from_state(xev.mods as c_uint));
+                        update_modifiers(
+                            &mut self.modifiers,
+                            self.focused_window,
+                            modifiers,
+                            &mut callback,
+                        );
+                    }
+                    _ => {}
+                }
+            }
             _ => {
                 if event_type == self.randr_event_offset {
                     // In the future, it would be quite easy to emit monitor hotplug events.
@@ -1103,14 +1521,377 @@ impl<T: 'static> EventProcessor<T> {
                         }
                     }
                 }
+                if wt.selections.changed_selection(xev) == Some(wt.selections.atoms.clipboard) {
+                    callback(Event::ClipboardChanged);
+                }
+            }
+        }
+
+        while let Ok(request) = self.ime_receiver.try_recv() {
+            match request {
+                ImeRequest::Position(window_id, x, y) => {
+                    wt.ime.borrow_mut().send_xim_spot(window_id, x, y);
+                }
+                ImeRequest::Allow(window_id, allowed) => {
+                    let result = wt.ime.borrow_mut().set_ime_allowed(window_id, allowed);
+                    if let Err(err) = result {
+                        warn!("Failed to replace the input context: {:?}", err);
+                    }
+                }
+            }
+        }
+
+        self.process_ime_events(&mut callback);
+    }
+
+    fn process_ime_events<F>(&mut self, mut callback: F)
+    where
+        F: FnMut(Event<T>),
+    {
+        while let Ok((window, event)) = self.ime_event_receiver.try_recv() {
+            let event = match event {
+                ImeEvent::Enabled => Ime::Enabled,
+                ImeEvent::Start => {
+                    self.composing.insert(window);
+                    Ime::Preedit(String::new(), None)
+                }
+                ImeEvent::Update(text, cursor_pos) => {
+                    if !self.composing.contains(&window) {
+                        continue;
+                    }
+                    Ime::Preedit(text, Some((cursor_pos, cursor_pos)))
+                }
+                ImeEvent::End => {
+                    self.composing.remove(&window);
+                    Ime::Preedit(String::new(), None)
+                }
+                ImeEvent::Disabled => {
+                    self.composing.remove(&window);
+                    Ime::Disabled
+                }
+            };
+            callback(Event::WindowEvent {
+                window_id: mkwid(window),
+                event: WindowEvent::Ime(event),
+            });
+        }
+    }
+
+    fn drag_motion<F>(&self, x: c_int, y: c_int, time: ffi::Time, callback: &mut F)
+    where
+        F: FnMut(Event<T>),
+    {
+        let wt = get_xtarget(&self.target);
+        let mut drag_lock = wt.drag.lock().unwrap();
+        let drag = match *drag_lock {
+            Some(ref mut drag) if drag.dropped.is_none() => drag,
+            _ => return,
+        };
+
+        let target = unsafe { self.dnd.find_target(wt.root, x, y) };
+        let mut action_changed = false;
+        if target.map(|(window, _)| window) != drag.target.map(|(window, _)| window) {
+            unsafe {
+                let _ = self.dnd.send_leave(drag);
+                drag.target = target;
+                drag.awaiting_status = false;
+                drag.pending_position = None;
+                let _ = self.dnd.send_enter(drag);
+            }
+            if drag.action.take().is_some() {
+                unsafe { self.dnd.set_drag_cursor(None) };
+                action_changed = true;
             }
         }
 
-        match self.ime_receiver.try_recv() {
-            Ok((window_id, x, y)) => {
-                wt.ime.borrow_mut().send_xim_spot(window_id, x, y);
+        if drag.target.is_some() {
+            // Only one `XdndPosition` may be in flight, the target could fall behind otherwise.
+            if drag.awaiting_status {
+                drag.pending_position = Some((x, y, time));
+            } else {
+                unsafe {
+                    let _ = self.dnd.send_position(drag, x, y, time);
+                }
+                drag.awaiting_status = true;
             }
-            Err(_) => (),
         }
+
+        let window_id = mkwid(drag.source_window);
+        // The callback may start another drag, so the lock can't be held while calling it.
+        drop(drag_lock);
+        if action_changed {
+            callback(Event::WindowEvent {
+                window_id,
+                event: WindowEvent::DragActionChanged(None),
+            });
+        }
+    }
+
+    fn drag_status<F>(&self, client_msg: &ffi::XClientMessageEvent, callback: &mut F)
+    where
+        F: FnMut(Event<T>),
+    {
+        let wt = get_xtarget(&self.target);
+        let mut drag_lock = wt.drag.lock().unwrap();
+        let drag = match *drag_lock {
+            Some(ref mut drag) if drag.dropped.is_none() => drag,
+            _ => return,
+        };
+        let target_window = client_msg.data.get_long(0) as ffi::Window;
+        if drag.target.map(|(window, _)| window) != Some(target_window) {
+            return;
+        }
+
+        drag.awaiting_status = false;
+        let accepted = client_msg.data.get_long(1) & 1 == 1;
+        let action = if accepted {
+            Some(
+                self.dnd
+                    .action_from_atom(client_msg.data.get_long(4) as ffi::Atom),
+            )
+        } else {
+            None
+        };
+        let action_changed = action != drag.action;
+        if action_changed {
+            drag.action = action;
+            unsafe { self.dnd.set_drag_cursor(action) };
+        }
+
+        if let Some((x, y, time)) = drag.pending_position.take() {
+            unsafe {
+                let _ = self.dnd.send_position(drag, x, y, time);
+            }
+            drag.awaiting_status = true;
+        }
+
+        let window_id = mkwid(drag.source_window);
+        drop(drag_lock);
+        if action_changed {
+            callback(Event::WindowEvent {
+                window_id,
+                event: WindowEvent::DragActionChanged(action),
+            });
+        }
+    }
+
+    fn drag_release<F>(&self, time: ffi::Time, callback: &mut F)
+    where
+        F: FnMut(Event<T>),
+    {
+        let wt = get_xtarget(&self.target);
+        let mut drag_lock = wt.drag.lock().unwrap();
+        let drag = match *drag_lock {
+            Some(ref mut drag) if drag.dropped.is_none() => drag,
+            _ => return,
+        };
+        unsafe {
+            (wt.xconn.xlib.XUngrabPointer)(wt.xconn.display, time);
+        }
+
+        if drag.target.is_some() && drag.action.is_some() {
+            // The drag ends once the target tells us it's done with the data.
+            unsafe {
+                let _ = self.dnd.send_drop(drag, time);
+            }
+            drag.dropped = Some(time);
+            self.drag_timer
+                .add_timeout(DROP_FINISH_TIMEOUT, (drag.source_window, time));
+        } else {
+            unsafe {
+                let _ = self.dnd.send_leave(drag);
+            }
+            let window_id = mkwid(drag.source_window);
+            *drag_lock = None;
+            drop(drag_lock);
+            let _ = wt.selections.clear(self.dnd.atoms.selection);
+            callback(Event::WindowEvent {
+                window_id,
+                event: WindowEvent::DragFinished(None),
+            });
+        }
+    }
+
+    fn drag_finished<F>(&self, client_msg: &ffi::XClientMessageEvent, callback: &mut F)
+    where
+        F: FnMut(Event<T>),
+    {
+        let wt = get_xtarget(&self.target);
+        let mut drag_lock = wt.drag.lock().unwrap();
+        let (window_id, action) = match *drag_lock {
+            Some(ref drag) if drag.dropped.is_some() => {
+                let (target_window, version) = match drag.target {
+                    Some(target) => target,
+                    None => return,
+                };
+                if target_window != client_msg.data.get_long(0) as ffi::Window {
+                    return;
+                }
+                // Before version 5, there's no way for the target to report failure.
+                let action = if version < 5 {
+                    drag.action
+                } else if client_msg.data.get_long(1) & 1 == 1 {
+                    Some(
+                        self.dnd
+                            .action_from_atom(client_msg.data.get_long(2) as ffi::Atom),
+                    )
+                } else {
+                    None
+                };
+                (mkwid(drag.source_window), action)
+            }
+            _ => return,
+        };
+        *drag_lock = None;
+        drop(drag_lock);
+        let _ = wt.selections.clear(self.dnd.atoms.selection);
+        callback(Event::WindowEvent {
+            window_id,
+            event: WindowEvent::DragFinished(action),
+        });
+    }
+
+    // Gives up on a drop whose target didn't send `XdndFinished` in time.
+    pub(super) fn drop_timed_out<F>(
+        &self,
+        source_window: ffi::Window,
+        time: ffi::Time,
+        callback: &mut F,
+    ) where
+        F: FnMut(Event<T>),
+    {
+        let wt = get_xtarget(&self.target);
+        let mut drag_lock = wt.drag.lock().unwrap();
+        // The drag may have finished since, and another one may have started.
+        let timed_out = match *drag_lock {
+            Some(ref drag) => drag.source_window == source_window && drag.dropped == Some(time),
+            None => false,
+        };
+        if !timed_out {
+            return;
+        }
+        *drag_lock = None;
+        drop(drag_lock);
+        let _ = wt.selections.clear(self.dnd.atoms.selection);
+        callback(Event::WindowEvent {
+            window_id: mkwid(source_window),
+            event: WindowEvent::DragFinished(None),
+        });
+    }
+}
+
+// Handles the `SelectionNotify` event answering the conversion of the data of a drop. Like the
+// functions below, this doesn't borrow the whole event processor.
+fn drop_data_notify<T, F>(
+    dnd: &mut Dnd,
+    selections: &Selections,
+    property: ffi::Atom,
+    callback: &mut F,
+) where
+    F: FnMut(Event<T>),
+{
+    let mut transfer = dnd.transfer.take().unwrap();
+    // A property of `None` means that the source refused to convert the data.
+    let data = if property != 0 {
+        unsafe { selections.read_property(transfer.window, property) }.ok()
+    } else {
+        None
+    };
+    match data {
+        Some((data_type, _)) if data_type == selections.atoms.incr => {
+            // Deleting the property (which reading it does) tells the source to start sending
+            // chunks.
+            transfer.incr_data = Some(Vec::new());
+            dnd.transfer = Some(transfer);
+        }
+        data => finish_drop_transfer(dnd, transfer, data.map(|(_, data)| data), callback),
+    }
+}
+
+// Handles a chunk of an incremental transfer of the data of a drop. An empty chunk marks the end
+// of the transfer.
+fn drop_data_chunk<T, F>(dnd: &mut Dnd, selections: &Selections, callback: &mut F)
+where
+    F: FnMut(Event<T>),
+{
+    let mut transfer = dnd.transfer.take().unwrap();
+    match unsafe { selections.read_property(transfer.window, dnd.atoms.selection) } {
+        Ok((_, ref chunk)) if !chunk.is_empty() => {
+            transfer
+                .incr_data
+                .as_mut()
+                .unwrap()
+                .extend_from_slice(chunk);
+            dnd.transfer = Some(transfer);
+        }
+        Ok(_) => {
+            let data = transfer.incr_data.take();
+            finish_drop_transfer(dnd, transfer, data, callback);
+        }
+        Err(_) => finish_drop_transfer(dnd, transfer, None, callback),
+    }
+}
+
+// Gives up on the transfer of an earlier drop, whose source never sent the data.
+fn cancel_drop_transfer<T, F>(dnd: &mut Dnd, callback: &mut F)
+where
+    F: FnMut(Event<T>),
+{
+    if let Some(transfer) = dnd.transfer.take() {
+        finish_drop_transfer(dnd, transfer, None, callback);
+    }
+}
+
+// Reports the data of a drop, or its cancellation if the data didn't arrive, and tells the source
+// that the drop is finished.
+fn finish_drop_transfer<T, F>(
+    dnd: &Dnd,
+    transfer: DropTransfer,
+    data: Option<Vec<u8>>,
+    callback: &mut F,
+) where
+    F: FnMut(Event<T>),
+{
+    let window_id = mkwid(transfer.window);
+    let state = if let Some(data) = data {
+        callback(Event::WindowEvent {
+            window_id,
+            event: WindowEvent::DroppedData {
+                mime_type: transfer.mime_type,
+                data,
+            },
+        });
+        DndState::Accepted
+    } else {
+        callback(Event::WindowEvent {
+            window_id,
+            event: WindowEvent::HoveredDataCancelled,
+        });
+        DndState::Rejected
+    };
+    unsafe {
+        let _ = dnd.send_finished(transfer.window, transfer.source_window, state);
+    }
+}
+
+// Records the new state of the modifiers, and reports it to the focused window if it changed.
+// This doesn't borrow the whole event processor, which the event loop target borrows from.
+fn update_modifiers<T, F>(
+    current: &mut ModifiersState,
+    focused_window: Option<ffi::Window>,
+    modifiers: ModifiersState,
+    callback: &mut F,
+) where
+    F: FnMut(Event<T>),
+{
+    if modifiers == *current {
+        return;
+    }
+    *current = modifiers;
+    if let Some(window) = focused_window {
+        callback(Event::WindowEvent {
+            window_id: mkwid(window),
+            event: WindowEvent::ModifiersChanged(modifiers),
+        });
     }
 }
@@ -1,4 +1,21 @@
+pub use x11_dl::xfixes::{XFixesSelectionNotifyEvent, Xlib as Xfixes};
 pub use x11_dl::{
     error::OpenError, keysym::*, xcursor::*, xinput::*, xinput2::*, xlib::*, xlib_xcb::*,
-    xrandr::*, xrender::*,
+    xpresent::*, xrandr::*, xrender::*,
 };
+
+use std::os::raw::{c_int, c_uint, c_ulong};
+
+// XKB constant that x11-dl doesn't provide.
+#[allow(non_upper_case_globals)]
+pub const XkbUseCoreKbd: c_uint = 0x0100;
+
+// XFixes constants that x11-dl doesn't provide.
+#[allow(non_upper_case_globals)]
+pub const XFixesSelectionNotify: c_int = 0;
+#[allow(non_upper_case_globals)]
+pub const XFixesSetSelectionOwnerNotifyMask: c_ulong = 1 << 0;
+#[allow(non_upper_case_globals)]
+pub const XFixesSelectionWindowDestroyNotifyMask: c_ulong = 1 << 1;
+#[allow(non_upper_case_globals)]
+pub const XFixesSelectionClientCloseNotifyMask: c_ulong = 1 << 2;
@@ -0,0 +1,121 @@
+use std::{
+    collections::HashMap,
+    mem,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use calloop::timer::TimerHandle;
+
+use crate::{event::FrameTiming, platform_impl::platform::instant_from_monotonic};
+
+use super::{ffi, WindowId, XConnection};
+
+// The refresh interval frames are paced on without the Present extension, that of a 60Hz display
+const FALLBACK_REFRESH_INTERVAL: Duration = Duration::from_nanos(16_666_667);
+
+// The frames of a window.
+#[derive(Default)]
+struct WindowFrames {
+    // Whether a frame was requested and not delivered yet
+    requested: bool,
+    // The time (in microseconds of `CLOCK_MONOTONIC`) and counter of the last vertical blank
+    // reported for the window
+    last_blank: Option<(u64, u64)>,
+    refresh_interval: Option<Duration>,
+}
+
+/// Paces the frames of the windows on the vertical blanks reported by the Present extension, or
+/// on a timer if the extension isn't available.
+pub struct FrameClock {
+    xconn: Arc<XConnection>,
+    // Whether the Present extension is available
+    present: bool,
+    timer: TimerHandle<WindowId>,
+    // The time the ticks of the timer are aligned on
+    start: Instant,
+    windows: Mutex<HashMap<WindowId, WindowFrames>>,
+}
+
+impl FrameClock {
+    pub fn new(xconn: Arc<XConnection>, present: bool, timer: TimerHandle<WindowId>) -> FrameClock {
+        FrameClock {
+            xconn,
+            present,
+            timer,
+            start: Instant::now(),
+            windows: Default::default(),
+        }
+    }
+
+    pub fn request_frame(&self, window: ffi::Window) {
+        let mut windows = self.windows.lock().unwrap();
+        let frames = windows.entry(WindowId(window)).or_insert_with(|| {
+            if self.present {
+                let xpresent = self.xconn.xpresent.as_ref().unwrap();
+                unsafe {
+                    (xpresent.XPresentSelectInput)(
+                        self.xconn.display,
+                        window,
+                        ffi::PresentCompleteNotifyMask as _,
+                    );
+                }
+            }
+            Default::default()
+        });
+        if frames.requested {
+            return;
+        }
+        frames.requested = true;
+
+        if self.present {
+            let xpresent = self.xconn.xpresent.as_ref().unwrap();
+            // A target of 0 with a divisor of 1 asks for the next vertical blank.
+            unsafe { (xpresent.XPresentNotifyMSC)(self.xconn.display, window, 0, 0, 1, 0) };
+            self.xconn
+                .flush_requests()
+                .expect("Failed to request a frame");
+        } else {
+            let interval = FALLBACK_REFRESH_INTERVAL.as_nanos();
+            let elapsed = self.start.elapsed().as_nanos() % interval;
+            let delay = Duration::from_nanos((interval - elapsed) as u64);
+            self.timer.add_timeout(delay, WindowId(window));
+        }
+    }
+
+    // Handles a `PresentCompleteNotify` event, returning the timing of the frame if one was
+    // requested.
+    pub fn complete(&self, window: ffi::Window, ust: u64, msc: u64) -> Option<FrameTiming> {
+        let mut windows = self.windows.lock().unwrap();
+        let frames = windows.get_mut(&WindowId(window))?;
+        if let Some((last_ust, last_msc)) = frames.last_blank {
+            if msc > last_msc && ust > last_ust {
+                frames.refresh_interval =
+                    Some(Duration::from_micros((ust - last_ust) / (msc - last_msc)));
+            }
+        }
+        frames.last_blank = Some((ust, msc));
+        if !mem::replace(&mut frames.requested, false) {
+            return None;
+        }
+        Some(FrameTiming {
+            presentation_time: Some(instant_from_monotonic(Duration::from_micros(ust))),
+            refresh_interval: frames.refresh_interval,
+        })
+    }
+
+    // Handles a tick of the timer, returning the timing of the frame if the window still exists.
+    pub fn tick(&self, window: WindowId) -> Option<FrameTiming> {
+        let mut windows = self.windows.lock().unwrap();
+        let frames = windows.get_mut(&window)?;
+        frames.requested = false;
+        Some(FrameTiming {
+            presentation_time: None,
+            refresh_interval: Some(FALLBACK_REFRESH_INTERVAL),
+        })
+    }
+
+    pub fn remove_window(&self, window: ffi::Window) {
+        self.windows.lock().unwrap().remove(&WindowId(window));
+    }
+}
@@ -1,6 +1,6 @@
 use std::{collections::HashMap, os::raw::c_char, ptr, sync::Arc};
 
-use super::{ffi, XConnection, XError};
+use super::{ffi, ImeEvent, XConnection, XError};
 
 use super::{
     context::{ImeContext, ImeContextCreationError},
@@ -107,8 +107,19 @@ unsafe fn replace_im(inner: *mut ImeInner) -> Result<(), ReplaceImError> {
     let mut new_contexts = HashMap::new();
     for (window, old_context) in (*inner).contexts.iter() {
         let spot = old_context.as_ref().map(|old_context| old_context.ic_spot);
+        let is_allowed = old_context
+            .as_ref()
+            .map(|old_context| old_context.is_allowed)
+            .unwrap_or(true);
         let new_context = {
-            let result = ImeContext::new(xconn, new_im.im, *window, spot);
+            let result = ImeContext::new(
+                xconn,
+                new_im.im,
+                *window,
+                spot,
+                is_allowed,
+                (*inner).event_sender.clone(),
+            );
             if result.is_err() {
                 let _ = close_im(xconn, new_im.im);
             }
@@ -159,6 +170,14 @@ pub unsafe extern "C" fn xim_destroy_callback(
     let inner: *mut ImeInner = client_data as _;
     if !inner.is_null() {
         (*inner).is_destroyed = true;
+        // The contexts are gone along with the input method, so any composition is dropped.
+        for (window, context) in (*inner).contexts.iter() {
+            if let Some(ref context) = *context {
+                if context.is_allowed {
+                    let _ = (*inner).event_sender.send((*window, ImeEvent::Disabled));
+                }
+            }
+        }
         let xconn = &(*inner).xconn;
         if !(*inner).is_fallback {
             let _ = set_instantiate_callback(xconn, client_data);
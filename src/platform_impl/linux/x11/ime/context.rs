@@ -1,10 +1,11 @@
 use std::{
-    os::raw::{c_short, c_void},
+    ffi::CStr,
+    os::raw::{c_int, c_short, c_void},
     ptr,
     sync::Arc,
 };
 
-use super::{ffi, util, XConnection, XError};
+use super::{ffi, util, ImeEvent, ImeEventSender, XConnection, XError};
 
 #[derive(Debug)]
 pub enum ImeContextCreationError {
@@ -28,6 +29,179 @@ unsafe fn create_pre_edit_attr<'a>(
     .expect("XVaCreateNestedList returned NULL")
 }
 
+unsafe fn create_pre_edit_attr_with_callbacks<'a>(
+    xconn: &'a Arc<XConnection>,
+    ic_spot: &'a ffi::XPoint,
+    callbacks: &'a PreeditCallbacks,
+) -> util::XSmartPointer<'a, c_void> {
+    util::XSmartPointer::new(
+        xconn,
+        (xconn.xlib.XVaCreateNestedList)(
+            0,
+            ffi::XNSpotLocation_0.as_ptr() as *const _,
+            ic_spot,
+            ffi::XNPreeditStartCallback_0.as_ptr() as *const _,
+            &callbacks.start_callback,
+            ffi::XNPreeditDoneCallback_0.as_ptr() as *const _,
+            &callbacks.done_callback,
+            ffi::XNPreeditDrawCallback_0.as_ptr() as *const _,
+            &callbacks.draw_callback,
+            ffi::XNPreeditCaretCallback_0.as_ptr() as *const _,
+            &callbacks.caret_callback,
+            ptr::null_mut::<()>(),
+        ),
+    )
+    .expect("XVaCreateNestedList returned NULL")
+}
+
+// The state shared with the pre-edit callbacks. It's boxed by `ImeContext`, since the input
+// method keeps a pointer to it for as long as the context exists.
+#[derive(Debug)]
+struct ImeContextClientData {
+    window: ffi::Window,
+    event_sender: ImeEventSender,
+    // The pre-edit text is kept as characters, since that's what the input method's offsets are
+    // expressed in.
+    text: Vec<char>,
+    cursor_pos: usize,
+}
+
+impl ImeContextClientData {
+    fn send(&self, event: ImeEvent) {
+        let _ = self.event_sender.send((self.window, event));
+    }
+
+    fn send_update(&self) {
+        let text: String = self.text.iter().collect();
+        let cursor_byte_pos = self.text[..self.cursor_pos]
+            .iter()
+            .map(|c| c.len_utf8())
+            .sum();
+        self.send(ImeEvent::Update(text, cursor_byte_pos));
+    }
+}
+
+// Called when a new composition starts. Returns the maximum length of the pre-edit text, where -1
+// means that there is no limit.
+unsafe extern "C" fn preedit_start_callback(
+    _ic: ffi::XIC,
+    client_data: ffi::XPointer,
+    _call_data: ffi::XPointer,
+) -> c_int {
+    let client_data = &mut *(client_data as *mut ImeContextClientData);
+    client_data.text.clear();
+    client_data.cursor_pos = 0;
+    client_data.send(ImeEvent::Start);
+    -1
+}
+
+// Called when the composition has ended, whether or not its text was committed.
+unsafe extern "C" fn preedit_done_callback(
+    _xim: ffi::XIM,
+    client_data: ffi::XPointer,
+    _call_data: ffi::XPointer,
+) {
+    let client_data = &mut *(client_data as *mut ImeContextClientData);
+    client_data.text.clear();
+    client_data.cursor_pos = 0;
+    client_data.send(ImeEvent::End);
+}
+
+// Called when a part of the pre-edit text is replaced.
+unsafe extern "C" fn preedit_draw_callback(
+    _xim: ffi::XIM,
+    client_data: ffi::XPointer,
+    call_data: ffi::XPointer,
+) {
+    let client_data = &mut *(client_data as *mut ImeContextClientData);
+    let call_data = &*(call_data as *const ffi::XIMPreeditDrawCallbackStruct);
+
+    let chg_first = (call_data.chg_first.max(0) as usize).min(client_data.text.len());
+    let chg_end = (chg_first + call_data.chg_length.max(0) as usize).min(client_data.text.len());
+
+    let new_chars: Vec<char> = if call_data.text.is_null() {
+        // The changed range was deleted.
+        Vec::new()
+    } else {
+        let xim_text = &*call_data.text;
+        if xim_text.encoding_is_wchar != 0 {
+            // Wide characters aren't used by any input method we know of, and they're not exposed
+            // properly by `x11-dl` anyway.
+            warn!("Ignoring pre-edit text encoded as wide characters");
+            return;
+        }
+        if xim_text.string.multi_byte.is_null() {
+            Vec::new()
+        } else {
+            CStr::from_ptr(xim_text.string.multi_byte)
+                .to_string_lossy()
+                .chars()
+                .collect()
+        }
+    };
+
+    client_data.text.splice(chg_first..chg_end, new_chars);
+    client_data.cursor_pos = (call_data.caret.max(0) as usize).min(client_data.text.len());
+    client_data.send_update();
+}
+
+// Called when the cursor moves inside of the pre-edit text without the text changing.
+unsafe extern "C" fn preedit_caret_callback(
+    _xim: ffi::XIM,
+    client_data: ffi::XPointer,
+    call_data: ffi::XPointer,
+) {
+    let client_data = &mut *(client_data as *mut ImeContextClientData);
+    let call_data = &mut *(call_data as *mut ffi::XIMPreeditCaretCallbackStruct);
+
+    let len = client_data.text.len();
+    client_data.cursor_pos = match call_data.direction {
+        ffi::XIMCaretDirection::XIMAbsolutePosition => {
+            (call_data.position.max(0) as usize).min(len)
+        }
+        ffi::XIMCaretDirection::XIMForwardChar => (client_data.cursor_pos + 1).min(len),
+        ffi::XIMCaretDirection::XIMBackwardChar => client_data.cursor_pos.saturating_sub(1),
+        ffi::XIMCaretDirection::XIMLineStart => 0,
+        ffi::XIMCaretDirection::XIMLineEnd => len,
+        _ => client_data.cursor_pos,
+    };
+    // The input method expects the new position to be written back.
+    call_data.position = client_data.cursor_pos as c_int;
+    client_data.send_update();
+}
+
+// The callbacks used by the on-the-spot pre-edit style. The input method copies these when the
+// context is created, so only the client data they point to needs to outlive the call.
+struct PreeditCallbacks {
+    start_callback: ffi::XICCallback,
+    done_callback: ffi::XIMCallback,
+    draw_callback: ffi::XIMCallback,
+    caret_callback: ffi::XIMCallback,
+}
+
+impl PreeditCallbacks {
+    fn new(client_data: ffi::XPointer) -> PreeditCallbacks {
+        PreeditCallbacks {
+            start_callback: ffi::XICCallback {
+                client_data,
+                callback: Some(preedit_start_callback),
+            },
+            done_callback: ffi::XIMCallback {
+                client_data,
+                callback: Some(preedit_done_callback),
+            },
+            draw_callback: ffi::XIMCallback {
+                client_data,
+                callback: Some(preedit_draw_callback),
+            },
+            caret_callback: ffi::XIMCallback {
+                client_data,
+                callback: Some(preedit_caret_callback),
+            },
+        }
+    }
+}
+
 // WARNING: this struct doesn't destroy its XIC resource when dropped.
 // This is intentional, as it doesn't have enough information to know whether or not the context
 // still exists on the server. Since `ImeInner` has that awareness, destruction must be handled
@@ -36,6 +210,11 @@ unsafe fn create_pre_edit_attr<'a>(
 pub struct ImeContext {
     pub ic: ffi::XIC,
     pub ic_spot: ffi::XPoint,
+    // Whether the window accepts input method composition, i.e. whether `ImeEvent::Enabled` was
+    // sent. When it doesn't, the context only serves to look up the text of key presses.
+    pub is_allowed: bool,
+    pub is_focused: bool,
+    _client_data: Box<ImeContextClientData>,
 }
 
 impl ImeContext {
@@ -44,11 +223,35 @@ impl ImeContext {
         im: ffi::XIM,
         window: ffi::Window,
         ic_spot: Option<ffi::XPoint>,
+        is_allowed: bool,
+        event_sender: ImeEventSender,
     ) -> Result<Self, ImeContextCreationError> {
-        let ic = if let Some(ic_spot) = ic_spot {
-            ImeContext::create_ic_with_spot(xconn, im, window, ic_spot)
-        } else {
+        let mut client_data = Box::new(ImeContextClientData {
+            window,
+            event_sender,
+            text: Vec::new(),
+            cursor_pos: 0,
+        });
+        let client_data_ptr = &mut *client_data as *mut ImeContextClientData as ffi::XPointer;
+
+        let spot = ic_spot.unwrap_or_else(|| ffi::XPoint { x: 0, y: 0 });
+        // With the on-the-spot style, the pre-edit text is reported through our callbacks rather
+        // than drawn by the input method itself.
+        let ic = if !is_allowed {
             ImeContext::create_ic(xconn, im, window)
+        } else {
+            match ImeContext::create_ic_with_callbacks(xconn, im, window, spot, client_data_ptr) {
+                Some(ic) => Some(ic),
+                // Not all input methods support the on-the-spot style, in which case we let the
+                // input method draw the pre-edit text on its own.
+                None => {
+                    if let Some(ic_spot) = ic_spot {
+                        ImeContext::create_ic_with_spot(xconn, im, window, ic_spot)
+                    } else {
+                        ImeContext::create_ic(xconn, im, window)
+                    }
+                }
+            }
         };
 
         let ic = ic.ok_or(ImeContextCreationError::Null)?;
@@ -56,12 +259,48 @@ impl ImeContext {
             .check_errors()
             .map_err(ImeContextCreationError::XError)?;
 
+        // Without the on-the-spot style, only the committed text is reported.
+        if is_allowed {
+            client_data.send(ImeEvent::Enabled);
+        }
+
         Ok(ImeContext {
             ic,
-            ic_spot: ic_spot.unwrap_or_else(|| ffi::XPoint { x: 0, y: 0 }),
+            ic_spot: spot,
+            is_allowed,
+            is_focused: false,
+            _client_data: client_data,
         })
     }
 
+    unsafe fn create_ic_with_callbacks(
+        xconn: &Arc<XConnection>,
+        im: ffi::XIM,
+        window: ffi::Window,
+        ic_spot: ffi::XPoint,
+        client_data: ffi::XPointer,
+    ) -> Option<ffi::XIC> {
+        let callbacks = PreeditCallbacks::new(client_data);
+        let pre_edit_attr = create_pre_edit_attr_with_callbacks(xconn, &ic_spot, &callbacks);
+        let ic = (xconn.xlib.XCreateIC)(
+            im,
+            ffi::XNInputStyle_0.as_ptr() as *const _,
+            ffi::XIMPreeditCallbacks | ffi::XIMStatusNothing,
+            ffi::XNClientWindow_0.as_ptr() as *const _,
+            window,
+            ffi::XNPreeditAttributes_0.as_ptr() as *const _,
+            pre_edit_attr.ptr,
+            ptr::null_mut::<()>(),
+        );
+        // A failed creation can leave an error behind, which shouldn't be blamed on the fallback.
+        let _ = xconn.check_errors();
+        if ic.is_null() {
+            None
+        } else {
+            Some(ic)
+        }
+    }
+
     unsafe fn create_ic(
         xconn: &Arc<XConnection>,
         im: ffi::XIM,
@@ -106,14 +345,20 @@ impl ImeContext {
         }
     }
 
-    pub fn focus(&self, xconn: &Arc<XConnection>) -> Result<(), XError> {
+    pub fn focus(&mut self, xconn: &Arc<XConnection>) -> Result<(), XError> {
+        self.is_focused = true;
+        // The input method isn't activated for a window that doesn't allow it.
+        if !self.is_allowed {
+            return Ok(());
+        }
         unsafe {
             (xconn.xlib.XSetICFocus)(self.ic);
         }
         xconn.check_errors()
     }
 
-    pub fn unfocus(&self, xconn: &Arc<XConnection>) -> Result<(), XError> {
+    pub fn unfocus(&mut self, xconn: &Arc<XConnection>) -> Result<(), XError> {
+        self.is_focused = false;
         unsafe {
             (xconn.xlib.XUnsetICFocus)(self.ic);
         }
@@ -125,6 +370,10 @@ impl ImeContext {
             return;
         }
         self.ic_spot = ffi::XPoint { x, y };
+        // The spot is kept for when the input method is allowed again.
+        if !self.is_allowed {
+            return;
+        }
 
         unsafe {
             let pre_edit_attr = create_pre_edit_attr(xconn, &self.ic_spot);
@@ -1,6 +1,6 @@
 use std::{collections::HashMap, mem, ptr, sync::Arc};
 
-use super::{ffi, XConnection, XError};
+use super::{ffi, ImeEventSender, XConnection, XError};
 
 use super::{context::ImeContext, input_method::PotentialInputMethods};
 
@@ -26,10 +26,15 @@ pub struct ImeInner {
     // (i.e. if ibus/fcitx/etc. was terminated/restarted)
     pub is_destroyed: bool,
     pub is_fallback: bool,
+    pub event_sender: ImeEventSender,
 }
 
 impl ImeInner {
-    pub fn new(xconn: Arc<XConnection>, potential_input_methods: PotentialInputMethods) -> Self {
+    pub fn new(
+        xconn: Arc<XConnection>,
+        potential_input_methods: PotentialInputMethods,
+        event_sender: ImeEventSender,
+    ) -> Self {
         ImeInner {
             xconn,
             im: ptr::null_mut(),
@@ -38,6 +43,7 @@ impl ImeInner {
             destroy_callback: unsafe { mem::zeroed() },
             is_destroyed: false,
             is_fallback: false,
+            event_sender,
         }
     }
 
@@ -20,8 +20,31 @@ use self::{
     input_method::PotentialInputMethods,
 };
 
-pub type ImeReceiver = Receiver<(ffi::Window, i16, i16)>;
-pub type ImeSender = Sender<(ffi::Window, i16, i16)>;
+pub type ImeReceiver = Receiver<ImeRequest>;
+pub type ImeSender = Sender<ImeRequest>;
+pub type ImeEventReceiver = Receiver<(ffi::Window, ImeEvent)>;
+pub type ImeEventSender = Sender<(ffi::Window, ImeEvent)>;
+
+// Requests from windows, which are handled on the event loop's thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImeRequest {
+    // Moves the spot of the candidate window.
+    Position(ffi::Window, i16, i16),
+    // Allows or disallows input method composition.
+    Allow(ffi::Window, bool),
+}
+
+// Events reported by the input method's callbacks, which are translated into `WindowEvent::Ime`
+// by the event processor.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ImeEvent {
+    Enabled,
+    Start,
+    // The pre-edit text and the byte position of the cursor within it.
+    Update(String, usize),
+    End,
+    Disabled,
+}
 
 #[derive(Debug)]
 pub enum ImeCreationError {
@@ -37,11 +60,14 @@ pub struct Ime {
 }
 
 impl Ime {
-    pub fn new(xconn: Arc<XConnection>) -> Result<Self, ImeCreationError> {
+    pub fn new(
+        xconn: Arc<XConnection>,
+        event_sender: ImeEventSender,
+    ) -> Result<Self, ImeCreationError> {
         let potential_input_methods = PotentialInputMethods::new(&xconn);
 
         let (mut inner, client_data) = {
-            let mut inner = Box::new(ImeInner::new(xconn, potential_input_methods));
+            let mut inner = Box::new(ImeInner::new(xconn, potential_input_methods, event_sender));
             let inner_ptr = Box::into_raw(inner);
             let client_data = inner_ptr as _;
             let destroy_callback = ffi::XIMCallback {
@@ -93,7 +119,16 @@ impl Ime {
             // Create empty entry in map, so that when IME is rebuilt, this window has a context.
             None
         } else {
-            Some(unsafe { ImeContext::new(&self.inner.xconn, self.inner.im, window, None) }?)
+            Some(unsafe {
+                ImeContext::new(
+                    &self.inner.xconn,
+                    self.inner.im,
+                    window,
+                    None,
+                    true,
+                    self.inner.event_sender.clone(),
+                )
+            }?)
         };
         self.inner.contexts.insert(window, context);
         Ok(!self.is_destroyed())
@@ -110,6 +145,14 @@ impl Ime {
         }
     }
 
+    // Whether the input method gets to see the key events of `window`.
+    pub fn is_ime_allowed(&self, window: ffi::Window) -> bool {
+        match self.inner.contexts.get(&window) {
+            Some(Some(context)) => context.is_allowed,
+            _ => true,
+        }
+    }
+
     pub fn remove_context(&mut self, window: ffi::Window) -> Result<bool, XError> {
         if let Some(Some(context)) = self.inner.contexts.remove(&window) {
             unsafe {
@@ -143,6 +186,63 @@ impl Ime {
         }
     }
 
+    pub fn set_ime_allowed(
+        &mut self,
+        window: ffi::Window,
+        allowed: bool,
+    ) -> Result<bool, ImeContextCreationError> {
+        if self.is_destroyed() {
+            return Ok(false);
+        }
+        let old_context = match self.inner.contexts.get_mut(&window) {
+            Some(context) => match context.take() {
+                Some(old_context) if old_context.is_allowed == allowed => {
+                    *context = Some(old_context);
+                    return Ok(false);
+                }
+                Some(old_context) => old_context,
+                None => return Ok(false),
+            },
+            None => return Ok(false),
+        };
+
+        // The input style of a context can't be changed, so it's replaced by a new one. The window
+        // keeps the old one if the input method refuses to create it.
+        let result = unsafe {
+            ImeContext::new(
+                &self.inner.xconn,
+                self.inner.im,
+                window,
+                Some(old_context.ic_spot),
+                allowed,
+                self.inner.event_sender.clone(),
+            )
+        };
+        let mut new_context = match result {
+            Ok(new_context) => new_context,
+            Err(err) => {
+                self.inner.contexts.insert(window, Some(old_context));
+                return Err(err);
+            }
+        };
+        if old_context.is_allowed {
+            let _ = self.inner.event_sender.send((window, ImeEvent::Disabled));
+        }
+        let focus_result = if old_context.is_focused {
+            new_context.focus(&self.xconn)
+        } else {
+            Ok(())
+        };
+        self.inner.contexts.insert(window, Some(new_context));
+        unsafe {
+            self.inner
+                .destroy_ic_if_necessary(old_context.ic)
+                .map_err(ImeContextCreationError::XError)?;
+        }
+        focus_result.map_err(ImeContextCreationError::XError)?;
+        Ok(true)
+    }
+
     pub fn send_xim_spot(&mut self, window: ffi::Window, x: i16, y: i16) {
         if self.is_destroyed() {
             return;
@@ -1,9 +1,10 @@
 #![cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd"))]
 
+mod clipboard;
 mod dnd;
 mod event_processor;
-mod events;
 pub mod ffi;
+mod frame;
 mod ime;
 mod monitor;
 pub mod util;
@@ -11,6 +12,7 @@ mod window;
 mod xdisplay;
 
 pub use self::{
+    clipboard::Clipboard,
     monitor::MonitorHandle,
     window::UnownedWindow,
     xdisplay::{XConnection, XError, XNotSupported},
@@ -18,28 +20,36 @@ pub use self::{
 
 use std::{
     cell::RefCell,
-    collections::{HashMap, HashSet, VecDeque},
+    collections::{HashMap, VecDeque},
     ffi::CStr,
+    io,
     mem::{self, MaybeUninit},
     ops::Deref,
-    os::raw::*,
+    os::{raw::*, unix::io::RawFd},
     rc::Rc,
     slice,
     sync::{mpsc, Arc, Mutex, Weak},
+    time::{Duration, Instant},
 };
 
 use libc::{self, setlocale, LC_CTYPE};
 
 use self::{
-    dnd::{Dnd, DndState},
+    clipboard::Selections,
+    dnd::{Dnd, DndState, Drag, DropTransfer, DROP_FINISH_TIMEOUT},
     event_processor::EventProcessor,
-    ime::{Ime, ImeCreationError, ImeReceiver, ImeSender},
+    frame::FrameClock,
+    ime::{Ime, ImeCreationError, ImeEvent, ImeEventReceiver, ImeReceiver, ImeRequest, ImeSender},
 };
 use crate::{
     error::OsError as RootOsError,
-    event::{Event, WindowEvent},
+    event::{Damage, Event, FdReadiness, FdToken, TimerToken, WindowEvent},
     event_loop::{ControlFlow, EventLoopClosed, EventLoopWindowTarget as RootELW},
-    platform_impl::{platform::sticky_exit_callback, PlatformSpecificWindowBuilderAttributes},
+    keyboard::{KeyCode, KeyRepeatInfo, KeyboardLayout},
+    platform_impl::{
+        platform::{sticky_exit_callback, timer::Timers, xkb, FdSources},
+        DropChoice, PlatformSpecificWindowBuilderAttributes,
+    },
     window::WindowAttributes,
 };
 
@@ -51,7 +61,14 @@ pub struct EventLoopWindowTarget<T> {
     root: ffi::Window,
     ime: RefCell<Ime>,
     windows: RefCell<HashMap<WindowId, Weak<UnownedWindow>>>,
-    pending_redraws: Arc<Mutex<HashSet<WindowId>>>,
+    pending_redraws: Arc<Mutex<HashMap<WindowId, Damage>>>,
+    selections: Arc<Selections>,
+    drag: Arc<Mutex<Option<Drag>>>,
+    frame_clock: Arc<FrameClock>,
+    // The keymap of the core keyboard, if libxkbcommon-x11 is available.
+    kb_state: RefCell<Option<xkb::KbState>>,
+    fd_sources: Rc<RefCell<FdSources>>,
+    timers: Timers,
     _marker: ::std::marker::PhantomData<T>,
 }
 
@@ -59,6 +76,8 @@ pub struct EventLoop<T: 'static> {
     inner_loop: ::calloop::EventLoop<()>,
     _x11_source: ::calloop::Source<::calloop::generic::Generic<::calloop::generic::EventedRawFd>>,
     _user_source: ::calloop::Source<::calloop::channel::Channel<T>>,
+    _frame_timer_source: ::calloop::Source<::calloop::timer::Timer<WindowId>>,
+    _drag_timer_source: ::calloop::Source<::calloop::timer::Timer<(ffi::Window, ffi::Time)>>,
     pending_user_events: Rc<RefCell<VecDeque<T>>>,
     event_processor: Rc<RefCell<EventProcessor<T>>>,
     user_sender: ::calloop::channel::Sender<T>,
@@ -82,14 +101,20 @@ impl<T: 'static> EventLoop<T> {
         let dnd = Dnd::new(Arc::clone(&xconn))
             .expect("Failed to call XInternAtoms when initializing drag and drop");
 
+        let selections = Arc::new(
+            Selections::new(Arc::clone(&xconn), root)
+                .expect("Failed to create the window used for selections"),
+        );
+
         let (ime_sender, ime_receiver) = mpsc::channel();
+        let (ime_event_sender, ime_event_receiver) = mpsc::channel();
         // Input methods will open successfully without setting the locale, but it won't be
         // possible to actually commit pre-edit sequences.
         unsafe {
             setlocale(LC_CTYPE, b"\0".as_ptr() as *const _);
         }
         let ime = RefCell::new({
-            let result = Ime::new(Arc::clone(&xconn));
+            let result = Ime::new(Arc::clone(&xconn), ime_event_sender);
             if let Err(ImeCreationError::OpenFailure(ref state)) = result {
                 panic!(format!("Failed to open input method: {:#?}", state));
             }
@@ -134,8 +159,90 @@ impl<T: 'static> EventLoop<T> {
             }
         }
 
+        // Without the XKB extension, the modifiers are only known from input events.
+        let xkbext = unsafe {
+            let mut ext = XExtension::default();
+            // The version of the extension we require
+            let mut major = 1;
+            let mut minor = 0;
+            let res = (xconn.xlib.XkbQueryExtension)(
+                xconn.display,
+                &mut ext.opcode,
+                &mut ext.first_event_id,
+                &mut ext.first_error_id,
+                &mut major,
+                &mut minor,
+            );
+            if res == ffi::True {
+                Some(ext)
+            } else {
+                None
+            }
+        };
+
+        // libxkbcommon-x11 may not be installed, in which case the keysyms computed by Xlib are used.
+        let kb_state = unsafe { xkb::KbState::from_x11_connection(xcb_connection(&xconn)) };
+        if xkbext.is_some() {
+            // Keep track of the modifiers, which doesn't need libxkbcommon-x11, and with it of the
+            // active layout and of changes to the keymap
+            let mut xkb_events = ffi::XkbStateNotifyMask;
+            if kb_state.is_some() {
+                xkb_events |= ffi::XkbNewKeyboardNotifyMask | ffi::XkbMapNotifyMask;
+            }
+            if let Some(flusher) = xconn.select_xkb_events(ffi::XkbUseCoreKbd, xkb_events) {
+                flusher.queue();
+            }
+        }
+
+        // Without the Present extension, frames are paced on a timer.
+        let presentext = xconn.xpresent.as_ref().and_then(|xpresent| unsafe {
+            let mut ext = XExtension::default();
+            let res = (xpresent.XPresentQueryExtension)(
+                xconn.display,
+                &mut ext.opcode,
+                &mut ext.first_event_id,
+                &mut ext.first_error_id,
+            );
+            if res == ffi::True {
+                Some(ext)
+            } else {
+                None
+            }
+        });
+        let frame_timer = ::calloop::timer::Timer::with_resolution(Duration::from_millis(1));
+        let frame_clock = Arc::new(FrameClock::new(
+            Arc::clone(&xconn),
+            presentext.is_some(),
+            frame_timer.handle(),
+        ));
+
         xconn.update_cached_wm_info(root);
 
+        // A calloop event loop to drive us
+        let inner_loop = ::calloop::EventLoop::new().unwrap();
+
+        let pending_events: Rc<RefCell<VecDeque<_>>> = Default::default();
+
+        // Handle the file descriptors registered by the application
+        let fd_sources = FdSources::new(inner_loop.handle(), {
+            let pending_events = pending_events.clone();
+            move |token, readiness| {
+                pending_events
+                    .borrow_mut()
+                    .push_back(Event::FdReady { token, readiness });
+            }
+        });
+
+        // Handle timers
+        let timers = Timers::new(&inner_loop.handle(), {
+            let pending_events = pending_events.clone();
+            move |token| {
+                pending_events
+                    .borrow_mut()
+                    .push_back(Event::TimerFired { token });
+            }
+        });
+
         let target = Rc::new(RootELW {
             p: super::EventLoopWindowTarget::X(EventLoopWindowTarget {
                 ime,
@@ -147,13 +254,16 @@ impl<T: 'static> EventLoop<T> {
                 wm_delete_window,
                 net_wm_ping,
                 pending_redraws: Default::default(),
+                selections,
+                drag: Default::default(),
+                frame_clock,
+                kb_state: RefCell::new(kb_state),
+                fd_sources: Rc::new(RefCell::new(fd_sources)),
+                timers,
             }),
             _marker: ::std::marker::PhantomData,
         });
 
-        // A calloop event loop to drive us
-        let inner_loop = ::calloop::EventLoop::new().unwrap();
-
         // Handle user events
         let pending_user_events = Rc::new(RefCell::new(VecDeque::new()));
         let pending_user_events2 = pending_user_events.clone();
@@ -170,15 +280,23 @@ impl<T: 'static> EventLoop<T> {
             .unwrap();
 
         // Handle X11 events
-        let pending_events: Rc<RefCell<VecDeque<_>>> = Default::default();
-
+        let drag_timer = ::calloop::timer::Timer::new();
         let processor = EventProcessor {
             target: target.clone(),
             dnd,
             devices: Default::default(),
             randr_event_offset,
             ime_receiver,
+            ime_event_receiver,
+            composing: Default::default(),
+            held_keys: Default::default(),
+            modifiers: Default::default(),
+            focused_window: None,
+            exposed: Default::default(),
             xi2ext,
+            xkbext,
+            presentext,
+            drag_timer: drag_timer.handle(),
         };
 
         // Register for device hotplug events
@@ -193,6 +311,22 @@ impl<T: 'static> EventLoop<T> {
         let processor = Rc::new(RefCell::new(processor));
         let event_processor = processor.clone();
 
+        // Give up on the drops that aren't finished in time
+        let _drag_timer_source = inner_loop
+            .handle()
+            .insert_source(drag_timer, {
+                let processor = processor.clone();
+                let pending_events = pending_events.clone();
+                move |((source_window, time), _), &mut ()| {
+                    let processor = processor.borrow();
+                    let mut pending_events = pending_events.borrow_mut();
+                    processor.drop_timed_out(source_window, time, &mut |event| {
+                        pending_events.push_back(event)
+                    });
+                }
+            })
+            .unwrap();
+
         // Setup the X11 event source
         let mut x11_events =
             ::calloop::generic::Generic::from_raw_fd(get_xtarget(&target).xconn.x11_fd);
@@ -211,11 +345,30 @@ impl<T: 'static> EventLoop<T> {
             })
             .unwrap();
 
+        // Handle the frames paced on the timer
+        let _frame_timer_source = inner_loop
+            .handle()
+            .insert_source(frame_timer, {
+                let pending_events = pending_events.clone();
+                let target = target.clone();
+                move |(wid, _), &mut ()| {
+                    if let Some(timing) = get_xtarget(&target).frame_clock.tick(wid) {
+                        pending_events.borrow_mut().push_back(Event::WindowEvent {
+                            window_id: crate::window::WindowId(super::WindowId::X(wid)),
+                            event: WindowEvent::FrameReady(timing),
+                        });
+                    }
+                }
+            })
+            .unwrap();
+
         let result = EventLoop {
             inner_loop,
             pending_events,
             _x11_source,
             _user_source,
+            _frame_timer_source,
+            _drag_timer_source,
             user_sender,
             pending_user_events,
             event_processor,
@@ -278,13 +431,13 @@ impl<T: 'static> EventLoop<T> {
             // Empty the redraw requests
             {
                 // Release the lock to prevent deadlock
-                let windows: Vec<_> = wt.pending_redraws.lock().unwrap().drain().collect();
+                let redraws: Vec<_> = wt.pending_redraws.lock().unwrap().drain().collect();
 
-                for wid in windows {
+                for (wid, damage) in redraws {
                     sticky_exit_callback(
                         Event::WindowEvent {
                             window_id: crate::window::WindowId(super::WindowId::X(wid)),
-                            event: WindowEvent::RedrawRequested,
+                            event: WindowEvent::RedrawRequested(damage),
                         },
                         &self.target,
                         &mut control_flow,
@@ -406,6 +559,66 @@ fn drain_events<T: 'static>(
     }
 }
 
+impl<T> EventLoopWindowTarget<T> {
+    #[inline]
+    pub fn clipboard(&self) -> Clipboard {
+        Clipboard::new(
+            Arc::clone(&self.selections),
+            self.selections.atoms.clipboard,
+        )
+    }
+
+    #[inline]
+    pub fn primary_selection(&self) -> Clipboard {
+        Clipboard::new(Arc::clone(&self.selections), self.selections.atoms.primary)
+    }
+
+    #[inline]
+    pub fn keyboard_layout(&self) -> Option<KeyboardLayout> {
+        self.kb_state
+            .borrow()
+            .as_ref()
+            .map(|kb_state| kb_state.layout())
+    }
+
+    #[inline]
+    pub fn key_repeat_info(&self) -> Option<KeyRepeatInfo> {
+        self.xconn.key_repeat_info()
+    }
+
+    pub fn key_label(&self, key: KeyCode) -> Option<String> {
+        self.kb_state.borrow().as_ref()?.key_label(key)
+    }
+
+    pub fn key_from_label(&self, label: &str) -> Option<KeyCode> {
+        self.kb_state.borrow().as_ref()?.key_from_label(label)
+    }
+
+    pub fn register_fd(&self, fd: RawFd, interest: FdReadiness) -> io::Result<FdToken> {
+        self.fd_sources.borrow_mut().register(fd, interest)
+    }
+
+    pub fn unregister_fd(&self, token: FdToken) {
+        self.fd_sources.borrow_mut().unregister(token);
+    }
+
+    pub fn register_timer(
+        &self,
+        deadline: Instant,
+        interval: Option<Duration>,
+    ) -> io::Result<TimerToken> {
+        self.timers.register(deadline, interval)
+    }
+
+    pub fn unregister_timer(&self, token: TimerToken) {
+        self.timers.unregister(token);
+    }
+}
+
+fn xcb_connection(xconn: &XConnection) -> *mut libc::c_void {
+    unsafe { (xconn.xlib_xcb.XGetXCBConnection)(xconn.display) as *mut _ }
+}
+
 fn get_xtarget<T>(rt: &RootELW<T>) -> &EventLoopWindowTarget<T> {
     if let super::EventLoopWindowTarget::X(ref target) = rt.p {
         target
@@ -461,6 +674,7 @@ impl<'a> Deref for DeviceInfo<'a> {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct WindowId(ffi::Window);
 
 impl WindowId {
@@ -470,6 +684,7 @@ impl WindowId {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct DeviceId(c_int);
 
 impl DeviceId {
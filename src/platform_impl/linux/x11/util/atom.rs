@@ -50,6 +50,19 @@ impl XConnection {
         self.get_atom(name)
     }
 
+    pub fn get_atom_name(&self, atom: ffi::Atom) -> Option<String> {
+        unsafe {
+            let name = (self.xlib.XGetAtomName)(self.display, atom);
+            self.check_errors().ok()?;
+            if name.is_null() {
+                return None;
+            }
+            let string = CStr::from_ptr(name).to_string_lossy().into_owned();
+            (self.xlib.XFree)(name as _);
+            Some(string)
+        }
+    }
+
     // Note: this doesn't use caching, for the sake of simplicity.
     // If you're dealing with this many atoms, you'll usually want to cache them locally anyway.
     pub unsafe fn get_atoms(&self, names: &[*mut c_char]) -> Result<Vec<ffi::Atom>, XError> {
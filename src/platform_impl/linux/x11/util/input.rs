@@ -1,7 +1,7 @@
-use std::{slice, str};
+use std::{slice, str, time::Duration};
 
 use super::*;
-use crate::event::ModifiersState;
+use crate::{event::ModifiersState, keyboard::KeyRepeatInfo};
 
 pub const VIRTUAL_CORE_POINTER: c_int = 2;
 pub const VIRTUAL_CORE_KEYBOARD: c_int = 3;
@@ -13,13 +13,22 @@ const TEXT_BUFFER_SIZE: usize = 1024;
 
 impl From<ffi::XIModifierState> for ModifiersState {
     fn from(mods: ffi::XIModifierState) -> Self {
-        let state = mods.effective as c_uint;
-        ModifiersState {
-            alt: state & ffi::Mod1Mask != 0,
-            shift: state & ffi::ShiftMask != 0,
-            ctrl: state & ffi::ControlMask != 0,
-            logo: state & ffi::Mod4Mask != 0,
-        }
+        modifiers_from_state(mods.effective as c_uint)
+    }
+}
+
+/// Reads the modifiers of a core modifier mask. The sides of the modifier keys are not part of
+/// it.
+pub fn modifiers_from_state(state: c_uint) -> ModifiersState {
+    ModifiersState {
+        alt: state & ffi::Mod1Mask != 0,
+        shift: state & ffi::ShiftMask != 0,
+        ctrl: state & ffi::ControlMask != 0,
+        logo: state & ffi::Mod4Mask != 0,
+        alt_graph: state & ffi::Mod5Mask != 0,
+        caps_lock: state & ffi::LockMask != 0,
+        num_lock: state & ffi::Mod2Mask != 0,
+        ..Default::default()
     }
 }
 
@@ -78,7 +87,6 @@ impl XConnection {
         Flusher::new(self)
     }
 
-    #[allow(dead_code)]
     pub fn select_xkb_events(&self, device_id: c_uint, mask: c_ulong) -> Option<Flusher<'_>> {
         let status = unsafe { (self.xlib.XkbSelectEvents)(self.display, device_id, mask, mask) };
         if status == ffi::True {
@@ -88,6 +96,35 @@ impl XConnection {
         }
     }
 
+    pub fn key_repeat_info(&self) -> Option<KeyRepeatInfo> {
+        let mut delay = 0;
+        let mut interval = 0;
+        let mut keyboard_state = MaybeUninit::<ffi::XKeyboardState>::uninit();
+        let status = unsafe {
+            (self.xlib.XGetKeyboardControl)(self.display, keyboard_state.as_mut_ptr());
+            (self.xlib.XkbGetAutoRepeatRate)(
+                self.display,
+                ffi::XkbUseCoreKbd,
+                &mut delay,
+                &mut interval,
+            )
+        };
+        if status != ffi::True {
+            return None;
+        }
+        let keyboard_state = unsafe { keyboard_state.assume_init() };
+        // The repeat can be turned off globally, regardless of its rate.
+        let rate = if keyboard_state.global_auto_repeat == ffi::AutoRepeatModeOn && interval != 0 {
+            1000 / interval
+        } else {
+            0
+        };
+        Some(KeyRepeatInfo {
+            delay: Duration::from_millis(delay.into()),
+            rate,
+        })
+    }
+
     pub fn query_pointer(
         &self,
         window: ffi::Window,
@@ -1,6 +1,6 @@
 use std::{
     cmp,
-    collections::HashSet,
+    collections::HashMap,
     env,
     ffi::CString,
     mem::{self, MaybeUninit},
@@ -16,15 +16,25 @@ use parking_lot::Mutex;
 use crate::{
     dpi::{LogicalPosition, LogicalSize},
     error::{ExternalError, NotSupportedError, OsError as RootOsError},
+    event::{Damage, DamageRect},
     monitor::MonitorHandle as RootMonitorHandle,
     platform_impl::{
+        platform::{cursor_names, cursor_pixels},
         x11::{ime::ImeContextCreationError, MonitorHandle as X11MonitorHandle},
         MonitorHandle as PlatformMonitorHandle, OsError, PlatformSpecificWindowBuilderAttributes,
     },
-    window::{CursorIcon, Icon, WindowAttributes},
+    window::{
+        CursorGrabMode, CursorIcon, CustomCursor, Icon, ImeHints, ImePurpose, WindowAttributes,
+    },
 };
 
-use super::{ffi, util, EventLoopWindowTarget, ImeSender, WindowId, XConnection, XError};
+use super::{
+    dnd::{self, Drag},
+    ffi,
+    frame::FrameClock,
+    util, Clipboard, DropChoice, EventLoopWindowTarget, ImeRequest, ImeSender, Selections,
+    WindowId, XConnection, XError,
+};
 
 unsafe extern "C" fn visibility_predicate(
     _display: *mut ffi::Display,
@@ -62,6 +72,13 @@ impl SharedState {
     }
 }
 
+// The cursor chosen for a window, kept so that it can be shown again after being hidden.
+#[derive(Debug, Clone)]
+enum WindowCursor {
+    Icon(CursorIcon),
+    Custom(CustomCursor),
+}
+
 unsafe impl Send for UnownedWindow {}
 unsafe impl Sync for UnownedWindow {}
 
@@ -70,12 +87,19 @@ pub struct UnownedWindow {
     xwindow: ffi::Window,        // never changes
     root: ffi::Window,           // never changes
     screen_id: i32,              // never changes
-    cursor: Mutex<CursorIcon>,
-    cursor_grabbed: Mutex<bool>,
+    cursor: Mutex<WindowCursor>,
+    cursor_grab: Mutex<CursorGrabMode>,
+    // Where the cursor is kept while it's locked
+    cursor_lock_position: Mutex<Option<(i32, i32)>>,
     cursor_visible: Mutex<bool>,
+    key_repeat: Mutex<bool>,
     ime_sender: Mutex<ImeSender>,
     pub shared_state: Mutex<SharedState>,
-    pending_redraws: Arc<::std::sync::Mutex<HashSet<WindowId>>>,
+    pending_redraws: Arc<::std::sync::Mutex<HashMap<WindowId, Damage>>>,
+    drag: Arc<::std::sync::Mutex<Option<Drag>>>,
+    drop_choice: Mutex<DropChoice>,
+    selections: Arc<Selections>,
+    frame_clock: Arc<FrameClock>,
 }
 
 impl UnownedWindow {
@@ -173,7 +197,9 @@ impl UnownedWindow {
                 | ffi::KeymapStateMask
                 | ffi::ButtonPressMask
                 | ffi::ButtonReleaseMask
-                | ffi::PointerMotionMask;
+                | ffi::PointerMotionMask
+                // For the incremental transfers of the data dropped on the window
+                | ffi::PropertyChangeMask;
             swa.border_pixel = 0;
             swa.override_redirect = pl_attribs.override_redirect as c_int;
             swa
@@ -222,12 +248,18 @@ impl UnownedWindow {
             xwindow,
             root,
             screen_id,
-            cursor: Default::default(),
-            cursor_grabbed: Mutex::new(false),
+            cursor: Mutex::new(WindowCursor::Icon(Default::default())),
+            cursor_grab: Mutex::new(CursorGrabMode::None),
+            cursor_lock_position: Mutex::new(None),
             cursor_visible: Mutex::new(true),
+            key_repeat: Mutex::new(true),
             ime_sender: Mutex::new(event_loop.ime_sender.clone()),
             shared_state: SharedState::new(dpi_factor),
             pending_redraws: event_loop.pending_redraws.clone(),
+            drag: event_loop.drag.clone(),
+            drop_choice: Mutex::new(DropChoice::Default),
+            selections: event_loop.selections.clone(),
+            frame_clock: event_loop.frame_clock.clone(),
         };
 
         // Title must be set before mapping. Some tiling window managers (i.e. i3) use the window
@@ -1038,16 +1070,12 @@ impl UnownedWindow {
         unsafe { (self.xconn.xlib_xcb.XGetXCBConnection)(self.xconn.display) as *mut _ }
     }
 
-    fn load_cursor(&self, name: &[u8]) -> ffi::Cursor {
-        unsafe {
-            (self.xconn.xcursor.XcursorLibraryLoadCursor)(
-                self.xconn.display,
-                name.as_ptr() as *const c_char,
-            )
-        }
+    fn load_cursor(&self, name: &str) -> ffi::Cursor {
+        let name = CString::new(name).expect("cursor names don't contain nul bytes");
+        unsafe { (self.xconn.xcursor.XcursorLibraryLoadCursor)(self.xconn.display, name.as_ptr()) }
     }
 
-    fn load_first_existing_cursor(&self, names: &[&[u8]]) -> ffi::Cursor {
+    fn load_first_existing_cursor(&self, names: &[&str]) -> ffi::Cursor {
         for name in names.iter() {
             let xcursor = self.load_cursor(name);
             if xcursor != 0 {
@@ -1058,56 +1086,44 @@ impl UnownedWindow {
     }
 
     fn get_cursor(&self, cursor: CursorIcon) -> ffi::Cursor {
-        let load = |name: &[u8]| self.load_cursor(name);
-
-        let loadn = |names: &[&[u8]]| self.load_first_existing_cursor(names);
-
-        // Try multiple names in some cases where the name
-        // differs on the desktop environments or themes.
-        //
-        // Try the better looking (or more suiting) names first.
-        match cursor {
-            CursorIcon::Alias => load(b"link\0"),
-            CursorIcon::Arrow => load(b"arrow\0"),
-            CursorIcon::Cell => load(b"plus\0"),
-            CursorIcon::Copy => load(b"copy\0"),
-            CursorIcon::Crosshair => load(b"crosshair\0"),
-            CursorIcon::Default => load(b"left_ptr\0"),
-            CursorIcon::Hand => loadn(&[b"hand2\0", b"hand1\0"]),
-            CursorIcon::Help => load(b"question_arrow\0"),
-            CursorIcon::Move => load(b"move\0"),
-            CursorIcon::Grab => loadn(&[b"openhand\0", b"grab\0"]),
-            CursorIcon::Grabbing => loadn(&[b"closedhand\0", b"grabbing\0"]),
-            CursorIcon::Progress => load(b"left_ptr_watch\0"),
-            CursorIcon::AllScroll => load(b"all-scroll\0"),
-            CursorIcon::ContextMenu => load(b"context-menu\0"),
-
-            CursorIcon::NoDrop => loadn(&[b"no-drop\0", b"circle\0"]),
-            CursorIcon::NotAllowed => load(b"crossed_circle\0"),
-
-            // Resize cursors
-            CursorIcon::EResize => load(b"right_side\0"),
-            CursorIcon::NResize => load(b"top_side\0"),
-            CursorIcon::NeResize => load(b"top_right_corner\0"),
-            CursorIcon::NwResize => load(b"top_left_corner\0"),
-            CursorIcon::SResize => load(b"bottom_side\0"),
-            CursorIcon::SeResize => load(b"bottom_right_corner\0"),
-            CursorIcon::SwResize => load(b"bottom_left_corner\0"),
-            CursorIcon::WResize => load(b"left_side\0"),
-            CursorIcon::EwResize => load(b"h_double_arrow\0"),
-            CursorIcon::NsResize => load(b"v_double_arrow\0"),
-            CursorIcon::NwseResize => loadn(&[b"bd_double_arrow\0", b"size_bdiag\0"]),
-            CursorIcon::NeswResize => loadn(&[b"fd_double_arrow\0", b"size_fdiag\0"]),
-            CursorIcon::ColResize => loadn(&[b"split_h\0", b"h_double_arrow\0"]),
-            CursorIcon::RowResize => loadn(&[b"split_v\0", b"v_double_arrow\0"]),
-
-            CursorIcon::Text => loadn(&[b"text\0", b"xterm\0"]),
-            CursorIcon::VerticalText => load(b"vertical-text\0"),
-
-            CursorIcon::Wait => load(b"watch\0"),
-
-            CursorIcon::ZoomIn => load(b"zoom-in\0"),
-            CursorIcon::ZoomOut => load(b"zoom-out\0"),
+        self.load_first_existing_cursor(cursor_names(cursor))
+    }
+
+    fn create_custom_cursor(&self, cursor: &CustomCursor) -> ffi::Cursor {
+        unsafe {
+            let images = (self.xconn.xcursor.XcursorImagesCreate)(cursor.frames.len() as c_int);
+            if images.is_null() {
+                return 0;
+            }
+            for frame in &cursor.frames {
+                let image = (self.xconn.xcursor.XcursorImageCreate)(
+                    frame.width as c_int,
+                    frame.height as c_int,
+                );
+                if image.is_null() {
+                    (self.xconn.xcursor.XcursorImagesDestroy)(images);
+                    return 0;
+                }
+                (*image).xhot = frame.hotspot_x;
+                (*image).yhot = frame.hotspot_y;
+                (*image).delay = frame.delay.as_millis() as u32;
+                let pixels = cursor_pixels(&frame.rgba);
+                ptr::copy_nonoverlapping(pixels.as_ptr(), (*image).pixels, pixels.len());
+                // The images are owned by `images` from now on, and destroyed along with it.
+                *(*images).images.offset((*images).nimage as isize) = image;
+                (*images).nimage += 1;
+            }
+            // With more than one image, this creates an animated cursor played by the X server.
+            let xcursor = (self.xconn.xcursor.XcursorImagesLoadCursor)(self.xconn.display, images);
+            (self.xconn.xcursor.XcursorImagesDestroy)(images);
+            xcursor
+        }
+    }
+
+    fn get_window_cursor(&self, cursor: &WindowCursor) -> ffi::Cursor {
+        match *cursor {
+            WindowCursor::Icon(icon) => self.get_cursor(icon),
+            WindowCursor::Custom(ref custom) => self.create_custom_cursor(custom),
         }
     }
 
@@ -1125,12 +1141,20 @@ impl UnownedWindow {
 
     #[inline]
     pub fn set_cursor_icon(&self, cursor: CursorIcon) {
-        *self.cursor.lock() = cursor;
-        if *self.cursor_visible.lock() {
+        *self.cursor.lock() = WindowCursor::Icon(cursor);
+        if self.is_cursor_shown() {
             self.update_cursor(self.get_cursor(cursor));
         }
     }
 
+    #[inline]
+    pub fn set_custom_cursor(&self, cursor: &CustomCursor) {
+        *self.cursor.lock() = WindowCursor::Custom(cursor.clone());
+        if self.is_cursor_shown() {
+            self.update_cursor(self.create_custom_cursor(cursor));
+        }
+    }
+
     // TODO: This could maybe be cached. I don't think it's worth
     // the complexity, since cursor changes are not so common,
     // and this is just allocating a 1x1 pixmap...
@@ -1164,9 +1188,9 @@ impl UnownedWindow {
     }
 
     #[inline]
-    pub fn set_cursor_grab(&self, grab: bool) -> Result<(), ExternalError> {
-        let mut grabbed_lock = self.cursor_grabbed.lock();
-        if grab == *grabbed_lock {
+    pub fn set_cursor_grab(&self, mode: CursorGrabMode) -> Result<(), ExternalError> {
+        let mut grab_lock = self.cursor_grab.lock();
+        if mode == *grab_lock {
             return Ok(());
         }
         unsafe {
@@ -1174,7 +1198,7 @@ impl UnownedWindow {
             // Therefore, this is common to both codepaths.
             (self.xconn.xlib.XUngrabPointer)(self.xconn.display, ffi::CurrentTime);
         }
-        let result = if grab {
+        let result = if mode != CursorGrabMode::None {
             let result = unsafe {
                 (self.xconn.xlib.XGrabPointer)(
                     self.xconn.display,
@@ -1220,26 +1244,62 @@ impl UnownedWindow {
                 .map_err(|err| ExternalError::Os(os_error!(OsError::XError(err))))
         };
         if result.is_ok() {
-            *grabbed_lock = grab;
+            *grab_lock = mode;
+            drop(grab_lock);
+            // A locked cursor is hidden, and warped back to where it was locked whenever it moves.
+            let lock_position = if mode == CursorGrabMode::Locked {
+                let position = self
+                    .xconn
+                    .query_pointer(self.xwindow, util::VIRTUAL_CORE_POINTER)
+                    .map(|pointer| (pointer.win_x.round() as i32, pointer.win_y.round() as i32));
+                Some(position.unwrap_or((0, 0)))
+            } else {
+                None
+            };
+            let was_locked = mem::replace(&mut *self.cursor_lock_position.lock(), lock_position);
+            if was_locked.is_some() != lock_position.is_some() {
+                self.update_cursor_visibility();
+            }
         }
         result
     }
 
+    // Warps the cursor back to where it was locked, if it's locked. Returns whether it was.
+    pub(super) fn restore_locked_cursor(&self, position: (f64, f64)) -> bool {
+        let (x, y) = match *self.cursor_lock_position.lock() {
+            Some(lock_position) => lock_position,
+            None => return false,
+        };
+        if (position.0.round() as i32, position.1.round() as i32) != (x, y) {
+            let _ = self.set_cursor_position_physical(x, y);
+        }
+        true
+    }
+
+    fn is_cursor_shown(&self) -> bool {
+        *self.cursor_visible.lock() && self.cursor_lock_position.lock().is_none()
+    }
+
+    // Shows the cursor of the window, or an empty one while it's hidden or locked.
+    fn update_cursor_visibility(&self) {
+        let cursor = if self.is_cursor_shown() {
+            self.get_window_cursor(&self.cursor.lock())
+        } else {
+            self.create_empty_cursor()
+                .expect("Failed to create empty cursor")
+        };
+        self.update_cursor(cursor);
+    }
+
     #[inline]
     pub fn set_cursor_visible(&self, visible: bool) {
         let mut visible_lock = self.cursor_visible.lock();
         if visible == *visible_lock {
             return;
         }
-        let cursor = if visible {
-            self.get_cursor(*self.cursor.lock())
-        } else {
-            self.create_empty_cursor()
-                .expect("Failed to create empty cursor")
-        };
         *visible_lock = visible;
         drop(visible_lock);
-        self.update_cursor(cursor);
+        self.update_cursor_visibility();
     }
 
     #[inline]
@@ -1269,15 +1329,40 @@ impl UnownedWindow {
         let _ = self
             .ime_sender
             .lock()
-            .send((self.xwindow, x as i16, y as i16));
+            .send(ImeRequest::Position(self.xwindow, x as i16, y as i16));
     }
 
     #[inline]
-    pub fn set_ime_position(&self, logical_spot: LogicalPosition) {
-        let (x, y) = logical_spot.to_physical(self.hidpi_factor()).into();
+    pub fn set_ime_position(&self, logical_spot: LogicalPosition, size: LogicalSize) {
+        // The spot is where the text baseline starts, so the bottom of the cursor is used.
+        let spot = LogicalPosition::new(logical_spot.x, logical_spot.y + size.height);
+        let (x, y) = spot.to_physical(self.hidpi_factor()).into();
         self.set_ime_position_physical(x, y);
     }
 
+    #[inline]
+    pub fn set_key_repeat(&self, enabled: bool) {
+        *self.key_repeat.lock() = enabled;
+    }
+
+    #[inline]
+    pub fn key_repeat(&self) -> bool {
+        *self.key_repeat.lock()
+    }
+
+    #[inline]
+    pub fn set_ime_allowed(&self, allowed: bool) {
+        let _ = self
+            .ime_sender
+            .lock()
+            .send(ImeRequest::Allow(self.xwindow, allowed));
+    }
+
+    #[inline]
+    pub fn set_ime_purpose(&self, _purpose: ImePurpose, _hints: ImeHints) {
+        // XIM has no notion of the purpose of a text field.
+    }
+
     #[inline]
     pub fn id(&self) -> WindowId {
         WindowId(self.xwindow)
@@ -1288,6 +1373,103 @@ impl UnownedWindow {
         self.pending_redraws
             .lock()
             .unwrap()
-            .insert(WindowId(self.xwindow));
+            .insert(WindowId(self.xwindow), Damage::Full);
+    }
+
+    #[inline]
+    pub fn request_redraw_region(&self, rect: DamageRect) {
+        self.pending_redraws
+            .lock()
+            .unwrap()
+            .entry(WindowId(self.xwindow))
+            .or_insert_with(|| Damage::Region(Vec::new()))
+            .add_rect(rect);
+    }
+
+    #[inline]
+    pub fn request_frame(&self) {
+        self.frame_clock.request_frame(self.xwindow);
+    }
+
+    #[inline]
+    pub fn clipboard(&self) -> Clipboard {
+        Clipboard::new(
+            Arc::clone(&self.selections),
+            self.selections.atoms.clipboard,
+        )
+    }
+
+    #[inline]
+    pub fn primary_selection(&self) -> Clipboard {
+        Clipboard::new(Arc::clone(&self.selections), self.selections.atoms.primary)
+    }
+
+    pub fn start_drag(&self, contents: Vec<(String, Vec<u8>)>) -> Result<(), ExternalError> {
+        let (selection, type_list) = unsafe {
+            (
+                self.xconn.get_atom_unchecked(b"XdndSelection\0"),
+                self.xconn.get_atom_unchecked(b"XdndTypeList\0"),
+            )
+        };
+        self.selections.set_contents(selection, contents)?;
+        let types = self.selections.owned_targets(selection);
+        self.xconn
+            .change_property(
+                self.xwindow,
+                type_list,
+                ffi::XA_ATOM,
+                util::PropMode::Replace,
+                &types,
+            )
+            .queue();
+
+        let result = unsafe {
+            // Until a target answers, we don't know whether the data can be dropped anywhere.
+            let cursor = dnd::load_drag_cursor(&self.xconn, None);
+            let result = (self.xconn.xlib.XGrabPointer)(
+                self.xconn.display,
+                self.xwindow,
+                ffi::False,
+                dnd::DRAG_EVENT_MASK,
+                ffi::GrabModeAsync,
+                ffi::GrabModeAsync,
+                0,
+                cursor,
+                ffi::CurrentTime,
+            );
+            if cursor != 0 {
+                (self.xconn.xlib.XFreeCursor)(self.xconn.display, cursor);
+            }
+            result
+        };
+        if result != ffi::GrabSuccess {
+            let _ = self.selections.clear(selection);
+            return Err(ExternalError::Os(os_error!(OsError::XMisc(
+                "Failed to grab the pointer for dragging"
+            ))));
+        }
+
+        *self.drag.lock().unwrap() = Some(Drag::new(self.xwindow, types));
+        self.xconn
+            .flush_requests()
+            .map_err(|err| ExternalError::Os(os_error!(OsError::XError(err))))
+    }
+
+    #[inline]
+    pub fn accept_drop(&self, mime_type: Option<&str>) {
+        *self.drop_choice.lock() = match mime_type {
+            Some(mime_type) => DropChoice::Accept(mime_type.to_owned()),
+            None => DropChoice::Reject,
+        };
+    }
+
+    #[inline]
+    pub(super) fn set_drop_choice(&self, choice: DropChoice) {
+        *self.drop_choice.lock() = choice;
+    }
+
+    #[inline]
+    pub(super) fn drop_choice(&self) -> DropChoice {
+        self.drop_choice.lock().clone()
     }
 }
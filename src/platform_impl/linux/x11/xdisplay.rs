@@ -16,6 +16,10 @@ pub struct XConnection {
     pub xinput2: ffi::XInput2,
     pub xlib_xcb: ffi::Xlib_xcb,
     pub xrender: ffi::Xrender,
+    /// Exposes XFixes functions, if the library is available
+    pub xfixes: Option<ffi::Xfixes>,
+    /// Exposes XPresent functions, if the library is available
+    pub xpresent: Option<ffi::Xpresent>,
     pub display: *mut ffi::Display,
     pub x11_fd: c_int,
     pub latest_error: Mutex<Option<XError>>,
@@ -37,6 +41,8 @@ impl XConnection {
         let xinput2 = ffi::XInput2::open()?;
         let xlib_xcb = ffi::Xlib_xcb::open()?;
         let xrender = ffi::Xrender::open()?;
+        let xfixes = ffi::Xfixes::open().ok();
+        let xpresent = ffi::Xpresent::open().ok();
 
         unsafe { (xlib.XInitThreads)() };
         unsafe { (xlib.XSetErrorHandler)(error_handler) };
@@ -61,6 +67,8 @@ impl XConnection {
             xinput2,
             xlib_xcb,
             xrender,
+            xfixes,
+            xpresent,
             display,
             x11_fd: fd,
             latest_error: Mutex::new(None),
@@ -0,0 +1,159 @@
+#![allow(non_camel_case_types)]
+
+use std::{
+    mem,
+    os::raw::{c_char, c_int, c_void},
+};
+
+use super::super::dlopen;
+
+pub enum xkb_context {}
+pub enum xkb_keymap {}
+pub enum xkb_state {}
+pub enum xkb_compose_table {}
+pub enum xkb_compose_state {}
+
+/// An opaque `xcb_connection_t`.
+pub type xcb_connection_t = c_void;
+
+pub type xkb_keycode_t = u32;
+pub type xkb_keysym_t = u32;
+pub type xkb_mod_mask_t = u32;
+pub type xkb_layout_index_t = u32;
+pub type xkb_level_index_t = u32;
+
+pub const XKB_CONTEXT_NO_FLAGS: c_int = 0;
+pub const XKB_KEYMAP_COMPILE_NO_FLAGS: c_int = 0;
+pub const XKB_KEYMAP_FORMAT_TEXT_V1: c_int = 1;
+
+pub const XKB_LAYOUT_INVALID: xkb_layout_index_t = 0xffff_ffff;
+
+pub const XKB_STATE_MODS_EFFECTIVE: c_int = 1 << 3;
+pub const XKB_STATE_LAYOUT_EFFECTIVE: c_int = 1 << 7;
+
+pub const XKB_COMPOSE_COMPILE_NO_FLAGS: c_int = 0;
+pub const XKB_COMPOSE_STATE_NO_FLAGS: c_int = 0;
+
+pub const XKB_COMPOSE_FEED_ACCEPTED: c_int = 1;
+
+pub const XKB_COMPOSE_COMPOSING: c_int = 1;
+pub const XKB_COMPOSE_COMPOSED: c_int = 2;
+pub const XKB_COMPOSE_CANCELLED: c_int = 3;
+
+pub const XKB_X11_MIN_MAJOR_XKB_VERSION: u16 = 1;
+pub const XKB_X11_MIN_MINOR_XKB_VERSION: u16 = 0;
+pub const XKB_X11_SETUP_XKB_EXTENSION_NO_FLAGS: c_int = 0;
+
+unsafe fn open_library(names: &[&str]) -> Option<*mut c_void> {
+    names.iter().find_map(|name| {
+        let handle = dlopen::dlopen(name.as_ptr() as *const c_char, dlopen::RTLD_LAZY);
+        if handle.is_null() {
+            None
+        } else {
+            Some(handle)
+        }
+    })
+}
+
+macro_rules! library {
+    ($name:ident, $libs:expr, $(fn $fn_name:ident($($arg:ty),*) $(-> $ret:ty)?;)*) => {
+        pub struct $name {
+            $(pub $fn_name: unsafe extern "C" fn($($arg),*) $(-> $ret)?,)*
+        }
+
+        impl $name {
+            /// Loads the library, or returns `None` if it or one of its functions is missing.
+            pub fn open() -> Option<$name> {
+                unsafe {
+                    let handle = open_library($libs)?;
+                    Some($name {
+                        $($fn_name: {
+                            let symbol = dlopen::dlsym(
+                                handle,
+                                concat!(stringify!($fn_name), "\0").as_ptr() as *const c_char,
+                            );
+                            if symbol.is_null() {
+                                return None;
+                            }
+                            let function: unsafe extern "C" fn($($arg),*) $(-> $ret)? =
+                                mem::transmute(symbol);
+                            function
+                        },)*
+                    })
+                }
+            }
+        }
+    };
+}
+
+library!(
+    XkbCommon,
+    &["libxkbcommon.so.0\0", "libxkbcommon.so\0"],
+    fn xkb_context_new(c_int) -> *mut xkb_context;
+    fn xkb_context_unref(*mut xkb_context);
+    fn xkb_keymap_new_from_string(*mut xkb_context, *const c_char, c_int, c_int) -> *mut xkb_keymap;
+    fn xkb_keymap_unref(*mut xkb_keymap);
+    fn xkb_keymap_layout_get_name(*mut xkb_keymap, xkb_layout_index_t) -> *const c_char;
+    fn xkb_keymap_key_repeats(*mut xkb_keymap, xkb_keycode_t) -> c_int;
+    fn xkb_keymap_key_get_syms_by_level(
+        *mut xkb_keymap,
+        xkb_keycode_t,
+        xkb_layout_index_t,
+        xkb_level_index_t,
+        *mut *const xkb_keysym_t
+    ) -> c_int;
+    fn xkb_keymap_min_keycode(*mut xkb_keymap) -> xkb_keycode_t;
+    fn xkb_keymap_max_keycode(*mut xkb_keymap) -> xkb_keycode_t;
+    fn xkb_state_new(*mut xkb_keymap) -> *mut xkb_state;
+    fn xkb_state_unref(*mut xkb_state);
+    fn xkb_state_update_mask(
+        *mut xkb_state,
+        xkb_mod_mask_t,
+        xkb_mod_mask_t,
+        xkb_mod_mask_t,
+        xkb_layout_index_t,
+        xkb_layout_index_t,
+        xkb_layout_index_t
+    ) -> c_int;
+    fn xkb_state_key_get_one_sym(*mut xkb_state, xkb_keycode_t) -> xkb_keysym_t;
+    fn xkb_state_key_get_utf8(*mut xkb_state, xkb_keycode_t, *mut c_char, usize) -> c_int;
+    fn xkb_state_key_get_layout(*mut xkb_state, xkb_keycode_t) -> xkb_layout_index_t;
+    fn xkb_state_serialize_layout(*mut xkb_state, c_int) -> xkb_layout_index_t;
+    fn xkb_state_mod_name_is_active(*mut xkb_state, *const c_char, c_int) -> c_int;
+    fn xkb_keysym_to_utf32(xkb_keysym_t) -> u32;
+    fn xkb_compose_table_new_from_locale(
+        *mut xkb_context,
+        *const c_char,
+        c_int
+    ) -> *mut xkb_compose_table;
+    fn xkb_compose_table_unref(*mut xkb_compose_table);
+    fn xkb_compose_state_new(*mut xkb_compose_table, c_int) -> *mut xkb_compose_state;
+    fn xkb_compose_state_unref(*mut xkb_compose_state);
+    fn xkb_compose_state_feed(*mut xkb_compose_state, xkb_keysym_t) -> c_int;
+    fn xkb_compose_state_reset(*mut xkb_compose_state);
+    fn xkb_compose_state_get_status(*mut xkb_compose_state) -> c_int;
+    fn xkb_compose_state_get_utf8(*mut xkb_compose_state, *mut c_char, usize) -> c_int;
+);
+
+library!(
+    XkbCommonX11,
+    &["libxkbcommon-x11.so.0\0", "libxkbcommon-x11.so\0"],
+    fn xkb_x11_setup_xkb_extension(
+        *mut xcb_connection_t,
+        u16,
+        u16,
+        c_int,
+        *mut u16,
+        *mut u16,
+        *mut u8,
+        *mut u8
+    ) -> c_int;
+    fn xkb_x11_get_core_keyboard_device_id(*mut xcb_connection_t) -> i32;
+    fn xkb_x11_keymap_new_from_device(
+        *mut xkb_context,
+        *mut xcb_connection_t,
+        i32,
+        c_int
+    ) -> *mut xkb_keymap;
+    fn xkb_x11_state_new_from_device(*mut xkb_keymap, *mut xcb_connection_t, i32) -> *mut xkb_state;
+);
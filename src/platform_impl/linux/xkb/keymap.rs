@@ -0,0 +1,605 @@
+use smithay_client_toolkit::keyboard::keysyms;
+
+use crate::{
+    event::{ModifierSides, VirtualKeyCode},
+    keyboard::{Key, KeyCode, KeyLocation, NamedKey},
+};
+
+/// Maps a Linux evdev key code to the physical key.
+///
+/// X11 key codes are the evdev key codes plus 8.
+pub fn raw_keycode_to_keycode(rawkey: u32) -> KeyCode {
+    match rawkey {
+        1 => KeyCode::Escape,
+        2 => KeyCode::Digit1,
+        3 => KeyCode::Digit2,
+        4 => KeyCode::Digit3,
+        5 => KeyCode::Digit4,
+        6 => KeyCode::Digit5,
+        7 => KeyCode::Digit6,
+        8 => KeyCode::Digit7,
+        9 => KeyCode::Digit8,
+        10 => KeyCode::Digit9,
+        11 => KeyCode::Digit0,
+        12 => KeyCode::Minus,
+        13 => KeyCode::Equal,
+        14 => KeyCode::Backspace,
+        15 => KeyCode::Tab,
+        16 => KeyCode::KeyQ,
+        17 => KeyCode::KeyW,
+        18 => KeyCode::KeyE,
+        19 => KeyCode::KeyR,
+        20 => KeyCode::KeyT,
+        21 => KeyCode::KeyY,
+        22 => KeyCode::KeyU,
+        23 => KeyCode::KeyI,
+        24 => KeyCode::KeyO,
+        25 => KeyCode::KeyP,
+        26 => KeyCode::BracketLeft,
+        27 => KeyCode::BracketRight,
+        28 => KeyCode::Enter,
+        29 => KeyCode::ControlLeft,
+        30 => KeyCode::KeyA,
+        31 => KeyCode::KeyS,
+        32 => KeyCode::KeyD,
+        33 => KeyCode::KeyF,
+        34 => KeyCode::KeyG,
+        35 => KeyCode::KeyH,
+        36 => KeyCode::KeyJ,
+        37 => KeyCode::KeyK,
+        38 => KeyCode::KeyL,
+        39 => KeyCode::Semicolon,
+        40 => KeyCode::Quote,
+        41 => KeyCode::Backquote,
+        42 => KeyCode::ShiftLeft,
+        43 => KeyCode::Backslash,
+        44 => KeyCode::KeyZ,
+        45 => KeyCode::KeyX,
+        46 => KeyCode::KeyC,
+        47 => KeyCode::KeyV,
+        48 => KeyCode::KeyB,
+        49 => KeyCode::KeyN,
+        50 => KeyCode::KeyM,
+        51 => KeyCode::Comma,
+        52 => KeyCode::Period,
+        53 => KeyCode::Slash,
+        54 => KeyCode::ShiftRight,
+        55 => KeyCode::NumpadMultiply,
+        56 => KeyCode::AltLeft,
+        57 => KeyCode::Space,
+        58 => KeyCode::CapsLock,
+        59 => KeyCode::F1,
+        60 => KeyCode::F2,
+        61 => KeyCode::F3,
+        62 => KeyCode::F4,
+        63 => KeyCode::F5,
+        64 => KeyCode::F6,
+        65 => KeyCode::F7,
+        66 => KeyCode::F8,
+        67 => KeyCode::F9,
+        68 => KeyCode::F10,
+        69 => KeyCode::NumLock,
+        70 => KeyCode::ScrollLock,
+        71 => KeyCode::Numpad7,
+        72 => KeyCode::Numpad8,
+        73 => KeyCode::Numpad9,
+        74 => KeyCode::NumpadSubtract,
+        75 => KeyCode::Numpad4,
+        76 => KeyCode::Numpad5,
+        77 => KeyCode::Numpad6,
+        78 => KeyCode::NumpadAdd,
+        79 => KeyCode::Numpad1,
+        80 => KeyCode::Numpad2,
+        81 => KeyCode::Numpad3,
+        82 => KeyCode::Numpad0,
+        83 => KeyCode::NumpadDecimal,
+        // KEY_ZENKAKUHANKAKU
+        85 => KeyCode::Backquote,
+        86 => KeyCode::IntlBackslash,
+        87 => KeyCode::F11,
+        88 => KeyCode::F12,
+        89 => KeyCode::IntlRo,
+        90 => KeyCode::Lang3,
+        91 => KeyCode::Lang4,
+        92 => KeyCode::Convert,
+        93 => KeyCode::KanaMode,
+        94 => KeyCode::NonConvert,
+        95 => KeyCode::NumpadComma,
+        96 => KeyCode::NumpadEnter,
+        97 => KeyCode::ControlRight,
+        98 => KeyCode::NumpadDivide,
+        99 => KeyCode::PrintScreen,
+        100 => KeyCode::AltRight,
+        102 => KeyCode::Home,
+        103 => KeyCode::ArrowUp,
+        104 => KeyCode::PageUp,
+        105 => KeyCode::ArrowLeft,
+        106 => KeyCode::ArrowRight,
+        107 => KeyCode::End,
+        108 => KeyCode::ArrowDown,
+        109 => KeyCode::PageDown,
+        110 => KeyCode::Insert,
+        111 => KeyCode::Delete,
+        113 => KeyCode::AudioVolumeMute,
+        114 => KeyCode::AudioVolumeDown,
+        115 => KeyCode::AudioVolumeUp,
+        116 => KeyCode::Power,
+        117 => KeyCode::NumpadEqual,
+        119 => KeyCode::Pause,
+        121 => KeyCode::NumpadComma,
+        122 => KeyCode::Lang1,
+        123 => KeyCode::Lang2,
+        124 => KeyCode::IntlYen,
+        125 => KeyCode::SuperLeft,
+        126 => KeyCode::SuperRight,
+        127 => KeyCode::ContextMenu,
+        128 => KeyCode::BrowserStop,
+        129 => KeyCode::Again,
+        130 => KeyCode::Props,
+        131 => KeyCode::Undo,
+        133 => KeyCode::Copy,
+        134 => KeyCode::Open,
+        135 => KeyCode::Paste,
+        136 => KeyCode::Find,
+        137 => KeyCode::Cut,
+        138 => KeyCode::Help,
+        140 => KeyCode::LaunchApp2,
+        142 => KeyCode::Sleep,
+        143 => KeyCode::WakeUp,
+        155 => KeyCode::LaunchMail,
+        156 => KeyCode::BrowserFavorites,
+        158 => KeyCode::BrowserBack,
+        159 => KeyCode::BrowserForward,
+        161 => KeyCode::Eject,
+        163 => KeyCode::MediaTrackNext,
+        164 => KeyCode::MediaPlayPause,
+        165 => KeyCode::MediaTrackPrevious,
+        166 => KeyCode::MediaStop,
+        172 => KeyCode::BrowserHome,
+        173 => KeyCode::BrowserRefresh,
+        183 => KeyCode::F13,
+        184 => KeyCode::F14,
+        185 => KeyCode::F15,
+        186 => KeyCode::F16,
+        187 => KeyCode::F17,
+        188 => KeyCode::F18,
+        189 => KeyCode::F19,
+        190 => KeyCode::F20,
+        191 => KeyCode::F21,
+        192 => KeyCode::F22,
+        193 => KeyCode::F23,
+        194 => KeyCode::F24,
+        217 => KeyCode::BrowserSearch,
+        226 => KeyCode::MediaSelect,
+        _ => KeyCode::Unidentified,
+    }
+}
+
+/// Maps a physical key to its Linux evdev key code, the reverse of `raw_keycode_to_keycode`.
+pub fn keycode_to_raw_keycode(key: KeyCode) -> Option<u32> {
+    if key == KeyCode::Unidentified {
+        return None;
+    }
+    (0..256).find(|&rawkey| raw_keycode_to_keycode(rawkey) == key)
+}
+
+/// Maps a keysym to the logical key it stands for.
+pub fn keysym_to_key(keysym: u32) -> Key {
+    if let Some(named) = keysym_to_named_key(keysym) {
+        return Key::Named(named);
+    }
+    if let Some(accent) = dead_keysym_to_accent(keysym) {
+        return Key::Dead(accent);
+    }
+    match super::keysym_to_char(keysym) {
+        Some(chr) if !chr.is_control() => Key::Character(chr.to_string()),
+        _ => Key::Unidentified,
+    }
+}
+
+fn keysym_to_named_key(keysym: u32) -> Option<NamedKey> {
+    Some(match keysym {
+        // modifiers
+        keysyms::XKB_KEY_Alt_L | keysyms::XKB_KEY_Alt_R => NamedKey::Alt,
+        keysyms::XKB_KEY_Meta_L | keysyms::XKB_KEY_Meta_R => NamedKey::Alt,
+        keysyms::XKB_KEY_ISO_Level3_Shift | keysyms::XKB_KEY_Mode_switch => NamedKey::AltGraph,
+        keysyms::XKB_KEY_Caps_Lock => NamedKey::CapsLock,
+        keysyms::XKB_KEY_Control_L | keysyms::XKB_KEY_Control_R => NamedKey::Control,
+        keysyms::XKB_KEY_Num_Lock => NamedKey::NumLock,
+        keysyms::XKB_KEY_Scroll_Lock => NamedKey::ScrollLock,
+        keysyms::XKB_KEY_Shift_L | keysyms::XKB_KEY_Shift_R => NamedKey::Shift,
+        keysyms::XKB_KEY_Super_L | keysyms::XKB_KEY_Super_R => NamedKey::Super,
+        // whitespace
+        keysyms::XKB_KEY_Return | keysyms::XKB_KEY_KP_Enter | keysyms::XKB_KEY_ISO_Enter => {
+            NamedKey::Enter
+        }
+        keysyms::XKB_KEY_Tab | keysyms::XKB_KEY_KP_Tab | keysyms::XKB_KEY_ISO_Left_Tab => {
+            NamedKey::Tab
+        }
+        // navigation
+        keysyms::XKB_KEY_Down | keysyms::XKB_KEY_KP_Down => NamedKey::ArrowDown,
+        keysyms::XKB_KEY_Left | keysyms::XKB_KEY_KP_Left => NamedKey::ArrowLeft,
+        keysyms::XKB_KEY_Right | keysyms::XKB_KEY_KP_Right => NamedKey::ArrowRight,
+        keysyms::XKB_KEY_Up | keysyms::XKB_KEY_KP_Up => NamedKey::ArrowUp,
+        keysyms::XKB_KEY_End | keysyms::XKB_KEY_KP_End => NamedKey::End,
+        keysyms::XKB_KEY_Home | keysyms::XKB_KEY_KP_Home => NamedKey::Home,
+        keysyms::XKB_KEY_Page_Down | keysyms::XKB_KEY_KP_Page_Down => NamedKey::PageDown,
+        keysyms::XKB_KEY_Page_Up | keysyms::XKB_KEY_KP_Page_Up => NamedKey::PageUp,
+        // editing
+        keysyms::XKB_KEY_BackSpace => NamedKey::Backspace,
+        keysyms::XKB_KEY_Clear | keysyms::XKB_KEY_KP_Begin => NamedKey::Clear,
+        keysyms::XKB_KEY_XF86Copy => NamedKey::Copy,
+        keysyms::XKB_KEY_XF86Cut => NamedKey::Cut,
+        keysyms::XKB_KEY_Delete | keysyms::XKB_KEY_KP_Delete => NamedKey::Delete,
+        keysyms::XKB_KEY_Insert | keysyms::XKB_KEY_KP_Insert => NamedKey::Insert,
+        keysyms::XKB_KEY_XF86Paste => NamedKey::Paste,
+        keysyms::XKB_KEY_Redo => NamedKey::Redo,
+        keysyms::XKB_KEY_Undo => NamedKey::Undo,
+        // user interface
+        keysyms::XKB_KEY_Cancel => NamedKey::Cancel,
+        keysyms::XKB_KEY_Menu => NamedKey::ContextMenu,
+        keysyms::XKB_KEY_Escape => NamedKey::Escape,
+        keysyms::XKB_KEY_Execute => NamedKey::Execute,
+        keysyms::XKB_KEY_Find => NamedKey::Find,
+        keysyms::XKB_KEY_Help => NamedKey::Help,
+        keysyms::XKB_KEY_Pause | keysyms::XKB_KEY_Break => NamedKey::Pause,
+        keysyms::XKB_KEY_Select => NamedKey::Select,
+        keysyms::XKB_KEY_Print | keysyms::XKB_KEY_Sys_Req => NamedKey::PrintScreen,
+        // input methods
+        keysyms::XKB_KEY_Multi_key => NamedKey::Compose,
+        keysyms::XKB_KEY_Henkan => NamedKey::Convert,
+        keysyms::XKB_KEY_Muhenkan => NamedKey::NonConvert,
+        keysyms::XKB_KEY_MultipleCandidate => NamedKey::AllCandidates,
+        keysyms::XKB_KEY_PreviousCandidate => NamedKey::PreviousCandidate,
+        keysyms::XKB_KEY_Eisu_toggle => NamedKey::Eisu,
+        keysyms::XKB_KEY_Hankaku => NamedKey::Hankaku,
+        keysyms::XKB_KEY_Hiragana => NamedKey::Hiragana,
+        keysyms::XKB_KEY_Hiragana_Katakana => NamedKey::HiraganaKatakana,
+        keysyms::XKB_KEY_Kana_Lock => NamedKey::KanaMode,
+        keysyms::XKB_KEY_Kanji => NamedKey::KanjiMode,
+        keysyms::XKB_KEY_Katakana => NamedKey::Katakana,
+        keysyms::XKB_KEY_Romaji => NamedKey::Romaji,
+        keysyms::XKB_KEY_Zenkaku => NamedKey::Zenkaku,
+        keysyms::XKB_KEY_Zenkaku_Hankaku => NamedKey::ZenkakuHankaku,
+        keysyms::XKB_KEY_Hangul => NamedKey::HangulMode,
+        keysyms::XKB_KEY_Hangul_Hanja => NamedKey::HanjaMode,
+        // multimedia
+        keysyms::XKB_KEY_XF86AudioLowerVolume => NamedKey::AudioVolumeDown,
+        keysyms::XKB_KEY_XF86AudioMute => NamedKey::AudioVolumeMute,
+        keysyms::XKB_KEY_XF86AudioRaiseVolume => NamedKey::AudioVolumeUp,
+        keysyms::XKB_KEY_XF86AudioPause => NamedKey::MediaPause,
+        keysyms::XKB_KEY_XF86AudioPlay => NamedKey::MediaPlayPause,
+        keysyms::XKB_KEY_XF86AudioRecord => NamedKey::MediaRecord,
+        keysyms::XKB_KEY_XF86AudioStop => NamedKey::MediaStop,
+        keysyms::XKB_KEY_XF86AudioNext => NamedKey::MediaTrackNext,
+        keysyms::XKB_KEY_XF86AudioPrev => NamedKey::MediaTrackPrevious,
+        // browser and applications
+        keysyms::XKB_KEY_XF86Back => NamedKey::BrowserBack,
+        keysyms::XKB_KEY_XF86Favorites => NamedKey::BrowserFavorites,
+        keysyms::XKB_KEY_XF86Forward => NamedKey::BrowserForward,
+        keysyms::XKB_KEY_XF86HomePage => NamedKey::BrowserHome,
+        keysyms::XKB_KEY_XF86Refresh => NamedKey::BrowserRefresh,
+        keysyms::XKB_KEY_XF86Search => NamedKey::BrowserSearch,
+        keysyms::XKB_KEY_XF86Stop => NamedKey::BrowserStop,
+        keysyms::XKB_KEY_XF86MyComputer => NamedKey::LaunchApplication1,
+        keysyms::XKB_KEY_XF86Calculator => NamedKey::LaunchApplication2,
+        keysyms::XKB_KEY_XF86Mail => NamedKey::LaunchMail,
+        // system
+        keysyms::XKB_KEY_XF86Eject => NamedKey::Eject,
+        keysyms::XKB_KEY_XF86PowerOff => NamedKey::Power,
+        keysyms::XKB_KEY_XF86Standby | keysyms::XKB_KEY_XF86Sleep => NamedKey::Standby,
+        keysyms::XKB_KEY_XF86WakeUp => NamedKey::WakeUp,
+        // function keys
+        keysyms::XKB_KEY_F1 | keysyms::XKB_KEY_KP_F1 => NamedKey::F1,
+        keysyms::XKB_KEY_F2 | keysyms::XKB_KEY_KP_F2 => NamedKey::F2,
+        keysyms::XKB_KEY_F3 | keysyms::XKB_KEY_KP_F3 => NamedKey::F3,
+        keysyms::XKB_KEY_F4 | keysyms::XKB_KEY_KP_F4 => NamedKey::F4,
+        keysyms::XKB_KEY_F5 => NamedKey::F5,
+        keysyms::XKB_KEY_F6 => NamedKey::F6,
+        keysyms::XKB_KEY_F7 => NamedKey::F7,
+        keysyms::XKB_KEY_F8 => NamedKey::F8,
+        keysyms::XKB_KEY_F9 => NamedKey::F9,
+        keysyms::XKB_KEY_F10 => NamedKey::F10,
+        keysyms::XKB_KEY_F11 => NamedKey::F11,
+        keysyms::XKB_KEY_F12 => NamedKey::F12,
+        keysyms::XKB_KEY_F13 => NamedKey::F13,
+        keysyms::XKB_KEY_F14 => NamedKey::F14,
+        keysyms::XKB_KEY_F15 => NamedKey::F15,
+        keysyms::XKB_KEY_F16 => NamedKey::F16,
+        keysyms::XKB_KEY_F17 => NamedKey::F17,
+        keysyms::XKB_KEY_F18 => NamedKey::F18,
+        keysyms::XKB_KEY_F19 => NamedKey::F19,
+        keysyms::XKB_KEY_F20 => NamedKey::F20,
+        keysyms::XKB_KEY_F21 => NamedKey::F21,
+        keysyms::XKB_KEY_F22 => NamedKey::F22,
+        keysyms::XKB_KEY_F23 => NamedKey::F23,
+        keysyms::XKB_KEY_F24 => NamedKey::F24,
+        _ => return None,
+    })
+}
+
+// Returns `Some` for dead keysyms, with the spacing form of their accent if it has one.
+fn dead_keysym_to_accent(keysym: u32) -> Option<Option<char>> {
+    let accent = match keysym {
+        keysyms::XKB_KEY_dead_grave => '`',
+        keysyms::XKB_KEY_dead_acute => '´',
+        keysyms::XKB_KEY_dead_circumflex => '^',
+        keysyms::XKB_KEY_dead_tilde => '~',
+        keysyms::XKB_KEY_dead_macron => '¯',
+        keysyms::XKB_KEY_dead_breve => '˘',
+        keysyms::XKB_KEY_dead_abovedot => '˙',
+        keysyms::XKB_KEY_dead_diaeresis => '¨',
+        keysyms::XKB_KEY_dead_abovering => '˚',
+        keysyms::XKB_KEY_dead_doubleacute => '˝',
+        keysyms::XKB_KEY_dead_caron => 'ˇ',
+        keysyms::XKB_KEY_dead_cedilla => '¸',
+        keysyms::XKB_KEY_dead_ogonek => '˛',
+        keysyms::XKB_KEY_dead_iota => 'ͺ',
+        keysyms::XKB_KEY_dead_voiced_sound => '゛',
+        keysyms::XKB_KEY_dead_semivoiced_sound => '゜',
+        keysyms::XKB_KEY_dead_currency => '¤',
+        _ => {
+            let dead_keysyms =
+                keysyms::XKB_KEY_dead_grave..=keysyms::XKB_KEY_dead_longsolidusoverlay;
+            let is_dead = dead_keysyms.contains(&keysym);
+            return if is_dead { Some(None) } else { None };
+        }
+    };
+    Some(Some(accent))
+}
+
+/// Returns the location of the key producing a keysym.
+pub fn keysym_location(keysym: u32) -> KeyLocation {
+    match keysym {
+        keysyms::XKB_KEY_Shift_L
+        | keysyms::XKB_KEY_Control_L
+        | keysyms::XKB_KEY_Meta_L
+        | keysyms::XKB_KEY_Alt_L
+        | keysyms::XKB_KEY_Super_L
+        | keysyms::XKB_KEY_Hyper_L => KeyLocation::Left,
+        keysyms::XKB_KEY_Shift_R
+        | keysyms::XKB_KEY_Control_R
+        | keysyms::XKB_KEY_Meta_R
+        | keysyms::XKB_KEY_Alt_R
+        | keysyms::XKB_KEY_Super_R
+        | keysyms::XKB_KEY_Hyper_R => KeyLocation::Right,
+        keysyms::XKB_KEY_KP_Space..=keysyms::XKB_KEY_KP_Equal => KeyLocation::Numpad,
+        _ => KeyLocation::Standard,
+    }
+}
+
+/// Returns the label of the key producing a keysym, as it would be printed on the keycap.
+///
+/// Characters are uppercased, dead keys show their accent and named keys their `NamedKey::label`.
+pub fn keysym_label(keysym: u32) -> Option<String> {
+    match keysym_to_key(keysym) {
+        Key::Named(named) => Some(named.label().to_owned()),
+        Key::Character(text) => Some(text.to_uppercase()),
+        Key::Dead(accent) => accent.map(|accent| accent.to_string()),
+        Key::Unidentified => None,
+    }
+}
+
+/// Records the press or release of a modifier key in the sides of the keyboard where modifier
+/// keys are held down, and returns whether the sides changed.
+pub fn update_modifier_sides(sides: &mut ModifierSides, keysym: u32, pressed: bool) -> bool {
+    let side = match keysym {
+        keysyms::XKB_KEY_Shift_L => &mut sides.left_shift,
+        keysyms::XKB_KEY_Shift_R => &mut sides.right_shift,
+        keysyms::XKB_KEY_Control_L => &mut sides.left_ctrl,
+        keysyms::XKB_KEY_Control_R => &mut sides.right_ctrl,
+        keysyms::XKB_KEY_Alt_L => &mut sides.left_alt,
+        keysyms::XKB_KEY_Alt_R => &mut sides.right_alt,
+        keysyms::XKB_KEY_Super_L => &mut sides.left_logo,
+        keysyms::XKB_KEY_Super_R => &mut sides.right_logo,
+        _ => return false,
+    };
+    let changed = *side != pressed;
+    *side = pressed;
+    changed
+}
+
+/// Derives the `VirtualKeyCode` of `KeyboardInput` from the physical and logical keys.
+pub fn virtual_keycode(
+    physical_key: KeyCode,
+    logical_key: &Key,
+    location: KeyLocation,
+) -> Option<VirtualKeyCode> {
+    // The digits keep their code on layouts where they need `Shift`, such as AZERTY.
+    match physical_key {
+        KeyCode::Digit1 => return Some(VirtualKeyCode::Key1),
+        KeyCode::Digit2 => return Some(VirtualKeyCode::Key2),
+        KeyCode::Digit3 => return Some(VirtualKeyCode::Key3),
+        KeyCode::Digit4 => return Some(VirtualKeyCode::Key4),
+        KeyCode::Digit5 => return Some(VirtualKeyCode::Key5),
+        KeyCode::Digit6 => return Some(VirtualKeyCode::Key6),
+        KeyCode::Digit7 => return Some(VirtualKeyCode::Key7),
+        KeyCode::Digit8 => return Some(VirtualKeyCode::Key8),
+        KeyCode::Digit9 => return Some(VirtualKeyCode::Key9),
+        KeyCode::Digit0 => return Some(VirtualKeyCode::Key0),
+        _ => (),
+    }
+    match *logical_key {
+        Key::Named(named) => named_key_to_virtual_keycode(named, location),
+        Key::Character(ref text) => {
+            let mut chars = text.chars();
+            match (chars.next(), chars.next()) {
+                (Some(chr), None) => char_to_virtual_keycode(chr, location),
+                _ => None,
+            }
+        }
+        Key::Dead(Some('^')) => Some(VirtualKeyCode::Caret),
+        Key::Dead(Some('`')) => Some(VirtualKeyCode::Grave),
+        Key::Dead(_) | Key::Unidentified => None,
+    }
+}
+
+fn named_key_to_virtual_keycode(named: NamedKey, location: KeyLocation) -> Option<VirtualKeyCode> {
+    Some(match (named, location) {
+        (NamedKey::Alt, KeyLocation::Right) => VirtualKeyCode::RAlt,
+        (NamedKey::Alt, _) => VirtualKeyCode::LAlt,
+        (NamedKey::Control, KeyLocation::Right) => VirtualKeyCode::RControl,
+        (NamedKey::Control, _) => VirtualKeyCode::LControl,
+        (NamedKey::Shift, KeyLocation::Right) => VirtualKeyCode::RShift,
+        (NamedKey::Shift, _) => VirtualKeyCode::LShift,
+        (NamedKey::Super, KeyLocation::Right) => VirtualKeyCode::RWin,
+        (NamedKey::Super, _) => VirtualKeyCode::LWin,
+        (NamedKey::CapsLock, _) => VirtualKeyCode::Capital,
+        (NamedKey::NumLock, _) => VirtualKeyCode::Numlock,
+        (NamedKey::ScrollLock, _) => VirtualKeyCode::Scroll,
+        (NamedKey::Enter, KeyLocation::Numpad) => VirtualKeyCode::NumpadEnter,
+        (NamedKey::Enter, _) => VirtualKeyCode::Return,
+        (NamedKey::Tab, _) => VirtualKeyCode::Tab,
+        (NamedKey::ArrowDown, _) => VirtualKeyCode::Down,
+        (NamedKey::ArrowLeft, _) => VirtualKeyCode::Left,
+        (NamedKey::ArrowRight, _) => VirtualKeyCode::Right,
+        (NamedKey::ArrowUp, _) => VirtualKeyCode::Up,
+        (NamedKey::End, _) => VirtualKeyCode::End,
+        (NamedKey::Home, _) => VirtualKeyCode::Home,
+        (NamedKey::PageDown, _) => VirtualKeyCode::PageDown,
+        (NamedKey::PageUp, _) => VirtualKeyCode::PageUp,
+        (NamedKey::Backspace, _) => VirtualKeyCode::Back,
+        (NamedKey::Copy, _) => VirtualKeyCode::Copy,
+        (NamedKey::Cut, _) => VirtualKeyCode::Cut,
+        (NamedKey::Delete, _) => VirtualKeyCode::Delete,
+        (NamedKey::Insert, _) => VirtualKeyCode::Insert,
+        (NamedKey::Paste, _) => VirtualKeyCode::Paste,
+        (NamedKey::ContextMenu, _) => VirtualKeyCode::Apps,
+        (NamedKey::Escape, _) => VirtualKeyCode::Escape,
+        (NamedKey::Pause, _) => VirtualKeyCode::Pause,
+        (NamedKey::PrintScreen, _) => VirtualKeyCode::Snapshot,
+        (NamedKey::Compose, _) => VirtualKeyCode::Compose,
+        (NamedKey::Convert, _) => VirtualKeyCode::Convert,
+        (NamedKey::NonConvert, _) => VirtualKeyCode::NoConvert,
+        (NamedKey::KanaMode, _) => VirtualKeyCode::Kana,
+        (NamedKey::KanjiMode, _) => VirtualKeyCode::Kanji,
+        (NamedKey::AudioVolumeDown, _) => VirtualKeyCode::VolumeDown,
+        (NamedKey::AudioVolumeMute, _) => VirtualKeyCode::Mute,
+        (NamedKey::AudioVolumeUp, _) => VirtualKeyCode::VolumeUp,
+        (NamedKey::MediaPlayPause, _) => VirtualKeyCode::PlayPause,
+        (NamedKey::MediaStop, _) => VirtualKeyCode::MediaStop,
+        (NamedKey::MediaTrackNext, _) => VirtualKeyCode::NextTrack,
+        (NamedKey::MediaTrackPrevious, _) => VirtualKeyCode::PrevTrack,
+        (NamedKey::BrowserBack, _) => VirtualKeyCode::NavigateBackward,
+        (NamedKey::BrowserFavorites, _) => VirtualKeyCode::WebFavorites,
+        (NamedKey::BrowserForward, _) => VirtualKeyCode::NavigateForward,
+        (NamedKey::BrowserHome, _) => VirtualKeyCode::WebHome,
+        (NamedKey::BrowserRefresh, _) => VirtualKeyCode::WebRefresh,
+        (NamedKey::BrowserSearch, _) => VirtualKeyCode::WebSearch,
+        (NamedKey::BrowserStop, _) => VirtualKeyCode::WebStop,
+        (NamedKey::LaunchApplication1, _) => VirtualKeyCode::MyComputer,
+        (NamedKey::LaunchApplication2, _) => VirtualKeyCode::Calculator,
+        (NamedKey::LaunchMail, _) => VirtualKeyCode::Mail,
+        (NamedKey::Power, _) => VirtualKeyCode::Power,
+        (NamedKey::Standby, _) => VirtualKeyCode::Sleep,
+        (NamedKey::WakeUp, _) => VirtualKeyCode::Wake,
+        (NamedKey::F1, _) => VirtualKeyCode::F1,
+        (NamedKey::F2, _) => VirtualKeyCode::F2,
+        (NamedKey::F3, _) => VirtualKeyCode::F3,
+        (NamedKey::F4, _) => VirtualKeyCode::F4,
+        (NamedKey::F5, _) => VirtualKeyCode::F5,
+        (NamedKey::F6, _) => VirtualKeyCode::F6,
+        (NamedKey::F7, _) => VirtualKeyCode::F7,
+        (NamedKey::F8, _) => VirtualKeyCode::F8,
+        (NamedKey::F9, _) => VirtualKeyCode::F9,
+        (NamedKey::F10, _) => VirtualKeyCode::F10,
+        (NamedKey::F11, _) => VirtualKeyCode::F11,
+        (NamedKey::F12, _) => VirtualKeyCode::F12,
+        (NamedKey::F13, _) => VirtualKeyCode::F13,
+        (NamedKey::F14, _) => VirtualKeyCode::F14,
+        (NamedKey::F15, _) => VirtualKeyCode::F15,
+        (NamedKey::F16, _) => VirtualKeyCode::F16,
+        (NamedKey::F17, _) => VirtualKeyCode::F17,
+        (NamedKey::F18, _) => VirtualKeyCode::F18,
+        (NamedKey::F19, _) => VirtualKeyCode::F19,
+        (NamedKey::F20, _) => VirtualKeyCode::F20,
+        (NamedKey::F21, _) => VirtualKeyCode::F21,
+        (NamedKey::F22, _) => VirtualKeyCode::F22,
+        (NamedKey::F23, _) => VirtualKeyCode::F23,
+        (NamedKey::F24, _) => VirtualKeyCode::F24,
+        _ => return None,
+    })
+}
+
+fn char_to_virtual_keycode(chr: char, location: KeyLocation) -> Option<VirtualKeyCode> {
+    if location == KeyLocation::Numpad {
+        return Some(match chr {
+            '0' => VirtualKeyCode::Numpad0,
+            '1' => VirtualKeyCode::Numpad1,
+            '2' => VirtualKeyCode::Numpad2,
+            '3' => VirtualKeyCode::Numpad3,
+            '4' => VirtualKeyCode::Numpad4,
+            '5' => VirtualKeyCode::Numpad5,
+            '6' => VirtualKeyCode::Numpad6,
+            '7' => VirtualKeyCode::Numpad7,
+            '8' => VirtualKeyCode::Numpad8,
+            '9' => VirtualKeyCode::Numpad9,
+            '+' => VirtualKeyCode::Add,
+            '-' => VirtualKeyCode::Subtract,
+            '*' => VirtualKeyCode::Multiply,
+            '/' => VirtualKeyCode::Divide,
+            '=' => VirtualKeyCode::NumpadEquals,
+            '.' => VirtualKeyCode::Decimal,
+            ',' => VirtualKeyCode::NumpadComma,
+            _ => return None,
+        });
+    }
+    Some(match chr.to_ascii_lowercase() {
+        'a' => VirtualKeyCode::A,
+        'b' => VirtualKeyCode::B,
+        'c' => VirtualKeyCode::C,
+        'd' => VirtualKeyCode::D,
+        'e' => VirtualKeyCode::E,
+        'f' => VirtualKeyCode::F,
+        'g' => VirtualKeyCode::G,
+        'h' => VirtualKeyCode::H,
+        'i' => VirtualKeyCode::I,
+        'j' => VirtualKeyCode::J,
+        'k' => VirtualKeyCode::K,
+        'l' => VirtualKeyCode::L,
+        'm' => VirtualKeyCode::M,
+        'n' => VirtualKeyCode::N,
+        'o' => VirtualKeyCode::O,
+        'p' => VirtualKeyCode::P,
+        'q' => VirtualKeyCode::Q,
+        'r' => VirtualKeyCode::R,
+        's' => VirtualKeyCode::S,
+        't' => VirtualKeyCode::T,
+        'u' => VirtualKeyCode::U,
+        'v' => VirtualKeyCode::V,
+        'w' => VirtualKeyCode::W,
+        'x' => VirtualKeyCode::X,
+        'y' => VirtualKeyCode::Y,
+        'z' => VirtualKeyCode::Z,
+        '0' => VirtualKeyCode::Key0,
+        '1' => VirtualKeyCode::Key1,
+        '2' => VirtualKeyCode::Key2,
+        '3' => VirtualKeyCode::Key3,
+        '4' => VirtualKeyCode::Key4,
+        '5' => VirtualKeyCode::Key5,
+        '6' => VirtualKeyCode::Key6,
+        '7' => VirtualKeyCode::Key7,
+        '8' => VirtualKeyCode::Key8,
+        '9' => VirtualKeyCode::Key9,
+        ' ' => VirtualKeyCode::Space,
+        '\'' => VirtualKeyCode::Apostrophe,
+        '*' => VirtualKeyCode::Multiply,
+        '+' => VirtualKeyCode::Add,
+        ',' => VirtualKeyCode::Comma,
+        '-' => VirtualKeyCode::Minus,
+        '.' => VirtualKeyCode::Period,
+        '/' => VirtualKeyCode::Slash,
+        ':' => VirtualKeyCode::Colon,
+        ';' => VirtualKeyCode::Semicolon,
+        '=' => VirtualKeyCode::Equals,
+        '@' => VirtualKeyCode::At,
+        '[' => VirtualKeyCode::LBracket,
+        '\\' => VirtualKeyCode::Backslash,
+        ']' => VirtualKeyCode::RBracket,
+        '^' => VirtualKeyCode::Caret,
+        '_' => VirtualKeyCode::Underline,
+        '`' => VirtualKeyCode::Grave,
+        '¥' => VirtualKeyCode::Yen,
+        _ => return None,
+    })
+}
@@ -0,0 +1,420 @@
+//! Keyboard layout handling shared by the X11 and Wayland backends, through libxkbcommon.
+//!
+//! libxkbcommon is loaded at runtime, so that winit keeps working without it: keysyms can still
+//! be interpreted, but X11 falls back to the keysyms computed by Xlib.
+
+use std::{
+    env,
+    ffi::{CStr, CString},
+    os::{
+        raw::{c_char, c_int, c_uint},
+        unix::io::RawFd,
+    },
+    ptr,
+};
+
+use crate::{
+    event::ModifiersState,
+    keyboard::{KeyCode, KeyboardLayout},
+};
+
+mod ffi;
+mod keymap;
+
+pub use self::keymap::{
+    keycode_to_raw_keycode, keysym_location, keysym_to_key, raw_keycode_to_keycode,
+    update_modifier_sides, virtual_keycode,
+};
+
+// Evdev keycodes are offset by 8 in xkb keymaps.
+const KEYCODE_OFFSET: u32 = 8;
+
+lazy_static! {
+    static ref XKBCOMMON: Option<ffi::XkbCommon> = ffi::XkbCommon::open();
+    static ref XKBCOMMON_X11: Option<ffi::XkbCommonX11> = ffi::XkbCommonX11::open();
+}
+
+/// Returns the character produced by a keysym, if any.
+pub fn keysym_to_char(keysym: u32) -> Option<char> {
+    let codepoint = match *XKBCOMMON {
+        Some(ref xkb) => unsafe { (xkb.xkb_keysym_to_utf32)(keysym) },
+        // Latin-1 keysyms and Unicode keysyms directly map to their character.
+        None => match keysym {
+            0x20..=0x7e | 0xa0..=0xff => keysym,
+            0x0100_0100..=0x0110_ffff => keysym - 0x0100_0000,
+            _ => 0,
+        },
+    };
+    match codepoint {
+        0 => None,
+        codepoint => std::char::from_u32(codepoint),
+    }
+}
+
+/// The outcome of feeding a key press to the compose state.
+pub enum ComposeStatus {
+    /// The key press isn't part of a compose sequence, and produces its usual text.
+    Ignored,
+    /// The key press continues a compose sequence, and produces no text.
+    Composing,
+    /// The key press finishes a compose sequence, producing this text.
+    Composed(Option<String>),
+    /// The key press cancels the compose sequence, and produces no text.
+    Cancelled,
+}
+
+/// The keymap of a keyboard along with the state of its modifiers and layout.
+pub struct KbState {
+    xkb: &'static ffi::XkbCommon,
+    context: *mut ffi::xkb_context,
+    keymap: *mut ffi::xkb_keymap,
+    state: *mut ffi::xkb_state,
+    // Both are null if the compose sequences of the locale couldn't be loaded.
+    compose_table: *mut ffi::xkb_compose_table,
+    compose_state: *mut ffi::xkb_compose_state,
+}
+
+unsafe impl Send for KbState {}
+
+impl KbState {
+    /// Loads the keymap of the core keyboard of an X server.
+    ///
+    /// Returns `None` if libxkbcommon-x11 or the XKB extension are not available.
+    pub unsafe fn from_x11_connection(connection: *mut ffi::xcb_connection_t) -> Option<KbState> {
+        let xkb = XKBCOMMON.as_ref()?;
+        let xkb_x11 = XKBCOMMON_X11.as_ref()?;
+        let supported = (xkb_x11.xkb_x11_setup_xkb_extension)(
+            connection,
+            ffi::XKB_X11_MIN_MAJOR_XKB_VERSION,
+            ffi::XKB_X11_MIN_MINOR_XKB_VERSION,
+            ffi::XKB_X11_SETUP_XKB_EXTENSION_NO_FLAGS,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            ptr::null_mut(),
+            ptr::null_mut(),
+        );
+        if supported == 0 {
+            return None;
+        }
+        let device_id = (xkb_x11.xkb_x11_get_core_keyboard_device_id)(connection);
+        if device_id == -1 {
+            return None;
+        }
+
+        let context = (xkb.xkb_context_new)(ffi::XKB_CONTEXT_NO_FLAGS);
+        if context.is_null() {
+            return None;
+        }
+        let keymap = (xkb_x11.xkb_x11_keymap_new_from_device)(
+            context,
+            connection,
+            device_id,
+            ffi::XKB_KEYMAP_COMPILE_NO_FLAGS,
+        );
+        if keymap.is_null() {
+            (xkb.xkb_context_unref)(context);
+            return None;
+        }
+        let state = (xkb_x11.xkb_x11_state_new_from_device)(keymap, connection, device_id);
+        if state.is_null() {
+            (xkb.xkb_keymap_unref)(keymap);
+            (xkb.xkb_context_unref)(context);
+            return None;
+        }
+        Some(KbState {
+            xkb,
+            context,
+            keymap,
+            state,
+            compose_table: ptr::null_mut(),
+            compose_state: ptr::null_mut(),
+        })
+    }
+
+    /// Loads the keymap sent by a Wayland compositor, closing its file descriptor.
+    ///
+    /// Compose sequences are handled by the returned state, as there is no input method doing it
+    /// on Wayland. Returns `None` if libxkbcommon is not available or the keymap is invalid.
+    pub unsafe fn from_wayland_keymap(fd: RawFd, size: usize) -> Option<KbState> {
+        let map = libc::mmap(
+            ptr::null_mut(),
+            size,
+            libc::PROT_READ,
+            libc::MAP_PRIVATE,
+            fd,
+            0,
+        );
+        libc::close(fd);
+        if map == libc::MAP_FAILED {
+            return None;
+        }
+        let result = KbState::from_string(map as *const c_char);
+        libc::munmap(map, size);
+        result
+    }
+
+    // The keymap is a null-terminated string in the text format of libxkbcommon.
+    unsafe fn from_string(keymap: *const c_char) -> Option<KbState> {
+        let xkb = XKBCOMMON.as_ref()?;
+        let context = (xkb.xkb_context_new)(ffi::XKB_CONTEXT_NO_FLAGS);
+        if context.is_null() {
+            return None;
+        }
+        let keymap = (xkb.xkb_keymap_new_from_string)(
+            context,
+            keymap,
+            ffi::XKB_KEYMAP_FORMAT_TEXT_V1,
+            ffi::XKB_KEYMAP_COMPILE_NO_FLAGS,
+        );
+        if keymap.is_null() {
+            (xkb.xkb_context_unref)(context);
+            return None;
+        }
+        let state = (xkb.xkb_state_new)(keymap);
+        if state.is_null() {
+            (xkb.xkb_keymap_unref)(keymap);
+            (xkb.xkb_context_unref)(context);
+            return None;
+        }
+        let mut kb_state = KbState {
+            xkb,
+            context,
+            keymap,
+            state,
+            compose_table: ptr::null_mut(),
+            compose_state: ptr::null_mut(),
+        };
+        kb_state.init_compose();
+        Some(kb_state)
+    }
+
+    // Loads the compose sequences of the locale, following the usual precedence of the
+    // environment variables.
+    unsafe fn init_compose(&mut self) {
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .filter_map(env::var_os)
+            .find(|value| !value.is_empty())
+            .and_then(|value| CString::new(value.into_string().ok()?).ok())
+            .unwrap_or_else(|| CString::new("C").unwrap());
+        let table = (self.xkb.xkb_compose_table_new_from_locale)(
+            self.context,
+            locale.as_ptr(),
+            ffi::XKB_COMPOSE_COMPILE_NO_FLAGS,
+        );
+        if table.is_null() {
+            return;
+        }
+        let state = (self.xkb.xkb_compose_state_new)(table, ffi::XKB_COMPOSE_STATE_NO_FLAGS);
+        if state.is_null() {
+            (self.xkb.xkb_compose_table_unref)(table);
+            return;
+        }
+        self.compose_table = table;
+        self.compose_state = state;
+    }
+
+    /// Sets the modifiers and layout from the `state` field of a core X11 key event.
+    ///
+    /// The X11 core modifiers are the first eight modifiers of the keymap of an X server, and
+    /// the layout is stored in bits 13 and 14.
+    pub fn update_from_x11_state(&mut self, x11_state: c_uint) {
+        let mods = x11_state & 0xff;
+        let layout = (x11_state >> 13) & 0x3;
+        unsafe {
+            (self.xkb.xkb_state_update_mask)(self.state, mods, 0, 0, 0, 0, layout);
+        }
+    }
+
+    /// Sets the modifiers and layout, as sent by the XKB extension of an X server or by a
+    /// Wayland compositor.
+    pub fn update_mask(
+        &mut self,
+        depressed_mods: u32,
+        latched_mods: u32,
+        locked_mods: u32,
+        depressed_layout: u32,
+        latched_layout: u32,
+        locked_layout: u32,
+    ) {
+        unsafe {
+            (self.xkb.xkb_state_update_mask)(
+                self.state,
+                depressed_mods,
+                latched_mods,
+                locked_mods,
+                depressed_layout,
+                latched_layout,
+                locked_layout,
+            );
+        }
+    }
+
+    /// Returns the active layout.
+    pub fn layout(&self) -> KeyboardLayout {
+        unsafe {
+            let index =
+                (self.xkb.xkb_state_serialize_layout)(self.state, ffi::XKB_STATE_LAYOUT_EFFECTIVE);
+            let name = (self.xkb.xkb_keymap_layout_get_name)(self.keymap, index);
+            let name = if name.is_null() {
+                String::new()
+            } else {
+                CStr::from_ptr(name).to_string_lossy().into_owned()
+            };
+            KeyboardLayout { index, name }
+        }
+    }
+
+    /// Returns the state of the modifiers. The sides of the modifier keys are not tracked here.
+    pub fn modifiers(&self) -> ModifiersState {
+        ModifiersState {
+            shift: self.mod_name_is_active(b"Shift\0"),
+            ctrl: self.mod_name_is_active(b"Control\0"),
+            alt: self.mod_name_is_active(b"Mod1\0"),
+            logo: self.mod_name_is_active(b"Mod4\0"),
+            alt_graph: self.mod_name_is_active(b"Mod5\0"),
+            caps_lock: self.mod_name_is_active(b"Lock\0"),
+            num_lock: self.mod_name_is_active(b"Mod2\0"),
+            ..Default::default()
+        }
+    }
+
+    fn mod_name_is_active(&self, name: &[u8]) -> bool {
+        unsafe {
+            (self.xkb.xkb_state_mod_name_is_active)(
+                self.state,
+                name.as_ptr() as *const c_char,
+                ffi::XKB_STATE_MODS_EFFECTIVE,
+            ) > 0
+        }
+    }
+
+    /// Returns the keysym produced by a key under the current state, or 0 if there are several
+    /// or none of them.
+    pub fn key_get_one_sym(&self, keycode: u32) -> u32 {
+        unsafe { (self.xkb.xkb_state_key_get_one_sym)(self.state, keycode) }
+    }
+
+    /// Returns the text produced by a key under the current state, if any.
+    pub fn key_get_utf8(&self, keycode: u32) -> Option<String> {
+        unsafe {
+            read_utf8(|buffer, size| {
+                (self.xkb.xkb_state_key_get_utf8)(self.state, keycode, buffer, size)
+            })
+        }
+    }
+
+    /// Returns the label of a physical key under the active layout, which is the label of the
+    /// keysym it produces without modifiers.
+    pub fn key_label(&self, key: KeyCode) -> Option<String> {
+        let rawkey = keycode_to_raw_keycode(key)?;
+        keymap::keysym_label(self.key_get_base_sym(rawkey + KEYCODE_OFFSET)?)
+    }
+
+    /// Returns the physical key with a label under the active layout, ignoring case.
+    pub fn key_from_label(&self, label: &str) -> Option<KeyCode> {
+        let label = label.to_uppercase();
+        let (min_keycode, max_keycode) = unsafe {
+            (
+                (self.xkb.xkb_keymap_min_keycode)(self.keymap),
+                (self.xkb.xkb_keymap_max_keycode)(self.keymap),
+            )
+        };
+        (min_keycode.max(KEYCODE_OFFSET)..=max_keycode)
+            .filter(|&keycode| {
+                let key_label = self
+                    .key_get_base_sym(keycode)
+                    .and_then(keymap::keysym_label);
+                key_label.map(|key_label| key_label.to_uppercase()).as_ref() == Some(&label)
+            })
+            .map(|keycode| raw_keycode_to_keycode(keycode - KEYCODE_OFFSET))
+            .find(|&key| key != KeyCode::Unidentified)
+    }
+
+    // Returns the keysym a key produces without modifiers under the active layout, if it produces
+    // exactly one.
+    fn key_get_base_sym(&self, keycode: u32) -> Option<u32> {
+        unsafe {
+            let layout = (self.xkb.xkb_state_key_get_layout)(self.state, keycode);
+            if layout == ffi::XKB_LAYOUT_INVALID {
+                return None;
+            }
+            let mut syms = ptr::null();
+            let count = (self.xkb.xkb_keymap_key_get_syms_by_level)(
+                self.keymap,
+                keycode,
+                layout,
+                0,
+                &mut syms,
+            );
+            if count == 1 {
+                Some(*syms)
+            } else {
+                None
+            }
+        }
+    }
+
+    /// Returns whether a key should repeat when held down, according to the keymap.
+    pub fn key_repeats(&self, keycode: u32) -> bool {
+        unsafe { (self.xkb.xkb_keymap_key_repeats)(self.keymap, keycode) != 0 }
+    }
+
+    /// Feeds the keysym of a key press to the compose state.
+    pub fn compose(&mut self, keysym: u32) -> ComposeStatus {
+        if self.compose_state.is_null() {
+            return ComposeStatus::Ignored;
+        }
+        unsafe {
+            let result = (self.xkb.xkb_compose_state_feed)(self.compose_state, keysym);
+            if result != ffi::XKB_COMPOSE_FEED_ACCEPTED {
+                return ComposeStatus::Ignored;
+            }
+            match (self.xkb.xkb_compose_state_get_status)(self.compose_state) {
+                ffi::XKB_COMPOSE_COMPOSING => ComposeStatus::Composing,
+                ffi::XKB_COMPOSE_COMPOSED => {
+                    let text = read_utf8(|buffer, size| {
+                        (self.xkb.xkb_compose_state_get_utf8)(self.compose_state, buffer, size)
+                    });
+                    (self.xkb.xkb_compose_state_reset)(self.compose_state);
+                    ComposeStatus::Composed(text)
+                }
+                ffi::XKB_COMPOSE_CANCELLED => {
+                    (self.xkb.xkb_compose_state_reset)(self.compose_state);
+                    ComposeStatus::Cancelled
+                }
+                _ => ComposeStatus::Ignored,
+            }
+        }
+    }
+}
+
+// Calls one of the functions of libxkbcommon writing text to a buffer, which return the size of
+// the whole text even if it doesn't fit.
+unsafe fn read_utf8<F>(mut write: F) -> Option<String>
+where
+    F: FnMut(*mut c_char, usize) -> c_int,
+{
+    let size = write(ptr::null_mut(), 0);
+    if size <= 0 {
+        return None;
+    }
+    let mut buffer = vec![0u8; size as usize + 1];
+    write(buffer.as_mut_ptr() as *mut c_char, buffer.len());
+    buffer.truncate(size as usize);
+    String::from_utf8(buffer).ok()
+}
+
+impl Drop for KbState {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.compose_state.is_null() {
+                (self.xkb.xkb_compose_state_unref)(self.compose_state);
+                (self.xkb.xkb_compose_table_unref)(self.compose_table);
+            }
+            (self.xkb.xkb_state_unref)(self.state);
+            (self.xkb.xkb_keymap_unref)(self.keymap);
+            (self.xkb.xkb_context_unref)(self.context);
+        }
+    }
+}
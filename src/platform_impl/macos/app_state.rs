@@ -13,7 +13,7 @@ use std::{
 use cocoa::{appkit::NSApp, base::nil};
 
 use crate::{
-    event::{Event, StartCause, WindowEvent},
+    event::{Damage, Event, StartCause, WindowEvent},
     event_loop::{ControlFlow, EventLoopWindowTarget as RootWindowTarget},
     platform_impl::platform::{observer::EventLoopWaker, util::Never},
     window::WindowId,
@@ -287,7 +287,7 @@ impl AppState {
             for window_id in HANDLER.should_redraw() {
                 HANDLER.handle_nonuser_event(Event::WindowEvent {
                     window_id,
-                    event: WindowEvent::RedrawRequested,
+                    event: WindowEvent::RedrawRequested(Damage::Full),
                 });
             }
             HANDLER.handle_nonuser_event(Event::EventsCleared);
@@ -229,6 +229,8 @@ pub fn event_mods(event: id) -> ModifiersState {
         ctrl: flags.contains(NSEventModifierFlags::NSControlKeyMask),
         alt: flags.contains(NSEventModifierFlags::NSAlternateKeyMask),
         logo: flags.contains(NSEventModifierFlags::NSCommandKeyMask),
+        caps_lock: flags.contains(NSEventModifierFlags::NSAlphaShiftKeyMask),
+        ..Default::default()
     }
 }
 
@@ -263,6 +265,7 @@ pub unsafe fn modifier_event(
                 scancode: scancode as _,
                 virtual_keycode,
                 modifiers: event_mods(ns_event),
+                is_repeat: false,
             },
         })
     } else {
@@ -18,8 +18,8 @@ use objc::{
 
 use crate::{
     event::{
-        DeviceEvent, ElementState, Event, KeyboardInput, MouseButton, MouseScrollDelta, TouchPhase,
-        VirtualKeyCode, WindowEvent,
+        DeviceEvent, ElementState, Event, Ime, KeyboardInput, MouseButton, MouseScrollDelta,
+        TouchPhase, VirtualKeyCode, WindowEvent,
     },
     platform_impl::platform::{
         app_state::AppState,
@@ -46,7 +46,8 @@ struct Modifiers {
 struct ViewState {
     ns_window: id,
     pub cursor: Arc<Mutex<util::Cursor>>,
-    ime_spot: Option<(f64, f64)>,
+    // The area of the text cursor, in screen coordinates.
+    ime_rect: Option<NSRect>,
     raw_characters: Option<String>,
     is_key_down: bool,
     modifiers: Modifiers,
@@ -58,7 +59,7 @@ pub fn new_view(ns_window: id) -> (IdRef, Weak<Mutex<util::Cursor>>) {
     let state = ViewState {
         ns_window,
         cursor,
-        ime_spot: None,
+        ime_rect: None,
         raw_characters: None,
         is_key_down: false,
         modifiers: Default::default(),
@@ -74,14 +75,25 @@ pub fn new_view(ns_window: id) -> (IdRef, Weak<Mutex<util::Cursor>>) {
     }
 }
 
-pub unsafe fn set_ime_position(ns_view: id, input_context: id, x: f64, y: f64) {
+pub unsafe fn set_ime_position(
+    ns_view: id,
+    input_context: id,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+) {
     let state_ptr: *mut c_void = *(*ns_view).get_mut_ivar("winitState");
     let state = &mut *(state_ptr as *mut ViewState);
     let content_rect =
         NSWindow::contentRectForFrameRect_(state.ns_window, NSWindow::frame(state.ns_window));
     let base_x = content_rect.origin.x as f64;
     let base_y = (content_rect.origin.y + content_rect.size.height) as f64;
-    state.ime_spot = Some((base_x + x, base_y - y));
+    // AppKit places the origin of a rectangle at its bottom left.
+    state.ime_rect = Some(NSRect::new(
+        NSPoint::new((base_x + x) as _, (base_y - y - height) as _),
+        NSSize::new(width as _, height as _),
+    ));
     let _: () = msg_send![input_context, invalidateCharacterCoordinates];
 }
 
@@ -346,11 +358,46 @@ extern "C" fn selected_range(_this: &Object, _sel: Sel) -> NSRange {
     util::EMPTY_RANGE
 }
 
+// Converts a `NSString` or `NSAttributedString` to a Rust string.
+unsafe fn string_from_text(text: id) -> String {
+    let has_attr = msg_send![text, isKindOfClass: class!(NSAttributedString)];
+    let characters = if has_attr {
+        // This is a *mut NSAttributedString
+        msg_send![text, string]
+    } else {
+        // This is already a *mut NSString
+        text
+    };
+    let slice = slice::from_raw_parts(characters.UTF8String() as *const c_uchar, characters.len());
+    str::from_utf8_unchecked(slice).to_owned()
+}
+
+// Converts an offset in UTF-16 code units, which is what AppKit uses, to a byte offset.
+fn utf16_to_byte_offset(string: &str, offset: usize) -> usize {
+    let mut utf16_offset = 0;
+    for (byte_offset, character) in string.char_indices() {
+        if utf16_offset >= offset {
+            return byte_offset;
+        }
+        utf16_offset += character.len_utf16();
+    }
+    string.len()
+}
+
+unsafe fn queue_ime_event(this: &Object, event: Ime) {
+    let state_ptr: *mut c_void = *this.get_ivar("winitState");
+    let state = &mut *(state_ptr as *mut ViewState);
+    AppState::queue_event(Event::WindowEvent {
+        window_id: WindowId(get_window_id(state.ns_window)),
+        event: WindowEvent::Ime(event),
+    });
+}
+
 extern "C" fn set_marked_text(
     this: &mut Object,
     _sel: Sel,
     string: id,
-    _selected_range: NSRange,
+    selected_range: NSRange,
     _replacement_range: NSRange,
 ) {
     trace!("Triggered `setMarkedText`");
@@ -365,6 +412,19 @@ extern "C" fn set_marked_text(
             marked_text.initWithString(string);
         };
         *marked_text_ref = marked_text;
+
+        let text = string_from_text(string);
+        let cursor_range = if text.is_empty() {
+            None
+        } else {
+            let start = selected_range.location as usize;
+            let end = start + selected_range.length as usize;
+            Some((
+                utf16_to_byte_offset(&text, start),
+                utf16_to_byte_offset(&text, end),
+            ))
+        };
+        queue_ime_event(this, Ime::Preedit(text, cursor_range));
     }
     trace!("Completed `setMarkedText`");
 }
@@ -373,10 +433,14 @@ extern "C" fn unmark_text(this: &Object, _sel: Sel) {
     trace!("Triggered `unmarkText`");
     unsafe {
         let marked_text: id = *this.get_ivar("markedText");
+        let had_marked_text = marked_text.length() > 0;
         let mutable_string = marked_text.mutableString();
         let _: () = msg_send![mutable_string, setString:""];
         let input_context: id = msg_send![this, inputContext];
         let _: () = msg_send![input_context, discardMarkedText];
+        if had_marked_text {
+            queue_ime_event(this, Ime::Preedit(String::new(), None));
+        }
     }
     trace!("Completed `unmarkText`");
 }
@@ -414,17 +478,17 @@ extern "C" fn first_rect_for_character_range(
         trace!("Triggered `firstRectForCharacterRange`");
         let state_ptr: *mut c_void = *this.get_ivar("winitState");
         let state = &mut *(state_ptr as *mut ViewState);
-        let (x, y) = state.ime_spot.unwrap_or_else(|| {
+        let rect = state.ime_rect.unwrap_or_else(|| {
             let content_rect = NSWindow::contentRectForFrameRect_(
                 state.ns_window,
                 NSWindow::frame(state.ns_window),
             );
             let x = content_rect.origin.x;
             let y = util::bottom_left_to_top_left(content_rect);
-            (x, y)
+            NSRect::new(NSPoint::new(x as _, y as _), NSSize::new(0.0, 0.0))
         });
         trace!("Completed `firstRectForCharacterRange`");
-        NSRect::new(NSPoint::new(x as _, y as _), NSSize::new(0.0, 0.0))
+        rect
     }
 }
 
@@ -434,32 +498,30 @@ extern "C" fn insert_text(this: &Object, _sel: Sel, string: id, _replacement_ran
         let state_ptr: *mut c_void = *this.get_ivar("winitState");
         let state = &mut *(state_ptr as *mut ViewState);
 
-        let has_attr = msg_send![string, isKindOfClass: class!(NSAttributedString)];
-        let characters = if has_attr {
-            // This is a *mut NSAttributedString
-            msg_send![string, string]
-        } else {
-            // This is already a *mut NSString
-            string
-        };
-
-        let slice =
-            slice::from_raw_parts(characters.UTF8String() as *const c_uchar, characters.len());
-        let string = str::from_utf8_unchecked(slice);
+        let string = string_from_text(string);
         state.is_key_down = true;
 
         // We don't need this now, but it's here if that changes.
         //let event: id = msg_send![NSApp(), currentEvent];
 
-        let mut events = VecDeque::with_capacity(characters.len());
-        for character in string.chars() {
-            events.push_back(Event::WindowEvent {
-                window_id: WindowId(get_window_id(state.ns_window)),
-                event: WindowEvent::ReceivedCharacter(character),
-            });
-        }
+        // If there was marked text, the string is the result of a composition.
+        let marked_text: id = *this.get_ivar("markedText");
+        if marked_text.length() > 0 {
+            let mutable_string = marked_text.mutableString();
+            let _: () = msg_send![mutable_string, setString:""];
+            queue_ime_event(this, Ime::Preedit(String::new(), None));
+            queue_ime_event(this, Ime::Commit(string));
+        } else {
+            let mut events = VecDeque::with_capacity(string.len());
+            for character in string.chars() {
+                events.push_back(Event::WindowEvent {
+                    window_id: WindowId(get_window_id(state.ns_window)),
+                    event: WindowEvent::ReceivedCharacter(character),
+                });
+            }
 
-        AppState::queue_events(events);
+            AppState::queue_events(events);
+        }
     }
     trace!("Completed `insertText`");
 }
@@ -566,6 +628,7 @@ extern "C" fn key_down(this: &Object, _sel: Sel, event: id) {
                     scancode,
                     virtual_keycode,
                     modifiers: event_mods(event),
+                    is_repeat,
                 },
             },
         };
@@ -617,6 +680,7 @@ extern "C" fn key_up(this: &Object, _sel: Sel, event: id) {
                     scancode,
                     virtual_keycode,
                     modifiers: event_mods(event),
+                    is_repeat: false,
                 },
             },
         };
@@ -725,6 +789,7 @@ extern "C" fn cancel_operation(this: &Object, _sel: Sel, _sender: id) {
                     scancode: scancode as _,
                     virtual_keycode,
                     modifiers: event_mods(event),
+                    is_repeat: false,
                 },
             },
         };
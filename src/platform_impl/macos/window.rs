@@ -26,6 +26,7 @@ use objc::{
 use crate::{
     dpi::{LogicalPosition, LogicalSize},
     error::{ExternalError, NotSupportedError, OsError as RootOsError},
+    event::DamageRect,
     icon::Icon,
     monitor::MonitorHandle as RootMonitorHandle,
     platform::macos::{ActivationPolicy, RequestUserAttentionType, WindowExtMacOS},
@@ -38,10 +39,14 @@ use crate::{
         window_delegate::new_delegate,
         OsError,
     },
-    window::{CursorIcon, WindowAttributes, WindowId as RootWindowId},
+    window::{
+        CursorGrabMode, CursorIcon, CustomCursor, DragData, ImeHints, ImePurpose, WindowAttributes,
+        WindowId as RootWindowId,
+    },
 };
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Id(pub usize);
 
 impl Id {
@@ -415,6 +420,10 @@ impl UnownedWindow {
         AppState::queue_redraw(RootWindowId(self.id()));
     }
 
+    pub fn request_redraw_region(&self, _rect: DamageRect) {
+        self.request_redraw();
+    }
+
     pub fn outer_position(&self) -> Result<LogicalPosition, NotSupportedError> {
         let frame_rect = unsafe { NSWindow::frame(*self.ns_window) };
         Ok((
@@ -516,9 +525,19 @@ impl UnownedWindow {
     }
 
     #[inline]
-    pub fn set_cursor_grab(&self, grab: bool) -> Result<(), ExternalError> {
-        // TODO: Do this for real https://stackoverflow.com/a/40922095/5435443
-        CGDisplay::associate_mouse_and_mouse_cursor_position(!grab)
+    pub fn set_custom_cursor(&self, _cursor: &CustomCursor) {}
+
+    #[inline]
+    pub fn set_cursor_grab(&self, mode: CursorGrabMode) -> Result<(), ExternalError> {
+        let locked = match mode {
+            CursorGrabMode::None => false,
+            CursorGrabMode::Locked => true,
+            // TODO: Do this for real https://stackoverflow.com/a/40922095/5435443
+            CursorGrabMode::Confined => {
+                return Err(ExternalError::NotSupported(NotSupportedError::new()))
+            }
+        };
+        CGDisplay::associate_mouse_and_mouse_cursor_position(!locked)
             .map_err(|status| ExternalError::Os(os_error!(OsError::CGError(status))))
     }
 
@@ -537,6 +556,14 @@ impl UnownedWindow {
         }
     }
 
+    #[inline]
+    pub fn start_drag(&self, _data: DragData) -> Result<(), ExternalError> {
+        Err(ExternalError::NotSupported(NotSupportedError::new()))
+    }
+
+    #[inline]
+    pub fn accept_drop(&self, _mime_type: Option<&str>) {}
+
     #[inline]
     pub fn hidpi_factor(&self) -> f64 {
         unsafe { NSWindow::backingScaleFactor(*self.ns_window) as _ }
@@ -730,17 +757,28 @@ impl UnownedWindow {
     }
 
     #[inline]
-    pub fn set_ime_position(&self, logical_spot: LogicalPosition) {
+    pub fn set_ime_position(&self, logical_spot: LogicalPosition, size: LogicalSize) {
         unsafe {
             view::set_ime_position(
                 *self.ns_view,
                 *self.input_context,
                 logical_spot.x,
                 logical_spot.y,
+                size.width,
+                size.height,
             );
         }
     }
 
+    #[inline]
+    pub fn set_ime_allowed(&self, _allowed: bool) {}
+
+    #[inline]
+    pub fn set_ime_purpose(&self, _purpose: ImePurpose, _hints: ImeHints) {}
+
+    #[inline]
+    pub fn set_key_repeat(&self, _enabled: bool) {}
+
     #[inline]
     pub fn current_monitor(&self) -> RootMonitorHandle {
         unsafe {
@@ -5,7 +5,7 @@ use std::{
     sync::atomic::{AtomicBool, AtomicPtr, Ordering},
 };
 
-use crate::event::{ModifiersState, ScanCode, VirtualKeyCode};
+use crate::event::{ModifierSides, ModifiersState, ScanCode, VirtualKeyCode};
 
 use winapi::{
     shared::minwindef::{HKL, HKL__, LPARAM, UINT, WPARAM},
@@ -16,6 +16,10 @@ fn key_pressed(vkey: c_int) -> bool {
     unsafe { (winuser::GetKeyState(vkey) & (1 << 15)) == (1 << 15) }
 }
 
+fn key_toggled(vkey: c_int) -> bool {
+    unsafe { (winuser::GetKeyState(vkey) & 1) == 1 }
+}
+
 pub fn get_key_mods() -> ModifiersState {
     let mut mods = ModifiersState::default();
     let filter_out_altgr = layout_uses_altgr() && key_pressed(winuser::VK_RMENU);
@@ -24,6 +28,20 @@ pub fn get_key_mods() -> ModifiersState {
     mods.ctrl = key_pressed(winuser::VK_CONTROL) && !filter_out_altgr;
     mods.alt = key_pressed(winuser::VK_MENU) && !filter_out_altgr;
     mods.logo = key_pressed(winuser::VK_LWIN) || key_pressed(winuser::VK_RWIN);
+    mods.alt_graph = filter_out_altgr;
+    mods.caps_lock = key_toggled(winuser::VK_CAPITAL);
+    mods.num_lock = key_toggled(winuser::VK_NUMLOCK);
+    // AltGr is reported as the left control and right alt keys.
+    mods.sides = ModifierSides {
+        left_shift: key_pressed(winuser::VK_LSHIFT),
+        right_shift: key_pressed(winuser::VK_RSHIFT),
+        left_ctrl: key_pressed(winuser::VK_LCONTROL) && !filter_out_altgr,
+        right_ctrl: key_pressed(winuser::VK_RCONTROL),
+        left_alt: key_pressed(winuser::VK_LMENU),
+        right_alt: key_pressed(winuser::VK_RMENU) && !filter_out_altgr,
+        left_logo: key_pressed(winuser::VK_LWIN),
+        right_logo: key_pressed(winuser::VK_RWIN),
+    };
     mods
 }
 
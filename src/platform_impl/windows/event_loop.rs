@@ -455,7 +455,7 @@ impl<T> EventLoopRunner<T> {
             (
                 true,
                 Event::WindowEvent {
-                    event: WindowEvent::RedrawRequested,
+                    event: WindowEvent::RedrawRequested(_),
                     ..
                 },
             )
@@ -522,7 +522,7 @@ impl<T> EventLoopRunner<T> {
                 .trigger_newevents_on_redraw
                 .store(false, Ordering::Relaxed),
             Event::WindowEvent {
-                event: WindowEvent::RedrawRequested,
+                event: WindowEvent::RedrawRequested(_),
                 ..
             } => self.in_repaint = true,
             _ => (),
@@ -911,7 +911,7 @@ unsafe extern "system" fn public_window_callback<T>(
         }
 
         _ if msg == *REQUEST_REDRAW_NO_NEWEVENTS_MSG_ID => {
-            use crate::event::WindowEvent::RedrawRequested;
+            use crate::event::{Damage, WindowEvent::RedrawRequested};
             let mut runner = subclass_input.event_loop_runner.runner.borrow_mut();
             subclass_input.window_state.lock().queued_out_of_band_redraw = false;
             if let Some(ref mut runner) = *runner {
@@ -923,7 +923,7 @@ unsafe extern "system" fn public_window_callback<T>(
                 let mut request_redraw = || {
                     runner.call_event_handler(Event::WindowEvent {
                         window_id: RootWindowId(WindowId(window)),
-                        event: RedrawRequested,
+                        event: RedrawRequested(Damage::Full),
                     });
                 };
                 match runner_state {
@@ -942,10 +942,10 @@ unsafe extern "system" fn public_window_callback<T>(
             0
         }
         winuser::WM_PAINT => {
-            use crate::event::WindowEvent::RedrawRequested;
+            use crate::event::{Damage, WindowEvent::RedrawRequested};
             subclass_input.send_event(Event::WindowEvent {
                 window_id: RootWindowId(WindowId(window)),
-                event: RedrawRequested,
+                event: RedrawRequested(Damage::Full),
             });
             commctrl::DefSubclassProc(window, msg, wparam, lparam)
         }
@@ -1133,6 +1133,8 @@ unsafe extern "system" fn public_window_callback<T>(
                                 scancode,
                                 virtual_keycode: vkey,
                                 modifiers: event::get_key_mods(),
+                                // The previous key state is set for repeated key presses.
+                                is_repeat: lparam & (1 << 30) != 0,
                             },
                         },
                     });
@@ -1161,6 +1163,7 @@ unsafe extern "system" fn public_window_callback<T>(
                             scancode,
                             virtual_keycode: vkey,
                             modifiers: event::get_key_mods(),
+                            is_repeat: false,
                         },
                     },
                 });
@@ -1424,6 +1427,7 @@ unsafe extern "system" fn public_window_callback<T>(
                                     state,
                                     virtual_keycode,
                                     modifiers: event::get_key_mods(),
+                                    is_repeat: false,
                                 }),
                             });
                         }
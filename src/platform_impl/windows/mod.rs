@@ -27,6 +27,7 @@ unsafe impl Send for Cursor {}
 unsafe impl Sync for Cursor {}
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct DeviceId(u32);
 
 impl DeviceId {
@@ -59,6 +60,21 @@ pub struct WindowId(HWND);
 unsafe impl Send for WindowId {}
 unsafe impl Sync for WindowId {}
 
+// The handle is only ever compared, so it's stored as a plain number.
+#[cfg(feature = "serde")]
+impl serde::Serialize for WindowId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(&(self.0 as usize), serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for WindowId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <usize as serde::Deserialize>::deserialize(deserializer).map(|hwnd| WindowId(hwnd as HWND))
+    }
+}
+
 impl WindowId {
     pub unsafe fn dummy() -> Self {
         use std::ptr::null_mut;
@@ -31,6 +31,7 @@ use winapi::{
 use crate::{
     dpi::{LogicalPosition, LogicalSize, PhysicalSize},
     error::{ExternalError, NotSupportedError, OsError as RootOsError},
+    event::DamageRect,
     monitor::MonitorHandle as RootMonitorHandle,
     platform_impl::platform::{
         dpi::{dpi_to_scale_factor, hwnd_dpi},
@@ -46,7 +47,10 @@ use crate::{
         window_state::{CursorFlags, SavedWindow, WindowFlags, WindowState},
         PlatformSpecificWindowBuilderAttributes, WindowId,
     },
-    window::{CursorIcon, Icon, WindowAttributes},
+    window::{
+        CursorGrabMode, CursorIcon, CustomCursor, DragData, Icon, ImeHints, ImePurpose,
+        WindowAttributes,
+    },
 };
 
 /// The Win32 implementation of the main `Window` object.
@@ -158,6 +162,11 @@ impl Window {
         }
     }
 
+    #[inline]
+    pub fn request_redraw_region(&self, _rect: DamageRect) {
+        self.request_redraw();
+    }
+
     pub(crate) fn outer_position_physical(&self) -> (i32, i32) {
         util::get_window_rect(self.window.0)
             .map(|rect| (rect.left as i32, rect.top as i32))
@@ -349,7 +358,17 @@ impl Window {
     }
 
     #[inline]
-    pub fn set_cursor_grab(&self, grab: bool) -> Result<(), ExternalError> {
+    pub fn set_custom_cursor(&self, _cursor: &CustomCursor) {}
+
+    #[inline]
+    pub fn set_cursor_grab(&self, mode: CursorGrabMode) -> Result<(), ExternalError> {
+        let grab = match mode {
+            CursorGrabMode::None => false,
+            CursorGrabMode::Confined => true,
+            CursorGrabMode::Locked => {
+                return Err(ExternalError::NotSupported(NotSupportedError::new()))
+            }
+        };
         let window = self.window.clone();
         let window_state = Arc::clone(&self.window_state);
         let (tx, rx) = channel();
@@ -382,6 +401,14 @@ impl Window {
         rx.recv().unwrap().ok();
     }
 
+    #[inline]
+    pub fn start_drag(&self, _data: DragData) -> Result<(), ExternalError> {
+        Err(ExternalError::NotSupported(NotSupportedError::new()))
+    }
+
+    #[inline]
+    pub fn accept_drop(&self, _mime_type: Option<&str>) {}
+
     #[inline]
     pub fn hidpi_factor(&self) -> f64 {
         self.window_state.lock().dpi_factor
@@ -556,9 +583,18 @@ impl Window {
     }
 
     #[inline]
-    pub fn set_ime_position(&self, _logical_spot: LogicalPosition) {
+    pub fn set_ime_position(&self, _logical_spot: LogicalPosition, _size: LogicalSize) {
         unimplemented!();
     }
+
+    #[inline]
+    pub fn set_ime_allowed(&self, _allowed: bool) {}
+
+    #[inline]
+    pub fn set_ime_purpose(&self, _purpose: ImePurpose, _hints: ImeHints) {}
+
+    #[inline]
+    pub fn set_key_repeat(&self, _enabled: bool) {}
 }
 
 impl Drop for Window {
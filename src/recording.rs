@@ -0,0 +1,149 @@
+//! Recording the events an application receives, and replaying them later.
+//!
+//! An [`EventRecorder`][recorder] keeps a copy of every event it's given along with the time it
+//! was received at, so that the session can be reproduced, for instance to turn a bug report into
+//! a regression test. With the `serde` feature, the recorded events can be saved and loaded in
+//! any format supported by Serde.
+//!
+//! An [`EventReplayer`][replayer] hands the recorded events back to an event handler, either
+//! waiting between them to follow the original timing, or at the pace of its caller. On Unix,
+//! `EventReplayerExtUnix::replay_headless` replays them to the application's own event handler,
+//! through an event loop using the headless backend.
+//!
+//! The window and device ids of recorded events are those of the recorded session. They only
+//! match the ids of the replaying session if the windows are created in the same order on a
+//! backend that assigns ids deterministically, such as the headless backend on Unix.
+//!
+//! [recorder]: ./struct.EventRecorder.html
+//! [replayer]: ./struct.EventReplayer.html
+use std::{
+    collections::VecDeque,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::event::Event;
+
+/// An event, with the time it was received at.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RecordedEvent<T> {
+    /// How long after the start of the recording the event was received.
+    pub time: Duration,
+    pub event: Event<T>,
+}
+
+/// Records the events given to an event handler.
+///
+/// Call `record` on every event at the start of the event handler:
+///
+/// ```no_run
+/// use winit::{event_loop::EventLoop, recording::EventRecorder};
+///
+/// let event_loop = EventLoop::new();
+/// let mut recorder = EventRecorder::new();
+///
+/// event_loop.run(move |event, _, _| {
+///     recorder.record(&event);
+///     // Handle the event
+/// });
+/// ```
+#[derive(Debug, Clone)]
+pub struct EventRecorder<T> {
+    start: Instant,
+    events: Vec<RecordedEvent<T>>,
+}
+
+impl<T: Clone> EventRecorder<T> {
+    /// Starts a new recording.
+    pub fn new() -> Self {
+        EventRecorder {
+            start: Instant::now(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Records an event, received now.
+    pub fn record(&mut self, event: &Event<T>) {
+        self.events.push(RecordedEvent {
+            time: self.start.elapsed(),
+            event: event.clone(),
+        });
+    }
+
+    /// Returns the events recorded so far.
+    pub fn events(&self) -> &[RecordedEvent<T>] {
+        &self.events
+    }
+
+    /// Ends the recording, returning the recorded events.
+    pub fn finish(self) -> Vec<RecordedEvent<T>> {
+        self.events
+    }
+}
+
+impl<T: Clone> Default for EventRecorder<T> {
+    fn default() -> Self {
+        EventRecorder::new()
+    }
+}
+
+/// Feeds recorded events back to an event handler.
+#[derive(Debug, Clone)]
+pub struct EventReplayer<T> {
+    events: VecDeque<RecordedEvent<T>>,
+}
+
+impl<T> EventReplayer<T> {
+    /// Prepares the replay of events, usually coming from `EventRecorder::finish`.
+    ///
+    /// The events are replayed in the given order.
+    pub fn new(events: Vec<RecordedEvent<T>>) -> Self {
+        EventReplayer {
+            events: events.into(),
+        }
+    }
+
+    /// Whether all events have been replayed.
+    pub fn is_finished(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Returns when the next event is due, as the time since the start of the replay.
+    pub fn next_time(&self) -> Option<Duration> {
+        self.events.front().map(|recorded| recorded.time)
+    }
+
+    /// Returns the next event if it's due, `elapsed` being the time since the start of the
+    /// replay.
+    ///
+    /// This lets the caller decide how time passes, such as by advancing the clock of the
+    /// headless backend on Unix up to `next_time`.
+    pub fn next_event(&mut self, elapsed: Duration) -> Option<Event<T>> {
+        if self.next_time()? <= elapsed {
+            self.events.pop_front().map(|recorded| recorded.event)
+        } else {
+            None
+        }
+    }
+
+    /// Replays all events to `handler`, sleeping until each one is due to follow the original
+    /// timing.
+    ///
+    /// The handler isn't given an event loop, so it can't be the one given to `EventLoop::run`.
+    /// Use `EventReplayerExtUnix::replay_headless` on Unix for that.
+    pub fn replay<F>(mut self, mut handler: F)
+    where
+        F: FnMut(Event<T>),
+    {
+        let start = Instant::now();
+        while let Some(time) = self.next_time() {
+            let elapsed = start.elapsed();
+            if time > elapsed {
+                thread::sleep(time - elapsed);
+            }
+            let recorded = self.events.pop_front().unwrap();
+            handler(recorded.event);
+        }
+    }
+}
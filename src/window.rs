@@ -1,9 +1,10 @@
 //! The `Window` struct and associated types.
-use std::fmt;
+use std::{fmt, path::PathBuf};
 
 use crate::{
     dpi::{LogicalPosition, LogicalSize},
     error::{ExternalError, NotSupportedError, OsError},
+    event::DamageRect,
     event_loop::EventLoopWindowTarget,
     monitor::{AvailableMonitorsIter, MonitorHandle},
     platform_impl,
@@ -52,6 +53,7 @@ impl fmt::Debug for Window {
 /// Whenever you receive an event specific to a window, this event contains a `WindowId` which you
 /// can then compare to the ids of your windows.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct WindowId(pub(crate) platform_impl::WindowId);
 
 impl WindowId {
@@ -357,6 +359,20 @@ impl Window {
     pub fn request_redraw(&self) {
         self.window.request_redraw()
     }
+
+    /// Like `request_redraw`, but only for a rectangle of the window, which is added to the
+    /// `Damage` of the `RedrawRequested` event.
+    ///
+    /// If the whole window already needs to be redrawn, the damage stays `Damage::Full`.
+    ///
+    /// ## Platform-specific
+    ///
+    /// - **Windows / macOS / iOS:** The whole window is redrawn.
+    /// - **iOS:** Can only be called on the main thread.
+    #[inline]
+    pub fn request_redraw_region(&self, rect: DamageRect) {
+        self.window.request_redraw_region(rect)
+    }
 }
 
 /// Position and size functions.
@@ -493,6 +509,11 @@ impl Window {
     /// If `false`, this will hide the window. If `true`, this will show the window.
     /// ## Platform-specific
     ///
+    /// - **Wayland:** `WindowEvent::RedrawRequested` isn't emitted while the window is hidden, since
+    ///   drawing to it would show it again. A shown window appears once it has been redrawn. Hiding
+    ///   only works if the application doesn't draw on its own: presenting a frame through EGL or
+    ///   Vulkan (e.g. from a render loop that isn't driven by `RedrawRequested`) attaches a buffer
+    ///   to the window, which shows it again.
     /// - **Android:** Has no effect.
     /// - **iOS:** Can only be called on the main thread.
     #[inline]
@@ -585,14 +606,63 @@ impl Window {
         self.window.set_window_icon(window_icon)
     }
 
-    /// Sets location of IME candidate box in client area coordinates relative to the top left.
+    /// Sets the area of the text cursor, so that the IME candidate box can be placed next to it
+    /// without covering it.
+    ///
+    /// `position` is the top-left corner of the area, in client area coordinates relative to the
+    /// top left, and `size` is its size.
+    ///
+    /// ## Platform-specific
+    ///
+    /// - **iOS:** Has no effect.
+    /// - **X11:** Only the bottom-left corner of the area is used.
+    /// - **Wayland:** Requires the compositor to support `zwp_text_input_v3`.
+    #[inline]
+    pub fn set_ime_position(&self, position: LogicalPosition, size: LogicalSize) {
+        self.window.set_ime_position(position, size)
+    }
+
+    /// Sets whether the window accepts input from an input method editor (IME).
+    ///
+    /// When the IME is allowed, the window receives `Ime` events while composing text. When it
+    /// isn't, the IME stays out of the way, and keyboard input only produces `KeyboardInput` and
+    /// `ReceivedCharacter` events, which is usually what a game wants. The IME is allowed by
+    /// default.
     ///
     /// ## Platform-specific
     ///
-    /// **iOS:** Has no effect.
+    /// - **X11:** While the IME isn't allowed, key events aren't passed through the input method,
+    ///   so compose sequences (e.g. dead keys) aren't applied either.
+    /// - **iOS / Android / Windows / macOS / Emscripten:** Has no effect.
     #[inline]
-    pub fn set_ime_position(&self, position: LogicalPosition) {
-        self.window.set_ime_position(position)
+    pub fn set_ime_allowed(&self, allowed: bool) {
+        self.window.set_ime_allowed(allowed)
+    }
+
+    /// Describes the text field that the IME is typing into, so that it can adapt to it, e.g. by
+    /// showing a numeric on-screen keyboard for `ImePurpose::Number`.
+    ///
+    /// ## Platform-specific
+    ///
+    /// - **iOS / Android / Windows / macOS / X11 / Emscripten:** Has no effect.
+    /// - **Wayland:** Requires the compositor to support `zwp_text_input_v3`.
+    #[inline]
+    pub fn set_ime_purpose(&self, purpose: ImePurpose, hints: ImeHints) {
+        self.window.set_ime_purpose(purpose, hints)
+    }
+
+    /// Sets whether holding a key down repeatedly sends key presses to the window.
+    ///
+    /// Repeated key presses have `is_repeat` set in their `KeyboardInput`. Disabling the repeat
+    /// drops them, so that a held key only sends one press and one release, which is usually
+    /// what a game wants. The repeat is enabled by default.
+    ///
+    /// ## Platform-specific
+    ///
+    /// - **iOS / Android / Windows / macOS / Emscripten:** Has no effect.
+    #[inline]
+    pub fn set_key_repeat(&self, enabled: bool) {
+        self.window.set_key_repeat(enabled)
     }
 }
 
@@ -609,6 +679,20 @@ impl Window {
         self.window.set_cursor_icon(cursor);
     }
 
+    /// Modifies the cursor of the window to show a custom image.
+    ///
+    /// The image is shown until it's replaced by another call to this function or to
+    /// `set_cursor_icon`. Animated cursors are played on their own, looping for as long as
+    /// they're shown.
+    ///
+    /// ## Platform-specific
+    ///
+    /// - **Windows / macOS / iOS / Android / Emscripten:** Has no effect.
+    #[inline]
+    pub fn set_custom_cursor(&self, cursor: &CustomCursor) {
+        self.window.set_custom_cursor(cursor);
+    }
+
     /// Changes the position of the cursor in window coordinates.
     ///
     /// ## Platform-specific
@@ -619,17 +703,25 @@ impl Window {
         self.window.set_cursor_position(position)
     }
 
-    /// Grabs the cursor, preventing it from leaving the window.
+    /// Grabs the cursor, either confining it to the window or locking it in place, or releases
+    /// it with `CursorGrabMode::None`.
+    ///
+    /// Returns a `NotSupported` error when the platform doesn't provide the requested mode.
     ///
     /// ## Platform-specific
     ///
-    /// - **macOS:** This presently merely locks the cursor in a fixed location, which looks visually
-    ///   awkward.
+    /// - **Windows:** `CursorGrabMode::Locked` isn't supported.
+    /// - **macOS:** `CursorGrabMode::Confined` isn't supported.
+    /// - **X11:** A locked cursor is hidden, and warped back to where it was locked whenever it
+    ///   moves.
+    /// - **Wayland:** Returns an `Err` if the compositor doesn't support
+    ///   `zwp_pointer_constraints_v1`.
+    /// - **Emscripten:** `CursorGrabMode::Confined` isn't supported.
     /// - **Android:** Has no effect.
     /// - **iOS:** Always returns an Err.
     #[inline]
-    pub fn set_cursor_grab(&self, grab: bool) -> Result<(), ExternalError> {
-        self.window.set_cursor_grab(grab)
+    pub fn set_cursor_grab(&self, mode: CursorGrabMode) -> Result<(), ExternalError> {
+        self.window.set_cursor_grab(mode)
     }
 
     /// Modifies the cursor's visibility.
@@ -640,6 +732,7 @@ impl Window {
     ///
     /// - **Windows:** The cursor is only hidden within the confines of the window.
     /// - **X11:** The cursor is only hidden within the confines of the window.
+    /// - **Wayland:** The cursor is only hidden within the confines of the window.
     /// - **macOS:** The cursor is hidden as long as the window has input focus, even if the cursor is
     ///   outside of the window.
     /// - **iOS:** Has no effect.
@@ -650,6 +743,47 @@ impl Window {
     }
 }
 
+/// Drag and drop functions.
+impl Window {
+    /// Starts dragging data out of the window.
+    ///
+    /// This should be called while a mouse button is held down, typically after the cursor moved
+    /// some distance from where the button was pressed. The drag ends when the button is
+    /// released. `WindowEvent::DragActionChanged` reports whether the drop target under the cursor
+    /// would accept the data, and `WindowEvent::DragFinished` reports the outcome.
+    ///
+    /// ## Platform-specific
+    ///
+    /// - **X11 / Wayland:** The data is only served while the event loop is running. Files are
+    ///   offered by their canonical path, and an `Err` is returned if one of them can't be
+    ///   canonicalized, e.g. because it doesn't exist.
+    /// - **X11:** If the drop target doesn't finish the drop within 5 seconds, the drag is
+    ///   cancelled and `WindowEvent::DragFinished(None)` is emitted.
+    /// - **Windows / macOS / iOS / Android / Emscripten:** Unsupported, always returns an `Err`.
+    #[inline]
+    pub fn start_drag(&self, data: DragData) -> Result<(), ExternalError> {
+        self.window.start_drag(data)
+    }
+
+    /// Accepts or rejects the data being dragged over the window.
+    ///
+    /// This is meant to be called in response to `WindowEvent::HoveredData`. With
+    /// `Some(mime_type)`, the data is accepted if it's offered as that MIME type, and dropping it
+    /// emits `WindowEvent::DroppedData` with the data converted to that type. With `None`, the
+    /// data is rejected. The choice lasts until the data leaves the window.
+    ///
+    /// If this isn't called, dragged files are accepted and reported with
+    /// `WindowEvent::HoveredFile` and `WindowEvent::DroppedFile`, and other data is rejected.
+    ///
+    /// ## Platform-specific
+    ///
+    /// - **Windows / macOS / iOS / Android / Emscripten:** Has no effect.
+    #[inline]
+    pub fn accept_drop(&self, mime_type: Option<&str>) {
+        self.window.accept_drop(mime_type)
+    }
+}
+
 /// Monitor info functions.
 impl Window {
     /// Returns the monitor on which the window currently resides
@@ -692,6 +826,79 @@ impl Window {
     }
 }
 
+/// Describes what kind of text a text field expects, see `Window::set_ime_purpose`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ImePurpose {
+    /// Any text.
+    Normal,
+    /// Only letters.
+    Alpha,
+    /// Only digits.
+    Digits,
+    /// A number, which may contain a sign, a decimal point or an exponent.
+    Number,
+    /// A phone number.
+    Phone,
+    /// A URL.
+    Url,
+    /// An email address.
+    Email,
+    /// The name of a person.
+    Name,
+    /// A password, which shouldn't be shown or remembered by the IME.
+    Password,
+    /// A PIN, which shouldn't be shown or remembered by the IME.
+    Pin,
+    /// A date.
+    Date,
+    /// A time.
+    Time,
+    /// A date and time.
+    DateTime,
+    /// Input for a terminal.
+    Terminal,
+}
+
+/// Hints about how the IME should behave in a text field, see `Window::set_ime_purpose`.
+#[derive(Default, Debug, Hash, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ImeHints {
+    /// Suggest word completions.
+    pub completion: bool,
+    /// Suggest word corrections.
+    pub spellcheck: bool,
+    /// Switch to uppercase letters at the start of a sentence.
+    pub auto_capitalization: bool,
+    /// Prefer lowercase letters.
+    pub lowercase: bool,
+    /// Prefer uppercase letters.
+    pub uppercase: bool,
+    /// Prefer the casing of titles and headings.
+    pub titlecase: bool,
+    /// The characters are hidden.
+    pub hidden_text: bool,
+    /// The typed text shouldn't be stored.
+    pub sensitive_data: bool,
+    /// Only Latin characters should be typed.
+    pub latin: bool,
+    /// The text field has multiple lines.
+    pub multiline: bool,
+}
+
+/// Describes how the cursor is grabbed by a window, see `Window::set_cursor_grab`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CursorGrabMode {
+    /// The cursor isn't grabbed, and moves freely.
+    None,
+    /// The cursor can't leave the window.
+    Confined,
+    /// The cursor can't move at all. Motion is only reported through `DeviceEvent::MouseMotion`,
+    /// which suits first-person cameras.
+    Locked,
+}
+
 /// Describes the appearance of the mouse cursor.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -754,3 +961,14 @@ impl Default for CursorIcon {
         CursorIcon::Default
     }
 }
+
+/// The data carried by a drag started with `Window::start_drag`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DragData {
+    /// A list of files, offered as `text/uri-list`.
+    Files(Vec<PathBuf>),
+    /// Text, offered as the usual text MIME types.
+    Text(String),
+    /// Arbitrary data, as `(mime_type, data)` pairs in order of preference.
+    Custom(Vec<(String, Vec<u8>)>),
+}
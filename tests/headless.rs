@@ -0,0 +1,294 @@
+#![cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+
+use std::{thread, time::Duration};
+
+use winit::{
+    dpi::LogicalSize,
+    event::{Damage, DeviceEvent, Event, FrameTiming, StartCause, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    platform::{
+        desktop::EventLoopExtDesktop,
+        unix::{
+            EventLoopExtUnix, EventLoopWindowTargetExtUnix, EventReplayerExtUnix, WindowExtUnix,
+        },
+    },
+    recording::{EventReplayer, RecordedEvent},
+    window::{Window, WindowBuilder},
+};
+
+#[test]
+fn window_state() {
+    let mut event_loop = EventLoop::<()>::new_headless();
+    let mut window = None;
+    let mut events = Vec::new();
+
+    event_loop.run_return(|event, target, control_flow| match event {
+        Event::NewEvents(StartCause::Init) => {
+            let new_window = WindowBuilder::new()
+                .with_title("Before")
+                .build(target)
+                .unwrap();
+            new_window.set_title("After");
+            new_window.set_inner_size(LogicalSize::new(320.0, 240.0));
+
+            let state = target
+                .headless_control()
+                .unwrap()
+                .window_state(new_window.id())
+                .unwrap();
+            assert_eq!(state.title, "After");
+            assert_eq!(state.inner_size, LogicalSize::new(320.0, 240.0));
+            window = Some(new_window);
+        }
+        Event::WindowEvent { event, .. } => events.push(event),
+        Event::EventsCleared => *control_flow = ControlFlow::Exit,
+        _ => (),
+    });
+
+    assert_eq!(
+        events,
+        vec![WindowEvent::Resized(LogicalSize::new(320.0, 240.0))]
+    );
+    assert_eq!(window.unwrap().inner_size(), LogicalSize::new(320.0, 240.0));
+}
+
+#[test]
+fn injected_events() {
+    let mut event_loop = EventLoop::<()>::new_headless();
+    let control = event_loop.headless_control().unwrap();
+    let window = Window::new(&event_loop).unwrap();
+    let window_id = window.id();
+    let device_id = control.device_id();
+
+    control.send_window_event(window_id, WindowEvent::Focused(true));
+    control.send_device_event(DeviceEvent::Added);
+    assert!(control.window_state(window_id).unwrap().focused);
+
+    let mut events = Vec::new();
+    event_loop.run_return(|event, _, control_flow| match event {
+        Event::WindowEvent { .. } | Event::DeviceEvent { .. } => events.push(event),
+        Event::EventsCleared => *control_flow = ControlFlow::Exit,
+        _ => (),
+    });
+
+    assert_eq!(
+        events,
+        vec![
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::Focused(true),
+            },
+            Event::DeviceEvent {
+                device_id,
+                event: DeviceEvent::Added,
+            },
+        ]
+    );
+}
+
+#[test]
+fn advance_clock_reaches_deadline() {
+    let mut event_loop = EventLoop::<()>::new_headless();
+    let control = event_loop.headless_control().unwrap();
+    let deadline = control.now() + Duration::from_secs(1);
+    let mut causes = Vec::new();
+
+    event_loop.run_return(|event, _, control_flow| match event {
+        Event::NewEvents(StartCause::Init) => {
+            *control_flow = ControlFlow::WaitUntil(deadline);
+            // The event loop only wakes up once the clock reaches the deadline, even when it's
+            // advanced in steps from another thread.
+            let control = control.clone();
+            thread::spawn(move || {
+                control.advance_clock(Duration::from_millis(600));
+                control.advance_clock(Duration::from_millis(400));
+            });
+        }
+        Event::NewEvents(cause) => {
+            causes.push(cause);
+            *control_flow = ControlFlow::Exit;
+        }
+        _ => (),
+    });
+
+    match causes[..] {
+        [StartCause::ResumeTimeReached {
+            requested_resume, ..
+        }] => assert_eq!(requested_resume, deadline),
+        _ => panic!("unexpected start causes: {:?}", causes),
+    }
+    assert_eq!(control.now(), deadline);
+}
+
+#[test]
+fn timers_follow_the_clock() {
+    let mut event_loop = EventLoop::<()>::new_headless();
+    let control = event_loop.headless_control().unwrap();
+    let start = control.now();
+    let mut tokens = None;
+    let mut fired = Vec::new();
+
+    event_loop.run_return(|event, target, control_flow| match event {
+        Event::NewEvents(StartCause::Init) => {
+            let once = target
+                .register_timer(start + Duration::from_secs(1), None)
+                .unwrap();
+            let repeating = target
+                .register_timer(
+                    start + Duration::from_millis(400),
+                    Some(Duration::from_millis(400)),
+                )
+                .unwrap();
+            tokens = Some((once, repeating));
+            *control_flow = ControlFlow::Wait;
+        }
+        Event::NewEvents(StartCause::ResumeTimeReached { .. }) => *control_flow = ControlFlow::Exit,
+        Event::TimerFired { token } => {
+            fired.push((control.now() - start, token));
+            let (once, repeating) = tokens.unwrap();
+            if token == once {
+                // Nothing fires anymore before the deadline
+                target.unregister_timer(repeating);
+                *control_flow = ControlFlow::WaitUntil(control.now() + Duration::from_secs(1));
+            }
+        }
+        Event::EventsCleared => match *control_flow {
+            ControlFlow::WaitUntil(deadline) => control.advance_clock(deadline - control.now()),
+            ControlFlow::Wait => control.advance_clock(Duration::from_millis(500)),
+            _ => (),
+        },
+        _ => (),
+    });
+
+    let (once, repeating) = tokens.unwrap();
+    assert_eq!(
+        fired,
+        vec![
+            (Duration::from_millis(500), repeating),
+            (Duration::from_millis(1000), repeating),
+            (Duration::from_millis(1000), once),
+        ]
+    );
+    assert_eq!(control.now() - start, Duration::from_millis(2000));
+}
+
+#[test]
+fn redraws_before_frames() {
+    let mut event_loop = EventLoop::<()>::new_headless();
+    let control = event_loop.headless_control().unwrap();
+    let window = Window::new(&event_loop).unwrap();
+    let window_id = window.id();
+    let mut events = Vec::new();
+
+    event_loop.run_return(|event, _, control_flow| match event {
+        Event::NewEvents(StartCause::Init) => {
+            // Requested in the reverse order of their dispatch
+            window.request_frame();
+            window.request_redraw();
+            control.send_window_event(window_id, WindowEvent::CloseRequested);
+        }
+        Event::NewEvents(_) | Event::LoopDestroyed => (),
+        Event::EventsCleared => {
+            events.push(event);
+            *control_flow = ControlFlow::Exit;
+        }
+        _ => events.push(event),
+    });
+
+    let window_event = |event| Event::WindowEvent { window_id, event };
+    assert_eq!(
+        events,
+        vec![
+            window_event(WindowEvent::CloseRequested),
+            window_event(WindowEvent::RedrawRequested(Damage::Full)),
+            window_event(WindowEvent::FrameReady(FrameTiming::default())),
+            Event::EventsCleared,
+        ]
+    );
+}
+
+#[test]
+fn replay_into_handler() {
+    let mut event_loop = EventLoop::<u32>::new_headless();
+    let control = event_loop.headless_control().unwrap();
+    let window = Window::new(&event_loop).unwrap();
+    let window_id = window.id();
+    let device_id = control.device_id();
+    let start = control.now();
+    let recorded = |millis, event| RecordedEvent {
+        time: Duration::from_millis(millis),
+        event,
+    };
+    let replayer = EventReplayer::new(vec![
+        recorded(0, Event::NewEvents(StartCause::Init)),
+        recorded(
+            0,
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::Focused(true),
+            },
+        ),
+        recorded(100, Event::UserEvent(7)),
+        recorded(
+            2000,
+            Event::DeviceEvent {
+                device_id,
+                event: DeviceEvent::Added,
+            },
+        ),
+        recorded(2000, Event::EventsCleared),
+    ]);
+
+    let mut events = Vec::new();
+    replayer.replay_headless(&mut event_loop, |event, target, control_flow| {
+        let now = target.headless_control().unwrap().now();
+        match event {
+            Event::NewEvents(StartCause::Init) => {
+                *control_flow = ControlFlow::WaitUntil(start + Duration::from_secs(1))
+            }
+            Event::NewEvents(StartCause::ResumeTimeReached { .. }) => {
+                events.push((now - start, event));
+                *control_flow = ControlFlow::Wait;
+            }
+            Event::WindowEvent { .. } | Event::DeviceEvent { .. } | Event::UserEvent(_) => {
+                events.push((now - start, event))
+            }
+            _ => (),
+        }
+    });
+
+    assert_eq!(
+        events,
+        vec![
+            (
+                Duration::from_millis(0),
+                Event::WindowEvent {
+                    window_id,
+                    event: WindowEvent::Focused(true),
+                },
+            ),
+            (Duration::from_millis(100), Event::UserEvent(7)),
+            (
+                Duration::from_millis(1000),
+                // The clock is advanced before the event loop starts waiting
+                Event::NewEvents(StartCause::ResumeTimeReached {
+                    start: start + Duration::from_secs(1),
+                    requested_resume: start + Duration::from_secs(1),
+                }),
+            ),
+            (
+                Duration::from_millis(2000),
+                Event::DeviceEvent {
+                    device_id,
+                    event: DeviceEvent::Added,
+                },
+            ),
+        ]
+    );
+}
@@ -23,3 +23,16 @@ fn ids_send() {
     needs_send::<winit::event::DeviceId>();
     needs_send::<winit::monitor::MonitorHandle>();
 }
+
+#[test]
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+fn clipboard_send() {
+    // ensures that `winit::platform::unix::Clipboard` implements `Send`
+    needs_send::<winit::platform::unix::Clipboard>();
+}
@@ -1,21 +1,31 @@
 #![cfg(feature = "serde")]
 
-use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use winit::{
     dpi::{LogicalPosition, LogicalSize, PhysicalPosition, PhysicalSize},
     event::{
-        ElementState, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta, TouchPhase,
-        VirtualKeyCode,
+        DeviceEvent, DeviceId, ElementState, Event, FrameTiming, KeyEvent, KeyboardInput,
+        ModifierSides, ModifiersState, MouseButton, MouseScrollDelta, StartCause, Touch,
+        TouchPhase, VirtualKeyCode, WindowEvent,
     },
-    window::CursorIcon,
+    keyboard::{Key, KeyCode, KeyLocation, KeyRepeatInfo, KeyboardLayout, NamedKey},
+    recording::RecordedEvent,
+    window::{CursorIcon, WindowId},
 };
 
 #[allow(dead_code)]
 fn needs_serde<S: Serialize + Deserialize<'static>>() {}
 
+fn round_trip<S: Serialize + DeserializeOwned>(value: &S) -> S {
+    serde_json::from_str(&serde_json::to_string(value).unwrap()).unwrap()
+}
+
 #[test]
 fn window_serde() {
     needs_serde::<CursorIcon>();
+    needs_serde::<WindowId>();
 }
 
 #[test]
@@ -27,6 +37,63 @@ fn events_serde() {
     needs_serde::<MouseScrollDelta>();
     needs_serde::<VirtualKeyCode>();
     needs_serde::<ModifiersState>();
+    needs_serde::<ModifierSides>();
+    needs_serde::<KeyEvent>();
+    needs_serde::<Touch>();
+    needs_serde::<DeviceId>();
+    needs_serde::<StartCause>();
+    needs_serde::<WindowEvent>();
+    needs_serde::<DeviceEvent>();
+    needs_serde::<Event<()>>();
+}
+
+#[test]
+fn recording_serde() {
+    needs_serde::<RecordedEvent<()>>();
+}
+
+#[test]
+fn instant_round_trip() {
+    // Taken before any `Instant` is serialized, and so before the time it's converted from
+    let before = Instant::now();
+    let cause = StartCause::WaitCancelled {
+        start: Instant::now(),
+        requested_resume: Some(before),
+    };
+    assert_eq!(round_trip(&cause), cause);
+
+    let later = Instant::now() + Duration::from_secs(60);
+    let cause = StartCause::ResumeTimeReached {
+        start: before,
+        requested_resume: later,
+    };
+    assert_eq!(round_trip(&cause), cause);
+
+    let recorded = RecordedEvent {
+        time: Duration::from_millis(1500),
+        event: Event::<()>::NewEvents(StartCause::WaitCancelled {
+            start: later,
+            requested_resume: None,
+        }),
+    };
+    assert_eq!(round_trip(&recorded), recorded);
+
+    let timing = FrameTiming {
+        presentation_time: Some(later),
+        refresh_interval: Some(Duration::from_micros(16_667)),
+    };
+    assert_eq!(round_trip(&timing), timing);
+    assert_eq!(round_trip(&FrameTiming::default()), FrameTiming::default());
+}
+
+#[test]
+fn keyboard_serde() {
+    needs_serde::<KeyCode>();
+    needs_serde::<Key>();
+    needs_serde::<NamedKey>();
+    needs_serde::<KeyLocation>();
+    needs_serde::<KeyboardLayout>();
+    needs_serde::<KeyRepeatInfo>();
 }
 
 #[test]